@@ -1,7 +1,13 @@
 #![warn(clippy::pedantic)]
 
 use log::warn;
-use std::{any::Any, hash::Hasher, marker::PhantomData, path::PathBuf};
+use serde::de::DeserializeOwned;
+use std::{
+    any::Any,
+    hash::Hasher,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
 
 use vfs::VirtualFileSystem;
 
@@ -11,10 +17,59 @@ pub type Result<T> = std::result::Result<T, AssetError>;
 #[derive(Debug)]
 pub enum AssetError {
     PathCanonicalizationFailed,
-    ImageDecodingFailed,
-    ReadFailed,
-    AssetPathIsInvalidUTF8,
+    ImageDecodingFailed(String),
+    PrefabParsingFailed(String),
+    ModelDecodingFailed(String),
+    RonAssetParsingFailed(String),
+    ReadFailed {
+        path: String,
+        source: std::io::Error,
+    },
+    AssetPathIsInvalidUTF8(PathBuf),
     ExecutablePathAcquisitionFailed(std::io::Error),
+    /// The directory the asset paths are resolved against doesn't exist
+    AssetDirectoryNotFound(PathBuf),
+    /// The file was read but its loader failed
+    LoadFailed {
+        path: String,
+        source: Box<AssetError>,
+    },
+}
+
+impl std::fmt::Display for AssetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssetError::PathCanonicalizationFailed => {
+                write!(f, "asset path canonicalization failed")
+            }
+            AssetError::ImageDecodingFailed(e) => write!(f, "image decoding failed: {e}"),
+            AssetError::PrefabParsingFailed(e) => write!(f, "prefab parsing failed: {e}"),
+            AssetError::ModelDecodingFailed(e) => write!(f, "model decoding failed: {e}"),
+            AssetError::RonAssetParsingFailed(e) => write!(f, "RON asset parsing failed: {e}"),
+            AssetError::ReadFailed { path, source } => write!(f, "couldn't read {path}: {source}"),
+            AssetError::AssetPathIsInvalidUTF8(path) => {
+                write!(f, "asset path {} isn't valid UTF-8", path.display())
+            }
+            AssetError::ExecutablePathAcquisitionFailed(e) => {
+                write!(f, "couldn't acquire the executable path: {e}")
+            }
+            AssetError::AssetDirectoryNotFound(path) => {
+                write!(f, "asset directory {} doesn't exist", path.display())
+            }
+            AssetError::LoadFailed { path, source } => write!(f, "couldn't load {path}: {source}"),
+        }
+    }
+}
+
+impl std::error::Error for AssetError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AssetError::ReadFailed { source, .. }
+            | AssetError::ExecutablePathAcquisitionFailed(source) => Some(source),
+            AssetError::LoadFailed { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -62,56 +117,81 @@ impl<T: 'static> AssetHandle<T> {
     }
 }
 
+/// Stores the loaded assets.
+///
+/// Unless the file system is [embedded](vfs::VirtualFileSystem::is_embedded),
+/// asset paths are resolved against the first of:
+/// 1. the asset root set with [`AssetStore::with_asset_root`],
+/// 2. the `assets/` directory of the crate, when run with `cargo run` and if
+///    it exists,
+/// 3. the `assets/` directory next to the executable.
 pub struct AssetStore {
-    fs: Box<dyn VirtualFileSystem>,
-    assets: Vec<Box<dyn Any>>,
+    fs: Box<dyn VirtualFileSystem + Send + Sync>,
+    asset_root: Option<PathBuf>,
+    assets: Vec<Box<dyn Any + Send + Sync>>,
 }
 impl AssetStore {
     #[must_use]
     pub fn new<FS>(fs: FS) -> Self
     where
-        FS: VirtualFileSystem + 'static,
+        FS: VirtualFileSystem + Send + Sync + 'static,
     {
         Self {
             fs: Box::new(fs),
+            asset_root: None,
             assets: vec![],
         }
     }
 
+    /// Sets the directory the asset paths are resolved against
+    #[must_use]
+    pub fn with_asset_root<P: Into<PathBuf>>(mut self, asset_root: P) -> Self {
+        self.asset_root = Some(asset_root.into());
+        self
+    }
+
+    /// Returns the path an asset path resolves to, see [`AssetStore`]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the path of the executable
+    /// is required and cannot be acquired, or if the asset directory doesn't
+    /// exist
+    pub fn resolve_path(&self, asset_path: &str) -> Result<PathBuf> {
+        if self.fs.is_embedded() || cfg!(target_arch = "wasm32") {
+            return Ok(PathBuf::from(asset_path));
+        }
+
+        let manifest_path = std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from);
+        let mut resolved_asset_path = asset_directory(
+            self.asset_root.as_deref(),
+            manifest_path.as_deref(),
+            std::env::current_exe,
+        )?;
+        resolved_asset_path.push(asset_path);
+        Ok(resolved_asset_path)
+    }
+
     /// Loads an asset using an asset path and returns the asset without storing it
     ///
     /// # Errors
     ///
     /// This function will return an error if the canonicalization of the path fails,
-    /// or if the asset cannot be loaded.
+    /// or if the asset cannot be loaded. Read and load errors contain the resolved
+    /// path of the asset.
     pub fn load_without_storing<A>(&self, asset_path: &str) -> Result<A>
     where
-        A: 'static + Asset,
+        A: 'static + Asset + Send + Sync,
     {
-        #[cfg(not(target_arch = "wasm32"))]
-        let mut resolved_asset_path = {
-            let mut resolved_asset_path =
-                if let Ok(manifest_path) = std::env::var("CARGO_MANIFEST_DIR") {
-                    PathBuf::from(manifest_path)
-                } else {
-                    let mut path = std::env::current_exe()
-                        .map_err(AssetError::ExecutablePathAcquisitionFailed)?;
-                    path.pop();
-                    path
-                };
-            resolved_asset_path.push("assets/");
-            resolved_asset_path
-        };
-        #[cfg(target_arch = "wasm32")]
-        let mut resolved_asset_path = PathBuf::new();
-
-        resolved_asset_path.push(asset_path);
-        let bytes = self.fs.read_bytes(
-            resolved_asset_path
-                .to_str()
-                .ok_or(AssetError::AssetPathIsInvalidUTF8)?,
-        )?;
-        A::Loader::load(&bytes)
+        let resolved_asset_path = self.resolve_path(asset_path)?;
+        let resolved_asset_path = resolved_asset_path
+            .to_str()
+            .ok_or_else(|| AssetError::AssetPathIsInvalidUTF8(resolved_asset_path.clone()))?;
+        let bytes = self.fs.read_bytes(resolved_asset_path)?;
+        A::Loader::load(&bytes).map_err(|e| AssetError::LoadFailed {
+            path: resolved_asset_path.to_string(),
+            source: Box::new(e),
+        })
     }
 
     /// Loads an asset using an asset path
@@ -122,14 +202,14 @@ impl AssetStore {
     /// or if the asset cannot be loaded.
     pub fn load<A>(&mut self, asset_path: &str) -> Result<AssetHandle<A>>
     where
-        A: 'static + Asset,
+        A: 'static + Asset + Send + Sync,
     {
         Ok(self.store(self.load_without_storing(asset_path)?))
     }
 
     pub fn store<A>(&mut self, asset: A) -> AssetHandle<A>
     where
-        A: 'static + Asset,
+        A: 'static + Asset + Send + Sync,
     {
         let asset_id = self.assets.len();
         self.assets.push(Box::new(asset));
@@ -142,6 +222,37 @@ impl AssetStore {
     }
 }
 
+/// Returns the directory the asset paths are resolved against: the asset
+/// root if set, else the `assets/` directory of the crate if it exists, else
+/// the `assets/` directory next to the executable
+fn asset_directory<F>(
+    asset_root: Option<&Path>,
+    manifest_path: Option<&Path>,
+    executable_path: F,
+) -> Result<PathBuf>
+where
+    F: FnOnce() -> std::io::Result<PathBuf>,
+{
+    let asset_directory = if let Some(asset_root) = asset_root {
+        asset_root.to_path_buf()
+    } else if let Some(manifest_assets) = manifest_path
+        .map(|manifest_path| manifest_path.join("assets"))
+        .filter(|manifest_assets| manifest_assets.is_dir())
+    {
+        manifest_assets
+    } else {
+        let mut path = executable_path().map_err(AssetError::ExecutablePathAcquisitionFailed)?;
+        path.pop();
+        path.join("assets")
+    };
+
+    if !asset_directory.is_dir() {
+        return Err(AssetError::AssetDirectoryNotFound(asset_directory));
+    }
+
+    Ok(asset_directory)
+}
+
 pub trait Asset: Sized {
     type Loader: AssetLoader<Self>;
 }
@@ -155,6 +266,31 @@ pub trait AssetLoader<T> {
     fn load(file_content: &[u8]) -> Result<T>;
 }
 
+/// Assets deserialized from RON files, such as configuration files:
+///
+/// ```ignore
+/// #[derive(Deserialize)]
+/// struct MaterialAsset {
+///     color: (f32, f32, f32),
+/// }
+/// impl RonAsset for MaterialAsset {}
+///
+/// let material = asset_store.load::<MaterialAsset>("white.ron")?;
+/// ```
+pub trait RonAsset: DeserializeOwned {}
+
+impl<T: RonAsset> Asset for T {
+    type Loader = RonAssetLoader;
+}
+
+pub struct RonAssetLoader;
+impl<T: RonAsset> AssetLoader<T> for RonAssetLoader {
+    fn load(file_content: &[u8]) -> Result<T> {
+        ron::de::from_bytes(file_content)
+            .map_err(|e| AssetError::RonAssetParsingFailed(e.to_string()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,19 +314,149 @@ mod tests {
         }
     }
 
+    /// Creates an empty directory for the test in the temporary directory
+    fn test_directory(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("tubereng_asset_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+
     #[test]
     fn asset_store_new() -> Result<()> {
         let fs = MockFS;
-        let mut asset_store = AssetStore::new(fs);
+        let mut asset_store = AssetStore::new(fs).with_asset_root(test_directory("new"));
         let asset_handle = asset_store.load::<Text>("test.txt")?;
         assert_eq!(asset_handle.id, 0);
         Ok(())
     }
 
+    pub struct Number;
+    impl Asset for Number {
+        type Loader = NumberAssetLoader;
+    }
+
+    pub struct NumberAssetLoader;
+    impl AssetLoader<Number> for NumberAssetLoader {
+        fn load(_file_content: &[u8]) -> Result<Number> {
+            Err(AssetError::PrefabParsingFailed("not a number".into()))
+        }
+    }
+
+    #[test]
+    fn asset_errors_contain_the_asset_path() {
+        let asset_store =
+            AssetStore::new(vfs::filesystem::FileSystem).with_asset_root(test_directory("errors"));
+        let Err(error) = asset_store.load_without_storing::<Text>("missing.txt") else {
+            panic!("missing.txt shouldn't exist");
+        };
+        assert!(matches!(
+            &error,
+            AssetError::ReadFailed { path, source }
+                if path.ends_with("missing.txt")
+                    && source.kind() == std::io::ErrorKind::NotFound
+        ));
+        assert!(error.to_string().contains("missing.txt"));
+
+        let asset_store = AssetStore::new(MockFS).with_asset_root(test_directory("errors"));
+        let Err(error) = asset_store.load_without_storing::<Number>("number.txt") else {
+            panic!("the number loader should fail");
+        };
+        assert!(error
+            .to_string()
+            .ends_with("number.txt: prefab parsing failed: not a number"));
+    }
+
+    #[derive(serde::Deserialize)]
+    pub struct MaterialAsset {
+        color: (f32, f32, f32),
+    }
+    impl RonAsset for MaterialAsset {}
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn ron_asset_loader() {
+        let material: MaterialAsset = RonAssetLoader::load(b"(color: (1.0, 0.5, 0.0))").unwrap();
+        assert_eq!(material.color, (1.0, 0.5, 0.0));
+
+        assert!(matches!(
+            <RonAssetLoader as AssetLoader<MaterialAsset>>::load(b"(colour: 1.0)"),
+            Err(AssetError::RonAssetParsingFailed(_))
+        ));
+    }
+
+    pub struct EmbeddedMockFS;
+    impl VirtualFileSystem for EmbeddedMockFS {
+        fn read_bytes(&self, _path: &str) -> std::result::Result<Vec<u8>, AssetError> {
+            Ok(vec![])
+        }
+
+        fn is_embedded(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn asset_store_resolve_path() -> Result<()> {
+        let asset_root = test_directory("resolve_path");
+        let asset_store = AssetStore::new(MockFS).with_asset_root(&asset_root);
+        assert_eq!(
+            asset_store.resolve_path("textures/player.png")?,
+            asset_root.join("textures/player.png")
+        );
+
+        let asset_store = AssetStore::new(EmbeddedMockFS).with_asset_root("/opt/game/data");
+        assert_eq!(
+            asset_store.resolve_path("player.png")?,
+            PathBuf::from("player.png")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn asset_directory_fallbacks() -> Result<()> {
+        let crate_directory = test_directory("crate_directory");
+        let executable_directory = test_directory("executable_directory");
+        let executable_path = || Ok(executable_directory.join("game"));
+
+        std::fs::create_dir(executable_directory.join("assets")).unwrap();
+        assert_eq!(
+            asset_directory(None, Some(&crate_directory), executable_path)?,
+            executable_directory.join("assets")
+        );
+        assert_eq!(
+            asset_directory(None, None, executable_path)?,
+            executable_directory.join("assets")
+        );
+
+        std::fs::create_dir(crate_directory.join("assets")).unwrap();
+        assert_eq!(
+            asset_directory(None, Some(&crate_directory), executable_path)?,
+            crate_directory.join("assets")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn asset_directory_must_exist() {
+        let missing_root = test_directory("missing").join("assets");
+        assert!(matches!(
+            asset_directory(Some(&missing_root), None, std::env::current_exe),
+            Err(AssetError::AssetDirectoryNotFound(path)) if path == missing_root
+        ));
+
+        let asset_store = AssetStore::new(MockFS).with_asset_root(&missing_root);
+        assert!(matches!(
+            asset_store.load_without_storing::<Text>("test.txt"),
+            Err(AssetError::AssetDirectoryNotFound(_))
+        ));
+    }
+
     #[test]
     fn asset_store_get() -> Result<()> {
         let fs = MockFS;
-        let mut asset_store = AssetStore::new(fs);
+        let mut asset_store = AssetStore::new(fs).with_asset_root(test_directory("get"));
         let asset_handle = asset_store.load::<Text>("test.txt")?;
         let asset = asset_store.get(asset_handle).unwrap();
         assert_eq!(&asset.0, "cheh");
@@ -0,0 +1,40 @@
+use include_dir::Dir;
+use log::trace;
+
+use crate::AssetError;
+use crate::Result;
+
+use super::VirtualFileSystem;
+
+/// File system reading the assets embedded in the binary with
+/// [`include_dir`](https://docs.rs/include_dir), so the application doesn't
+/// depend on an `assets/` directory next to it
+pub struct Embedded {
+    assets: &'static Dir<'static>,
+}
+
+impl Embedded {
+    #[must_use]
+    pub fn new(assets: &'static Dir<'static>) -> Self {
+        Self { assets }
+    }
+}
+
+impl VirtualFileSystem for Embedded {
+    fn read_bytes(&self, path: &str) -> Result<Vec<u8>> {
+        trace!("Reading embedded bytes from {path}");
+        Ok(self
+            .assets
+            .get_file(path)
+            .ok_or_else(|| AssetError::ReadFailed {
+                path: path.to_string(),
+                source: std::io::ErrorKind::NotFound.into(),
+            })?
+            .contents()
+            .to_vec())
+    }
+
+    fn is_embedded(&self) -> bool {
+        true
+    }
+}
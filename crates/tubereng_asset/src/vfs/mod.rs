@@ -1,5 +1,6 @@
 use crate::Result;
 
+pub mod embedded;
 pub mod filesystem;
 
 #[cfg(target_arch = "wasm32")]
@@ -11,4 +12,10 @@ pub trait VirtualFileSystem {
     /// # Errors
     /// An error will be returned if the file cannot be read
     fn read_bytes(&self, path: &str) -> Result<Vec<u8>>;
+
+    /// Whether the files are embedded in the binary, in which case asset
+    /// paths are read as is instead of being resolved against the asset root
+    fn is_embedded(&self) -> bool {
+        false
+    }
 }
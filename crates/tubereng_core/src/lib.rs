@@ -8,8 +8,78 @@ use tubereng_math::{
     vector::Vector3f,
 };
 
+pub mod spatial_hash;
+pub mod timer;
+pub mod tween;
+
+/// Resource holding the duration of the current frame in seconds, the same
+/// value as [`Time::delta`]
 pub struct DeltaTime(pub f32);
 
+/// Resource holding the timing of the frames, advanced at the start of each
+/// update
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Time {
+    delta: f32,
+    elapsed_secs: f64,
+    frame_count: u64,
+}
+
+impl Time {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts a new frame lasting `delta` seconds
+    pub fn advance(&mut self, delta: f32) {
+        self.delta = delta;
+        self.elapsed_secs += f64::from(delta);
+        self.frame_count += 1;
+    }
+
+    /// Returns the duration of the current frame in seconds
+    #[must_use]
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    /// Returns the time elapsed since the first frame in seconds, including
+    /// the current frame
+    #[must_use]
+    pub fn elapsed_secs(&self) -> f64 {
+        self.elapsed_secs
+    }
+
+    /// Returns the number of frames started, including the current one
+    #[must_use]
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+}
+
+/// Resource used by systems to ask the engine to stop running
+#[derive(Debug, Default)]
+pub struct ExitRequest {
+    requested: bool,
+}
+
+impl ExitRequest {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    #[must_use]
+    pub fn is_requested(&self) -> bool {
+        self.requested
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Transform {
     pub translation: Vector3f,
@@ -24,6 +94,53 @@ impl Transform {
             * Matrix4f::new_translation(&self.translation)
             * self.rotation.rotation_matrix()
     }
+
+    /// Returns the direction the transform is facing, -Z rotated by the
+    /// rotation of the transform like cameras
+    pub fn forward(&self) -> Vector3f {
+        self.rotation
+            .apply_to_vector(&Vector3f::new(0.0, 0.0, -1.0))
+    }
+
+    pub fn right(&self) -> Vector3f {
+        self.rotation.apply_to_vector(&Vector3f::new(1.0, 0.0, 0.0))
+    }
+
+    pub fn up(&self) -> Vector3f {
+        self.rotation.apply_to_vector(&Vector3f::new(0.0, 1.0, 0.0))
+    }
+
+    pub fn translate(&mut self, delta: Vector3f) {
+        self.translation += delta;
+    }
+
+    /// Applies `rotation` on top of the current rotation of the transform
+    pub fn rotate(&mut self, rotation: &Quaternion) {
+        self.rotation = (rotation.clone() * self.rotation.clone()).normalized();
+    }
+
+    /// Returns the transform of `child` expressed in the space `self` is
+    /// relative to, as if it was a child of `self`.
+    ///
+    /// The result matches `self.as_matrix4() * child.as_matrix4()` as long as
+    /// the scale of `child` is uniform.
+    #[must_use]
+    pub fn combine(&self, child: &Transform) -> Transform {
+        let rotated_child_translation = self.rotation.apply_to_vector(&child.translation);
+        Transform {
+            translation: Vector3f::new(
+                self.translation.x / child.scale.x + rotated_child_translation.x,
+                self.translation.y / child.scale.y + rotated_child_translation.y,
+                self.translation.z / child.scale.z + rotated_child_translation.z,
+            ),
+            scale: Vector3f::new(
+                self.scale.x * child.scale.x,
+                self.scale.y * child.scale.y,
+                self.scale.z * child.scale.z,
+            ),
+            rotation: (self.rotation.clone() * child.rotation.clone()).normalized(),
+        }
+    }
 }
 
 impl From<Matrix4f> for Transform {
@@ -69,6 +186,10 @@ impl TransformCache {
         self.transform_matrices.insert(id, matrix);
     }
 
+    pub fn remove(&mut self, id: usize) {
+        self.transform_matrices.remove(&id);
+    }
+
     #[must_use]
     pub fn get(&self, id: usize) -> Matrix4f {
         *self
@@ -89,7 +210,81 @@ impl Default for Transform {
         Self {
             translation: Vector3f::new(0.0, 0.0, 0.0),
             scale: Vector3f::new(1.0, 1.0, 1.0),
-            rotation: Quaternion::new(1.0, Vector3f::new(0.0, 0.0, 0.0)),
+            rotation: Quaternion::identity(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    fn assert_approx_eq(a: f32, b: f32) {
+        assert!((a - b).abs() < 0.0001, "{a} != {b}");
+    }
+
+    fn assert_vector_approx_eq(a: &Vector3f, b: &Vector3f) {
+        assert_approx_eq(a.x, b.x);
+        assert_approx_eq(a.y, b.y);
+        assert_approx_eq(a.z, b.z);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn time_accumulates_frames() {
+        let mut time = Time::new();
+        time.advance(0.5);
+        time.advance(0.25);
+
+        assert_eq!(time.delta(), 0.25);
+        assert_eq!(time.elapsed_secs(), 0.75);
+        assert_eq!(time.frame_count(), 2);
+    }
+
+    #[test]
+    fn transform_basis() {
+        let mut transform = Transform::default();
+        assert_vector_approx_eq(&transform.forward(), &Vector3f::new(0.0, 0.0, -1.0));
+        assert_vector_approx_eq(&transform.right(), &Vector3f::new(1.0, 0.0, 0.0));
+        assert_vector_approx_eq(&transform.up(), &Vector3f::new(0.0, 1.0, 0.0));
+
+        transform.rotate(&Quaternion::from_axis_angle(
+            &Vector3f::new(0.0, 1.0, 0.0),
+            FRAC_PI_2,
+        ));
+        assert_vector_approx_eq(&transform.forward(), &Vector3f::new(-1.0, 0.0, 0.0));
+        assert_vector_approx_eq(&transform.right(), &Vector3f::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn transform_translate() {
+        let mut transform = Transform::default();
+        transform.translate(Vector3f::new(1.0, 2.0, 3.0));
+        transform.translate(Vector3f::new(1.0, 0.0, 0.0));
+        assert_vector_approx_eq(&transform.translation, &Vector3f::new(2.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn transform_combine_matches_matrix_product() {
+        let parent = Transform {
+            translation: Vector3f::new(1.0, -2.0, 3.0),
+            scale: Vector3f::new(2.0, 1.0, 0.5),
+            rotation: Quaternion::from_axis_angle(&Vector3f::new(0.0, 0.0, 1.0), 0.7),
+        };
+        let child = Transform {
+            translation: Vector3f::new(4.0, 0.5, -1.0),
+            scale: Vector3f::new(3.0, 3.0, 3.0),
+            rotation: Quaternion::from_axis_angle(&Vector3f::new(1.0, 0.0, 0.0), 1.2),
+        };
+
+        let combined = parent.combine(&child).as_matrix4();
+        let expected = parent.as_matrix4() * child.as_matrix4();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_approx_eq(combined[i][j], expected[i][j]);
+            }
         }
     }
 }
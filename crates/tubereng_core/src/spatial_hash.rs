@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+
+use tubereng_math::vector::Vector2f;
+
+/// An axis-aligned rectangle of the xy plane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector2f,
+    pub max: Vector2f,
+}
+
+impl Aabb {
+    #[must_use]
+    pub fn new(min: Vector2f, max: Vector2f) -> Self {
+        Self { min, max }
+    }
+
+    #[must_use]
+    pub fn contains(&self, point: Vector2f) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x) && (self.min.y..=self.max.y).contains(&point.y)
+    }
+}
+
+/// Resource bucketing the entities by the cell of a grid containing their
+/// world position on the xy plane, for broad-phase collision and neighbor
+/// queries.
+///
+/// The engine rebuilds it every frame from the effective transforms of the
+/// entities, the cell size should be close to the size of the queried
+/// regions.
+///
+/// ```
+/// # use tubereng_core::spatial_hash::{Aabb, SpatialHash};
+/// # use tubereng_math::vector::Vector2f;
+/// let mut spatial_hash = SpatialHash::new(32.0);
+/// spatial_hash.rebuild([(0, Vector2f::new(10.0, 10.0)), (1, Vector2f::new(100.0, 10.0))]);
+/// let region = Aabb::new(Vector2f::new(0.0, 0.0), Vector2f::new(50.0, 50.0));
+/// assert_eq!(spatial_hash.query_region(&region).collect::<Vec<_>>(), [0]);
+/// ```
+#[derive(Debug)]
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    positions: HashMap<usize, Vector2f>,
+}
+
+impl SpatialHash {
+    /// # Panics
+    ///
+    /// Will panic if `cell_size` isn't strictly positive
+    #[must_use]
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "The cell size should be strictly positive");
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.positions.clear();
+    }
+
+    /// Replaces the content of the spatial hash with the given entities and
+    /// positions
+    pub fn rebuild(&mut self, entities: impl IntoIterator<Item = (usize, Vector2f)>) {
+        self.clear();
+        for (entity, position) in entities {
+            self.insert(entity, position);
+        }
+    }
+
+    pub fn insert(&mut self, entity: usize, position: Vector2f) {
+        if let Some(previous_position) = self.positions.insert(entity, position) {
+            let previous_cell = self.cell(previous_position);
+            if let Some(cell) = self.cells.get_mut(&previous_cell) {
+                cell.retain(|&e| e != entity);
+            }
+        }
+
+        self.cells
+            .entry(self.cell(position))
+            .or_default()
+            .push(entity);
+    }
+
+    #[must_use]
+    pub fn position(&self, entity: usize) -> Option<Vector2f> {
+        self.positions.get(&entity).copied()
+    }
+
+    /// Returns the entities whose position is in the region
+    pub fn query_region<'a>(&'a self, region: &Aabb) -> impl Iterator<Item = usize> + 'a {
+        let region = *region;
+        let (min_x, min_y) = self.cell(region.min);
+        let (max_x, max_y) = self.cell(region.max);
+        (min_y..=max_y)
+            .flat_map(move |y| (min_x..=max_x).map(move |x| (x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .filter(move |entity| region.contains(self.positions[entity]))
+    }
+
+    /// Returns the other entities within `radius` of an entity, none if the
+    /// entity isn't in the spatial hash
+    pub fn neighbors(&self, entity: usize, radius: f32) -> impl Iterator<Item = usize> + '_ {
+        let center = self.position(entity);
+        let region = center.map_or(
+            Aabb::new(Vector2f::new(0.0, 0.0), Vector2f::new(-1.0, -1.0)),
+            |center| {
+                Aabb::new(
+                    Vector2f::new(center.x - radius, center.y - radius),
+                    Vector2f::new(center.x + radius, center.y + radius),
+                )
+            },
+        );
+
+        self.query_region(&region).filter(move |&other| {
+            let (Some(center), true) = (center, other != entity) else {
+                return false;
+            };
+            let position = self.positions[&other];
+            let (dx, dy) = (position.x - center.x, position.y - center.y);
+            dx * dx + dy * dy <= radius * radius
+        })
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn cell(&self, position: Vector2f) -> (i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+}
+
+/// A spatial hash with cells of 64 units
+impl Default for SpatialHash {
+    fn default() -> Self {
+        Self::new(64.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(entities: impl Iterator<Item = usize>) -> Vec<usize> {
+        let mut entities = entities.collect::<Vec<_>>();
+        entities.sort_unstable();
+        entities
+    }
+
+    #[test]
+    fn query_region() {
+        let mut spatial_hash = SpatialHash::new(10.0);
+        spatial_hash.rebuild([
+            (0, Vector2f::new(1.0, 1.0)),
+            (1, Vector2f::new(15.0, 5.0)),
+            (2, Vector2f::new(-5.0, -5.0)),
+            (3, Vector2f::new(45.0, 45.0)),
+        ]);
+
+        let region = Aabb::new(Vector2f::new(-6.0, -6.0), Vector2f::new(16.0, 6.0));
+        assert_eq!(sorted(spatial_hash.query_region(&region)), [0, 1, 2]);
+        let region = Aabb::new(Vector2f::new(0.0, 0.0), Vector2f::new(14.0, 14.0));
+        assert_eq!(sorted(spatial_hash.query_region(&region)), [0]);
+    }
+
+    #[test]
+    fn neighbors() {
+        let mut spatial_hash = SpatialHash::new(10.0);
+        spatial_hash.rebuild([
+            (0, Vector2f::new(0.0, 0.0)),
+            (1, Vector2f::new(3.0, 4.0)),
+            (2, Vector2f::new(4.0, 4.0)),
+        ]);
+        spatial_hash.insert(2, Vector2f::new(-3.0, -4.0));
+
+        assert_eq!(sorted(spatial_hash.neighbors(0, 5.0)), [1, 2]);
+        assert_eq!(sorted(spatial_hash.neighbors(1, 5.0)), [0]);
+        assert_eq!(spatial_hash.neighbors(42, 5.0).count(), 0);
+    }
+}
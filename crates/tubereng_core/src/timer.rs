@@ -0,0 +1,119 @@
+/// A timer counting down a duration, ticked with the frame's delta time.
+///
+/// ```
+/// # use tubereng_core::timer::Timer;
+/// let mut spawn_timer = Timer::new(2.0).repeating();
+/// assert!(!spawn_timer.tick(1.5));
+/// assert!(spawn_timer.tick(1.0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Timer {
+    duration: f32,
+    elapsed: f32,
+    repeating: bool,
+    finished: bool,
+}
+
+impl Timer {
+    /// Creates a one-shot timer of the given duration, in seconds
+    #[must_use]
+    pub fn new(duration: f32) -> Self {
+        Self {
+            duration,
+            elapsed: 0.0,
+            repeating: false,
+            finished: false,
+        }
+    }
+
+    /// Makes the timer restart every time it finishes
+    #[must_use]
+    pub fn repeating(mut self) -> Self {
+        self.repeating = true;
+        self
+    }
+
+    /// Advances the timer by `delta` seconds, returns true if the timer
+    /// finished during this tick
+    pub fn tick(&mut self, delta: f32) -> bool {
+        if self.finished {
+            return false;
+        }
+
+        self.elapsed += delta;
+        if self.elapsed < self.duration {
+            return false;
+        }
+
+        if self.repeating && self.duration > 0.0 {
+            self.elapsed %= self.duration;
+        } else {
+            self.elapsed = self.duration;
+            self.finished = true;
+        }
+
+        true
+    }
+
+    /// Restarts the timer from zero
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.finished = false;
+    }
+
+    #[must_use]
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    #[must_use]
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    #[must_use]
+    pub fn is_repeating(&self) -> bool {
+        self.repeating
+    }
+
+    /// Returns true if a one-shot timer has finished, repeating timers never
+    /// finish
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_shot_timer_finishes_once() {
+        let mut timer = Timer::new(1.0);
+        assert!(!timer.tick(0.5));
+        assert!(!timer.is_finished());
+        assert!(timer.tick(0.6));
+        assert!(timer.is_finished());
+        assert!(!timer.tick(1.0));
+    }
+
+    #[test]
+    fn repeating_timer_keeps_the_remainder() {
+        let mut timer = Timer::new(1.0).repeating();
+        assert!(timer.tick(1.25));
+        assert!(!timer.is_finished());
+        assert!((timer.elapsed() - 0.25).abs() < f32::EPSILON);
+        assert!(!timer.tick(0.5));
+        assert!(timer.tick(0.25));
+    }
+
+    #[test]
+    fn reset_timer() {
+        let mut timer = Timer::new(1.0);
+        assert!(timer.tick(1.0));
+        timer.reset();
+        assert!(!timer.is_finished());
+        assert!(timer.tick(1.0));
+    }
+}
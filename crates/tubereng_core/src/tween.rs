@@ -0,0 +1,135 @@
+/// Easing functions mapping a linear progress in `[0, 1]` to an eased one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    #[must_use]
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t,
+            Easing::EaseOut => t * (2.0 - t),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    2.0 * t * t
+                } else {
+                    -1.0 + (4.0 - 2.0 * t) * t
+                }
+            }
+        }
+    }
+}
+
+/// Values that can be linearly interpolated
+pub trait Lerp: Copy {
+    #[must_use]
+    fn lerp(self, to: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, to: Self, t: f32) -> Self {
+        self + (to - self) * t
+    }
+}
+
+/// Interpolates a value between two bounds over a duration.
+///
+/// ```
+/// # use tubereng_core::tween::{Easing, Tween};
+/// let mut tween = Tween::new(0.0, 10.0, 2.0, Easing::Linear);
+/// assert_eq!(tween.tick(1.0), 5.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Tween<T> {
+    from: T,
+    to: T,
+    duration: f32,
+    elapsed: f32,
+    easing: Easing,
+}
+
+impl<T: Lerp> Tween<T> {
+    /// Creates a tween going from `from` to `to` in `duration` seconds
+    #[must_use]
+    pub fn new(from: T, to: T, duration: f32, easing: Easing) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
+            easing,
+        }
+    }
+
+    /// Advances the tween by `delta` seconds and returns the new value
+    pub fn tick(&mut self, delta: f32) -> T {
+        self.elapsed = (self.elapsed + delta).min(self.duration);
+        self.value()
+    }
+
+    #[must_use]
+    pub fn value(&self) -> T {
+        self.from.lerp(self.to, self.easing.apply(self.progress()))
+    }
+
+    /// The linear progress of the tween, between 0 and 1
+    #[must_use]
+    pub fn progress(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return 1.0;
+        }
+
+        self.elapsed / self.duration
+    }
+
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_approx_eq(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{actual} is not equal to {expected}"
+        );
+    }
+
+    #[test]
+    fn easing_bounds() {
+        for easing in [
+            Easing::Linear,
+            Easing::EaseIn,
+            Easing::EaseOut,
+            Easing::EaseInOut,
+        ] {
+            assert_approx_eq(easing.apply(0.0), 0.0);
+            assert_approx_eq(easing.apply(1.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn easing_midpoints() {
+        assert_approx_eq(Easing::EaseIn.apply(0.5), 0.25);
+        assert_approx_eq(Easing::EaseOut.apply(0.5), 0.75);
+        assert_approx_eq(Easing::EaseInOut.apply(0.5), 0.5);
+    }
+
+    #[test]
+    fn tween_stops_at_the_end() {
+        let mut tween = Tween::new(2.0, 4.0, 1.0, Easing::Linear);
+        assert_approx_eq(tween.tick(0.5), 3.0);
+        assert_approx_eq(tween.tick(1.0), 4.0);
+        assert!(tween.is_finished());
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{relationship::ChildOf, EntityDefinition, EntityId, Storage};
+
+/// Identifier of an entity inside an [`EntityBundle`], only meaningful for
+/// the bundle it has been obtained from
+pub type LocalId = usize;
+
+type RelationshipInsertFn = fn(&mut Storage, EntityId, EntityId);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum BundleError {
+    RootNotSet,
+}
+
+/// A set of entities and relationships described with bundle-local ids that
+/// can be instantiated into a live world
+#[derive(Default)]
+pub struct EntityBundle {
+    entities: Vec<Vec<Box<dyn EntityDefinition>>>,
+    relationships: Vec<(RelationshipInsertFn, LocalId, LocalId)>,
+    root: Option<LocalId>,
+}
+
+impl EntityBundle {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_entity<ED>(&mut self, entity_definition: ED) -> LocalId
+    where
+        ED: 'static + EntityDefinition,
+    {
+        self.entities.push(vec![Box::new(entity_definition)]);
+        self.entities.len() - 1
+    }
+
+    /// Adds a component to an entity of the bundle
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the entity doesn't exist in the bundle
+    pub fn add_component<C>(&mut self, entity: LocalId, component: C)
+    where
+        C: 'static + std::fmt::Debug + Send + Sync,
+    {
+        self.entities[entity].push(Box::new((component,)));
+    }
+
+    /// Adds the components of an entity definition to an entity of the bundle
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the entity doesn't exist in the bundle
+    pub fn add_definition<ED>(&mut self, entity: LocalId, entity_definition: ED)
+    where
+        ED: 'static + EntityDefinition,
+    {
+        self.entities[entity].push(Box::new(entity_definition));
+    }
+
+    pub fn add_relationship<R: 'static>(&mut self, source: LocalId, target: LocalId) {
+        self.relationships
+            .push((insert_relationship_fn_of::<R>, source, target));
+    }
+
+    pub fn add_child(&mut self, child: LocalId, parent: LocalId) {
+        self.add_relationship::<ChildOf>(child, parent);
+    }
+
+    pub fn set_root(&mut self, root: LocalId) {
+        self.root = Some(root);
+    }
+
+    #[must_use]
+    pub fn root(&self) -> Option<LocalId> {
+        self.root
+    }
+
+    #[must_use]
+    pub fn entity_count(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Writes the entities of the bundle into the storage, allocating a new
+    /// entity id for each of them and rewiring the relationships accordingly,
+    /// then runs the insertion observers. Returns the id of the root entity.
+    pub(crate) fn instantiate(self, storage: &mut Storage) -> Result<EntityId, BundleError> {
+        let root = self.root.ok_or(BundleError::RootNotSet)?;
+
+        let mut entity_ids = Vec::with_capacity(self.entities.len());
+        for entity_definitions in self.entities {
+            let entity_id = storage.allocate_entity();
+            for entity_definition in entity_definitions {
+                entity_definition
+                    .write_into_component_stores(entity_id, &mut storage.component_stores);
+            }
+            entity_ids.push(entity_id);
+        }
+
+        for (insert_relationship_fn, source, target) in self.relationships {
+            insert_relationship_fn(storage, entity_ids[source], entity_ids[target]);
+        }
+
+        // Observers run once the whole bundle is in the storage, so they can
+        // see the other entities and the relationships of the bundle
+        for &entity_id in &entity_ids {
+            storage.notify_entity_inserted(entity_id);
+        }
+
+        Ok(entity_ids[root])
+    }
+}
+
+fn insert_relationship_fn_of<R: 'static>(
+    storage: &mut Storage,
+    source: EntityId,
+    target: EntityId,
+) {
+    storage.insert_relationship::<R>(source, target);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Ecs;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Name(&'static str);
+    #[derive(Debug, PartialEq)]
+    struct Health(i32);
+
+    #[test]
+    fn spawn_bundle_remaps_ids() {
+        let mut ecs = Ecs::new();
+        let _ = ecs.insert((Name("Already there"),));
+
+        let mut bundle = EntityBundle::new();
+        let root = bundle.add_entity((Name("Root"),));
+        let child = bundle.add_entity((Name("Child"),));
+        bundle.add_component(child, Health(3));
+        bundle.add_child(child, root);
+        bundle.set_root(root);
+
+        let root_id = ecs.spawn_bundle(bundle).unwrap();
+        assert_eq!(root_id, 1);
+        assert_eq!(ecs.entity_count(), 3);
+        assert_eq!(
+            ecs.component::<Name>(root_id).as_deref(),
+            Some(&Name("Root"))
+        );
+
+        let children = ecs
+            .relationship::<ChildOf>()
+            .unwrap()
+            .sources(root_id)
+            .unwrap();
+        assert_eq!(children.len(), 1);
+        let child_id = *children.iter().next().unwrap();
+        assert_eq!(
+            ecs.component::<Name>(child_id).as_deref(),
+            Some(&Name("Child"))
+        );
+        assert_eq!(
+            ecs.component::<Health>(child_id).as_deref(),
+            Some(&Health(3))
+        );
+    }
+
+    #[test]
+    fn spawn_bundle_runs_insertion_observers() {
+        use std::sync::{Arc, Mutex};
+
+        let mut ecs = Ecs::new();
+        let inserted = Arc::new(Mutex::new(vec![]));
+        let observer_inserted = inserted.clone();
+        ecs.on_insert::<Health, _>(move |storage, entity_id| {
+            let health = storage.component::<Health>(entity_id).unwrap().0;
+            observer_inserted.lock().unwrap().push((entity_id, health));
+        });
+
+        let mut bundle = EntityBundle::new();
+        let root = bundle.add_entity((Name("Root"), Health(10)));
+        let child = bundle.add_entity((Name("Child"),));
+        bundle.add_component(child, Health(3));
+        bundle.add_child(child, root);
+        bundle.set_root(root);
+
+        let root_id = ecs.spawn_bundle(bundle).unwrap();
+        assert_eq!(
+            *inserted.lock().unwrap(),
+            vec![(root_id, 10), (root_id + 1, 3)]
+        );
+    }
+
+    #[test]
+    fn spawn_bundle_without_root() {
+        let mut ecs = Ecs::new();
+        let mut bundle = EntityBundle::new();
+        bundle.add_entity((Name("Orphan"),));
+
+        assert_eq!(ecs.spawn_bundle(bundle), Err(BundleError::RootNotSet));
+        assert_eq!(ecs.entity_count(), 0);
+    }
+}
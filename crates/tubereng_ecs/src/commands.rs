@@ -1,49 +1,98 @@
 use std::{
-    cell::RefCell,
+    any::{Any, TypeId},
     marker::PhantomData,
-    sync::atomic::{self, AtomicUsize},
+    sync::{
+        atomic::{self, AtomicUsize},
+        Mutex, MutexGuard, PoisonError,
+    },
     vec::IntoIter,
 };
 
-use crate::{Ecs, EntityDefinition, EntityId};
+use crate::{
+    bundle::{BundleError, EntityBundle},
+    resource::Resource,
+    system::{self, ScheduleChange, SystemHandle},
+    Bundle, Ecs, EntityDefinition, EntityId,
+};
 
 pub struct CommandQueue {
     allocated_entity_count: AtomicUsize,
-    next_entity_id: usize,
-    deleted_entities: Vec<EntityId>,
-    commands: RefCell<Vec<Box<dyn Command>>>,
+    next_entity_id: AtomicUsize,
+    deleted_entities: Mutex<Vec<EntityId>>,
+    commands: Mutex<Vec<Box<dyn Command>>>,
+}
+
+/// Locks a field of the queue, a panicking system doesn't make the queue
+/// unusable
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
 }
+
 impl CommandQueue {
     #[must_use]
     pub fn new(next_entity_id: usize, deleted_entities: &[EntityId]) -> Self {
         Self {
             allocated_entity_count: AtomicUsize::new(0),
-            next_entity_id,
-            deleted_entities: deleted_entities.to_vec(),
-            commands: RefCell::new(vec![]),
+            next_entity_id: AtomicUsize::new(next_entity_id),
+            deleted_entities: Mutex::new(deleted_entities.to_vec()),
+            commands: Mutex::new(vec![]),
         }
     }
     fn compute_next_entity_id(&self) -> EntityId {
-        let allocated_entity_count = self.allocated_entity_count.load(atomic::Ordering::Relaxed);
-        let id = if allocated_entity_count < self.deleted_entities.len() {
-            self.deleted_entities[allocated_entity_count]
-        } else {
-            self.next_entity_id + allocated_entity_count - self.deleted_entities.len()
-        };
-        self.allocated_entity_count
+        let deleted_entities = lock(&self.deleted_entities);
+        let allocated_entity_count = self
+            .allocated_entity_count
             .fetch_add(1, atomic::Ordering::Relaxed);
-        id
+        if allocated_entity_count < deleted_entities.len() {
+            deleted_entities[allocated_entity_count]
+        } else {
+            self.next_entity_id.load(atomic::Ordering::Relaxed) + allocated_entity_count
+                - deleted_entities.len()
+        }
     }
 
-    pub fn insert<ED>(&self, entity_definition: ED) -> EntityId
+    /// Queues the insertion of an entity and returns the id it will have.
+    ///
+    /// Consecutive insertions of entities with the same definition type are
+    /// grouped so they are written in a single pass.
+    pub fn insert<B>(&self, bundle: B) -> EntityId
     where
-        ED: 'static + EntityDefinition,
+        B: Bundle,
+        B::Components: 'static,
     {
-        self.push_command(InsertEntity::new(entity_definition));
+        let entity_definition = bundle.into_components();
+        // The commands stay locked until the id is computed so the ids
+        // follow the order of the insertions when systems run concurrently
+        let mut commands = lock(&self.commands);
+        let last_insertion = commands
+            .last_mut()
+            .and_then(|command| command.as_any_mut())
+            .and_then(|command| command.downcast_mut::<InsertEntities<B::Components>>());
+        if let Some(last_insertion) = last_insertion {
+            last_insertion.entity_definitions.push(entity_definition);
+        } else {
+            commands.push(Box::new(InsertEntities::new(entity_definition)));
+        }
         self.compute_next_entity_id()
     }
 
-    pub fn insert_component<C: 'static>(&self, entity_id: EntityId, component: C) {
+    /// Queues the instantiation of a bundle and returns the id its root
+    /// entity will have
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if the root of the bundle has not been set
+    pub fn spawn_bundle(&self, bundle: EntityBundle) -> Result<EntityId, BundleError> {
+        let root = bundle.root().ok_or(BundleError::RootNotSet)?;
+        let mut commands = lock(&self.commands);
+        let entity_ids: Vec<EntityId> = (0..bundle.entity_count())
+            .map(|_| self.compute_next_entity_id())
+            .collect();
+        commands.push(Box::new(SpawnBundle::new(bundle)));
+        Ok(entity_ids[root])
+    }
+
+    pub fn insert_component<C: 'static + Send + Sync>(&self, entity_id: EntityId, component: C) {
         self.push_command(InsertComponent::new(entity_id, component));
     }
 
@@ -55,9 +104,20 @@ impl CommandQueue {
         self.push_command(DeleteEntity::new(entity_id));
     }
 
+    /// Queues the deletion of every entity, resources are kept. Entities
+    /// inserted after this call get ids starting from zero.
+    pub fn clear_entities(&self) {
+        self.push_command(ClearEntities);
+        let mut deleted_entities = lock(&self.deleted_entities);
+        self.next_entity_id.store(0, atomic::Ordering::Relaxed);
+        deleted_entities.clear();
+        self.allocated_entity_count
+            .store(0, atomic::Ordering::Relaxed);
+    }
+
     pub fn insert_resource<R>(&self, resource: R)
     where
-        R: 'static,
+        R: Resource,
     {
         self.push_command(InsertResource::new(resource));
     }
@@ -66,11 +126,53 @@ impl CommandQueue {
         self.push_command(InsertRelationship::<R>::new(source, target));
     }
 
+    /// Queues the removal of the edge from `source` to `target`
+    pub fn remove_relationship<R: 'static>(&self, source: EntityId, target: EntityId) {
+        self.push_command(RemoveRelationship::<R>::new(source, target));
+    }
+
+    /// Queues the registration of a system for a stage of the schedule and
+    /// returns the handle it will have. The system runs from the next run of
+    /// the schedule on.
+    pub fn register_system<Stage, F, S>(&self, _stage: &Stage, system: F) -> SystemHandle
+    where
+        Stage: 'static,
+        S: 'static,
+        F: 'static + system::Into<S>,
+    {
+        let handle = SystemHandle::next();
+        self.push_command(RegisterSystem {
+            stage: TypeId::of::<Stage>(),
+            handle,
+            system: Some(system.into_system()),
+        });
+        handle
+    }
+
+    /// Queues the removal of a system from the schedule. A system removed
+    /// during a run of the schedule still runs until the end of that run.
+    pub fn remove_system(&self, handle: SystemHandle) {
+        self.push_command(RemoveSystem { handle });
+    }
+
+    /// Queues a system that runs a single time at the start of the next run
+    /// of the schedule, before the stages, and is then discarded. The
+    /// commands it queues are processed right after it runs.
+    pub fn run_once<F, S>(&self, system: F)
+    where
+        S: 'static,
+        F: 'static + system::Into<S>,
+    {
+        self.push_command(RunOnce {
+            system: Some(system.into_system()),
+        });
+    }
+
     fn push_command<C>(&self, command: C)
     where
         C: 'static + Command,
     {
-        self.commands.borrow_mut().push(Box::new(command));
+        lock(&self.commands).push(Box::new(command));
     }
 }
 
@@ -79,32 +181,77 @@ impl IntoIterator for CommandQueue {
     type IntoIter = IntoIter<Box<dyn Command>>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.commands.into_inner().into_iter()
+        self.commands
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .into_iter()
     }
 }
 
-pub trait Command {
+/// A change of the Ecs queued by a system, possibly from another thread
+pub trait Command: Send {
     fn apply(&mut self, ecs: &mut Ecs);
+
+    /// Gives access to the concrete command so the queue can merge
+    /// consecutive commands of the same type
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        None
+    }
 }
 
-pub struct InsertEntity {
-    entity_definition: Option<Box<dyn EntityDefinition>>,
+pub struct InsertEntities<ED> {
+    entity_definitions: Vec<ED>,
 }
-impl InsertEntity {
-    pub fn new<ED>(entity_definition: ED) -> Self
-    where
-        ED: 'static + EntityDefinition,
-    {
+impl<ED> InsertEntities<ED>
+where
+    ED: 'static + EntityDefinition,
+{
+    pub fn new(entity_definition: ED) -> Self {
         Self {
-            entity_definition: Some(Box::new(entity_definition)),
+            entity_definitions: vec![entity_definition],
         }
     }
 }
 
-impl Command for InsertEntity {
+impl<ED> Command for InsertEntities<ED>
+where
+    ED: 'static + EntityDefinition,
+{
     fn apply(&mut self, ecs: &mut Ecs) {
-        let boxed_ed = self.entity_definition.take().unwrap();
-        ecs.insert(boxed_ed);
+        ecs.insert_batch(std::mem::take(&mut self.entity_definitions));
+    }
+
+    fn as_any_mut(&mut self) -> Option<&mut dyn Any> {
+        Some(self)
+    }
+}
+
+pub struct ClearEntities;
+
+impl Command for ClearEntities {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        ecs.clear_entities();
+    }
+}
+
+pub struct SpawnBundle {
+    bundle: Option<EntityBundle>,
+}
+
+impl SpawnBundle {
+    #[must_use]
+    pub fn new(bundle: EntityBundle) -> Self {
+        Self {
+            bundle: Some(bundle),
+        }
+    }
+}
+
+impl Command for SpawnBundle {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        let bundle = self.bundle.take().unwrap();
+        ecs.spawn_bundle(bundle)
+            .expect("The root of the bundle should have been checked when queuing the command");
     }
 }
 
@@ -122,7 +269,7 @@ impl<C> InsertComponent<C> {
     }
 }
 
-impl<C: 'static> Command for InsertComponent<C> {
+impl<C: 'static + Send + Sync> Command for InsertComponent<C> {
     fn apply(&mut self, ecs: &mut Ecs) {
         ecs.insert_component(self.entity_id, self.component.take().unwrap());
     }
@@ -130,7 +277,7 @@ impl<C: 'static> Command for InsertComponent<C> {
 
 pub struct RemoveComponent<C> {
     entity_id: EntityId,
-    _marker: PhantomData<C>,
+    _marker: PhantomData<fn() -> C>,
 }
 
 impl<C> RemoveComponent<C> {
@@ -168,14 +315,14 @@ impl Command for DeleteEntity {
 
 pub struct InsertResource<R>
 where
-    R: 'static,
+    R: Resource,
 {
     resource: Option<R>,
 }
 
 impl<R> InsertResource<R>
 where
-    R: 'static,
+    R: Resource,
 {
     pub fn new(resource: R) -> Self {
         Self {
@@ -186,7 +333,7 @@ where
 
 impl<R> Command for InsertResource<R>
 where
-    R: 'static,
+    R: Resource,
 {
     fn apply(&mut self, ecs: &mut Ecs) {
         ecs.insert_resource(self.resource.take().unwrap());
@@ -199,7 +346,7 @@ where
 {
     source: EntityId,
     target: EntityId,
-    _marker: PhantomData<R>,
+    _marker: PhantomData<fn() -> R>,
 }
 
 impl<R> InsertRelationship<R> {
@@ -221,3 +368,73 @@ where
         ecs.insert_relationship::<R>(self.source, self.target);
     }
 }
+
+pub struct RemoveRelationship<R>
+where
+    R: 'static,
+{
+    source: EntityId,
+    target: EntityId,
+    _marker: PhantomData<fn() -> R>,
+}
+
+impl<R> RemoveRelationship<R> {
+    #[must_use]
+    pub fn new(source: EntityId, target: EntityId) -> Self {
+        Self {
+            source,
+            target,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<R> Command for RemoveRelationship<R>
+where
+    R: 'static,
+{
+    fn apply(&mut self, ecs: &mut Ecs) {
+        ecs.remove_relationship::<R>(self.source, self.target);
+    }
+}
+
+pub struct RegisterSystem {
+    stage: TypeId,
+    handle: SystemHandle,
+    system: Option<system::System>,
+}
+
+impl Command for RegisterSystem {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        if let Some(system) = self.system.take() {
+            ecs.schedule_changes.push(ScheduleChange::AddSystem {
+                stage: self.stage,
+                handle: self.handle,
+                system,
+            });
+        }
+    }
+}
+
+pub struct RemoveSystem {
+    handle: SystemHandle,
+}
+
+impl Command for RemoveSystem {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        ecs.schedule_changes
+            .push(ScheduleChange::RemoveSystem(self.handle));
+    }
+}
+
+pub struct RunOnce {
+    system: Option<system::System>,
+}
+
+impl Command for RunOnce {
+    fn apply(&mut self, ecs: &mut Ecs) {
+        if let Some(system) = self.system.take() {
+            ecs.schedule_changes.push(ScheduleChange::RunOnce(system));
+        }
+    }
+}
@@ -1,27 +1,49 @@
 use std::{
     alloc::Layout,
     any::TypeId,
-    cell::{RefCell, UnsafeCell},
-    ops::{Deref, DerefMut},
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut, Range},
     ptr::NonNull,
-    rc::Rc,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
 };
 
 use crate::{bitset::BitSet, EntityId, MAX_ENTITY_COUNT};
 
 type EntityBitSet = [u8; MAX_ENTITY_COUNT / 8];
 
+/// An entity bit set shared between a store and the borrows of its
+/// components, which can be on other threads
+#[derive(Debug, Clone)]
+struct SharedBitSet(Arc<Mutex<EntityBitSet>>);
+
+impl SharedBitSet {
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new([0u8; MAX_ENTITY_COUNT / 8])))
+    }
+
+    /// Locks the bit set, a panicking thread doesn't make it unusable
+    fn lock(&self) -> MutexGuard<'_, EntityBitSet> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
 pub struct ComponentStore {
     component_layout: Layout,
     data: UnsafeCell<NonNull<u8>>,
     cap: usize,
     entities_bitset: EntityBitSet,
-    dirty_bitset: Rc<RefCell<EntityBitSet>>,
-    read_access_bitset: Rc<RefCell<EntityBitSet>>,
-    write_access_bitset: Rc<RefCell<EntityBitSet>>,
+    dirty_bitset: SharedBitSet,
+    read_access_bitset: SharedBitSet,
+    write_access_bitset: SharedBitSet,
     drop_fn: unsafe fn(*mut u8),
 }
 
+// SAFETY: The store only holds components that are Send and Sync, see
+// ComponentStore::store, and the access bit sets check the borrows of the
+// components across threads
+unsafe impl Send for ComponentStore {}
+unsafe impl Sync for ComponentStore {}
+
 impl ComponentStore {
     pub fn new(component_layout: Layout, drop_fn: unsafe fn(*mut u8)) -> Self {
         // In the case of ZSTs we don't want to allocate any data. To avoid any
@@ -38,29 +60,29 @@ impl ComponentStore {
             data: UnsafeCell::new(NonNull::dangling()),
             cap,
             entities_bitset: [0u8; MAX_ENTITY_COUNT / 8],
-            dirty_bitset: Rc::new(RefCell::new([0u8; MAX_ENTITY_COUNT / 8])),
-            read_access_bitset: Rc::new(RefCell::new([0u8; MAX_ENTITY_COUNT / 8])),
-            write_access_bitset: Rc::new(RefCell::new([0u8; MAX_ENTITY_COUNT / 8])),
+            dirty_bitset: SharedBitSet::new(),
+            read_access_bitset: SharedBitSet::new(),
+            write_access_bitset: SharedBitSet::new(),
             drop_fn,
         }
     }
 
     pub fn clear_dirty_bitset(&mut self) {
-        self.dirty_bitset.borrow_mut().clear_bits();
+        self.dirty_bitset.lock().clear_bits();
     }
 
     pub fn set_dirty(&self, entity_id: EntityId) {
-        self.dirty_bitset.borrow_mut().set_bit(entity_id);
+        self.dirty_bitset.lock().set_bit(entity_id);
     }
 
     pub fn dirty(&self, entity_id: EntityId) -> bool {
-        self.dirty_bitset.borrow_mut().bit(entity_id)
+        self.dirty_bitset.lock().bit(entity_id)
     }
 
-    pub fn store<C>(&mut self, entity_id: EntityId, mut component: C) {
+    pub fn store<C: Send + Sync>(&mut self, entity_id: EntityId, mut component: C) {
         assert!(entity_id < MAX_ENTITY_COUNT, "The component store is full");
         self.entities_bitset.set_bit(entity_id);
-        self.dirty_bitset.borrow_mut().set_bit(entity_id);
+        self.dirty_bitset.lock().set_bit(entity_id);
         self.ensure_capacity(entity_id + 1);
 
         if self.component_layout.size() > 0 {
@@ -74,6 +96,10 @@ impl ComponentStore {
         }
     }
 
+    pub fn contains(&self, entity_id: EntityId) -> bool {
+        entity_id < self.cap && self.entities_bitset.bit(entity_id)
+    }
+
     pub fn delete(&mut self, entity_id: EntityId) {
         if entity_id >= self.cap || !self.entities_bitset.bit(entity_id) {
             return;
@@ -101,13 +127,16 @@ impl ComponentStore {
             return None;
         }
 
+        // The write bit set is locked first and held until the read bit is
+        // set, as in get_mut, so that concurrent borrows can't both succeed
+        let write_access_bitset = self.write_access_bitset.lock();
         assert!(
-            !self.write_access_bitset.borrow().bit(entity_id),
+            !write_access_bitset.bit(entity_id),
             "Component {:?} of entity {entity_id} is already accessed mutably",
             TypeId::of::<C>()
         );
-
-        self.read_access_bitset.borrow_mut().set_bit(entity_id);
+        self.read_access_bitset.lock().set_bit(entity_id);
+        std::mem::drop(write_access_bitset);
 
         // SAFETY:
         // We checked that entity_id is smaller than self.cap, so it must be
@@ -137,17 +166,19 @@ impl ComponentStore {
             return None;
         }
 
+        let mut write_access_bitset = self.write_access_bitset.lock();
         assert!(
-            !self.write_access_bitset.borrow().bit(entity_id),
+            !write_access_bitset.bit(entity_id),
             "Component {:?} of entity {entity_id} is already accessed mutably",
             TypeId::of::<C>()
         );
         assert!(
-            !self.read_access_bitset.borrow().bit(entity_id),
+            !self.read_access_bitset.lock().bit(entity_id),
             "Component {:?} of entity {entity_id} is already accessed immutably",
             TypeId::of::<C>()
         );
-        self.write_access_bitset.borrow_mut().set_bit(entity_id);
+        write_access_bitset.set_bit(entity_id);
+        std::mem::drop(write_access_bitset);
 
         // SAFETY:
         // We checked that entity_id is smaller than self.cap, so it must be
@@ -219,6 +250,124 @@ impl ComponentStore {
             UnsafeCell::new(NonNull::new(new_data).expect("ComponentStore data allocation failed"));
     }
 
+    /// Returns the entity ids of the components if they occupy consecutive
+    /// slots, `None` if the store is empty or has gaps
+    pub fn dense_range(&self) -> Option<Range<EntityId>> {
+        let slot_count = self.cap.min(MAX_ENTITY_COUNT);
+        let first = (0..slot_count).find(|&i| self.entities_bitset.bit(i))?;
+        let last = (first..slot_count)
+            .rev()
+            .find(|&i| self.entities_bitset.bit(i))?;
+        (first..=last)
+            .all(|i| self.entities_bitset.bit(i))
+            .then_some(first..last + 1)
+    }
+
+    /// Mutably borrows the components of a range of slots as a slice
+    ///
+    /// # Safety
+    /// `C` must be the type of the components and every slot of the range
+    /// must hold a component
+    ///
+    /// # Panics
+    /// Will panic if one of the components is already borrowed
+    pub unsafe fn slice_mut<C: 'static>(
+        &self,
+        entity_ids: Range<EntityId>,
+    ) -> ComponentSliceMut<'_, C> {
+        {
+            let mut write_access_bitset = self.write_access_bitset.lock();
+            let read_access_bitset = self.read_access_bitset.lock();
+            for entity_id in entity_ids.clone() {
+                assert!(
+                    !write_access_bitset.bit(entity_id),
+                    "Component {:?} of entity {entity_id} is already accessed mutably",
+                    TypeId::of::<C>()
+                );
+                assert!(
+                    !read_access_bitset.bit(entity_id),
+                    "Component {:?} of entity {entity_id} is already accessed immutably",
+                    TypeId::of::<C>()
+                );
+                write_access_bitset.set_bit(entity_id);
+            }
+        }
+
+        ComponentSliceMut {
+            components: std::slice::from_raw_parts_mut(
+                self.ptr_at(entity_ids.start).cast::<C>(),
+                entity_ids.len(),
+            ),
+            entity_ids,
+            access: self.write_access_bitset.clone(),
+        }
+    }
+
+    /// The number of stored components
+    pub fn len(&self) -> usize {
+        self.entities_bitset
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum()
+    }
+
+    /// The number of component slots allocated, zero for zero-sized
+    /// components which never allocate
+    pub fn capacity(&self) -> usize {
+        if self.component_layout.size() == 0 {
+            0
+        } else {
+            self.cap
+        }
+    }
+
+    /// Frees the slots past the component of the highest entity id. Slots
+    /// are indexed by entity id, so the slots of deleted entities with a
+    /// lower id are kept.
+    pub fn shrink_to_fit(&mut self) {
+        if self.component_layout.size() == 0 || self.cap == 0 {
+            return;
+        }
+
+        let new_capacity = (0..self.cap)
+            .rev()
+            .find(|&i| self.entities_bitset.bit(i))
+            .map_or(0, |i| i + 1);
+        if new_capacity == self.cap {
+            return;
+        }
+
+        let component_size = self.component_layout.size();
+        let array_alignment = self.component_layout.align();
+        let previous_layout = Layout::from_size_align(self.cap * component_size, array_alignment)
+            .expect("Invalid layout when shrinking component store data");
+        if new_capacity == 0 {
+            // SAFETY: The data was allocated with the same allocator and the
+            // given layout is the one used to allocate it
+            unsafe {
+                std::alloc::dealloc(self.data.get_mut().as_ptr(), previous_layout);
+            }
+            self.data = UnsafeCell::new(NonNull::dangling());
+            self.cap = 0;
+            return;
+        }
+
+        // SAFETY:
+        // - self.data has been allocated with the same allocator
+        // - previous_layout matches the layout used to create the array
+        // - the new size is non-zero and smaller than the previous one
+        let new_data = unsafe {
+            std::alloc::realloc(
+                self.data.get_mut().as_ptr(),
+                previous_layout,
+                new_capacity * component_size,
+            )
+        };
+        self.cap = new_capacity;
+        self.data =
+            UnsafeCell::new(NonNull::new(new_data).expect("ComponentStore data allocation failed"));
+    }
+
     pub fn clear(&mut self) {
         for i in 0..self.cap {
             if self.entities_bitset.bit(i) {
@@ -259,7 +408,7 @@ pub unsafe fn drop_fn_of<T>(ptr: *mut u8) {
 #[derive(Debug)]
 pub struct ComponentRef<T> {
     inner: *const T,
-    access: Rc<RefCell<EntityBitSet>>,
+    access: SharedBitSet,
     entity_id: EntityId,
 }
 
@@ -275,15 +424,15 @@ impl<T> Deref for ComponentRef<T> {
 
 impl<T> Drop for ComponentRef<T> {
     fn drop(&mut self) {
-        self.access.borrow_mut().unset_bit(self.entity_id);
+        self.access.lock().unset_bit(self.entity_id);
     }
 }
 
 #[derive(Debug)]
 pub struct ComponentRefMut<T> {
     inner: *mut T,
-    access: Rc<RefCell<EntityBitSet>>,
-    dirty: Rc<RefCell<EntityBitSet>>,
+    access: SharedBitSet,
+    dirty: SharedBitSet,
     entity_id: EntityId,
 }
 
@@ -299,14 +448,59 @@ impl<T> Deref for ComponentRefMut<T> {
 
 impl<T> DerefMut for ComponentRefMut<T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        self.dirty.borrow_mut().set_bit(self.entity_id);
+        self.dirty.lock().set_bit(self.entity_id);
         unsafe { &mut *self.inner }
     }
 }
 
 impl<T> Drop for ComponentRefMut<T> {
     fn drop(&mut self) {
-        self.access.borrow_mut().unset_bit(self.entity_id);
+        self.access.lock().unset_bit(self.entity_id);
+    }
+}
+
+/// The components of consecutive entities, mutably borrowed as a single
+/// slice. Modifications don't mark the components as changed.
+pub struct ComponentSliceMut<'a, T> {
+    components: &'a mut [T],
+    entity_ids: Range<EntityId>,
+    access: SharedBitSet,
+}
+
+impl<T> ComponentSliceMut<'_, T> {
+    /// Returns the ids of the entities of the components, in the order of
+    /// the components
+    #[must_use]
+    pub fn entity_ids(&self) -> &[EntityId] {
+        &crate::ENTITY_IDS[self.entity_ids.clone()]
+    }
+
+    /// Returns the entity ids along with the components
+    pub fn as_mut_parts(&mut self) -> (&[EntityId], &mut [T]) {
+        (&crate::ENTITY_IDS[self.entity_ids.clone()], self.components)
+    }
+}
+
+impl<T> Deref for ComponentSliceMut<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.components
+    }
+}
+
+impl<T> DerefMut for ComponentSliceMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.components
+    }
+}
+
+impl<T> Drop for ComponentSliceMut<'_, T> {
+    fn drop(&mut self) {
+        let mut access = self.access.lock();
+        for entity_id in self.entity_ids.clone() {
+            access.unset_bit(entity_id);
+        }
     }
 }
 
@@ -328,6 +522,23 @@ mod tests {
         assert_eq!(store.cap, 6);
     }
 
+    #[test]
+    fn component_store_shrink_to_fit() {
+        let mut store = ComponentStore::new(Layout::new::<Position>(), drop_fn_of::<Position>);
+        store.store(2, Position { x: 43, y: 45 });
+        store.store(5, Position { x: 23, y: 12 });
+        store.delete(5);
+        assert_eq!((store.len(), store.capacity()), (1, 6));
+
+        store.shrink_to_fit();
+        assert_eq!((store.len(), store.capacity()), (1, 3));
+        assert_eq!(store.get::<Position>(2).unwrap().x, 43);
+
+        store.delete(2);
+        store.shrink_to_fit();
+        assert_eq!((store.len(), store.capacity()), (0, 0));
+    }
+
     #[test]
     fn component_store_get() {
         let mut store = ComponentStore::new(Layout::new::<Position>(), drop_fn_of::<Position>);
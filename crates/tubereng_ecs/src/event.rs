@@ -0,0 +1,139 @@
+use crate::resource::{ResourceRef, ResourceRefMut};
+
+use crate::{
+    commands::CommandQueue,
+    system::{Access, Argument},
+    Storage,
+};
+
+/// The events of a type, stored as a resource registered with
+/// [`Ecs::add_event`](crate::Ecs::add_event).
+///
+/// The events sent during a frame are read during the next frame: each call
+/// to [`Ecs::update_events`](crate::Ecs::update_events) makes the events sent
+/// since the previous call readable and drops the ones read so far. Every
+/// reader then sees each event exactly once, whatever the order of the
+/// systems.
+pub struct Events<E> {
+    readable: Vec<E>,
+    sent: Vec<E>,
+}
+
+impl<E> Events<E> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            readable: vec![],
+            sent: vec![],
+        }
+    }
+
+    pub fn send(&mut self, event: E) {
+        self.sent.push(event);
+    }
+
+    /// Iterates over the events sent during the previous frame
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.readable.iter()
+    }
+
+    /// Makes the events sent since the last update readable
+    pub fn update(&mut self) {
+        self.readable = std::mem::take(&mut self.sent);
+    }
+}
+
+impl<E> Default for Events<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// System argument sending events of a type
+pub struct EventWriter<'a, E: 'static>(ResourceRefMut<'a, Events<E>>);
+
+impl<E> EventWriter<'_, E> {
+    pub fn send(&mut self, event: E) {
+        self.0.send(event);
+    }
+}
+
+impl<E: 'static + Send + Sync> Argument for EventWriter<'_, E> {
+    type Type<'a> = EventWriter<'a, E>;
+
+    fn provide<'a>(
+        _command_queue: &'a CommandQueue,
+        storage: &'a Storage,
+    ) -> Option<Self::Type<'a>> {
+        Some(EventWriter(storage.resource_mut::<Events<E>>()?))
+    }
+
+    fn access(access: &mut Access) {
+        access.write::<Events<E>>();
+    }
+}
+
+/// System argument reading the events of a type sent during the previous
+/// frame
+pub struct EventReader<'a, E: 'static>(ResourceRef<'a, Events<E>>);
+
+impl<E> EventReader<'_, E> {
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.0.iter()
+    }
+}
+
+impl<E: 'static + Send + Sync> Argument for EventReader<'_, E> {
+    type Type<'a> = EventReader<'a, E>;
+
+    fn provide<'a>(
+        _command_queue: &'a CommandQueue,
+        storage: &'a Storage,
+    ) -> Option<Self::Type<'a>> {
+        Some(EventReader(storage.resource::<Events<E>>()?))
+    }
+
+    fn access(access: &mut Access) {
+        access.read::<Events<E>>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{system::Into, Ecs};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Hit(u32);
+
+    #[test]
+    fn events_are_read_on_the_next_frame() {
+        #[derive(Debug, Default)]
+        struct Log {
+            hits: Vec<u32>,
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.add_event::<Hit>();
+        ecs.insert_resource(Log::default());
+        let read_system = (|events: EventReader<Hit>, mut log: crate::system::ResMut<Log>| {
+            log.hits.extend(events.iter().map(|hit| hit.0));
+        })
+        .into_system();
+        let write_system = (|mut events: EventWriter<Hit>| events.send(Hit(3))).into_system();
+
+        ecs.run_single_run_system(&write_system);
+        ecs.run_single_run_system(&read_system);
+        assert!(ecs.resource::<Log>().unwrap().hits.is_empty());
+
+        ecs.update_events();
+        ecs.run_single_run_system(&read_system);
+        ecs.run_single_run_system(&read_system);
+        assert_eq!(ecs.resource::<Log>().unwrap().hits, vec![3, 3]);
+
+        ecs.update_events();
+        ecs.run_single_run_system(&read_system);
+        assert_eq!(ecs.resource::<Log>().unwrap().hits, vec![3, 3]);
+    }
+}
@@ -1,35 +1,128 @@
 #![warn(clippy::pedantic)]
 
+// Lets the code generated by the derive macros refer to this crate by name
+extern crate self as tubereng_ecs;
+
 use log::trace;
-use relationship::{Relationship, Relationships};
+use relationship::{Relationship, RelationshipKind, Relationships};
 use std::{
     alloc::Layout,
     any::{Any, TypeId},
-    cell::{Ref, RefCell, RefMut},
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    sync::{Mutex, PoisonError},
 };
 
+use bundle::{BundleError, EntityBundle};
 use commands::CommandQueue;
-use component_store::{drop_fn_of, ComponentRef, ComponentRefMut, ComponentStore};
+use component_store::{drop_fn_of, ComponentStore};
+use observer::{ObserverFn, Observers};
+use resource::{Resource, ResourceCell, ResourceRef, ResourceRefMut};
 
 mod bitset;
+pub mod bundle;
 pub mod commands;
 mod component_store;
+pub mod event;
+pub mod observer;
+pub mod profile;
 pub mod query;
 pub mod relationship;
+pub mod resource;
 pub mod system;
 
+pub use component_store::{ComponentRef, ComponentRefMut, ComponentSliceMut};
+pub use tubereng_ecs_macros::EntityDefinition;
+
 pub type EntityId = usize;
 pub type ComponentStores = HashMap<TypeId, ComponentStore>;
-pub type Resources = HashMap<TypeId, RefCell<Box<dyn Any>>>;
+pub type Resources = HashMap<TypeId, ResourceCell>;
 
 const MAX_ENTITY_COUNT: usize = 1024;
+
+/// Every entity id in order, sliced by [`Storage::component_slice`]
+static ENTITY_IDS: [EntityId; MAX_ENTITY_COUNT] = {
+    let mut entity_ids = [0; MAX_ENTITY_COUNT];
+    let mut i = 0;
+    while i < MAX_ENTITY_COUNT {
+        entity_ids[i] = i;
+        i += 1;
+    }
+    entity_ids
+};
+
+/// Component naming an entity for debugging purposes, see [`Storage::name`]
+/// and [`Storage::debug_entities`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Name(pub String);
+
+impl Name {
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A component of an entity, see [`Storage::entity_components`]
+#[derive(Debug)]
+pub struct EntityComponent {
+    pub type_id: TypeId,
+    /// A view of the component, for the component types registered with
+    /// [`Storage::register_debug_component`]
+    pub debug: Option<Box<dyn std::fmt::Debug>>,
+}
+
+/// The occupancy of a component store, see [`Storage::store_occupancy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StoreOccupancy {
+    pub type_id: TypeId,
+    pub component_count: usize,
+    /// The number of component slots allocated
+    pub capacity: usize,
+}
+
+/// Returns a debug view of the component of an entity stored in a component
+/// store of a registered component type
+type DebugViewFn = fn(&ComponentStore, EntityId) -> Option<Box<dyn std::fmt::Debug>>;
+
+fn debug_view_of<C>(
+    component_store: &ComponentStore,
+    entity_id: EntityId,
+) -> Option<Box<dyn std::fmt::Debug>>
+where
+    C: 'static + std::fmt::Debug,
+{
+    struct DebugView<C>(ComponentRef<C>);
+    impl<C: std::fmt::Debug> std::fmt::Debug for DebugView<C> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            (*self.0).fmt(f)
+        }
+    }
+
+    Some(Box::new(DebugView(component_store.get::<C>(entity_id)?)))
+}
+
+/// Debugging information about a live entity, see
+/// [`Storage::debug_entities`]
+#[derive(Debug)]
+pub struct EntityDebugInfo {
+    pub id: EntityId,
+    pub name: Option<String>,
+    /// The types of the components of the entity, sorted
+    pub component_types: Vec<TypeId>,
+}
 pub struct Storage {
     next_entity_id: EntityId,
     deleted_entities: Vec<EntityId>,
     component_stores: ComponentStores,
     relationships: Relationships,
     resources: Resources,
+    /// The resources mutably accessed since the last call to
+    /// [`Storage::clear_dirty_flags`]
+    changed_resources: Mutex<HashSet<TypeId>>,
+    /// Updates the [`Events`](event::Events) resource of each event type
+    event_updaters: HashMap<TypeId, fn(&mut Resources)>,
+    debug_views: HashMap<TypeId, DebugViewFn>,
+    observers: Observers,
 }
 
 impl Default for Storage {
@@ -46,7 +139,14 @@ impl Storage {
             deleted_entities: vec![],
             component_stores: ComponentStores::new(),
             resources: Resources::new(),
+            changed_resources: Mutex::new(HashSet::new()),
+            event_updaters: HashMap::new(),
+            debug_views: HashMap::from([(
+                TypeId::of::<Name>(),
+                debug_view_of::<Name> as DebugViewFn,
+            )]),
             relationships: Relationships::new(),
+            observers: Observers::default(),
         }
     }
 
@@ -64,6 +164,82 @@ impl Storage {
         for component_store in self.component_stores.values_mut() {
             component_store.clear_dirty_bitset();
         }
+        self.changed_resources
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Frees the memory of the component slots past the highest entity id
+    /// having the component, in each component store. Entity ids index the
+    /// stores and aren't remapped, so the slots of deleted entities below it
+    /// are kept for their ids to be reused.
+    pub fn compact(&mut self) {
+        for component_store in self.component_stores.values_mut() {
+            component_store.shrink_to_fit();
+        }
+    }
+
+    /// Returns the occupancy of each component store, sorted by type, to
+    /// decide whether [`Storage::compact`] is worthwhile
+    #[must_use]
+    pub fn store_occupancy(&self) -> Vec<StoreOccupancy> {
+        let mut occupancy = self
+            .component_stores
+            .iter()
+            .map(|(&type_id, component_store)| StoreOccupancy {
+                type_id,
+                component_count: component_store.len(),
+                capacity: component_store.capacity(),
+            })
+            .collect::<Vec<_>>();
+        occupancy.sort_by_key(|store_occupancy| store_occupancy.type_id);
+        occupancy
+    }
+
+    /// Returns true if a resource has been inserted or mutably accessed
+    /// since the last call to [`Storage::clear_dirty_flags`].
+    ///
+    /// Any mutable access marks the resource as changed, even if it isn't
+    /// actually modified.
+    #[must_use]
+    pub fn resource_changed<R: Any>(&self) -> bool {
+        self.changed_resources
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .contains(&TypeId::of::<R>())
+    }
+
+    /// Registers an event type, inserting its [`Events`](event::Events)
+    /// resource if it is missing
+    pub fn add_event<E: 'static + Send + Sync>(&mut self) {
+        if !self.contains_resource::<event::Events<E>>() {
+            self.insert_resource(event::Events::<E>::new());
+        }
+        self.event_updaters
+            .insert(TypeId::of::<E>(), |resources: &mut Resources| {
+                if let Some(events) = resources
+                    .get_mut(&TypeId::of::<event::Events<E>>())
+                    .and_then(|events| events.get_mut().downcast_mut::<event::Events<E>>())
+                {
+                    events.update();
+                }
+            });
+    }
+
+    /// Makes the events sent since the last call readable, see
+    /// [`Events`](event::Events)
+    pub fn update_events(&mut self) {
+        for update_events in self.event_updaters.values() {
+            update_events(&mut self.resources);
+        }
+    }
+
+    fn mark_resource_changed(&self, resource_type: TypeId) {
+        self.changed_resources
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(resource_type);
     }
 
     #[must_use]
@@ -75,79 +251,237 @@ impl Storage {
         component_store.dirty(entity_id)
     }
 
-    pub fn insert<ED>(&mut self, entity_definition: ED) -> EntityId
+    pub fn insert<B>(&mut self, bundle: B) -> EntityId
     where
-        ED: EntityDefinition,
+        B: Bundle,
     {
+        let entity_definition = bundle.into_components();
         let entity_id = self.allocate_entity();
         trace!("Inserting entity {entity_id} with definition {entity_definition:?}");
         entity_definition.write_into_component_stores(entity_id, &mut self.component_stores);
+        self.notify_entity_inserted(entity_id);
         entity_id
     }
 
-    pub fn insert_component<C: 'static>(&mut self, entity_id: EntityId, component: C) {
+    /// Inserts several entities with the same definition type, looking up
+    /// each component store once for the whole batch
+    pub fn insert_batch<B>(&mut self, bundles: Vec<B>) -> Vec<EntityId>
+    where
+        B: Bundle,
+    {
+        let entity_definitions = bundles
+            .into_iter()
+            .map(Bundle::into_components)
+            .collect::<Vec<_>>();
+        let entity_ids = entity_definitions
+            .iter()
+            .map(|_| self.allocate_entity())
+            .collect::<Vec<_>>();
+        trace!("Inserting entities {entity_ids:?}");
+        B::Components::write_batch_into_component_stores(
+            entity_definitions,
+            &entity_ids,
+            &mut self.component_stores,
+        );
+        for entity_id in &entity_ids {
+            self.notify_entity_inserted(*entity_id);
+        }
+        entity_ids
+    }
+
+    fn notify_entity_inserted(&self, entity_id: EntityId) {
+        for (component_type, observers) in self.observers.on_insert() {
+            if self.has_component_of_type(*component_type, entity_id) {
+                for observer in observers {
+                    observer(self, entity_id);
+                }
+            }
+        }
+    }
+
+    /// Instantiates the entities of a bundle, returning the id of its root
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if the root of the bundle has not been set
+    pub fn spawn_bundle(&mut self, bundle: EntityBundle) -> Result<EntityId, BundleError> {
+        bundle.instantiate(self)
+    }
+
+    pub fn insert_component<C: 'static + Send + Sync>(
+        &mut self,
+        entity_id: EntityId,
+        component: C,
+    ) {
         let component_store = self
             .component_stores
             .entry(TypeId::of::<C>())
             .or_insert(ComponentStore::new(Layout::new::<C>(), drop_fn_of::<C>));
         component_store.store(entity_id, component);
+        self.notify_observers(self.observers.on_insert(), TypeId::of::<C>(), entity_id);
     }
 
     pub fn remove_component<C: 'static>(&mut self, entity_id: EntityId) {
-        let Some(component_store) = self.component_stores.get_mut(&TypeId::of::<C>()) else {
+        if !self.has_component_of_type(TypeId::of::<C>(), entity_id) {
             return;
-        };
+        }
 
-        component_store.delete(entity_id);
+        self.notify_observers(self.observers.on_remove(), TypeId::of::<C>(), entity_id);
+        if let Some(component_store) = self.component_stores.get_mut(&TypeId::of::<C>()) {
+            component_store.delete(entity_id);
+        }
     }
 
     pub fn delete(&mut self, entity_id: EntityId) {
+        for (component_type, observers) in self.observers.on_remove() {
+            if self.has_component_of_type(*component_type, entity_id) {
+                for observer in observers {
+                    observer(self, entity_id);
+                }
+            }
+        }
         for component_store in self.component_stores.values_mut() {
             component_store.delete(entity_id);
         }
         self.deleted_entities.push(entity_id);
     }
 
+    /// Deletes every entity along with their components and relationship
+    /// edges, and resets the entity ids. Resources, relationship definitions
+    /// and observers are kept, removal observers are run for every deleted
+    /// component.
+    pub fn clear_entities(&mut self) {
+        for entity_id in 0..self.next_entity_id {
+            if self.deleted_entities.contains(&entity_id) {
+                continue;
+            }
+
+            for (component_type, observers) in self.observers.on_remove() {
+                if self.has_component_of_type(*component_type, entity_id) {
+                    for observer in observers {
+                        observer(self, entity_id);
+                    }
+                }
+            }
+        }
+
+        self.component_stores.clear();
+        self.relationships.clear_edges();
+        self.next_entity_id = 0;
+        self.deleted_entities.clear();
+    }
+
+    /// Deletes every entity like [`Storage::clear_entities`] and drops every
+    /// resource
+    pub fn clear(&mut self) {
+        self.clear_entities();
+        self.resources.clear();
+    }
+
+    /// Registers a callback run after a component of type `C` is inserted
+    /// into an entity
+    pub fn on_insert<C, F>(&mut self, observer: F)
+    where
+        C: 'static,
+        F: 'static + Fn(&Storage, EntityId) + Send + Sync,
+    {
+        self.observers.add_on_insert::<C>(Box::new(observer));
+    }
+
+    /// Registers a callback run before a component of type `C` is removed
+    /// from an entity, either explicitly or because the entity is deleted
+    pub fn on_remove<C, F>(&mut self, observer: F)
+    where
+        C: 'static,
+        F: 'static + Fn(&Storage, EntityId) + Send + Sync,
+    {
+        self.observers.add_on_remove::<C>(Box::new(observer));
+    }
+
+    fn notify_observers(
+        &self,
+        observers: &HashMap<TypeId, Vec<ObserverFn>>,
+        component_type: TypeId,
+        entity_id: EntityId,
+    ) {
+        for observer in observers.get(&component_type).into_iter().flatten() {
+            observer(self, entity_id);
+        }
+    }
+
+    fn has_component_of_type(&self, component_type: TypeId, entity_id: EntityId) -> bool {
+        !self.deleted_entities.contains(&entity_id)
+            && self
+                .component_stores
+                .get(&component_type)
+                .is_some_and(|component_store| component_store.contains(entity_id))
+    }
+
     pub fn insert_resource<R>(&mut self, resource: R)
     where
-        R: Any,
+        R: Resource,
     {
         self.resources
-            .insert(TypeId::of::<R>(), RefCell::new(Box::new(resource)));
+            .insert(TypeId::of::<R>(), ResourceCell::new(Box::new(resource)));
+        self.mark_resource_changed(TypeId::of::<R>());
+    }
+
+    #[must_use]
+    pub fn contains_resource<R: Any>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<R>())
+    }
+
+    /// Fetches a mutable resource, inserting the value returned by `f` first
+    /// if the resource is missing
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the resource is already borrowed
+    pub fn resource_or_insert_with<R: Resource>(
+        &mut self,
+        f: impl FnOnce() -> R,
+    ) -> ResourceRefMut<'_, R> {
+        self.mark_resource_changed(TypeId::of::<R>());
+        self.resources
+            .entry(TypeId::of::<R>())
+            .or_insert_with(|| ResourceCell::new(Box::new(f())))
+            .borrow_mut()
     }
 
     /// Fetches a resource from the Ecs
     ///
     /// # Panics
     ///
-    /// Will panic if the resource can't be downcasted to its actual type
+    /// Will panic if the resource is already mutably borrowed
     #[must_use]
-    pub fn resource<R: Any>(&self) -> Option<Ref<'_, R>> {
-        Some(Ref::map(
-            self.resources.get(&TypeId::of::<R>())?.borrow(),
-            |r| r.downcast_ref::<R>().expect("Couldn't downcast resource"),
-        ))
+    pub fn resource<R: Resource>(&self) -> Option<ResourceRef<'_, R>> {
+        Some(self.resources.get(&TypeId::of::<R>())?.borrow())
     }
 
     /// Fetches a mutable resource from the Ecs
     ///
     /// # Panics
     ///
-    /// Will panic if the resource can't be downcasted to its actual type
+    /// Will panic if the resource is already borrowed
     #[must_use]
-    pub fn resource_mut<R: Any>(&self) -> Option<RefMut<'_, R>> {
-        Some(RefMut::map(
-            self.resources.get(&TypeId::of::<R>())?.borrow_mut(),
-            |r| r.downcast_mut::<R>().expect("Couldn't downcast resource"),
-        ))
+    pub fn resource_mut<R: Resource>(&self) -> Option<ResourceRefMut<'_, R>> {
+        let resource = self.resources.get(&TypeId::of::<R>())?.borrow_mut();
+        self.mark_resource_changed(TypeId::of::<R>());
+        Some(resource)
     }
 
     pub fn insert_relationship<R: 'static>(&mut self, source: EntityId, target: EntityId) {
         self.relationships.insert::<R>(source, target);
     }
 
-    pub fn define_relationship<R: 'static>(&mut self) {
-        self.relationships.define::<R>();
+    /// Removes the edge from `source` to `target`, does nothing if there is
+    /// no such edge
+    pub fn remove_relationship<R: 'static>(&mut self, source: EntityId, target: EntityId) {
+        self.relationships.remove::<R>(source, target);
+    }
+
+    pub fn define_relationship<R: 'static>(&mut self, kind: RelationshipKind) {
+        self.relationships.define::<R>(kind);
     }
 
     #[must_use]
@@ -155,6 +489,15 @@ impl Storage {
         self.relationships.get::<R>()
     }
 
+    /// Calls `f` for each relationship type, in the order the relationships
+    /// were defined or first inserted into
+    pub fn for_each_relationship<F>(&self, f: F)
+    where
+        F: FnMut(TypeId, &Relationship),
+    {
+        self.relationships.for_each_defined(f);
+    }
+
     #[must_use]
     pub fn component<C>(&self, entity_id: EntityId) -> Option<ComponentRef<C>>
     where
@@ -169,6 +512,71 @@ impl Storage {
             .get(entity_id)
     }
 
+    /// Returns the [`Name`] of an entity, if it has one
+    #[must_use]
+    pub fn name(&self, entity_id: EntityId) -> Option<ComponentRef<Name>> {
+        self.component::<Name>(entity_id)
+    }
+
+    /// Lists the live entities with their name and the types of their
+    /// components, sorted by id
+    #[must_use]
+    pub fn debug_entities(&self) -> Vec<EntityDebugInfo> {
+        (0..self.next_entity_id)
+            .filter(|entity_id| !self.deleted_entities.contains(entity_id))
+            .map(|entity_id| {
+                let mut component_types = self
+                    .component_stores
+                    .iter()
+                    .filter(|(_, component_store)| component_store.contains(entity_id))
+                    .map(|(&component_type, _)| component_type)
+                    .collect::<Vec<_>>();
+                component_types.sort();
+                EntityDebugInfo {
+                    id: entity_id,
+                    name: self.name(entity_id).map(|name| name.0.clone()),
+                    component_types,
+                }
+            })
+            .collect()
+    }
+
+    /// Registers a component type whose components are listed with a debug
+    /// view by [`Storage::entity_components`]. [`Name`] is registered by
+    /// default.
+    pub fn register_debug_component<C>(&mut self)
+    where
+        C: 'static + std::fmt::Debug,
+    {
+        self.debug_views
+            .insert(TypeId::of::<C>(), debug_view_of::<C>);
+    }
+
+    /// Lists the components of an entity sorted by type, with a debug view
+    /// of the registered component types. The list is empty if the entity
+    /// doesn't exist.
+    #[must_use]
+    pub fn entity_components(&self, entity_id: EntityId) -> Vec<EntityComponent> {
+        if entity_id >= self.next_entity_id || self.deleted_entities.contains(&entity_id) {
+            return vec![];
+        }
+
+        let mut components = self
+            .component_stores
+            .iter()
+            .filter(|(_, component_store)| component_store.contains(entity_id))
+            .map(|(&type_id, component_store)| EntityComponent {
+                type_id,
+                debug: self
+                    .debug_views
+                    .get(&type_id)
+                    .and_then(|debug_view| debug_view(component_store, entity_id)),
+            })
+            .collect::<Vec<_>>();
+        components.sort_by_key(|component| component.type_id);
+        components
+    }
+
     #[must_use]
     pub fn component_mut<C>(&self, entity_id: EntityId) -> Option<ComponentRefMut<C>>
     where
@@ -183,15 +591,52 @@ impl Storage {
             .get_mut(entity_id)
     }
 
+    /// Mutably borrows every `C` component as a single slice along with the
+    /// ids of their entities, for systems processing many components in a
+    /// tight loop without borrowing them one by one.
+    ///
+    /// Components are stored by entity id, so the slice is only available
+    /// when the entities having a `C` component have consecutive ids, `None`
+    /// is returned otherwise. Only `Copy` components can be accessed this
+    /// way.
+    ///
+    /// Writes through the slice bypass the change detection, the modified
+    /// components must be marked with [`Storage::mark_component_dirty`].
+    ///
+    /// # Panics
+    ///
+    /// Will panic if one of the components is already borrowed
+    #[must_use]
+    pub fn component_slice<C>(&self) -> Option<ComponentSliceMut<'_, C>>
+    where
+        C: 'static + Copy,
+    {
+        let component_store = self.component_stores.get(&TypeId::of::<C>())?;
+        let entity_ids = component_store.dense_range()?;
+        // SAFETY: The store holds the components of type C, and every slot
+        // of the dense range holds a component
+        Some(unsafe { component_store.slice_mut::<C>(entity_ids) })
+    }
+
+    /// Marks the `C` component of an entity as changed, for the components
+    /// modified through [`Storage::component_slice`]
+    pub fn mark_component_dirty<C: 'static>(&self, entity_id: EntityId) {
+        if let Some(component_store) = self.component_stores.get(&TypeId::of::<C>()) {
+            if component_store.contains(entity_id) {
+                component_store.set_dirty(entity_id);
+            }
+        }
+    }
+
     #[must_use]
-    pub fn query<QD>(&self) -> query::State<QD>
+    pub fn query<QD>(&self) -> query::State<'_, QD>
     where
         QD: query::Definition,
     {
         query::State::new(
             &self.component_stores,
             &self.deleted_entities,
-            self.next_entity_id - 1,
+            self.next_entity_id.saturating_sub(1),
         )
     }
 
@@ -209,6 +654,9 @@ impl Storage {
 pub struct Ecs {
     storage: Storage,
     command_queue: CommandQueue,
+    /// The schedule changes queued by commands, applied by the next run of
+    /// the schedule
+    schedule_changes: Vec<system::ScheduleChange>,
 }
 
 impl Ecs {
@@ -217,6 +665,7 @@ impl Ecs {
         Ecs {
             storage: Storage::new(),
             command_queue: CommandQueue::new(0, &[]),
+            schedule_changes: vec![],
         }
     }
 
@@ -226,14 +675,36 @@ impl Ecs {
     }
 
     /// Inserts a new entity with its components into the Ecs
-    pub fn insert<ED>(&mut self, entity_definition: ED) -> EntityId
+    pub fn insert<B>(&mut self, bundle: B) -> EntityId
     where
-        ED: EntityDefinition,
+        B: Bundle,
     {
-        self.storage.insert(entity_definition)
+        self.storage.insert(bundle)
     }
 
-    pub fn insert_component<C: 'static>(&mut self, entity_id: EntityId, component: C) {
+    /// Instantiates the entities of a bundle into the Ecs, returning the id
+    /// of its root
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if the root of the bundle has not been set
+    pub fn spawn_bundle(&mut self, bundle: EntityBundle) -> Result<EntityId, BundleError> {
+        self.storage.spawn_bundle(bundle)
+    }
+
+    /// Inserts several entities with the same definition type
+    pub fn insert_batch<B>(&mut self, bundles: Vec<B>) -> Vec<EntityId>
+    where
+        B: Bundle,
+    {
+        self.storage.insert_batch(bundles)
+    }
+
+    pub fn insert_component<C: 'static + Send + Sync>(
+        &mut self,
+        entity_id: EntityId,
+        component: C,
+    ) {
         self.storage.insert_component(entity_id, component);
     }
 
@@ -246,10 +717,24 @@ impl Ecs {
         self.storage.delete(entity_id);
     }
 
+    /// Deletes every entity, keeping the resources.
+    ///
+    /// The ids returned by the command queue for commands queued before this
+    /// call are no longer valid, use [`CommandQueue::clear_entities`] to clear
+    /// the entities from a system.
+    pub fn clear_entities(&mut self) {
+        self.storage.clear_entities();
+    }
+
+    /// Deletes every entity and drops every resource
+    pub fn clear(&mut self) {
+        self.storage.clear();
+    }
+
     /// Inserts a resource into the Ecs, replaces it if already present
     pub fn insert_resource<R>(&mut self, resource: R)
     where
-        R: Any,
+        R: Resource,
     {
         self.storage.insert_resource(resource);
     }
@@ -258,8 +743,46 @@ impl Ecs {
         self.storage.insert_relationship::<R>(source, target);
     }
 
-    pub fn define_relationship<R: 'static>(&mut self) {
-        self.storage.define_relationship::<R>();
+    /// Removes the edge from `source` to `target`, does nothing if there is
+    /// no such edge
+    pub fn remove_relationship<R: 'static>(&mut self, source: EntityId, target: EntityId) {
+        self.storage.remove_relationship::<R>(source, target);
+    }
+
+    /// Registers a callback run after a component of type `C` is inserted
+    /// into an entity
+    pub fn on_insert<C, F>(&mut self, observer: F)
+    where
+        C: 'static,
+        F: 'static + Fn(&Storage, EntityId) + Send + Sync,
+    {
+        self.storage.on_insert::<C, F>(observer);
+    }
+
+    /// Registers a callback run before a component of type `C` is removed
+    /// from an entity, either explicitly or because the entity is deleted
+    pub fn on_remove<C, F>(&mut self, observer: F)
+    where
+        C: 'static,
+        F: 'static + Fn(&Storage, EntityId) + Send + Sync,
+    {
+        self.storage.on_remove::<C, F>(observer);
+    }
+
+    /// Defines a relationship type and the constraint enforced when inserting
+    /// its edges. Relationships inserted without being defined are
+    /// [`RelationshipKind::OneToMany`].
+    pub fn define_relationship<R: 'static>(&mut self, kind: RelationshipKind) {
+        self.storage.define_relationship::<R>(kind);
+    }
+
+    /// Registers a component type listed with a debug view, see
+    /// [`Storage::register_debug_component`]
+    pub fn register_debug_component<C>(&mut self)
+    where
+        C: 'static + std::fmt::Debug,
+    {
+        self.storage.register_debug_component::<C>();
     }
 
     pub fn relationship<R: 'static>(&self) -> Option<&Relationship> {
@@ -270,21 +793,41 @@ impl Ecs {
         &self.command_queue
     }
 
-    /// Retrieves a ``Ref`` to a stored resource or None if its not found
+    #[must_use]
+    pub fn contains_resource<R: Any>(&self) -> bool {
+        self.storage.contains_resource::<R>()
+    }
+
+    /// Retrieves a ``ResourceRefMut`` to a stored resource, inserting the
+    /// value returned by `f` first if the resource is missing
     ///
     /// # Panics
     ///
-    /// Will panic if the downcasting to the resource type
-    pub fn resource<R: Any>(&self) -> Option<Ref<'_, R>> {
+    /// Will panic if the resource is already borrowed
+    pub fn resource_or_insert_with<R: Resource>(
+        &mut self,
+        f: impl FnOnce() -> R,
+    ) -> ResourceRefMut<'_, R> {
+        self.storage.resource_or_insert_with(f)
+    }
+
+    /// Retrieves a ``ResourceRef`` to a stored resource or None if its not
+    /// found
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the resource is already mutably borrowed
+    pub fn resource<R: Resource>(&self) -> Option<ResourceRef<'_, R>> {
         self.storage.resource()
     }
 
-    /// Retrieves a ``RefMut`` to a stored resource or None if its not found
+    /// Retrieves a ``ResourceRefMut`` to a stored resource or None if its
+    /// not found
     ///
     /// # Panics
     ///
-    /// Will panic if the downcasting to the resource type
-    pub fn resource_mut<R: Any>(&self) -> Option<RefMut<'_, R>> {
+    /// Will panic if the resource is already borrowed
+    pub fn resource_mut<R: Resource>(&self) -> Option<ResourceRefMut<'_, R>> {
         self.storage.resource_mut()
     }
 
@@ -306,7 +849,7 @@ impl Ecs {
         self.storage.component_mut(entity_id)
     }
 
-    pub fn query<QD>(&mut self) -> query::State<QD>
+    pub fn query<QD>(&mut self) -> query::State<'_, QD>
     where
         QD: query::Definition,
     {
@@ -322,6 +865,30 @@ impl Ecs {
         self.storage.clear_dirty_flags();
     }
 
+    /// Frees the unused memory of the component stores, see
+    /// [`Storage::compact`]
+    pub fn compact(&mut self) {
+        self.storage.compact();
+    }
+
+    /// Registers an event type, see [`Events`](event::Events)
+    pub fn add_event<E: 'static + Send + Sync>(&mut self) {
+        self.storage.add_event::<E>();
+    }
+
+    /// Makes the events sent since the last call readable, the engine calls
+    /// it at the start of each frame
+    pub fn update_events(&mut self) {
+        self.storage.update_events();
+    }
+
+    /// Returns true if a resource has been inserted or mutably accessed
+    /// since the last call to [`Ecs::clear_dirty_flags`]
+    #[must_use]
+    pub fn resource_changed<R: Any>(&self) -> bool {
+        self.storage.resource_changed::<R>()
+    }
+
     pub fn process_command_queue(&mut self) {
         let mut command_queue =
             CommandQueue::new(self.storage.next_entity_id, &self.storage.deleted_entities);
@@ -338,12 +905,86 @@ impl Default for Ecs {
     }
 }
 
-pub trait EntityDefinition: BoxedEntityDefinition + std::fmt::Debug {
+pub trait EntityDefinition: BoxedEntityDefinition + std::fmt::Debug + Send + Sync {
     fn write_into_component_stores(
         self,
         entity_id: EntityId,
         component_stores: &mut ComponentStores,
     );
+
+    /// Writes several entity definitions, `entity_ids` holding the id of
+    /// each of them
+    fn write_batch_into_component_stores(
+        entity_definitions: Vec<Self>,
+        entity_ids: &[EntityId],
+        component_stores: &mut ComponentStores,
+    ) where
+        Self: Sized,
+    {
+        for (entity_definition, entity_id) in entity_definitions.into_iter().zip(entity_ids) {
+            entity_definition.write_into_component_stores(*entity_id, component_stores);
+        }
+    }
+}
+
+/// A reusable set of components spawned as a single entity, such as the
+/// components of a player.
+///
+/// Every [`EntityDefinition`] is a bundle, so tuples of components can be
+/// inserted directly. A struct becomes a bundle by converting itself into a
+/// tuple of components:
+///
+/// ```
+/// # use tubereng_ecs::{Bundle, Ecs};
+/// #[derive(Debug)]
+/// struct Position(i32, i32);
+/// #[derive(Debug)]
+/// struct Health(i32);
+///
+/// struct PlayerBundle {
+///     position: Position,
+/// }
+///
+/// impl Bundle for PlayerBundle {
+///     type Components = (Position, Health);
+///
+///     fn into_components(self) -> Self::Components {
+///         (self.position, Health(10))
+///     }
+/// }
+///
+/// let mut ecs = Ecs::new();
+/// ecs.insert(PlayerBundle { position: Position(2, 3) });
+/// ```
+pub trait Bundle {
+    type Components: EntityDefinition;
+
+    fn into_components(self) -> Self::Components;
+}
+
+impl<ED> Bundle for ED
+where
+    ED: EntityDefinition,
+{
+    type Components = Self;
+
+    fn into_components(self) -> Self::Components {
+        self
+    }
+}
+
+/// Writes a component of an entity, used by the code generated by
+/// `#[derive(EntityDefinition)]`
+#[doc(hidden)]
+pub fn store_component<C: 'static + Send + Sync>(
+    component_stores: &mut ComponentStores,
+    entity_id: EntityId,
+    component: C,
+) {
+    component_stores
+        .entry(TypeId::of::<C>())
+        .or_insert_with(|| ComponentStore::new(Layout::new::<C>(), drop_fn_of::<C>))
+        .store(entity_id, component);
 }
 
 pub trait BoxedEntityDefinition {
@@ -394,8 +1035,8 @@ macro_rules! impl_entity_definition_for_tuple {
     ($head:ident: $head_i:tt, $($tail:ident: $tail_i:tt,)*) => {
         impl<$head, $($tail,)*> EntityDefinition for ($head, $($tail,)*)
         where
-            $head: 'static + std::fmt::Debug,
-            $($tail: 'static + std::fmt::Debug,)*
+            $head: 'static + std::fmt::Debug + Send + Sync,
+            $($tail: 'static + std::fmt::Debug + Send + Sync,)*
         {
             fn write_into_component_stores(
                 self,
@@ -411,6 +1052,31 @@ macro_rules! impl_entity_definition_for_tuple {
                     .or_insert_with(|| ComponentStore::new(Layout::new::<$tail>(), drop_fn_of::<$tail>))
                     .store(entity_id, self.$tail_i);)*
             }
+
+            fn write_batch_into_component_stores(
+                entity_definitions: Vec<Self>,
+                entity_ids: &[EntityId],
+                component_stores: &mut ComponentStores,
+            ) {
+                let mut columns: (Vec<$head>, $(Vec<$tail>,)*) = Default::default();
+                for entity_definition in entity_definitions {
+                    columns.$head_i.push(entity_definition.$head_i);
+                    $(columns.$tail_i.push(entity_definition.$tail_i);)*
+                }
+
+                let component_store = component_stores
+                    .entry(TypeId::of::<$head>())
+                    .or_insert_with(|| ComponentStore::new(Layout::new::<$head>(), drop_fn_of::<$head>));
+                for (entity_id, component) in entity_ids.iter().zip(columns.$head_i) {
+                    component_store.store(*entity_id, component);
+                }
+                $(let component_store = component_stores
+                    .entry(TypeId::of::<$tail>())
+                    .or_insert_with(|| ComponentStore::new(Layout::new::<$tail>(), drop_fn_of::<$tail>));
+                for (entity_id, component) in entity_ids.iter().zip(columns.$tail_i) {
+                    component_store.store(*entity_id, component);
+                })*
+            }
         }
     };
     () => {}
@@ -423,6 +1089,12 @@ impl_entity_definition_for_tuple!(A: 0, B: 1, C: 2,);
 impl_entity_definition_for_tuple!(A: 0, B: 1, C: 2, D: 3,);
 impl_entity_definition_for_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4,);
 impl_entity_definition_for_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5,);
+impl_entity_definition_for_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6,);
+impl_entity_definition_for_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7,);
+impl_entity_definition_for_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7, I: 8,);
+impl_entity_definition_for_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7, I: 8, J: 9,);
+impl_entity_definition_for_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7, I: 8, J: 9, K: 10,);
+impl_entity_definition_for_tuple!(A: 0, B: 1, C: 2, D: 3, E: 4, F: 5, G: 6, H: 7, I: 8, J: 9, K: 10, L: 11,);
 
 #[cfg(test)]
 mod tests {
@@ -441,6 +1113,145 @@ mod tests {
         y: i32,
     }
 
+    #[test]
+    fn ecs_observers() {
+        use std::sync::{Arc, Mutex};
+
+        let events = Arc::new(Mutex::new(vec![]));
+        let mut ecs = Ecs::new();
+        let insert_events = events.clone();
+        ecs.on_insert::<Health, _>(move |storage, entity_id| {
+            let health = storage.component::<Health>(entity_id).unwrap().0;
+            insert_events
+                .lock()
+                .unwrap()
+                .push(("insert", entity_id, health));
+        });
+        let remove_events = events.clone();
+        ecs.on_remove::<Health, _>(move |storage, entity_id| {
+            let health = storage.component::<Health>(entity_id).unwrap().0;
+            remove_events
+                .lock()
+                .unwrap()
+                .push(("remove", entity_id, health));
+        });
+
+        let player = ecs.insert((Player, Health(10)));
+        let enemy = ecs.insert((Enemy,));
+        ecs.insert_component(enemy, Health(5));
+        ecs.remove_component::<Health>(enemy);
+        ecs.remove_component::<Health>(enemy);
+        ecs.delete(player);
+        ecs.delete(enemy);
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ("insert", player, 10),
+                ("insert", enemy, 5),
+                ("remove", enemy, 5),
+                ("remove", player, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn ecs_insert_batch() {
+        let mut ecs = Ecs::new();
+        let ids = ecs.insert_batch(vec![(Enemy, Health(5)), (Enemy, Health(2))]);
+        assert_eq!(ids, vec![0, 1]);
+        assert_eq!(*ecs.component::<Health>(1).unwrap(), Health(2));
+    }
+
+    #[test]
+    fn ecs_queued_insertions_keep_their_ids() {
+        let mut ecs = Ecs::new();
+        let queue = ecs.command_queue();
+        let first_enemy = queue.insert((Enemy, Health(5)));
+        let second_enemy = queue.insert((Enemy, Health(2)));
+        let player = queue.insert((Player, Position { x: 1, y: 2 }));
+        let third_enemy = queue.insert((Enemy, Health(1)));
+        ecs.process_command_queue();
+
+        assert_eq!(ecs.entity_count(), 4);
+        assert_eq!(*ecs.component::<Health>(first_enemy).unwrap(), Health(5));
+        assert_eq!(*ecs.component::<Health>(second_enemy).unwrap(), Health(2));
+        assert_eq!(
+            *ecs.component::<Position>(player).unwrap(),
+            Position { x: 1, y: 2 }
+        );
+        assert_eq!(*ecs.component::<Health>(third_enemy).unwrap(), Health(1));
+    }
+
+    #[test]
+    fn ecs_clear_entities() {
+        #[derive(Debug)]
+        struct MyResource;
+
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(MyResource);
+        let a = ecs.insert((Player, Health(10)));
+        let b = ecs.insert((Enemy, Health(5)));
+        ecs.insert_relationship::<relationship::ChildOf>(b, a);
+        ecs.delete(a);
+
+        ecs.clear_entities();
+        assert_eq!(ecs.entity_count(), 0);
+        assert_eq!(ecs.query::<&Health>().iter().count(), 0);
+        assert!(ecs
+            .relationship::<relationship::ChildOf>()
+            .unwrap()
+            .targets(b)
+            .is_none());
+        assert!(ecs.resource::<MyResource>().is_some());
+        assert_eq!(ecs.insert((Player,)), 0);
+
+        ecs.clear();
+        assert!(ecs.resource::<MyResource>().is_none());
+    }
+
+    #[test]
+    fn ecs_clear_entities_from_command_queue() {
+        let mut ecs = Ecs::new();
+        ecs.insert((Player,));
+        ecs.insert((Enemy,));
+
+        let queue = ecs.command_queue();
+        queue.clear_entities();
+        let enemy = queue.insert((Enemy, Health(3)));
+        ecs.process_command_queue();
+
+        assert_eq!(enemy, 0);
+        assert_eq!(ecs.entity_count(), 1);
+        assert_eq!(*ecs.component::<Health>(enemy).unwrap(), Health(3));
+    }
+
+    #[test]
+    fn ecs_query_single() {
+        let mut ecs = Ecs::new();
+        assert_eq!(
+            ecs.query::<&Player>().single().err(),
+            Some(query::SingleError::NoMatch)
+        );
+
+        let player = ecs.insert((Player, Health(10)));
+        ecs.insert((Enemy, Health(5)));
+        let (id, mut health) = ecs
+            .query::<(&mut Health, &Player)>()
+            .single_with_id()
+            .map(|(id, (health, _))| (id, health))
+            .unwrap();
+        health.0 = 8;
+        assert_eq!(id, player);
+        drop(health);
+        assert_eq!(*ecs.component::<Health>(player).unwrap(), Health(8));
+
+        assert_eq!(
+            ecs.query::<&Health>().single().err(),
+            Some(query::SingleError::MultipleMatches)
+        );
+    }
+
     #[test]
     fn ecs_new() {
         let ecs = Ecs::new();
@@ -577,6 +1388,261 @@ mod tests {
         assert_eq!(&*r, &SomeResource(10));
     }
 
+    #[test]
+    fn ecs_resource_or_insert_with() {
+        #[derive(Debug, PartialEq)]
+        struct SomeResource(i32);
+        let mut ecs = Ecs::new();
+        assert!(!ecs.contains_resource::<SomeResource>());
+
+        ecs.resource_or_insert_with(|| SomeResource(23)).0 += 1;
+        assert!(ecs.contains_resource::<SomeResource>());
+        ecs.resource_or_insert_with(|| SomeResource(0)).0 += 1;
+
+        let r = ecs.resource::<SomeResource>().unwrap();
+        assert_eq!(&*r, &SomeResource(25));
+    }
+
+    #[test]
+    fn storage_debug_entities() {
+        let mut storage = Storage::new();
+        let player = storage.insert((Name("player".to_string()), Health(10)));
+        let unnamed = storage.insert((Health(5),));
+        let deleted = storage.insert((Name("deleted".to_string()),));
+        storage.delete(deleted);
+
+        assert_eq!(storage.name(player).unwrap().as_str(), "player");
+        assert!(storage.name(unnamed).is_none());
+
+        let entities = storage.debug_entities();
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities[0].id, player);
+        assert_eq!(entities[0].name.as_deref(), Some("player"));
+        let mut player_component_types = vec![TypeId::of::<Name>(), TypeId::of::<Health>()];
+        player_component_types.sort();
+        assert_eq!(entities[0].component_types, player_component_types);
+        assert_eq!(entities[1].id, unnamed);
+        assert_eq!(entities[1].name, None);
+        assert_eq!(entities[1].component_types, [TypeId::of::<Health>()]);
+    }
+
+    #[test]
+    fn storage_entity_components() {
+        let mut storage = Storage::new();
+        storage.register_debug_component::<Health>();
+        let entity = storage.insert((
+            Name("player".to_string()),
+            Health(10),
+            Position { x: 1, y: 2 },
+        ));
+        let deleted = storage.insert((Health(5),));
+        storage.delete(deleted);
+
+        let components = storage.entity_components(entity);
+        let mut component_types = vec![
+            TypeId::of::<Name>(),
+            TypeId::of::<Health>(),
+            TypeId::of::<Position>(),
+        ];
+        component_types.sort();
+        assert_eq!(
+            components
+                .iter()
+                .map(|component| component.type_id)
+                .collect::<Vec<_>>(),
+            component_types
+        );
+
+        let debug_view = |type_id| {
+            components
+                .iter()
+                .find(|component| component.type_id == type_id)
+                .and_then(|component| component.debug.as_ref())
+                .map(|debug| format!("{debug:?}"))
+        };
+        assert_eq!(
+            debug_view(TypeId::of::<Name>()).as_deref(),
+            Some("Name(\"player\")")
+        );
+        assert_eq!(
+            debug_view(TypeId::of::<Health>()).as_deref(),
+            Some("Health(10)")
+        );
+        assert_eq!(debug_view(TypeId::of::<Position>()), None);
+        assert!(storage.entity_components(deleted).is_empty());
+    }
+
+    #[test]
+    fn storage_component_slice() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Velocity(i32);
+
+        let mut ecs = Ecs::new();
+        ecs.insert((Player,));
+        let first = ecs.insert((Position { x: 0, y: 0 }, Velocity(1)));
+        ecs.insert((Position { x: 0, y: 0 }, Velocity(2)));
+        {
+            let mut velocities = ecs.storage.component_slice::<Velocity>().unwrap();
+            let (entity_ids, velocities) = velocities.as_mut_parts();
+            assert_eq!(entity_ids, &[first, first + 1]);
+            for velocity in velocities.iter_mut() {
+                velocity.0 *= 10;
+            }
+        }
+        assert_eq!(*ecs.component::<Velocity>(first + 1).unwrap(), Velocity(20));
+
+        ecs.clear_dirty_flags();
+        ecs.storage.mark_component_dirty::<Velocity>(first);
+        let velocity_store = &ecs.storage.component_stores[&TypeId::of::<Velocity>()];
+        assert!(velocity_store.dirty(first));
+        assert!(!velocity_store.dirty(first + 1));
+
+        ecs.insert((Player,));
+        ecs.insert((Velocity(3),));
+        assert!(ecs.storage.component_slice::<Velocity>().is_none());
+    }
+
+    #[test]
+    fn storage_compact() {
+        #[derive(Debug)]
+        struct Health(u32);
+        let mut ecs = Ecs::new();
+        let entity_ids = (0..8).map(|_| ecs.insert((Health(1),))).collect::<Vec<_>>();
+        for entity_id in &entity_ids[2..] {
+            ecs.delete(*entity_id);
+        }
+
+        let occupancy = ecs.storage.store_occupancy();
+        let health_occupancy = occupancy
+            .iter()
+            .find(|store_occupancy| store_occupancy.type_id == TypeId::of::<Health>())
+            .unwrap();
+        assert_eq!(health_occupancy.component_count, 2);
+        assert_eq!(health_occupancy.capacity, 8);
+
+        ecs.compact();
+        let occupancy = ecs.storage.store_occupancy();
+        let health_occupancy = occupancy
+            .iter()
+            .find(|store_occupancy| store_occupancy.type_id == TypeId::of::<Health>())
+            .unwrap();
+        assert_eq!(health_occupancy.capacity, 2);
+        assert_eq!(
+            ecs.query::<&Health>()
+                .iter()
+                .map(|health| health.0)
+                .sum::<u32>(),
+            2
+        );
+    }
+
+    #[test]
+    fn ecs_insert_bundle() {
+        struct PlayerBundle {
+            position: Position,
+        }
+
+        impl Bundle for PlayerBundle {
+            type Components = (Player, Position, Health);
+
+            fn into_components(self) -> Self::Components {
+                (Player, self.position, Health(10))
+            }
+        }
+
+        let mut ecs = Ecs::new();
+        let player = ecs.insert(PlayerBundle {
+            position: Position { x: 1, y: 2 },
+        });
+        ecs.insert_batch(vec![
+            PlayerBundle {
+                position: Position { x: 3, y: 4 },
+            },
+            PlayerBundle {
+                position: Position { x: 5, y: 6 },
+            },
+        ]);
+        ecs.command_queue().insert(PlayerBundle {
+            position: Position { x: 7, y: 8 },
+        });
+        ecs.process_command_queue();
+
+        assert_eq!(ecs.entity_count(), 4);
+        assert_eq!(
+            *ecs.storage.component::<Position>(player).unwrap(),
+            Position { x: 1, y: 2 }
+        );
+        assert_eq!(ecs.query::<(&Player, &Health)>().iter().count(), 4);
+    }
+
+    #[test]
+    fn ecs_insert_derived_entity_definition() {
+        #[derive(Debug, EntityDefinition)]
+        struct PlayerDefinition {
+            player: Player,
+            position: Position,
+            #[component(skip)]
+            #[allow(dead_code)]
+            spawn_delay: f32,
+        }
+        #[derive(Debug, EntityDefinition)]
+        #[entity_definition(crate = crate)]
+        struct EnemyDefinition(Enemy, Health);
+
+        let mut ecs = Ecs::new();
+        let player = ecs.insert(PlayerDefinition {
+            player: Player,
+            position: Position { x: 1, y: 2 },
+            spawn_delay: 0.5,
+        });
+        let enemy = ecs.insert(EnemyDefinition(Enemy, Health(3)));
+
+        assert_eq!(
+            *ecs.storage.component::<Position>(player).unwrap(),
+            Position { x: 1, y: 2 }
+        );
+        assert!(ecs.storage.component::<f32>(player).is_none());
+        assert_eq!(*ecs.storage.component::<Health>(enemy).unwrap(), Health(3));
+        assert_eq!(ecs.query::<&Player>().iter().count(), 1);
+    }
+
+    #[test]
+    fn ecs_insert_and_query_large_tuples() {
+        #[derive(Debug, PartialEq)]
+        struct C0(i32);
+        #[derive(Debug)]
+        struct C1;
+        #[derive(Debug)]
+        struct C2;
+        #[derive(Debug)]
+        struct C3;
+        #[derive(Debug)]
+        struct C4;
+        #[derive(Debug)]
+        struct C5;
+        #[derive(Debug)]
+        struct C6;
+        #[derive(Debug, PartialEq)]
+        struct C7(i32);
+        #[derive(Debug)]
+        struct C8;
+        #[derive(Debug)]
+        struct C9;
+
+        let mut ecs = Ecs::new();
+        ecs.insert((C0(1), C1, C2, C3, C4, C5, C6, C7(2), C8, C9));
+        ecs.insert_batch(vec![(C0(3), C1, C2, C3, C4, C5, C6, C7(4), C8, C9)]);
+        ecs.insert((C0(5), C1));
+
+        let mut query = ecs.query::<(&C0, &C1, &C2, &C3, &C4, &C5, &C6, &mut C7)>();
+        let mut values = vec![];
+        for (c0, _, _, _, _, _, _, mut c7) in query.iter() {
+            c7.0 += 1;
+            values.push((c0.0, c7.0));
+        }
+        assert_eq!(values, vec![(1, 3), (3, 5)]);
+    }
+
     #[test]
     fn ecs_insert_relationship() {
         struct ChildOf;
@@ -592,6 +1658,46 @@ mod tests {
             .contains(&entity_b));
     }
 
+    #[test]
+    fn ecs_remove_relationship() {
+        struct ChildOf;
+        let mut ecs = Ecs::new();
+        let entity_a = ecs.insert(());
+        let entity_b = ecs.insert(());
+        ecs.insert_relationship::<ChildOf>(entity_b, entity_a);
+        ecs.remove_relationship::<ChildOf>(entity_b, entity_a);
+        ecs.remove_relationship::<ChildOf>(entity_a, entity_b);
+
+        let relationship = ecs.relationship::<ChildOf>().unwrap();
+        assert!(!relationship.sources(entity_a).unwrap().contains(&entity_b));
+        assert!(relationship.successors(entity_b).is_empty());
+        assert!(relationship.ancestors(entity_a).is_empty());
+    }
+
+    #[test]
+    fn ecs_resource_changed() {
+        #[derive(Debug)]
+        struct Score(u32);
+
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(Score(0));
+        assert!(ecs.resource_changed::<Score>());
+        ecs.clear_dirty_flags();
+        assert!(!ecs.resource_changed::<Score>());
+
+        assert_eq!(ecs.resource::<Score>().unwrap().0, 0);
+        assert!(!ecs.resource_changed::<Score>());
+        ecs.resource_mut::<Score>().unwrap().0 += 1;
+        assert!(ecs.resource_changed::<Score>());
+        assert!(!ecs.resource_changed::<Health>());
+
+        ecs.clear_dirty_flags();
+        let mut schedule = system::Schedule::new();
+        schedule.add_system(&(), |mut score: system::ResMut<Score>| score.0 += 1);
+        schedule.run_systems(&mut ecs);
+        assert!(ecs.resource_changed::<Score>());
+    }
+
     #[test]
     fn storage_clear_dirty_flags() {
         let mut storage = Storage::new();
@@ -0,0 +1,36 @@
+use std::{any::TypeId, collections::HashMap};
+
+use crate::{EntityId, Storage};
+
+/// Callback run when a component is inserted into or removed from an entity
+pub type ObserverFn = Box<dyn Fn(&Storage, EntityId) + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct Observers {
+    on_insert: HashMap<TypeId, Vec<ObserverFn>>,
+    on_remove: HashMap<TypeId, Vec<ObserverFn>>,
+}
+
+impl Observers {
+    pub fn add_on_insert<C: 'static>(&mut self, observer: ObserverFn) {
+        self.on_insert
+            .entry(TypeId::of::<C>())
+            .or_default()
+            .push(observer);
+    }
+
+    pub fn add_on_remove<C: 'static>(&mut self, observer: ObserverFn) {
+        self.on_remove
+            .entry(TypeId::of::<C>())
+            .or_default()
+            .push(observer);
+    }
+
+    pub fn on_insert(&self) -> &HashMap<TypeId, Vec<ObserverFn>> {
+        &self.on_insert
+    }
+
+    pub fn on_remove(&self) -> &HashMap<TypeId, Vec<ObserverFn>> {
+        &self.on_remove
+    }
+}
@@ -0,0 +1,90 @@
+//! Per-system execution times.
+//!
+//! Timing the systems has a cost, so [`Schedule::run_systems`] only measures
+//! them while a [`SystemProfile`] resource is present:
+//!
+//! ```ignore
+//! ecs.insert_resource(SystemProfile::new());
+//! schedule.run_systems(&mut ecs);
+//! for (label, duration) in ecs.resource::<SystemProfile>().unwrap().slowest(5) {
+//!     println!("{label}: {duration:?}");
+//! }
+//! ```
+//!
+//! [`Schedule::run_systems`]: crate::system::Schedule::run_systems
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// The weight of the last run of a system in its rolling average
+const SMOOTHING_FACTOR: f64 = 0.1;
+
+/// Resource holding a rolling average of the execution time of each system,
+/// keyed by system label
+#[derive(Debug, Default)]
+pub struct SystemProfile {
+    average_durations: HashMap<&'static str, Duration>,
+}
+
+impl SystemProfile {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records an execution of a system. Systems sharing a label share their
+    /// average.
+    pub fn record(&mut self, label: &'static str, duration: Duration) {
+        self.average_durations
+            .entry(label)
+            .and_modify(|average| {
+                *average =
+                    average.mul_f64(1.0 - SMOOTHING_FACTOR) + duration.mul_f64(SMOOTHING_FACTOR);
+            })
+            .or_insert(duration);
+    }
+
+    /// Returns the average execution time of a system, `None` if it never
+    /// ran while profiling
+    #[must_use]
+    pub fn average(&self, label: &str) -> Option<Duration> {
+        self.average_durations.get(label).copied()
+    }
+
+    /// Returns the `count` systems with the highest average execution time,
+    /// slowest first
+    #[must_use]
+    pub fn slowest(&self, count: usize) -> Vec<(&'static str, Duration)> {
+        let mut durations = self
+            .average_durations
+            .iter()
+            .map(|(label, duration)| (*label, *duration))
+            .collect::<Vec<_>>();
+        durations.sort_unstable_by(|(label_a, duration_a), (label_b, duration_b)| {
+            duration_b.cmp(duration_a).then(label_a.cmp(label_b))
+        });
+        durations.truncate(count);
+        durations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_keeps_a_rolling_average() {
+        let mut profile = SystemProfile::new();
+        profile.record("fast", Duration::from_millis(1));
+        profile.record("slow", Duration::from_millis(10));
+        profile.record("slow", Duration::from_millis(20));
+
+        assert_eq!(profile.average("fast"), Some(Duration::from_millis(1)));
+        assert_eq!(profile.average("slow"), Some(Duration::from_millis(11)));
+        assert_eq!(profile.average("unknown"), None);
+        assert_eq!(
+            profile.slowest(1),
+            vec![("slow", Duration::from_millis(11))]
+        );
+    }
+}
@@ -2,9 +2,29 @@ use std::{any::TypeId, marker::PhantomData};
 
 use crate::{
     component_store::{ComponentRef, ComponentRefMut},
+    system::Access,
     ComponentStores, EntityId,
 };
 
+/// Error returned by the single queries when the number of matching entities
+/// isn't exactly one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SingleError {
+    NoMatch,
+    MultipleMatches,
+}
+
+impl std::fmt::Display for SingleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SingleError::NoMatch => write!(f, "no entity matches the query"),
+            SingleError::MultipleMatches => write!(f, "several entities match the query"),
+        }
+    }
+}
+
+impl std::error::Error for SingleError {}
+
 pub struct State<'w, QD>
 where
     QD: Definition,
@@ -33,6 +53,46 @@ where
         }
     }
 
+    /// Fetches the item of a single entity, or None if the entity doesn't
+    /// match the query
+    pub fn get(&mut self, entity_id: EntityId) -> Option<QD::Item<'_>> {
+        if entity_id > self.max_entity_index || self.deleted_entities.contains(&entity_id) {
+            return None;
+        }
+
+        QD::fetch(self.component_stores, entity_id)
+    }
+
+    /// Returns the item of the only entity matching the query. Mutable
+    /// access is obtained by querying `&mut C`.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if no entity or more than one entity matches the
+    /// query
+    pub fn single(&mut self) -> Result<QD::Item<'w>, SingleError> {
+        self.single_with_id().map(|(_, item)| item)
+    }
+
+    /// Returns the id and the item of the only entity matching the query
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if no entity or more than one entity matches the
+    /// query
+    pub fn single_with_id(&mut self) -> Result<(EntityId, QD::Item<'w>), SingleError> {
+        let mut iter = self.iter_with_ids();
+        let first = iter.next().ok_or(SingleError::NoMatch)?;
+        if iter.next().is_some() {
+            return Err(SingleError::MultipleMatches);
+        }
+
+        Ok(first)
+    }
+
+    /// Iterates over the items of the matching entities, in ascending entity
+    /// id order. The order is guaranteed so systems can rely on it for
+    /// deterministic tie-breaking and replays.
     pub fn iter<'s>(&'s mut self) -> Iter<'w, 's, QD> {
         Iter::new(
             self,
@@ -42,6 +102,8 @@ where
         )
     }
 
+    /// Iterates over the ids and the items of the matching entities, in
+    /// ascending entity id order like [`State::iter`]
     pub fn iter_with_ids<'s>(&'s mut self) -> IterWithIds<'w, 's, QD> {
         IterWithIds::new(
             self,
@@ -84,7 +146,7 @@ where
     }
 }
 
-impl<'w, 's, QD> Iterator for IterWithIds<'w, 's, QD>
+impl<'w, QD> Iterator for IterWithIds<'w, '_, QD>
 where
     QD: Definition,
 {
@@ -148,7 +210,7 @@ where
     }
 }
 
-impl<'w, 's, QD> Iterator for Iter<'w, 's, QD>
+impl<'w, QD> Iterator for Iter<'w, '_, QD>
 where
     QD: Definition,
 {
@@ -164,6 +226,9 @@ pub trait Definition {
     fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>>
     where
         Self: Sized;
+
+    /// Declares the component stores read and written by the query
+    fn access(access: &mut Access);
 }
 
 macro_rules! impl_definition_for_tuples {
@@ -177,6 +242,11 @@ macro_rules! impl_definition_for_tuples {
                     $($tail::fetch(component_stores, entity_id)?,)*
                 ))
             }
+
+            fn access(access: &mut Access) {
+                $head::access(access);
+                $($tail::access(access);)*
+            }
         }
 
         impl_definition_for_tuples!($($tail,)*);
@@ -184,7 +254,7 @@ macro_rules! impl_definition_for_tuples {
     () => {};
 }
 
-impl_definition_for_tuples!(A, B, C, D, E, F,);
+impl_definition_for_tuples!(A, B, C, D, E, F, G, H, I, J, K, L,);
 
 pub struct DirtyState<C>(PhantomData<C>);
 impl<C: 'static> Definition for DirtyState<C> {
@@ -196,14 +266,150 @@ impl<C: 'static> Definition for DirtyState<C> {
     {
         Some(component_stores.get(&TypeId::of::<C>())?.dirty(entity_id))
     }
+
+    fn access(access: &mut Access) {
+        access.read::<C>();
+    }
+}
+
+/// A condition on the components of an entity, usable in a query definition
+/// where it yields `()` for the matching entities
+pub trait Filter {
+    fn matches(component_stores: &ComponentStores, entity_id: EntityId) -> bool;
+
+    /// Declares the component stores read by the filter, checking whether an
+    /// entity has a component doesn't borrow it
+    fn access(_access: &mut Access) {}
+}
+
+/// Matches the entities having a `C` component, without borrowing it
+pub struct With<C>(PhantomData<C>);
+impl<C: 'static> Filter for With<C> {
+    fn matches(component_stores: &ComponentStores, entity_id: EntityId) -> bool {
+        component_stores
+            .get(&TypeId::of::<C>())
+            .is_some_and(|store| store.contains(entity_id))
+    }
+}
+
+/// Matches the entities without a `C` component
+pub struct Without<C>(PhantomData<C>);
+impl<C: 'static> Filter for Without<C> {
+    fn matches(component_stores: &ComponentStores, entity_id: EntityId) -> bool {
+        !With::<C>::matches(component_stores, entity_id)
+    }
+}
+
+/// Matches the entities whose `C` component changed since the dirty flags
+/// were last cleared
+pub struct Changed<C>(PhantomData<C>);
+impl<C: 'static> Filter for Changed<C> {
+    fn matches(component_stores: &ComponentStores, entity_id: EntityId) -> bool {
+        component_stores
+            .get(&TypeId::of::<C>())
+            .is_some_and(|store| store.contains(entity_id) && store.dirty(entity_id))
+    }
+
+    fn access(access: &mut Access) {
+        access.read::<C>();
+    }
+}
+
+/// Matches the entities matching any filter of the tuple `F`, e.g.
+/// `Or<(With<Enemy>, With<Crate>)>`
+pub struct Or<F>(PhantomData<F>);
+impl<F: Filters> Filter for Or<F> {
+    fn matches(component_stores: &ComponentStores, entity_id: EntityId) -> bool {
+        F::any(component_stores, entity_id)
+    }
+
+    fn access(access: &mut Access) {
+        F::access(access);
+    }
+}
+
+/// Matches the entities matching every filter of the tuple `F`, to be nested
+/// in an [`Or`]
+pub struct And<F>(PhantomData<F>);
+impl<F: Filters> Filter for And<F> {
+    fn matches(component_stores: &ComponentStores, entity_id: EntityId) -> bool {
+        F::all(component_stores, entity_id)
+    }
+
+    fn access(access: &mut Access) {
+        F::access(access);
+    }
 }
 
+/// A tuple of filters combined by [`Or`] and [`And`]
+pub trait Filters {
+    fn any(component_stores: &ComponentStores, entity_id: EntityId) -> bool;
+    fn all(component_stores: &ComponentStores, entity_id: EntityId) -> bool;
+    fn access(access: &mut Access);
+}
+
+macro_rules! impl_filters_for_tuples {
+    ($head:tt, $($tail:tt,)*) => {
+        impl<$head: Filter, $($tail: Filter,)*> Filters for ($head, $($tail,)*) {
+            fn any(component_stores: &ComponentStores, entity_id: EntityId) -> bool {
+                $head::matches(component_stores, entity_id)
+                    $(|| $tail::matches(component_stores, entity_id))*
+            }
+
+            fn all(component_stores: &ComponentStores, entity_id: EntityId) -> bool {
+                $head::matches(component_stores, entity_id)
+                    $(&& $tail::matches(component_stores, entity_id))*
+            }
+
+            fn access(access: &mut Access) {
+                $head::access(access);
+                $($tail::access(access);)*
+            }
+        }
+
+        impl_filters_for_tuples!($($tail,)*);
+    };
+    () => {};
+}
+
+impl_filters_for_tuples!(A, B, C, D, E, F, G, H,);
+
+macro_rules! impl_definition_for_filters {
+    ($($filter:ident<$param:ident: $bound:tt>),*) => {
+        $(
+            impl<$param: $bound> Definition for $filter<$param> {
+                type Item<'a> = ();
+
+                fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>> {
+                    Self::matches(component_stores, entity_id).then_some(())
+                }
+
+                fn access(access: &mut Access) {
+                    <Self as Filter>::access(access);
+                }
+            }
+        )*
+    };
+}
+
+impl_definition_for_filters!(
+    With<C: 'static>,
+    Without<C: 'static>,
+    Changed<C: 'static>,
+    Or<F: Filters>,
+    And<F: Filters>
+);
+
 impl<T: 'static> Definition for &T {
     type Item<'a> = ComponentRef<T>;
 
     fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>> {
         component_stores.get(&TypeId::of::<T>())?.get(entity_id)
     }
+
+    fn access(access: &mut Access) {
+        access.read::<T>();
+    }
 }
 
 impl<T: 'static> Definition for &mut T {
@@ -212,6 +418,10 @@ impl<T: 'static> Definition for &mut T {
     fn fetch(component_stores: &ComponentStores, entity_id: usize) -> Option<Self::Item<'_>> {
         component_stores.get(&TypeId::of::<T>())?.get_mut(entity_id)
     }
+
+    fn access(access: &mut Access) {
+        access.write::<T>();
+    }
 }
 
 #[cfg(test)]
@@ -222,6 +432,12 @@ mod tests {
 
     #[derive(Debug)]
     struct Name(&'static str);
+    #[derive(Debug)]
+    struct Enemy;
+    #[derive(Debug)]
+    struct Crate;
+    #[derive(Debug)]
+    struct Invulnerable;
 
     #[test]
     fn set_component_dirty_flag() {
@@ -243,4 +459,84 @@ mod tests {
         assert!(ecs.query::<DirtyState<Name>>().iter().next().unwrap());
         assert!(ecs.storage.component_stores[&TypeId::of::<Name>()].dirty(entity));
     }
+
+    #[test]
+    fn or_filter() {
+        let mut ecs = Ecs::new();
+        let enemy = ecs.insert((Name("Enemy"), Enemy));
+        let crate_entity = ecs.insert((Name("Crate"), Crate));
+        ecs.insert((Name("Tree"),));
+
+        let matching = ecs
+            .query::<(&Name, Or<(With<Enemy>, With<Crate>)>)>()
+            .iter_with_ids()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        assert_eq!(matching, vec![enemy, crate_entity]);
+    }
+
+    #[test]
+    fn nested_filters() {
+        let mut ecs = Ecs::new();
+        let enemy = ecs.insert((Name("Enemy"), Enemy));
+        ecs.insert((Name("Boss"), Enemy, Invulnerable));
+        let crate_entity = ecs.insert((Name("Crate"), Crate));
+        ecs.insert((Name("Tree"),));
+
+        let matching = ecs
+            .query::<And<(
+                With<Name>,
+                Or<(And<(With<Enemy>, Without<Invulnerable>)>, With<Crate>)>,
+            )>>()
+            .iter_with_ids()
+            .map(|(id, ())| id)
+            .collect::<Vec<_>>();
+        assert_eq!(matching, vec![enemy, crate_entity]);
+    }
+
+    #[test]
+    fn changed_filter() {
+        let mut ecs = Ecs::new();
+        ecs.insert((Name("Unchanged"),));
+        let changed = ecs.insert((Name("Changed"),));
+        ecs.storage.clear_dirty_flags();
+        ecs.query::<&mut Name>().get(changed).unwrap().0 = "Still changed";
+
+        let matching = ecs
+            .query::<Changed<Name>>()
+            .iter_with_ids()
+            .map(|(id, ())| id)
+            .collect::<Vec<_>>();
+        assert_eq!(matching, vec![changed]);
+    }
+
+    #[test]
+    fn iteration_order_is_ascending_entity_id() {
+        let mut ecs = Ecs::new();
+        let first = ecs.insert((Name("First"),));
+        let second = ecs.insert((Name("Second"),));
+        let third = ecs.insert((Name("Third"), Enemy));
+        ecs.delete(second);
+        let reused = ecs.insert((Name("Reused"),));
+        let fourth = ecs.insert((Enemy, Name("Fourth")));
+
+        let mut expected = vec![first, third, reused, fourth];
+        expected.sort_unstable();
+        let ids = ecs
+            .query::<&Name>()
+            .iter_with_ids()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, expected);
+        assert_eq!(
+            ecs.query::<&Name>()
+                .iter()
+                .map(|name| name.0)
+                .collect::<Vec<_>>(),
+            expected
+                .iter()
+                .map(|id| ecs.query::<&Name>().get(*id).unwrap().0)
+                .collect::<Vec<_>>()
+        );
+    }
 }
@@ -7,40 +7,128 @@ use crate::EntityId;
 
 pub struct ChildOf;
 
+/// Constraint enforced by a relationship when edges are inserted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RelationshipKind {
+    /// No constraint, sources and targets can have any number of edges
+    #[default]
+    OneToMany,
+    /// Each source has at most one target and each target at most one source,
+    /// inserting an edge removes the previous edges of both entities
+    OneToOne,
+    /// Each source has at most one target, inserting an edge replaces the
+    /// previous target of the source
+    Exclusive,
+}
+
 pub(crate) struct Relationships {
     relationships: HashMap<TypeId, Relationship>,
+    /// The relationship types in the order they were registered
+    registration_order: Vec<TypeId>,
 }
 
 impl Relationships {
     pub fn new() -> Self {
         Self {
             relationships: HashMap::new(),
+            registration_order: vec![],
         }
     }
 
-    pub fn define<R: 'static>(&mut self) {
-        self.relationships
-            .insert(TypeId::of::<R>(), Relationship::default());
+    pub fn define<R: 'static>(&mut self, kind: RelationshipKind) {
+        let relationship_type = TypeId::of::<R>();
+        if self
+            .relationships
+            .insert(relationship_type, Relationship::new(kind))
+            .is_none()
+        {
+            self.registration_order.push(relationship_type);
+        }
     }
 
     pub fn insert<R: 'static>(&mut self, source: EntityId, target: EntityId) {
-        let relationship = self.relationships.entry(TypeId::of::<R>()).or_default();
+        let relationship_type = TypeId::of::<R>();
+        let relationship = self
+            .relationships
+            .entry(relationship_type)
+            .or_insert_with(|| {
+                self.registration_order.push(relationship_type);
+                Relationship::default()
+            });
         relationship.add(source, target);
     }
 
+    /// Removes the edge from `source` to `target`, does nothing if there is
+    /// no such edge
+    pub fn remove<R: 'static>(&mut self, source: EntityId, target: EntityId) {
+        if let Some(relationship) = self.relationships.get_mut(&TypeId::of::<R>()) {
+            relationship.remove(source, target);
+        }
+    }
+
     pub fn get<R: 'static>(&self) -> Option<&Relationship> {
         self.relationships.get(&TypeId::of::<R>())
     }
+
+    /// Removes every edge while keeping the relationship definitions
+    pub fn clear_edges(&mut self) {
+        for relationship in self.relationships.values_mut() {
+            relationship.sources_for_entity.clear();
+            relationship.targets_for_entity.clear();
+        }
+    }
+
+    /// Calls `f` for each relationship, in the order the relationships were
+    /// defined or first inserted into
+    pub fn for_each_defined<F>(&self, mut f: F)
+    where
+        F: FnMut(TypeId, &Relationship),
+    {
+        for relationship_type in &self.registration_order {
+            f(*relationship_type, &self.relationships[relationship_type]);
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct Relationship {
+    kind: RelationshipKind,
     sources_for_entity: HashMap<EntityId, HashSet<EntityId>>,
     targets_for_entity: HashMap<EntityId, HashSet<EntityId>>,
 }
 
 impl Relationship {
+    #[must_use]
+    pub fn new(kind: RelationshipKind) -> Self {
+        Self {
+            kind,
+            ..Default::default()
+        }
+    }
+
+    #[must_use]
+    pub fn kind(&self) -> RelationshipKind {
+        self.kind
+    }
+
+    /// Adds an edge from `source` to `target`, removing the edges that would
+    /// break the constraint of the relationship kind
     pub fn add(&mut self, source: EntityId, target: EntityId) {
+        if matches!(
+            self.kind,
+            RelationshipKind::OneToOne | RelationshipKind::Exclusive
+        ) {
+            for previous_target in self.targets_for_entity.remove(&source).unwrap_or_default() {
+                self.remove(source, previous_target);
+            }
+        }
+
+        if self.kind == RelationshipKind::OneToOne {
+            for previous_source in self.sources_for_entity.remove(&target).unwrap_or_default() {
+                self.remove(previous_source, target);
+            }
+        }
+
         self.sources_for_entity
             .entry(target)
             .or_default()
@@ -51,6 +139,27 @@ impl Relationship {
             .insert(target);
     }
 
+    pub fn remove(&mut self, source: EntityId, target: EntityId) {
+        if let Some(sources) = self.sources_for_entity.get_mut(&target) {
+            sources.remove(&source);
+        }
+        if let Some(targets) = self.targets_for_entity.get_mut(&source) {
+            targets.remove(&target);
+        }
+    }
+
+    /// Returns every `(source, target)` edge of the relationship, sorted by
+    /// source then target
+    pub fn edges(&self) -> impl Iterator<Item = (EntityId, EntityId)> {
+        let mut edges = self
+            .targets_for_entity
+            .iter()
+            .flat_map(|(source, targets)| targets.iter().map(|target| (*source, *target)))
+            .collect::<Vec<_>>();
+        edges.sort_unstable();
+        edges.into_iter()
+    }
+
     #[must_use]
     pub fn sources(&self, target: EntityId) -> Option<&HashSet<EntityId>> {
         self.sources_for_entity.get(&target)
@@ -107,6 +216,80 @@ impl Relationship {
 mod tests {
     use super::*;
 
+    #[test]
+    fn for_each_defined_in_registration_order() {
+        struct First;
+        struct Second;
+        struct Third;
+
+        let mut relationships = Relationships::new();
+        relationships.insert::<Second>(0, 1);
+        relationships.define::<Third>(RelationshipKind::OneToOne);
+        relationships.define::<First>(RelationshipKind::Exclusive);
+        relationships.define::<Second>(RelationshipKind::OneToMany);
+        relationships.insert::<Third>(1, 2);
+
+        let mut relationship_types = vec![];
+        relationships.for_each_defined(|relationship_type, _| {
+            relationship_types.push(relationship_type);
+        });
+        assert_eq!(
+            relationship_types,
+            [
+                TypeId::of::<Second>(),
+                TypeId::of::<Third>(),
+                TypeId::of::<First>()
+            ]
+        );
+    }
+
+    #[test]
+    fn exclusive_relationship_replaces_target() {
+        let mut relationship = Relationship::new(RelationshipKind::Exclusive);
+        relationship.add(1, 2);
+        relationship.add(1, 3);
+        relationship.add(4, 3);
+
+        assert_eq!(relationship.targets(1), Some(&HashSet::from([3])));
+        assert!(relationship.sources(2).unwrap().is_empty());
+        assert_eq!(relationship.sources(3), Some(&HashSet::from([1, 4])));
+    }
+
+    #[test]
+    fn one_to_one_relationship_replaces_both_ends() {
+        let mut relationship = Relationship::new(RelationshipKind::OneToOne);
+        relationship.add(1, 2);
+        relationship.add(3, 2);
+        assert!(relationship.targets(1).unwrap().is_empty());
+        assert_eq!(relationship.sources(2), Some(&HashSet::from([3])));
+
+        relationship.add(3, 4);
+        assert!(relationship.sources(2).unwrap().is_empty());
+        assert_eq!(relationship.targets(3), Some(&HashSet::from([4])));
+    }
+
+    #[test]
+    fn one_to_many_relationship_accumulates() {
+        let mut relationship = Relationship::default();
+        relationship.add(1, 2);
+        relationship.add(1, 3);
+        assert_eq!(relationship.targets(1), Some(&HashSet::from([2, 3])));
+    }
+
+    #[test]
+    fn edges_are_sorted() {
+        let mut relationship = Relationship::default();
+        relationship.add(4, 3);
+        relationship.add(1, 2);
+        relationship.add(1, 0);
+        relationship.add(3, 2);
+
+        assert_eq!(
+            relationship.edges().collect::<Vec<_>>(),
+            vec![(1, 0), (1, 2), (3, 2), (4, 3)]
+        );
+    }
+
     #[test]
     fn ancestors() {
         let mut relationship = Relationship::default();
@@ -0,0 +1,173 @@
+//! Storage of the resources.
+//!
+//! Resources are [`Send`] and [`Sync`] and behind read-write locks: any
+//! number of [`ResourceRef`] can be held at the same time while a
+//! [`ResourceRefMut`] is exclusive, which lets systems reading the same
+//! resources run concurrently on several threads. Conflicting accesses panic
+//! instead of blocking, as the systems of a batch never conflict and they can
+//! only come from a thread borrowing a resource twice.
+
+use std::{
+    any::Any,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError},
+};
+
+/// Types that can be stored as resources
+pub trait Resource: Any + Send + Sync {}
+
+impl<T: Any + Send + Sync> Resource for T {}
+
+type BoxedResource = Box<dyn Any + Send + Sync>;
+
+/// Downcasts a resource to its type. Resources are keyed by the type id of
+/// their type so the downcast can't fail, it is only checked in debug builds.
+fn downcast_resource<R: Resource>(resource: &(dyn Any + Send + Sync)) -> &R {
+    debug_assert!(resource.is::<R>(), "The resource isn't of its key type");
+    // SAFETY: The resource is stored under the type id of its type
+    unsafe { resource.downcast_ref::<R>().unwrap_unchecked() }
+}
+
+/// Mutable version of [`downcast_resource`]
+fn downcast_resource_mut<R: Resource>(resource: &mut (dyn Any + Send + Sync)) -> &mut R {
+    debug_assert!(resource.is::<R>(), "The resource isn't of its key type");
+    // SAFETY: The resource is stored under the type id of its type
+    unsafe { resource.downcast_mut::<R>().unwrap_unchecked() }
+}
+
+/// A resource behind its lock
+pub struct ResourceCell {
+    lock: RwLock<BoxedResource>,
+}
+
+impl ResourceCell {
+    #[must_use]
+    pub fn new(resource: BoxedResource) -> Self {
+        Self {
+            lock: RwLock::new(resource),
+        }
+    }
+
+    /// Borrows the resource, a panicking system doesn't make it unusable
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the resource is already mutably borrowed
+    pub(crate) fn borrow<R: Resource>(&self) -> ResourceRef<'_, R> {
+        let guard = match self.lock.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("The resource is already mutably borrowed"),
+        };
+        ResourceRef {
+            resource: NonNull::from(downcast_resource::<R>(guard.as_ref())),
+            _guard: guard,
+        }
+    }
+
+    /// Mutably borrows the resource
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the resource is already borrowed
+    pub(crate) fn borrow_mut<R: Resource>(&self) -> ResourceRefMut<'_, R> {
+        let mut guard = match self.lock.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(TryLockError::WouldBlock) => panic!("The resource is already borrowed"),
+        };
+        ResourceRefMut {
+            resource: NonNull::from(downcast_resource_mut::<R>(guard.as_mut())),
+            _guard: guard,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the resource, the exclusive borrow of the cell guarantees no
+    /// lock is held
+    pub(crate) fn get_mut(&mut self) -> &mut (dyn Any + Send + Sync) {
+        self.lock
+            .get_mut()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .as_mut()
+    }
+}
+
+/// A shared borrow of a resource
+pub struct ResourceRef<'a, R> {
+    /// The resource, downcast once when borrowed
+    resource: NonNull<R>,
+    _guard: RwLockReadGuard<'a, BoxedResource>,
+}
+
+impl<R> Deref for ResourceRef<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The resource is boxed so it doesn't move, and the guard
+        // keeps it borrowed for the lifetime of self
+        unsafe { self.resource.as_ref() }
+    }
+}
+
+/// An exclusive borrow of a resource
+pub struct ResourceRefMut<'a, R> {
+    /// The resource, downcast once when borrowed
+    resource: NonNull<R>,
+    _guard: RwLockWriteGuard<'a, BoxedResource>,
+    _marker: PhantomData<&'a mut R>,
+}
+
+impl<R> Deref for ResourceRefMut<'_, R> {
+    type Target = R;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The resource is boxed so it doesn't move, and the guard
+        // keeps it exclusively borrowed for the lifetime of self
+        unsafe { self.resource.as_ref() }
+    }
+}
+
+impl<R> DerefMut for ResourceRefMut<'_, R> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: See deref
+        unsafe { self.resource.as_mut() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resource_cell_allows_concurrent_reads() {
+        let cell = ResourceCell::new(Box::new(3u32));
+        let first = cell.borrow::<u32>();
+        let second = cell.borrow::<u32>();
+        assert_eq!(*first + *second, 6);
+        std::mem::drop((first, second));
+
+        *cell.borrow_mut::<u32>() += 1;
+        assert_eq!(*cell.borrow::<u32>(), 4);
+    }
+
+    #[test]
+    fn resource_cell_can_be_read_from_several_threads() {
+        let cell = ResourceCell::new(Box::new(3u32));
+        std::thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| assert_eq!(*cell.borrow::<u32>(), 3));
+            }
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "The resource is already borrowed")]
+    fn resource_cell_mutable_borrow_is_exclusive() {
+        let cell = ResourceCell::new(Box::new(3u32));
+        let _read = cell.borrow::<u32>();
+        let _write = cell.borrow_mut::<u32>();
+    }
+}
@@ -1,17 +1,106 @@
+use crate::resource::{Resource, ResourceRef, ResourceRefMut};
 use std::any::TypeId;
-use std::cell::{Ref, RefMut};
 use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::marker::PhantomData;
-use std::ops::{Deref, DerefMut};
+use std::ops::{Deref, DerefMut, Range};
+use std::sync::atomic::{self, AtomicU64};
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
 
 use crate::commands::CommandQueue;
+use crate::profile::SystemProfile;
 use crate::relationship::Relationship;
 use crate::{query, ComponentStores, Ecs, EntityId, Storage};
 
+/// Identifies a system registered in a [`Schedule`], so it can be removed
+/// later on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SystemHandle(u64);
+
+impl SystemHandle {
+    /// Returns a handle that has never been returned before
+    pub(crate) fn next() -> Self {
+        static NEXT_HANDLE: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_HANDLE.fetch_add(1, atomic::Ordering::Relaxed))
+    }
+}
+
+/// A change of a [`Schedule`] requested from a
+/// [`CommandQueue`](crate::commands::CommandQueue)
+pub(crate) enum ScheduleChange {
+    AddSystem {
+        stage: TypeId,
+        handle: SystemHandle,
+        system: System,
+    },
+    RemoveSystem(SystemHandle),
+    RunOnce(System),
+}
+
+/// The resources and components accessed by a system, used to find the
+/// systems of a stage that can run concurrently.
+///
+/// Resources and components are both identified by the id of their type, so
+/// a type used as both is conservatively considered the same data.
+#[derive(Debug, Default, Clone)]
+pub struct Access {
+    reads: HashSet<TypeId>,
+    writes: HashSet<TypeId>,
+    exclusive: bool,
+}
+
+impl Access {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read<T: 'static>(&mut self) {
+        self.reads.insert(TypeId::of::<T>());
+    }
+
+    pub fn write<T: 'static>(&mut self) {
+        self.writes.insert(TypeId::of::<T>());
+    }
+
+    /// Marks the access as conflicting with any other access, for systems
+    /// that may access anything in the storage
+    pub fn exclusive(&mut self) {
+        self.exclusive = true;
+    }
+
+    /// Returns true if two systems with these accesses can't run
+    /// concurrently, that is if either writes data the other accesses
+    #[must_use]
+    pub fn conflicts_with(&self, other: &Access) -> bool {
+        self.exclusive
+            || other.exclusive
+            || !self.writes.is_disjoint(&other.writes)
+            || !self.writes.is_disjoint(&other.reads)
+            || !self.reads.is_disjoint(&other.writes)
+    }
+
+    fn extend(&mut self, other: &Access) {
+        self.reads.extend(&other.reads);
+        self.writes.extend(&other.writes);
+        self.exclusive |= other.exclusive;
+    }
+}
+
 pub struct Schedule {
     stages: Vec<TypeId>,
-    stages_systems: HashMap<TypeId, Vec<System>>,
+    stages_systems: HashMap<TypeId, Vec<(SystemHandle, System)>>,
+    /// The batches of each stage, recomputed when the systems of the stage
+    /// change
+    stages_batches: HashMap<TypeId, Vec<Range<usize>>>,
+    disabled_stages: HashSet<TypeId>,
+    sequential_stages: HashSet<TypeId>,
+    run_once_systems: Vec<System>,
 }
 
 impl Schedule {
@@ -20,60 +109,255 @@ impl Schedule {
         Self {
             stages: vec![],
             stages_systems: HashMap::new(),
+            stages_batches: HashMap::new(),
+            disabled_stages: HashSet::new(),
+            sequential_stages: HashSet::new(),
+            run_once_systems: vec![],
         }
     }
 
-    /// Run the systems registered in the schedule
+    /// Run the systems registered in the schedule, stage by stage in the
+    /// order of the stages.
+    ///
+    /// The systems registered or removed through the command queue are
+    /// applied first. Such changes only take effect on the next run: a
+    /// system removed during a run still runs until the end of that run.
+    /// The systems queued with
+    /// [`CommandQueue::run_once`](crate::commands::CommandQueue::run_once)
+    /// then run once, each followed by the processing of the command queue,
+    /// before the stages.
+    ///
+    /// The systems of a stage are split in batches of consecutive systems
+    /// whose [`Access`] don't conflict, see [`Schedule::stage_batches`]. The
+    /// batches run one after the other, the systems of a batch run
+    /// concurrently on scoped threads. On wasm, where threads aren't
+    /// available, they run one after the other.
+    ///
+    /// The execution time of each system of the stages is recorded in the
+    /// [`SystemProfile`] resource if it is present.
     ///
     /// # Panics
     ///
     /// Will panic if the systems of a stage cannot be found
     pub fn run_systems(&mut self, ecs: &mut Ecs) {
+        self.apply_changes(ecs);
+        for system in std::mem::take(&mut self.run_once_systems) {
+            ecs.run_single_run_system(&system);
+        }
+        let mut system_durations = ecs.resource::<SystemProfile>().is_some().then(Vec::new);
         for stage in &self.stages {
-            let systems = self.stages_systems.get_mut(stage).unwrap();
-            for system in systems.iter_mut() {
-                system.run(ecs);
+            if self.disabled_stages.contains(stage) {
+                continue;
+            }
+
+            let systems = self.stages_systems.get(stage).unwrap();
+            for batch in &self.stages_batches[stage] {
+                run_batch(
+                    &systems[batch.clone()],
+                    &ecs.command_queue,
+                    &ecs.storage,
+                    system_durations.as_mut(),
+                );
+            }
+        }
+
+        if let Some(system_durations) = system_durations {
+            if let Some(mut profile) = ecs.resource_mut::<SystemProfile>() {
+                for (label, duration) in system_durations {
+                    profile.record(label, duration);
+                }
             }
         }
     }
 
-    pub fn add_system<Stage, F, S>(&mut self, _stage: &Stage, system: F)
+    fn apply_changes(&mut self, ecs: &mut Ecs) {
+        for change in std::mem::take(&mut ecs.schedule_changes) {
+            match change {
+                ScheduleChange::AddSystem {
+                    stage,
+                    handle,
+                    system,
+                } => self.push_system(stage, handle, system),
+                ScheduleChange::RemoveSystem(handle) => {
+                    self.remove_system(handle);
+                }
+                ScheduleChange::RunOnce(system) => self.run_once_systems.push(system),
+            }
+        }
+    }
+
+    /// Appends a stage to the schedule if it isn't part of it yet
+    pub fn add_stage<Stage>(&mut self, _stage: &Stage)
     where
         Stage: 'static,
-        S: 'static,
-        F: 'static + Into<S>,
     {
         let stage = TypeId::of::<Stage>();
-        if !self.stages_systems.contains_key(&stage) {
+        if let Entry::Vacant(entry) = self.stages_systems.entry(stage) {
+            entry.insert(vec![]);
+            self.stages_batches.insert(stage, vec![]);
             self.stages.push(stage);
         }
+    }
 
-        self.stages_systems
-            .entry(TypeId::of::<Stage>())
-            .or_default()
-            .push(system.into_system());
+    /// Inserts a stage right after another one. If the stage is already part
+    /// of the schedule, it is moved with its systems.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `after` isn't part of the schedule
+    pub fn insert_stage_after<After, Stage>(&mut self, _after: &After, _stage: &Stage)
+    where
+        After: 'static,
+        Stage: 'static,
+    {
+        self.insert_stage_relative_to(TypeId::of::<After>(), TypeId::of::<Stage>(), 1);
+    }
+
+    /// Inserts a stage right before another one. If the stage is already part
+    /// of the schedule, it is moved with its systems.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `before` isn't part of the schedule
+    pub fn insert_stage_before<Before, Stage>(&mut self, _before: &Before, _stage: &Stage)
+    where
+        Before: 'static,
+        Stage: 'static,
+    {
+        self.insert_stage_relative_to(TypeId::of::<Before>(), TypeId::of::<Stage>(), 0);
+    }
+
+    fn insert_stage_relative_to(&mut self, anchor: TypeId, stage: TypeId, offset: usize) {
+        self.stages
+            .retain(|&existing_stage| existing_stage != stage);
+        let anchor_index = self
+            .stages
+            .iter()
+            .position(|&existing_stage| existing_stage == anchor)
+            .expect("The anchor stage should be part of the schedule");
+        self.stages.insert(anchor_index + offset, stage);
+        self.stages_systems.entry(stage).or_default();
+        self.stages_batches.entry(stage).or_default();
+    }
+
+    /// Enables or disables a stage, the systems of a disabled stage are not
+    /// run by [`Schedule::run_systems`]
+    pub fn set_stage_enabled<Stage>(&mut self, _stage: &Stage, enabled: bool)
+    where
+        Stage: 'static,
+    {
+        let stage = TypeId::of::<Stage>();
+        if enabled {
+            self.disabled_stages.remove(&stage);
+        } else {
+            self.disabled_stages.insert(stage);
+        }
+    }
+
+    /// Makes every system of a stage wait for the previous one, for stages
+    /// whose systems rely on side effects their accesses don't show
+    pub fn set_stage_sequential<Stage>(&mut self, _stage: &Stage)
+    where
+        Stage: 'static,
+    {
+        let stage = TypeId::of::<Stage>();
+        self.sequential_stages.insert(stage);
+        self.update_stage_batches(stage);
+    }
+
+    /// Returns the labels of the systems of a stage grouped in the batches
+    /// they run in, the systems of a batch can run concurrently
+    #[must_use]
+    pub fn stage_batches<Stage>(&self, _stage: &Stage) -> Vec<Vec<&'static str>>
+    where
+        Stage: 'static,
+    {
+        let stage = TypeId::of::<Stage>();
+        let (Some(systems), Some(batches)) = (
+            self.stages_systems.get(&stage),
+            self.stages_batches.get(&stage),
+        ) else {
+            return vec![];
+        };
+        batches
+            .iter()
+            .map(|batch| {
+                systems[batch.clone()]
+                    .iter()
+                    .map(|(_, system)| system.label())
+                    .collect()
+            })
+            .collect()
+    }
+
+    pub fn add_system<Stage, F, S>(&mut self, _stage: &Stage, system: F) -> SystemHandle
+    where
+        Stage: 'static,
+        S: 'static,
+        F: 'static + Into<S>,
+    {
+        let handle = SystemHandle::next();
+        self.push_system(TypeId::of::<Stage>(), handle, system.into_system());
+        handle
     }
 
     /// Registers a system to the schedule for a given stage.
     /// If the stage doesn't exist, it is created and will run
     /// after the already registered stages.
-    pub fn register_system_for_stage<S>(&mut self, system: System)
+    pub fn register_system_for_stage<S>(&mut self, system: System) -> SystemHandle
     where
         S: 'static,
     {
-        let stage_id = TypeId::of::<S>();
-        if let Entry::Vacant(entry) = self.stages_systems.entry(stage_id) {
+        let handle = SystemHandle::next();
+        self.push_system(TypeId::of::<S>(), handle, system);
+        handle
+    }
+
+    fn push_system(&mut self, stage: TypeId, handle: SystemHandle, system: System) {
+        if let Entry::Vacant(entry) = self.stages_systems.entry(stage) {
             entry.insert(vec![]);
-            self.stages.push(stage_id);
+            self.stages.push(stage);
         }
 
         // SAFETY: If the entry was vacant we created it, so it must be here
         unsafe {
             self.stages_systems
-                .get_mut(&TypeId::of::<S>())
+                .get_mut(&stage)
                 .unwrap_unchecked()
-                .push(system);
+                .push((handle, system));
         }
+        self.update_stage_batches(stage);
+    }
+
+    fn update_stage_batches(&mut self, stage: TypeId) {
+        let stage_batches = self
+            .stages_systems
+            .get(&stage)
+            .map_or_else(Vec::new, |systems| {
+                batches(systems, self.sequential_stages.contains(&stage))
+            });
+        self.stages_batches.insert(stage, stage_batches);
+    }
+
+    /// Removes a system from the schedule, returns false if no system has
+    /// the given handle
+    pub fn remove_system(&mut self, handle: SystemHandle) -> bool {
+        let removed_from_stage = self
+            .stages_systems
+            .iter_mut()
+            .find_map(|(&stage, systems)| {
+                let index = systems
+                    .iter()
+                    .position(|(system_handle, _)| *system_handle == handle)?;
+                systems.remove(index);
+                Some(stage)
+            });
+        let Some(stage) = removed_from_stage else {
+            return false;
+        };
+
+        self.update_stage_batches(stage);
+        true
     }
 }
 
@@ -83,15 +367,93 @@ impl Default for Schedule {
     }
 }
 
-type SystemFn = Box<dyn Fn(&mut CommandQueue, &Storage)>;
+/// Splits systems in ranges of consecutive systems whose accesses don't
+/// conflict, so running the ranges in order keeps the conflicting systems in
+/// registration order
+fn batches(systems: &[(SystemHandle, System)], sequential: bool) -> Vec<Range<usize>> {
+    let mut batches: Vec<Range<usize>> = vec![];
+    let mut batch_access = Access::new();
+    for (index, (_, system)) in systems.iter().enumerate() {
+        match batches.last_mut() {
+            Some(batch) if !sequential && !batch_access.conflicts_with(&system.access) => {
+                batch.end = index + 1;
+            }
+            _ => {
+                batches.push(index..index + 1);
+                batch_access = Access::new();
+            }
+        }
+        batch_access.extend(&system.access);
+    }
 
+    batches
+}
+
+/// Runs the systems of a batch, concurrently unless on wasm, recording
+/// their execution time in `system_durations`
+fn run_batch(
+    systems: &[(SystemHandle, System)],
+    command_queue: &CommandQueue,
+    storage: &Storage,
+    system_durations: Option<&mut Vec<(&'static str, Duration)>>,
+) {
+    let run_system = |system: &System| {
+        let start_instant = system_durations.is_some().then(Instant::now);
+        (system.system_fn)(command_queue, storage);
+        start_instant.map(|start_instant| (system.label(), start_instant.elapsed()))
+    };
+
+    let durations: Vec<_> = if cfg!(target_arch = "wasm32") || systems.len() == 1 {
+        systems
+            .iter()
+            .map(|(_, system)| run_system(system))
+            .collect()
+    } else {
+        std::thread::scope(|scope| {
+            let handles = systems[1..]
+                .iter()
+                .map(|(_, system)| scope.spawn(|| run_system(system)))
+                .collect::<Vec<_>>();
+            let first_duration = run_system(&systems[0].1);
+            std::iter::once(first_duration)
+                .chain(handles.into_iter().map(|handle| {
+                    handle
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                }))
+                .collect()
+        })
+    };
+
+    if let Some(system_durations) = system_durations {
+        system_durations.extend(durations.into_iter().flatten());
+    }
+}
+
+type SystemFn = Box<dyn Fn(&CommandQueue, &Storage) + Send + Sync>;
+
+#[allow(clippy::struct_field_names)]
 pub struct System {
+    label: &'static str,
+    access: Access,
     system_fn: SystemFn,
 }
 
 impl System {
     pub fn run(&self, ecs: &mut Ecs) {
-        (self.system_fn)(&mut ecs.command_queue, &mut ecs.storage);
+        (self.system_fn)(&ecs.command_queue, &ecs.storage);
+    }
+
+    /// The label of the system, the path of the function it was created from
+    #[must_use]
+    pub fn label(&self) -> &'static str {
+        self.label
+    }
+
+    /// The data accessed by the system, declared by its arguments
+    #[must_use]
+    pub fn access(&self) -> &Access {
+        &self.access
     }
 }
 
@@ -99,6 +461,8 @@ pub struct Noop;
 impl<A> Into<A> for Noop {
     fn into_system(self) -> System {
         System {
+            label: "noop",
+            access: Access::new(),
             system_fn: Box::new(|_, _| {}),
         }
     }
@@ -135,10 +499,12 @@ impl<A> Into<A> for Box<dyn Into<A>> {
 
 impl<F> Into<()> for F
 where
-    F: 'static + Fn(),
+    F: 'static + Fn() + Send + Sync,
 {
     fn into_system(self) -> System {
         System {
+            label: std::any::type_name::<F>(),
+            access: Access::new(),
             system_fn: Box::new(move |_, _| (self)()),
         }
     }
@@ -148,12 +514,17 @@ macro_rules! impl_into_for_tuples {
     ($head:tt, $($tail:tt,)*) => {
         impl<FN, $head, $($tail,)*> Into<($head, $($tail,)*)> for FN
         where
-            for<'a> FN: 'static + Fn($head, $($tail,)*) + Fn($head::Type<'a>, $($tail::Type<'a>,)*),
+            for<'a> FN: 'static + Send + Sync + Fn($head, $($tail,)*) + Fn($head::Type<'a>, $($tail::Type<'a>,)*),
             $head: Argument,
             $($tail: Argument,)*
         {
             fn into_system(self) -> System {
+                let mut access = Access::new();
+                $head::access(&mut access);
+                $($tail::access(&mut access);)*
                 System {
+                    label: std::any::type_name::<FN>(),
+                    access,
                     system_fn: Box::new(move |command_queue, storage| (self)($head::provide(command_queue, storage).unwrap(), $($tail::provide(command_queue, storage).unwrap(),)*)),
                 }
             }
@@ -170,6 +541,12 @@ pub trait Argument {
     type Type<'a>;
     fn provide<'a>(command_queue: &'a CommandQueue, storage: &'a Storage)
         -> Option<Self::Type<'a>>;
+
+    /// Declares the data accessed through the argument, arguments that don't
+    /// declare it are considered to access anything
+    fn access(access: &mut Access) {
+        access.exclusive();
+    }
 }
 
 impl Argument for () {
@@ -181,6 +558,8 @@ impl Argument for () {
     ) -> Option<Self::Type<'a>> {
         Some(())
     }
+
+    fn access(_access: &mut Access) {}
 }
 
 impl Argument for &Storage {
@@ -206,6 +585,10 @@ where
     ) -> Option<Self::Type<'a>> {
         Some(A::provide(command_queue, storage))
     }
+
+    fn access(access: &mut Access) {
+        A::access(access);
+    }
 }
 
 pub struct Q<'ecs, QD>
@@ -224,9 +607,9 @@ where
     pub fn new(
         component_stores: &'ecs ComponentStores,
         deleted_entities: &'ecs [EntityId],
-        entity_count: usize,
+        max_entity_index: usize,
     ) -> Self {
-        let state = query::State::new(component_stores, deleted_entities, entity_count);
+        let state = query::State::new(component_stores, deleted_entities, max_entity_index);
         Self {
             state,
             _marker: PhantomData,
@@ -240,15 +623,44 @@ where
     pub fn first_with_id(&mut self) -> Option<(EntityId, QD::Item<'_>)> {
         self.state.iter_with_ids().next()
     }
+
+    /// Returns the item of the only entity matching the query
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if no entity or more than one entity matches the
+    /// query
+    pub fn single(&mut self) -> Result<QD::Item<'ecs>, query::SingleError> {
+        self.state.single()
+    }
+
+    /// Returns the id and the item of the only entity matching the query
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if no entity or more than one entity matches the
+    /// query
+    pub fn single_with_id(&mut self) -> Result<(EntityId, QD::Item<'ecs>), query::SingleError> {
+        self.state.single_with_id()
+    }
+
+    /// Fetches the item of a single entity, or None if the entity doesn't
+    /// match the query
+    pub fn with_id(&mut self, entity_id: EntityId) -> Option<QD::Item<'_>> {
+        self.state.get(entity_id)
+    }
+
     pub fn iter<'a>(&'a mut self) -> query::Iter<'ecs, 'a, QD> {
         self.state.iter()
     }
+
+    /// Iterates over the matching entities along with their ids
     pub fn iter_with_ids<'a>(&'a mut self) -> query::IterWithIds<'ecs, 'a, QD> {
         self.state.iter_with_ids()
     }
 }
 
-impl<'ecs, QD> Argument for Q<'ecs, QD>
+impl<QD> Argument for Q<'_, QD>
 where
     QD: query::Definition,
 {
@@ -261,9 +673,13 @@ where
         Some(Q::new(
             &storage.component_stores,
             &storage.deleted_entities,
-            storage.entity_count(),
+            storage.next_entity_id().saturating_sub(1),
         ))
     }
+
+    fn access(access: &mut Access) {
+        QD::access(access);
+    }
 }
 
 impl Argument for &CommandQueue {
@@ -274,6 +690,12 @@ impl Argument for &CommandQueue {
     ) -> Option<Self::Type<'a>> {
         Some(command_queue)
     }
+
+    /// Queuing commands reserves entity ids, so the systems queuing commands
+    /// don't run concurrently
+    fn access(access: &mut Access) {
+        access.write::<CommandQueue>();
+    }
 }
 
 pub struct Rel<'a, R>(&'a Relationship, PhantomData<&'a R>);
@@ -297,64 +719,68 @@ where
     ) -> Option<Self::Type<'a>> {
         Some(Rel(storage.relationship::<R>()?, PhantomData))
     }
+
+    fn access(access: &mut Access) {
+        access.read::<Rel<'static, R>>();
+    }
 }
 
-pub struct Res<'a, T>(Ref<'a, T>);
+pub struct Res<'a, T>(ResourceRef<'a, T>);
 impl<'a, T> Deref for Res<'a, T> {
-    type Target = Ref<'a, T>;
+    type Target = ResourceRef<'a, T>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
 
-impl<T: 'static> Argument for Res<'_, T> {
+impl<T: Resource> Argument for Res<'_, T> {
     type Type<'a> = Res<'a, T>;
 
     fn provide<'a>(
         _command_queue: &'a CommandQueue,
         storage: &'a Storage,
     ) -> Option<Self::Type<'a>> {
-        Some(Res(Ref::map(
-            storage.resources.get(&TypeId::of::<T>()).as_ref()?.borrow(),
-            |r| r.downcast_ref::<T>().unwrap(),
-        )))
+        Some(Res(storage.resource::<T>()?))
+    }
+
+    fn access(access: &mut Access) {
+        access.read::<T>();
     }
 }
-pub struct ResMut<'a, T>(RefMut<'a, T>);
+pub struct ResMut<'a, T>(ResourceRefMut<'a, T>);
 impl<'a, T> Deref for ResMut<'a, T> {
-    type Target = RefMut<'a, T>;
+    type Target = ResourceRefMut<'a, T>;
 
     fn deref(&self) -> &Self::Target {
         &self.0
     }
 }
-impl<'a, T> DerefMut for ResMut<'a, T> {
+impl<T> DerefMut for ResMut<'_, T> {
     fn deref_mut(&mut self) -> &mut Self::Target {
         &mut self.0
     }
 }
 
-impl<T: 'static> Argument for ResMut<'_, T> {
+impl<T: Resource> Argument for ResMut<'_, T> {
     type Type<'a> = ResMut<'a, T>;
 
     fn provide<'a>(
         _command_queue: &'a CommandQueue,
         storage: &'a Storage,
     ) -> Option<Self::Type<'a>> {
-        Some(ResMut(RefMut::map(
-            storage
-                .resources
-                .get(&TypeId::of::<T>())
-                .as_ref()?
-                .borrow_mut(),
-            |r| r.downcast_mut::<T>().unwrap(),
-        )))
+        Some(ResMut(storage.resource_mut::<T>()?))
+    }
+
+    fn access(access: &mut Access) {
+        access.write::<T>();
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::atomic::AtomicUsize;
+
     use crate::{relationship::ChildOf, Ecs};
 
     use super::*;
@@ -371,6 +797,237 @@ mod tests {
         y: i32,
     }
 
+    #[test]
+    fn schedule_skips_disabled_stages() {
+        struct Update;
+        struct Render;
+        let mut ecs = Ecs::new();
+        let mut schedule = Schedule::new();
+        schedule.add_system(&Update, |command_queue: &CommandQueue| {
+            command_queue.insert((Player,));
+        });
+        schedule.add_system(&Render, |command_queue: &CommandQueue| {
+            command_queue.insert((Enemy,));
+        });
+
+        schedule.set_stage_enabled(&Render, false);
+        schedule.run_systems(&mut ecs);
+        ecs.process_command_queue();
+        assert_eq!(ecs.entity_count(), 1);
+
+        schedule.set_stage_enabled(&Render, true);
+        schedule.run_systems(&mut ecs);
+        ecs.process_command_queue();
+        assert_eq!(ecs.entity_count(), 3);
+    }
+
+    fn move_system(_positions: Q<&mut Position>) {}
+    fn heal_system(_health: Q<&mut Health>) {}
+    fn show_system(_positions: Q<&Position>) {}
+    fn score_system(_score: ResMut<u32>) {}
+    fn read_score_system(_score: Res<u32>) {}
+
+    #[test]
+    fn stage_batches_group_non_conflicting_systems() {
+        struct Update;
+        struct Render;
+        let mut schedule = Schedule::new();
+        schedule.add_system(&Update, move_system);
+        schedule.add_system(&Update, heal_system);
+        schedule.add_system(&Update, score_system);
+        schedule.add_system(&Update, show_system);
+        schedule.add_system(&Update, read_score_system);
+        schedule.add_system(&Render, move_system);
+        schedule.add_system(&Render, heal_system);
+        schedule.set_stage_sequential(&Render);
+
+        let labels = |batches: Vec<Vec<&'static str>>| {
+            batches
+                .into_iter()
+                .map(|batch| {
+                    batch
+                        .into_iter()
+                        .map(|label| label.rsplit("::").next().unwrap())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            labels(schedule.stage_batches(&Update)),
+            vec![
+                vec!["move_system", "heal_system", "score_system"],
+                vec!["show_system", "read_score_system"],
+            ]
+        );
+        assert_eq!(
+            labels(schedule.stage_batches(&Render)),
+            vec![vec!["move_system"], vec!["heal_system"]]
+        );
+    }
+
+    #[derive(Default)]
+    struct Rendezvous {
+        arrived: AtomicUsize,
+        met: AtomicUsize,
+    }
+
+    /// Waits for another system to arrive, for at most a second
+    fn rendezvous_system(rendezvous: Res<Rendezvous>) {
+        rendezvous.arrived.fetch_add(1, atomic::Ordering::SeqCst);
+        let start_instant = Instant::now();
+        while start_instant.elapsed() < Duration::from_secs(1) {
+            if rendezvous.arrived.load(atomic::Ordering::SeqCst) == 2 {
+                rendezvous.met.fetch_add(1, atomic::Ordering::SeqCst);
+                break;
+            }
+            std::thread::yield_now();
+        }
+        std::mem::drop(rendezvous);
+    }
+
+    #[test]
+    fn systems_of_a_batch_run_concurrently() {
+        struct Update;
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(Rendezvous::default());
+        let mut schedule = Schedule::new();
+        schedule.add_system(&Update, rendezvous_system);
+        schedule.add_system(&Update, rendezvous_system);
+
+        schedule.run_systems(&mut ecs);
+        let rendezvous = ecs.resource::<Rendezvous>().unwrap();
+        assert_eq!(rendezvous.met.load(atomic::Ordering::SeqCst), 2);
+    }
+
+    fn profiled_system() {}
+
+    #[test]
+    fn schedule_profiles_systems() {
+        let mut ecs = Ecs::new();
+        let mut schedule = Schedule::new();
+        schedule.add_system(&(), profiled_system);
+        schedule.run_systems(&mut ecs);
+
+        ecs.insert_resource(SystemProfile::new());
+        schedule.run_systems(&mut ecs);
+        let profile = ecs.resource::<SystemProfile>().unwrap();
+        let slowest = profile.slowest(2);
+        assert_eq!(slowest.len(), 1);
+        assert!(slowest[0].0.ends_with("profiled_system"));
+    }
+
+    #[test]
+    fn schedule_applies_systems_changes_from_commands() {
+        struct Update;
+        #[derive(Debug, Default)]
+        struct Counter {
+            count: u32,
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(Counter::default());
+        let mut schedule = Schedule::new();
+        let handle = ecs
+            .command_queue()
+            .register_system(&Update, |mut counter: ResMut<Counter>| counter.count += 1);
+        ecs.process_command_queue();
+
+        schedule.run_systems(&mut ecs);
+        schedule.run_systems(&mut ecs);
+        assert_eq!(ecs.resource::<Counter>().unwrap().count, 2);
+
+        // The removal takes effect on the next run
+        schedule.add_system(&Update, move |queue: &CommandQueue| {
+            queue.remove_system(handle);
+        });
+        schedule.run_systems(&mut ecs);
+        ecs.process_command_queue();
+        schedule.run_systems(&mut ecs);
+        assert_eq!(ecs.resource::<Counter>().unwrap().count, 3);
+        assert!(!schedule.remove_system(handle));
+    }
+
+    #[test]
+    fn schedule_runs_run_once_systems_once() {
+        struct Update;
+        #[derive(Debug, Default)]
+        struct Log {
+            entries: Vec<&'static str>,
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(Log::default());
+        let mut schedule = Schedule::new();
+        schedule.add_system(&Update, |mut log: ResMut<Log>| log.entries.push("update"));
+        schedule.add_system(&Update, |queue: &CommandQueue, storage: &Storage| {
+            if storage.resource::<Log>().unwrap().entries.len() == 1 {
+                queue.run_once(|queue: &CommandQueue| {
+                    queue.insert((Player,));
+                });
+            }
+        });
+
+        schedule.run_systems(&mut ecs);
+        ecs.process_command_queue();
+        assert_eq!(ecs.entity_count(), 0);
+
+        // The entity inserted by the run once system is visible to the
+        // systems of the same run
+        schedule.add_system(&Update, |mut log: ResMut<Log>, mut query: Q<&Player>| {
+            if query.iter().count() == 1 {
+                log.entries.push("player");
+            }
+        });
+        schedule.run_systems(&mut ecs);
+        ecs.process_command_queue();
+        schedule.run_systems(&mut ecs);
+        ecs.process_command_queue();
+        assert_eq!(ecs.entity_count(), 1);
+        assert_eq!(
+            ecs.resource::<Log>().unwrap().entries,
+            vec!["update", "update", "player", "update", "player"]
+        );
+    }
+
+    #[test]
+    fn schedule_runs_stages_in_configured_order() {
+        struct StartFrame;
+        struct Update;
+        struct Physics;
+        struct Render;
+        #[derive(Debug, Default)]
+        struct Log {
+            entries: Vec<&'static str>,
+        }
+
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(Log::default());
+        let mut schedule = Schedule::new();
+        schedule.add_stage(&StartFrame);
+        schedule.add_stage(&Update);
+        schedule.add_system(&Render, |mut log: ResMut<Log>| log.entries.push("render"));
+        schedule.add_system(&Update, |mut log: ResMut<Log>| log.entries.push("update"));
+        schedule.add_system(&Physics, |mut log: ResMut<Log>| log.entries.push("physics"));
+        schedule.add_system(&StartFrame, |mut log: ResMut<Log>| {
+            log.entries.push("start_frame");
+        });
+        schedule.insert_stage_after(&Update, &Physics);
+
+        schedule.run_systems(&mut ecs);
+        assert_eq!(
+            ecs.resource::<Log>().unwrap().entries,
+            vec!["start_frame", "update", "physics", "render"]
+        );
+
+        schedule.insert_stage_before(&StartFrame, &Render);
+        ecs.resource_mut::<Log>().unwrap().entries.clear();
+        schedule.run_systems(&mut ecs);
+        assert_eq!(
+            ecs.resource::<Log>().unwrap().entries,
+            vec!["render", "start_frame", "update", "physics"]
+        );
+    }
+
     #[test]
     fn ecs_run_single_system() {
         let mut ecs = Ecs::new();
@@ -385,6 +1042,25 @@ mod tests {
         assert_eq!(ecs.entity_count(), 3);
     }
 
+    #[test]
+    fn q_iter_with_ids_after_deletion() {
+        let mut ecs = Ecs::new();
+        let a = ecs.insert((Health(10),));
+        let b = ecs.insert((Health(5),));
+        let c = ecs.insert((Health(2),));
+        ecs.delete(a);
+
+        ecs.run_single_run_system(
+            &(move |mut query: Q<&Health>| {
+                let ids = query.iter_with_ids().map(|(id, _)| id).collect::<Vec<_>>();
+                assert_eq!(ids, vec![b, c]);
+                assert_eq!(query.with_id(c).map(|health| health.0), Some(2));
+                assert!(query.with_id(a).is_none());
+            })
+            .into_system(),
+        );
+    }
+
     #[derive(Debug, PartialEq, Eq)]
     struct MyResource;
     #[test]
@@ -0,0 +1,112 @@
+#![warn(clippy::pedantic)]
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Index};
+
+/// Implements `EntityDefinition` for a struct, each of its fields being
+/// written as a component of the entity.
+///
+/// Fields marked with `#[component(skip)]` are not written. The struct must
+/// implement `Debug`.
+///
+/// The generated code refers to the ecs crate as `tubereng_ecs`, crates
+/// depending on it through another path set it with
+/// `#[entity_definition(crate = tubereng::ecs)]`.
+#[proc_macro_derive(EntityDefinition, attributes(component, entity_definition))]
+pub fn derive_entity_definition(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_entity_definition(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_entity_definition(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            input.span(),
+            "EntityDefinition can only be derived for structs",
+        ));
+    };
+
+    let mut field_accesses = vec![];
+    let mut field_types = vec![];
+    for (index, field) in data.fields.iter().enumerate() {
+        if is_skipped(field)? {
+            continue;
+        }
+
+        field_accesses.push(if let Some(ident) = &field.ident {
+            quote!(#ident)
+        } else {
+            let index = Index::from(index);
+            quote!(#index)
+        });
+        field_types.push(&field.ty);
+    }
+
+    let ecs_crate = ecs_crate_path(&input.attrs)?;
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    let where_clause = generics.make_where_clause();
+    for field_type in &field_types {
+        where_clause
+            .predicates
+            .push(syn::parse_quote!(#field_type: 'static));
+    }
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #ecs_crate::EntityDefinition for #name #type_generics #where_clause {
+            fn write_into_component_stores(
+                self,
+                entity_id: #ecs_crate::EntityId,
+                component_stores: &mut #ecs_crate::ComponentStores,
+            ) {
+                #(#ecs_crate::store_component(component_stores, entity_id, self.#field_accesses);)*
+            }
+        }
+    })
+}
+
+/// Returns the path of the ecs crate set with
+/// `#[entity_definition(crate = ...)]`, `::tubereng_ecs` by default
+fn ecs_crate_path(attributes: &[syn::Attribute]) -> syn::Result<syn::Path> {
+    let mut ecs_crate = syn::parse_quote!(::tubereng_ecs);
+    for attribute in attributes
+        .iter()
+        .filter(|attr| attr.path().is_ident("entity_definition"))
+    {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                ecs_crate = meta.value()?.parse()?;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported entity_definition attribute, expected `crate`"))
+            }
+        })?;
+    }
+
+    Ok(ecs_crate)
+}
+
+/// Returns true if the field is marked with `#[component(skip)]`
+fn is_skipped(field: &syn::Field) -> syn::Result<bool> {
+    let mut skipped = false;
+    for attribute in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("component"))
+    {
+        attribute.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skipped = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported component attribute, expected `skip`"))
+            }
+        })?;
+    }
+
+    Ok(skipped)
+}
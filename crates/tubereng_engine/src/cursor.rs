@@ -0,0 +1,129 @@
+//! The cursor shown over the window, either one of the cursors of the
+//! platform or a custom image.
+//!
+//! Custom images are drawn by the engine on top of the UI while the cursor of
+//! the platform is hidden, so they work on every platform including the web.
+
+use tubereng_ecs::system::{Res, ResMut};
+use tubereng_input::InputState;
+use tubereng_renderer::{
+    texture,
+    ui_pass::{DrawUiNineSliceCommand, Insets, UiScaleMode, UiViewport},
+    Color, GraphicsState,
+};
+
+/// The shape of the cursor over the window, the runner uses the cursor of the
+/// platform for each shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorIcon {
+    #[default]
+    Default,
+    /// A hand with a pointing finger, usually shown over links and buttons
+    Pointer,
+    Text,
+    Crosshair,
+    Move,
+    Grab,
+    Grabbing,
+    NotAllowed,
+    Wait,
+    ResizeHorizontal,
+    ResizeVertical,
+}
+
+/// A custom cursor image, set with [`crate::Engine::set_cursor_image`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorImage {
+    pub texture: texture::Id,
+    /// The size of the image, in window pixels
+    pub width: u32,
+    pub height: u32,
+    /// The pixel of the image placed at the cursor position, from its
+    /// top-left corner
+    pub hotspot: (u32, u32),
+}
+
+/// Resource holding the cursor shown over the window. The changes made
+/// through the [`crate::Engine`] take effect immediately, the ones made by
+/// the systems are applied by the runner at the end of the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Cursor {
+    pub icon: CursorIcon,
+    /// The image replacing the cursor of the platform, if any
+    pub image: Option<CursorImage>,
+}
+
+/// Returns the rect `(x, y, width, height)` covered by a cursor image of the
+/// given size in UI coordinates, for a cursor at `position` in window pixels
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+fn cursor_image_rect(
+    size: (u32, u32),
+    hotspot: (u32, u32),
+    position: (f64, f64),
+    viewport: &UiViewport,
+) -> (f32, f32, f32, f32) {
+    let left = position.0 as f32 - hotspot.0 as f32;
+    let top = position.1 as f32 - hotspot.1 as f32;
+    let (x, y) = viewport.window_to_ui(left, top);
+    let (right, bottom) = viewport.window_to_ui(left + size.0 as f32, top + size.1 as f32);
+    (x, y, right - x, bottom - y)
+}
+
+/// Draws the custom cursor image at the cursor position
+pub(crate) fn draw_cursor_image_system(
+    cursor: Res<Cursor>,
+    input: Res<InputState>,
+    scale_mode: Option<Res<UiScaleMode>>,
+    gfx: Option<ResMut<GraphicsState>>,
+) {
+    if let (Some(image), Some(mut gfx)) = (cursor.image, gfx) {
+        let viewport = scale_mode
+            .as_deref()
+            .map_or(UiViewport::IDENTITY, |scale_mode| {
+                scale_mode.viewport(*gfx.window_size())
+            });
+        let (x, y, width, height) = cursor_image_rect(
+            (image.width, image.height),
+            image.hotspot,
+            *input.mouse.position(),
+            &viewport,
+        );
+        #[allow(clippy::cast_precision_loss)]
+        gfx.draw_ui_nine_slice(DrawUiNineSliceCommand {
+            texture: image.texture,
+            texture_rect: texture::Rect::new(0.0, 0.0, image.width as f32, image.height as f32),
+            insets: Insets::uniform(0.0),
+            x,
+            y,
+            width,
+            height,
+            color: Color::WHITE,
+        });
+    }
+
+    std::mem::drop(cursor);
+    std::mem::drop(input);
+    std::mem::drop(scale_mode);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_image_rect_places_the_hotspot_at_the_cursor() {
+        let rect = cursor_image_rect((16, 24), (4, 2), (100.0, 50.0), &UiViewport::IDENTITY);
+        assert_eq!(rect, (96.0, 48.0, 16.0, 24.0));
+    }
+
+    #[test]
+    fn cursor_image_rect_keeps_the_size_in_window_pixels() {
+        let viewport = UiViewport {
+            scale: (2.0, 2.0),
+            offset: (10.0, 0.0),
+            clip: None,
+        };
+        let rect = cursor_image_rect((16, 24), (4, 2), (100.0, 50.0), &viewport);
+        assert_eq!(rect, (43.0, 24.0, 8.0, 12.0));
+    }
+}
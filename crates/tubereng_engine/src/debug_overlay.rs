@@ -0,0 +1,143 @@
+//! An on-screen overlay displaying the statistics of the engine and of the
+//! renderer, toggled with [`TOGGLE_KEY`].
+//!
+//! The overlay is disabled by default, it can be enabled from the start by
+//! inserting the resource on the builder:
+//!
+//! ```ignore
+//! let mut builder = Engine::builder();
+//! builder.insert_resource(DebugOverlay { enabled: true });
+//! ```
+//!
+//! The slowest systems are listed as well when the systems are profiled,
+//! which is done by inserting a [`SystemProfile`] resource.
+
+use std::fmt::Write;
+
+use tubereng_ecs::{
+    profile::SystemProfile,
+    system::{Res, ResMut},
+};
+use tubereng_input::{keyboard::Key, InputState};
+use tubereng_renderer::{Color, GraphicsState, RenderStats};
+
+use crate::statistics::EngineStatistics;
+
+/// The key toggling the debug overlay
+pub const TOGGLE_KEY: Key = Key::F3;
+
+/// The number of systems listed when the systems are profiled
+const LISTED_SYSTEM_COUNT: usize = 5;
+
+/// Resource controlling the display of the debug overlay
+#[derive(Debug, Default)]
+pub struct DebugOverlay {
+    pub enabled: bool,
+}
+
+/// Toggles the debug overlay and draws it. It runs in the render stage, so the
+/// overlay stays visible while the simulation is paused, and before the frame
+/// is rendered, so the render statistics are the ones of the previous frame.
+pub(crate) fn debug_overlay_system(
+    input: Res<InputState>,
+    mut debug_overlay: ResMut<DebugOverlay>,
+    statistics: Res<EngineStatistics>,
+    render_stats: Option<Res<RenderStats>>,
+    system_profile: Option<Res<SystemProfile>>,
+    gfx: Option<ResMut<GraphicsState>>,
+) {
+    let keyboard = &input.keyboard;
+    if keyboard.is_key_down(TOGGLE_KEY) && !keyboard.was_key_down(TOGGLE_KEY) {
+        debug_overlay.enabled = !debug_overlay.enabled;
+    }
+
+    if debug_overlay.enabled {
+        if let Some(mut gfx) = gfx {
+            let render_stats = render_stats.map(|render_stats| **render_stats);
+            let text = overlay_text(
+                &statistics,
+                render_stats.as_ref(),
+                system_profile
+                    .as_deref()
+                    .map(|system_profile| &**system_profile),
+            );
+            gfx.draw_ui_text(4.0, 4.0, &text, Color::WHITE);
+        }
+    }
+
+    std::mem::drop(input);
+    std::mem::drop(statistics);
+    std::mem::drop(system_profile);
+}
+
+fn overlay_text(
+    statistics: &EngineStatistics,
+    render_stats: Option<&RenderStats>,
+    system_profile: Option<&SystemProfile>,
+) -> String {
+    let mut text = format!(
+        "{:.0} FPS ({:.2} ms CPU)\nEntities: {}",
+        statistics.average_fps(),
+        statistics.last_frame_cpu_time() * 1000.0,
+        statistics.entity_count()
+    );
+    if let Some(render_stats) = render_stats {
+        let _ = write!(
+            text,
+            "\nDraw calls: {}\nBatches: {}\nVertices: {}",
+            render_stats.draw_calls, render_stats.batches, render_stats.vertices
+        );
+    }
+    if let Some(system_profile) = system_profile {
+        for (label, duration) in system_profile.slowest(LISTED_SYSTEM_COUNT) {
+            let _ = write!(
+                text,
+                "\n{}: {:.2} ms",
+                short_system_label(label),
+                duration.as_secs_f64() * 1000.0
+            );
+        }
+    }
+
+    text
+}
+
+/// Strips the module path from a system label, keeping the function name
+fn short_system_label(label: &str) -> &str {
+    label.rsplit("::").next().unwrap_or(label)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn overlay_text_includes_render_stats() {
+        let mut statistics = EngineStatistics::new();
+        statistics.record_frame(0.5, 0.001, 3);
+        let render_stats = RenderStats {
+            draw_calls: 4,
+            batches: 2,
+            vertices: 12,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            overlay_text(&statistics, None, None),
+            "2 FPS (1.00 ms CPU)\nEntities: 3"
+        );
+        assert_eq!(
+            overlay_text(&statistics, Some(&render_stats), None),
+            "2 FPS (1.00 ms CPU)\nEntities: 3\nDraw calls: 4\nBatches: 2\nVertices: 12"
+        );
+
+        let mut system_profile = SystemProfile::new();
+        system_profile.record("game::physics_system", Duration::from_micros(1500));
+        assert_eq!(
+            overlay_text(&statistics, None, Some(&system_profile)),
+            "2 FPS (1.00 ms CPU)\nEntities: 3\nphysics_system: 1.50 ms"
+        );
+    }
+}
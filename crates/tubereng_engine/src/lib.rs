@@ -1,31 +1,63 @@
 #![warn(clippy::pedantic)]
 
 use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
 use tubereng_asset::vfs::VirtualFileSystem;
+use tubereng_asset::AssetError;
 use tubereng_asset::AssetLoader;
 use tubereng_asset::AssetStore;
+use tubereng_core::spatial_hash::SpatialHash;
 use tubereng_core::TransformCache;
 
-use tubereng_ecs::system::Into;
 use tubereng_math::matrix::Identity;
 use tubereng_math::matrix::Matrix4f;
+use tubereng_math::vector::Vector2f;
 
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 use tubereng_core::DeltaTime;
+use tubereng_core::ExitRequest;
+use tubereng_core::Time;
 use tubereng_core::Transform;
 
-use tubereng_ecs::relationship::ChildOf;
+use tubereng_ecs::relationship::{ChildOf, RelationshipKind};
+use tubereng_ecs::resource::{Resource, ResourceRefMut};
 
 use tubereng_ecs::Storage;
+use tubereng_gui as gui;
 use tubereng_image::ImageLoader;
-use tubereng_input::{Input, InputState};
+use tubereng_input::{Input, InputConfig, InputState};
 
 use tubereng_ecs::{
     system::{self, System},
     Ecs,
 };
-use tubereng_renderer::texture;
+use tubereng_renderer::{
+    texture, ui_pass::UiScaleMode, GraphicsState, RendererConfig, RendererInitError, WindowId,
+    WindowSize,
+};
+
+use statistics::EngineStatistics;
+
+pub mod cursor;
+pub mod debug_overlay;
+pub mod loading;
+pub mod model;
+pub mod plugin;
+pub mod prefab;
+pub mod statistics;
+pub mod window;
 
+pub use cursor::{Cursor, CursorIcon, CursorImage};
+
+/// The stages of the engine, run in the order they are declared in. Custom
+/// stages can be inserted between them with
+/// [`EngineBuilder::with_stage_after`].
 pub mod system_stage {
     pub struct StartFrame;
     pub struct Update;
@@ -33,21 +65,141 @@ pub mod system_stage {
     pub struct FinalizeRender;
 }
 
+/// Resource selecting the engine stages run by [`Engine::update`], both
+/// enabled by default.
+///
+/// The simulation covers [`system_stage::StartFrame`] and
+/// [`system_stage::Update`], the rendering covers [`system_stage::Render`]
+/// and [`system_stage::FinalizeRender`]. The stages inserted by the
+/// application always run. The command queue is processed at the end of every
+/// frame whatever the enabled stages, so the commands of the simulation are
+/// applied even while the rendering is disabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameControl {
+    pub simulation_enabled: bool,
+    /// Whether the frames are rendered and presented, the window keeps its
+    /// last frame while the rendering is disabled
+    pub rendering_enabled: bool,
+}
+
+impl Default for FrameControl {
+    fn default() -> Self {
+        Self {
+            simulation_enabled: true,
+            rendering_enabled: true,
+        }
+    }
+}
+
+/// How the cursor is grabbed by the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorGrabMode {
+    #[default]
+    None,
+    /// The cursor cannot leave the window
+    Confined,
+    /// The cursor is locked in place, only relative mouse motion is reported
+    Locked,
+}
+
+/// How the window occupies the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FullscreenMode {
+    #[default]
+    Windowed,
+    /// The window covers the whole monitor without changing its video mode
+    Borderless,
+    /// The monitor switches to the given resolution
+    Exclusive { width: u32, height: u32 },
+}
+
+/// The configuration applied to the window when it is created
+#[derive(Debug, Clone, Copy)]
+pub struct WindowConfiguration {
+    width: u32,
+    height: u32,
+    icon: Option<&'static [u8]>,
+    cursor_visible: bool,
+    cursor_grab: CursorGrabMode,
+    fullscreen: FullscreenMode,
+}
+
+impl WindowConfiguration {
+    /// The initial size of the window, in physical pixels
+    #[must_use]
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The encoded image used as the window icon
+    #[must_use]
+    pub fn icon(&self) -> Option<&'static [u8]> {
+        self.icon
+    }
+
+    #[must_use]
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    #[must_use]
+    pub fn cursor_grab(&self) -> CursorGrabMode {
+        self.cursor_grab
+    }
+
+    #[must_use]
+    pub fn fullscreen(&self) -> FullscreenMode {
+        self.fullscreen
+    }
+}
+
+impl Default for WindowConfiguration {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 600,
+            icon: None,
+            cursor_visible: true,
+            cursor_grab: CursorGrabMode::None,
+            fullscreen: FullscreenMode::Windowed,
+        }
+    }
+}
+
 pub struct Engine {
     application_title: &'static str,
+    window_configuration: WindowConfiguration,
+    /// The mode used when toggling fullscreen from windowed mode
+    preferred_fullscreen_mode: FullscreenMode,
+    fullscreen_changed: bool,
     ecs: Ecs,
-    init_system: System,
-    init_system_ran: bool,
+    init_systems: Vec<System>,
+    init_systems_ran: bool,
+    shutdown_systems: Vec<System>,
     system_schedule: system::Schedule,
+    minimized: bool,
+    target_fps: Option<u32>,
+    renderer_config: RendererConfig,
+    /// Applies the cursor to the window, set by the runner
+    cursor_listener: Option<CursorListener>,
 }
 
+/// Applies a [`Cursor`] to the window, see [`Engine::on_cursor_changed`]
+type CursorListener = Box<dyn FnMut(&Cursor)>;
+
 impl Engine {
     #[must_use]
     pub fn builder() -> EngineBuilder {
         EngineBuilder::default()
     }
 
-    pub async fn init_graphics<W>(&mut self, window: Arc<W>)
+    /// Initializes the renderer for a window
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if the renderer cannot be initialized, for
+    /// instance if no suitable GPU adapter is found
+    pub async fn init_graphics<W>(&mut self, window: Arc<W>) -> Result<(), RendererInitError>
     where
         W: HasWindowHandle + HasDisplayHandle + std::marker::Send + std::marker::Sync,
     {
@@ -60,22 +212,140 @@ impl Engine {
             width: placeholder_texture_image.width(),
             height: placeholder_texture_image.height(),
         };
-        tubereng_renderer::renderer_init(&mut self.ecs, window, &placeholder_texture_descriptor)
-            .await;
+        // SAFETY: The font image is a valid PNG file that is loaded at compile time
+        let font_texture_image =
+            unsafe { ImageLoader::load(include_bytes!("../res/font.png")).unwrap_unchecked() };
+        let font_texture_descriptor = texture::Descriptor {
+            data: font_texture_image.data(),
+            width: font_texture_image.width(),
+            height: font_texture_image.height(),
+        };
+        let (width, height) = self.window_configuration.size();
+        tubereng_renderer::renderer_init(
+            &mut self.ecs,
+            window,
+            WindowSize { width, height },
+            &self.renderer_config,
+            &placeholder_texture_descriptor,
+            &font_texture_descriptor,
+        )
+        .await
     }
 
     /// Updates the state of the engine
+    ///
+    /// # Panics
+    ///
+    /// Will panic if
+    /// - the ``gui::Context`` is missing from the engine resources
+    /// - the ``InputState`` or the ``InputConfig`` are missing from the engine
+    ///   resources
+    /// - the ``EngineStatistics`` are missing from the engine resources
+    /// - the ``FrameControl`` is missing from the engine resources
+    /// - the ``Time`` is missing from the engine resources
     pub fn update(&mut self, delta_time: f32) {
+        let update_start_instant = Instant::now();
         self.ecs.insert_resource(DeltaTime(delta_time));
+        self.ecs
+            .resource_mut::<Time>()
+            .expect("Time should be present in the engine's resources")
+            .advance(delta_time);
+        {
+            let input_config = *self
+                .ecs
+                .resource::<InputConfig>()
+                .expect("InputConfig should be present in the engine's resources");
+            let mut input_state = self
+                .ecs
+                .resource_mut::<InputState>()
+                .expect("InputState should be present in the engine's resources");
+            input_state.set_config(input_config);
+            input_state.advance_time(delta_time);
+        }
+        self.ecs.update_events();
         self.ecs.clear_dirty_flags();
-        if !self.init_system_ran {
-            self.init_system.run(&mut self.ecs);
-            self.ecs.process_command_queue();
-            self.init_system_ran = true;
+        self.ecs
+            .resource_mut::<gui::Context>()
+            .expect("gui::Context should be present in the engine's resources")
+            .begin_frame();
+        if !self.init_systems_ran {
+            for init_system in &mut self.init_systems {
+                init_system.run(&mut self.ecs);
+                self.ecs.process_command_queue();
+            }
+            self.init_systems_ran = true;
         }
 
+        self.apply_frame_control();
         self.system_schedule.run_systems(&mut self.ecs);
         self.ecs.process_command_queue();
+        self.ecs
+            .resource_mut::<InputState>()
+            .expect("InputState should be present in the engine's resources")
+            .clear_last_frame_inputs();
+
+        let entity_count = self.ecs.entity_count();
+        self.ecs
+            .resource_mut::<EngineStatistics>()
+            .expect("EngineStatistics should be present in the engine's resources")
+            .record_frame(
+                delta_time,
+                update_start_instant.elapsed().as_secs_f32(),
+                entity_count,
+            );
+    }
+
+    fn apply_frame_control(&mut self) {
+        let frame_control = *self
+            .ecs
+            .resource::<FrameControl>()
+            .expect("FrameControl should be present in the engine's resources");
+        let rendering_enabled = frame_control.rendering_enabled && !self.minimized;
+        self.system_schedule
+            .set_stage_enabled(&system_stage::StartFrame, frame_control.simulation_enabled);
+        self.system_schedule
+            .set_stage_enabled(&system_stage::Update, frame_control.simulation_enabled);
+        self.system_schedule
+            .set_stage_enabled(&system_stage::Render, rendering_enabled);
+        self.system_schedule
+            .set_stage_enabled(&system_stage::FinalizeRender, rendering_enabled);
+    }
+
+    /// Enables or disables the simulation stages, see [`FrameControl`]
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the ``FrameControl`` is missing from the engine resources
+    pub fn set_simulation_enabled(&mut self, simulation_enabled: bool) {
+        self.ecs
+            .resource_mut::<FrameControl>()
+            .expect("FrameControl should be present in the engine's resources")
+            .simulation_enabled = simulation_enabled;
+    }
+
+    /// Enables or disables the rendering stages, see [`FrameControl`]
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the ``FrameControl`` is missing from the engine resources
+    pub fn set_rendering_enabled(&mut self, rendering_enabled: bool) {
+        self.ecs
+            .resource_mut::<FrameControl>()
+            .expect("FrameControl should be present in the engine's resources")
+            .rendering_enabled = rendering_enabled;
+    }
+
+    /// Runs the shutdown systems, e.g. to flush a save. Only the first call
+    /// has an effect.
+    ///
+    /// The runner calls this right before the application stops. The
+    /// graphics resources are still valid while the shutdown systems run but
+    /// the window may already be closing, so nothing should be rendered.
+    pub fn shutdown(&mut self) {
+        for shutdown_system in std::mem::take(&mut self.shutdown_systems) {
+            shutdown_system.run(&mut self.ecs);
+            self.ecs.process_command_queue();
+        }
     }
 
     /// Handles the input
@@ -91,37 +361,476 @@ impl Engine {
             .resource_mut::<InputState>()
             .expect("InputState should be present in the engine's resources");
         input_state.on_input(&input);
+
+        // The GUI works in UI coordinates, which differ from the window
+        // pixels when a UiScaleMode is set
+        let input = match (
+            input,
+            self.ecs.resource::<UiScaleMode>(),
+            self.ecs.resource::<GraphicsState>(),
+        ) {
+            (Input::CursorMoved((x, y)), Some(scale_mode), Some(gfx)) => {
+                #[allow(clippy::cast_possible_truncation)]
+                let (x, y) = scale_mode
+                    .viewport(*gfx.window_size())
+                    .window_to_ui(x as f32, y as f32);
+                Input::CursorMoved((f64::from(x), f64::from(y)))
+            }
+            (input, _, _) => input,
+        };
+        let mut gui_context = self
+            .ecs
+            .resource_mut::<gui::Context>()
+            .expect("gui::Context should be present in the engine's resources");
+        gui_context.on_input(&input);
+    }
+
+    /// Returns true if a system asked the engine to stop running
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the ``ExitRequest`` is missing from the engine resources
+    #[must_use]
+    pub fn should_exit(&self) -> bool {
+        self.ecs
+            .resource::<ExitRequest>()
+            .expect("ExitRequest should be present in the engine's resources")
+            .is_requested()
+    }
+
+    /// Changes the fullscreen mode of the window, the change is applied by the
+    /// runner at the end of the frame
+    pub fn set_fullscreen(&mut self, fullscreen_mode: FullscreenMode) {
+        if fullscreen_mode != FullscreenMode::Windowed {
+            self.preferred_fullscreen_mode = fullscreen_mode;
+        }
+
+        self.window_configuration.fullscreen = fullscreen_mode;
+        self.fullscreen_changed = true;
+    }
+
+    /// Switches between windowed mode and the last used fullscreen mode
+    pub fn toggle_fullscreen(&mut self) {
+        if self.window_configuration.fullscreen == FullscreenMode::Windowed {
+            self.set_fullscreen(self.preferred_fullscreen_mode);
+        } else {
+            self.set_fullscreen(FullscreenMode::Windowed);
+        }
+    }
+
+    /// Returns the fullscreen mode to apply to the window if it changed since
+    /// the last call
+    pub fn take_fullscreen_change(&mut self) -> Option<FullscreenMode> {
+        if !std::mem::take(&mut self.fullscreen_changed) {
+            return None;
+        }
+
+        Some(self.window_configuration.fullscreen)
+    }
+
+    /// Handles a resize of the window by resizing the rendering surface.
+    /// While the window has a zero size (e.g. when it is minimized) the render
+    /// stages are not run.
+    pub fn on_window_resized(&mut self, width: u32, height: u32) {
+        let minimized = width == 0 || height == 0;
+        if !minimized {
+            if let Some(mut gfx) = self.ecs.resource_mut::<GraphicsState>() {
+                gfx.resize(width, height);
+            }
+        }
+
+        self.minimized = minimized;
+    }
+
+    /// Returns the secondary windows requested since the last call, for the
+    /// runner to create them
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the ``WindowRequests`` are missing from the engine
+    /// resources
+    pub fn take_window_requests(&mut self) -> Vec<window::WindowDescriptor> {
+        self.ecs
+            .resource_mut::<window::WindowRequests>()
+            .expect("WindowRequests should be present in the engine's resources")
+            .take()
+    }
+
+    /// Creates the rendering surface of a secondary window created by the
+    /// runner. The window must outlive its surface, see
+    /// [`Engine::on_window_closed`].
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if the surface cannot be created
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the graphics haven't been initialized
+    pub fn on_window_opened<W>(
+        &mut self,
+        window_id: WindowId,
+        window: &W,
+        window_descriptor: &window::WindowDescriptor,
+    ) -> Result<(), RendererInitError>
+    where
+        W: HasWindowHandle + HasDisplayHandle,
+    {
+        self.ecs
+            .resource_mut::<GraphicsState>()
+            .expect("The graphics should be initialized before opening windows")
+            .add_window(
+                window_id,
+                window,
+                WindowSize {
+                    width: window_descriptor.width,
+                    height: window_descriptor.height,
+                },
+                window_descriptor.clear_color,
+            )
+    }
+
+    /// Handles a resize of a secondary window
+    pub fn on_secondary_window_resized(&mut self, window_id: WindowId, width: u32, height: u32) {
+        if let Some(mut gfx) = self.ecs.resource_mut::<GraphicsState>() {
+            gfx.resize_window(window_id, width, height);
+        }
+    }
+
+    /// Drops the rendering surface of a secondary window, to be called before
+    /// the window is destroyed
+    pub fn on_window_closed(&mut self, window_id: WindowId) {
+        if let Some(mut gfx) = self.ecs.resource_mut::<GraphicsState>() {
+            gfx.remove_window(window_id);
+        }
+    }
+
+    /// Sets the function applying the cursor to the window, called as soon
+    /// as the cursor is changed through the engine. The runner sets it.
+    pub fn on_cursor_changed<F>(&mut self, listener: F)
+    where
+        F: 'static + FnMut(&Cursor),
+    {
+        self.cursor_listener = Some(Box::new(listener));
+    }
+
+    /// Changes the shape of the cursor, replacing the custom cursor image if
+    /// any. The change takes effect immediately.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the ``Cursor`` is missing from the engine resources
+    pub fn set_cursor_icon(&mut self, cursor_icon: CursorIcon) {
+        self.replace_cursor_image(None);
+        self.cursor_mut().icon = cursor_icon;
+        self.notify_cursor_changed();
+    }
+
+    /// Replaces the cursor of the platform with an encoded image (e.g. a PNG
+    /// file) drawn at its native size. `hotspot` is the pixel of the image
+    /// placed at the cursor position, from its top-left corner. The change
+    /// takes effect immediately, [`Engine::set_cursor_icon`] restores the
+    /// cursor of the platform.
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if the image cannot be decoded
+    ///
+    /// # Panics
+    ///
+    /// Will panic if
+    /// - the graphics aren't initialized, see [`Engine::init_graphics`]
+    /// - the ``Cursor`` is missing from the engine resources
+    pub fn set_cursor_image(
+        &mut self,
+        image_bytes: &[u8],
+        hotspot: (u32, u32),
+    ) -> Result<(), AssetError> {
+        let image = ImageLoader::load(image_bytes)?;
+        let texture = self
+            .ecs
+            .resource_mut::<GraphicsState>()
+            .expect("The graphics should be initialized before setting a cursor image")
+            .load_texture(&texture::Descriptor {
+                data: image.data(),
+                width: image.width(),
+                height: image.height(),
+            });
+        self.replace_cursor_image(Some(CursorImage {
+            texture,
+            width: image.width(),
+            height: image.height(),
+            hotspot,
+        }));
+        self.notify_cursor_changed();
+        Ok(())
+    }
+
+    /// Sets the cursor image, unloading the texture of the previous one
+    fn replace_cursor_image(&mut self, image: Option<CursorImage>) {
+        let previous_image = std::mem::replace(&mut self.cursor_mut().image, image);
+        if let (Some(previous_image), Some(mut gfx)) =
+            (previous_image, self.ecs.resource_mut::<GraphicsState>())
+        {
+            gfx.unload_texture(previous_image.texture);
+        }
+    }
+
+    fn cursor_mut(&self) -> ResourceRefMut<'_, Cursor> {
+        self.ecs
+            .resource_mut::<Cursor>()
+            .expect("Cursor should be present in the engine's resources")
+    }
+
+    fn notify_cursor_changed(&mut self) {
+        let cursor = self.cursor();
+        if let Some(cursor_listener) = &mut self.cursor_listener {
+            cursor_listener(&cursor);
+        }
+    }
+
+    /// Returns the cursor that should be shown over the window
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the ``Cursor`` is missing from the engine resources
+    #[must_use]
+    pub fn cursor(&self) -> Cursor {
+        *self
+            .ecs
+            .resource::<Cursor>()
+            .expect("Cursor should be present in the engine's resources")
+    }
+
+    /// Returns the shape the cursor should have
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the ``Cursor`` is missing from the engine resources
+    #[must_use]
+    pub fn cursor_icon(&self) -> CursorIcon {
+        self.cursor().icon
+    }
+
+    #[must_use]
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
     }
 
     #[must_use]
     pub fn application_title(&self) -> &'static str {
         self.application_title
     }
+
+    /// Returns the minimum duration of a frame if the frame rate is capped
+    #[must_use]
+    pub fn target_frame_duration(&self) -> Option<Duration> {
+        self.target_fps
+            .map(|target_fps| Duration::from_secs_f64(1.0 / f64::from(target_fps)))
+    }
+
+    #[must_use]
+    pub fn window_configuration(&self) -> &WindowConfiguration {
+        &self.window_configuration
+    }
 }
 
+/// Inserts a resource registered on the [`EngineBuilder`] into the engine
+type ResourceInserter = Box<dyn FnOnce(&mut Ecs)>;
+
 pub struct EngineBuilder {
     application_title: &'static str,
-    init_system: system::System,
+    window_configuration: WindowConfiguration,
+    target_fps: Option<u32>,
+    renderer_config: RendererConfig,
+    /// The init system set with [`EngineBuilder::with_init_system`], run
+    /// before the other init systems
+    init_system: Option<system::System>,
+    init_systems: Vec<system::System>,
+    shutdown_systems: Vec<system::System>,
     system_schedule: system::Schedule,
+    component_registry: prefab::ComponentRegistry,
+    resource_inserters: Vec<ResourceInserter>,
+    asset_root: Option<std::path::PathBuf>,
 }
 
 impl EngineBuilder {
+    /// Sets the first init system, run before the ones added with
+    /// [`EngineBuilder::add_init_system`] or by plugins, replacing the one
+    /// previously set with this method
     #[must_use]
     pub fn with_init_system<F, A>(mut self, init_system: F) -> Self
     where
         F: 'static + system::Into<A>,
     {
-        self.init_system = init_system.into_system();
+        self.init_system = Some(init_system.into_system());
+        self
+    }
+
+    /// Adds a system run once before the first update. Init systems run in
+    /// the order they are added, the commands of each one are processed
+    /// before the next one runs.
+    #[must_use]
+    pub fn add_init_system<F, A>(mut self, init_system: F) -> Self
+    where
+        F: 'static + system::Into<A>,
+    {
+        self.register_init_system(init_system);
+        self
+    }
+
+    /// Adds an init system, see [`EngineBuilder::add_init_system`]
+    pub fn register_init_system<F, A>(&mut self, init_system: F) -> &mut Self
+    where
+        F: 'static + system::Into<A>,
+    {
+        self.init_systems.push(init_system.into_system());
         self
     }
+
+    /// Adds a system run once when the application stops, see
+    /// [`Engine::shutdown`]. Shutdown systems run in the order they are
+    /// added.
+    #[must_use]
+    pub fn add_shutdown_system<F, A>(mut self, shutdown_system: F) -> Self
+    where
+        F: 'static + system::Into<A>,
+    {
+        self.register_shutdown_system(shutdown_system);
+        self
+    }
+
+    /// Adds a shutdown system, see [`EngineBuilder::add_shutdown_system`]
+    pub fn register_shutdown_system<F, A>(&mut self, shutdown_system: F) -> &mut Self
+    where
+        F: 'static + system::Into<A>,
+    {
+        self.shutdown_systems.push(shutdown_system.into_system());
+        self
+    }
+
+    /// Adds the systems, resources and init systems of a plugin
+    #[must_use]
+    pub fn with_plugin<P>(mut self, plugin: &P) -> Self
+    where
+        P: plugin::Plugin,
+    {
+        plugin.build(&mut self);
+        self
+    }
+
+    /// Inserts a resource into the engine when it is built, replacing the
+    /// engine resource of the same type if any
+    pub fn insert_resource<R>(&mut self, resource: R) -> &mut Self
+    where
+        R: Resource,
+    {
+        self.resource_inserters
+            .push(Box::new(move |ecs| ecs.insert_resource(resource)));
+        self
+    }
+
     #[must_use]
     pub fn with_application_title(mut self, application_title: &'static str) -> Self {
         self.application_title = application_title;
         self
     }
 
+    /// Sets the initial size of the window, in physical pixels
+    #[must_use]
+    pub fn with_window_size(mut self, width: u32, height: u32) -> Self {
+        self.window_configuration.width = width;
+        self.window_configuration.height = height;
+        self
+    }
+
+    /// Sets the window icon from an encoded image (e.g. a PNG file)
+    #[must_use]
+    pub fn with_window_icon(mut self, image_bytes: &'static [u8]) -> Self {
+        self.window_configuration.icon = Some(image_bytes);
+        self
+    }
+
+    #[must_use]
+    pub fn with_cursor_visible(mut self, cursor_visible: bool) -> Self {
+        self.window_configuration.cursor_visible = cursor_visible;
+        self
+    }
+
+    #[must_use]
+    pub fn with_cursor_grab(mut self, cursor_grab: CursorGrabMode) -> Self {
+        self.window_configuration.cursor_grab = cursor_grab;
+        self
+    }
+
+    #[must_use]
+    pub fn with_fullscreen(mut self, fullscreen_mode: FullscreenMode) -> Self {
+        self.window_configuration.fullscreen = fullscreen_mode;
+        self
+    }
+
+    /// Caps the frame rate of the application. The cap is ignored on wasm32
+    /// where frames are paced by the browser.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `target_fps` is zero
+    #[must_use]
+    pub fn with_target_fps(mut self, target_fps: u32) -> Self {
+        assert!(target_fps > 0, "The target FPS should be strictly positive");
+        self.target_fps = Some(target_fps);
+        self
+    }
+
+    /// Sets the GPU features and limits required by the application
+    #[must_use]
+    pub fn with_renderer_config(mut self, renderer_config: RendererConfig) -> Self {
+        self.renderer_config = renderer_config;
+        self
+    }
+
     #[must_use]
     pub fn with_system<Stage, F, S>(mut self, stage: &Stage, system: F) -> Self
+    where
+        F: 'static + system::Into<S>,
+        S: 'static,
+        Stage: 'static,
+    {
+        self.register_system(stage, system);
+        self
+    }
+
+    /// Inserts a custom stage right after an existing one, e.g. a physics
+    /// stage between [`system_stage::Update`] and [`system_stage::Render`]
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `after` isn't a stage of the engine
+    #[must_use]
+    pub fn with_stage_after<After, Stage>(mut self, after: &After, stage: &Stage) -> Self
+    where
+        After: 'static,
+        Stage: 'static,
+    {
+        self.register_stage_after(after, stage);
+        self
+    }
+
+    /// Inserts a custom stage, see [`EngineBuilder::with_stage_after`]
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `after` isn't a stage of the engine
+    pub fn register_stage_after<After, Stage>(&mut self, after: &After, stage: &Stage) -> &mut Self
+    where
+        After: 'static,
+        Stage: 'static,
+    {
+        self.system_schedule.insert_stage_after(after, stage);
+        self
+    }
+
+    pub fn register_system<Stage, F, S>(&mut self, stage: &Stage, system: F) -> &mut Self
     where
         F: 'static + system::Into<S>,
         S: 'static,
@@ -131,20 +840,82 @@ impl EngineBuilder {
         self
     }
 
+    /// Registers a component type so it can be referenced by name in prefab
+    /// files
+    #[must_use]
+    pub fn with_prefab_component<C>(mut self, name: &str) -> Self
+    where
+        C: 'static + serde::de::DeserializeOwned + std::fmt::Debug + Send + Sync,
+    {
+        self.component_registry.register::<C>(name);
+        self
+    }
+
+    /// Sets the directory asset paths are resolved against, see
+    /// [`AssetStore`] for the default resolution
+    #[must_use]
+    pub fn with_asset_root<P: Into<std::path::PathBuf>>(mut self, asset_root: P) -> Self {
+        self.asset_root = Some(asset_root.into());
+        self
+    }
+
     pub fn build<VFS>(mut self, fs: VFS) -> Engine
     where
-        VFS: 'static + VirtualFileSystem,
+        VFS: 'static + VirtualFileSystem + Send + Sync,
     {
         let mut ecs = Ecs::new();
         ecs.insert_resource(InputState::new());
+        ecs.insert_resource(InputConfig::default());
+        ecs.insert_resource(gui::Context::new());
         ecs.insert_resource(TransformCache::new());
-        ecs.define_relationship::<ChildOf>();
-        ecs.insert_resource(AssetStore::new(fs));
+        ecs.insert_resource(SpatialHash::default());
+        // Entity ids are reused, so the matrices of deleted entities must not
+        // outlive them
+        ecs.on_remove::<Transform, _>(|storage, entity_id| {
+            if let Some(mut transform_cache) = storage.resource_mut::<TransformCache>() {
+                transform_cache.remove(entity_id);
+            }
+        });
+        ecs.insert_resource(ExitRequest::new());
+        ecs.insert_resource(Time::new());
+        ecs.insert_resource(FrameControl::default());
+        ecs.insert_resource(Cursor::default());
+        ecs.insert_resource(window::WindowRequests::new());
+        ecs.insert_resource(debug_overlay::DebugOverlay::default());
+        ecs.insert_resource(loading::LoadQueue::new());
+        let mut statistics = EngineStatistics::new();
+        statistics.set_target_fps(self.target_fps);
+        ecs.insert_resource(statistics);
+        ecs.define_relationship::<ChildOf>(RelationshipKind::OneToMany);
+        let mut asset_store = AssetStore::new(fs);
+        if let Some(asset_root) = self.asset_root {
+            asset_store = asset_store.with_asset_root(asset_root);
+        }
+        ecs.insert_resource(asset_store);
+        ecs.insert_resource(self.component_registry);
+        for insert_resource in self.resource_inserters {
+            insert_resource(&mut ecs);
+        }
 
+        self.system_schedule
+            .add_system(&system_stage::StartFrame, loading::run_load_tasks_system);
+        self.system_schedule.add_system(
+            &system_stage::Update,
+            tubereng_renderer::camera::follow_system,
+        );
+        // Before the render statistics are reset by begin_frame_system
+        self.system_schedule
+            .add_system(&system_stage::Render, debug_overlay::debug_overlay_system);
         self.system_schedule
             .add_system(&system_stage::Render, compute_effective_transforms_system);
+        self.system_schedule
+            .add_system(&system_stage::Render, rebuild_spatial_hash_system);
         self.system_schedule
             .add_system(&system_stage::Render, tubereng_renderer::begin_frame_system);
+        self.system_schedule
+            .add_system(&system_stage::Render, gui::render_system);
+        self.system_schedule
+            .add_system(&system_stage::Render, cursor::draw_cursor_image_system);
         self.system_schedule.add_system(
             &system_stage::Render,
             tubereng_renderer::prepare_passes_system,
@@ -156,24 +927,76 @@ impl EngineBuilder {
 
         Engine {
             application_title: self.application_title,
+            window_configuration: self.window_configuration,
+            preferred_fullscreen_mode: match self.window_configuration.fullscreen {
+                FullscreenMode::Windowed => FullscreenMode::Borderless,
+                fullscreen_mode => fullscreen_mode,
+            },
+            fullscreen_changed: false,
             ecs,
-            init_system: self.init_system,
-            init_system_ran: false,
+            init_systems: self
+                .init_system
+                .into_iter()
+                .chain(self.init_systems)
+                .collect(),
+            init_systems_ran: false,
+            shutdown_systems: self.shutdown_systems,
             system_schedule: self.system_schedule,
+            minimized: false,
+            target_fps: self.target_fps,
+            renderer_config: self.renderer_config,
+            cursor_listener: None,
         }
     }
 }
 
+fn default_schedule() -> system::Schedule {
+    let mut schedule = system::Schedule::new();
+    schedule.add_stage(&system_stage::StartFrame);
+    schedule.add_stage(&system_stage::Update);
+    schedule.add_stage(&system_stage::Render);
+    schedule.add_stage(&system_stage::FinalizeRender);
+    schedule
+}
+
 impl Default for EngineBuilder {
     fn default() -> Self {
         Self {
             application_title: "Tuber application",
-            init_system: Into::<()>::into_system(system::Noop),
-            system_schedule: system::Schedule::default(),
+            window_configuration: WindowConfiguration::default(),
+            target_fps: None,
+            renderer_config: RendererConfig::default(),
+            init_system: None,
+            init_systems: vec![],
+            shutdown_systems: vec![],
+            system_schedule: default_schedule(),
+            component_registry: prefab::ComponentRegistry::new(),
+            resource_inserters: vec![],
+            asset_root: None,
         }
     }
 }
 
+/// Buckets the entities with a transform by their effective world position
+fn rebuild_spatial_hash_system(storage: &Storage) {
+    let transform_cache = storage
+        .resource::<TransformCache>()
+        .expect("A TransformCache resource should be present");
+    let Some(mut spatial_hash) = storage.resource_mut::<SpatialHash>() else {
+        return;
+    };
+
+    spatial_hash.rebuild(
+        storage
+            .query::<&Transform>()
+            .iter_with_ids()
+            .map(|(id, _)| {
+                let matrix = transform_cache.get(id);
+                (id, Vector2f::new(matrix[0][3], matrix[1][3]))
+            }),
+    );
+}
+
 fn compute_effective_transforms_system(storage: &Storage) {
     let Some(child_of_relationship) = storage.relationship::<ChildOf>() else {
         return;
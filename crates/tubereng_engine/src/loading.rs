@@ -0,0 +1,185 @@
+//! Loading of heavy assets spread over several frames.
+//!
+//! Load tasks are queued in the [`LoadQueue`] resource and run at the start
+//! of the frames until the frame budget of the queue is spent, so a large
+//! scene streams in instead of stalling a single frame.
+//!
+//! ```ignore
+//! fn load_level_system(mut load_queue: ResMut<LoadQueue>) {
+//!     load_queue.push(|storage, command_queue| {
+//!         // Load a chunk of the level
+//!     });
+//! }
+//!
+//! fn loading_bar_system(load_queue: Res<LoadQueue>, mut gfx: ResMut<GraphicsState>) {
+//!     let (loaded, total) = load_queue.progress();
+//!     gfx.draw_ui_text(0.0, 0.0, &format!("Loading {loaded}/{total}"), Color::WHITE);
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+use tubereng_ecs::{commands::CommandQueue, Storage};
+
+/// A load task, given the storage and the command queue of the frame it runs
+/// in. Tasks can queue more tasks in the [`LoadQueue`].
+pub type LoadTask = Box<dyn FnOnce(&Storage, &CommandQueue) + Send + Sync>;
+
+/// Resource holding the pending load tasks
+pub struct LoadQueue {
+    tasks: VecDeque<LoadTask>,
+    frame_budget: Duration,
+    loaded_task_count: usize,
+    total_task_count: usize,
+}
+
+impl LoadQueue {
+    /// Creates an empty queue with a frame budget of 4ms
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            tasks: VecDeque::new(),
+            frame_budget: Duration::from_millis(4),
+            loaded_task_count: 0,
+            total_task_count: 0,
+        }
+    }
+
+    /// Sets the time spent running tasks each frame. At least one task runs
+    /// per frame, even if it exceeds the budget.
+    #[must_use]
+    pub fn with_frame_budget(mut self, frame_budget: Duration) -> Self {
+        self.frame_budget = frame_budget;
+        self
+    }
+
+    /// Queues a task, run after the previously queued ones
+    pub fn push<F>(&mut self, task: F)
+    where
+        F: 'static + FnOnce(&Storage, &CommandQueue) + Send + Sync,
+    {
+        self.tasks.push_back(Box::new(task));
+        self.total_task_count += 1;
+    }
+
+    /// Returns the number of tasks run and the number of tasks queued since
+    /// the queue was last empty
+    #[must_use]
+    pub fn progress(&self) -> (usize, usize) {
+        (self.loaded_task_count, self.total_task_count)
+    }
+
+    /// Returns true if no task is pending
+    #[must_use]
+    pub fn is_done(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    fn pop(&mut self) -> Option<LoadTask> {
+        self.tasks.pop_front()
+    }
+
+    fn task_done(&mut self) {
+        self.loaded_task_count += 1;
+        if self.tasks.is_empty() {
+            self.loaded_task_count = 0;
+            self.total_task_count = 0;
+        }
+    }
+}
+
+impl Default for LoadQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Runs the pending load tasks until the frame budget of the [`LoadQueue`]
+/// is spent
+pub(crate) fn run_load_tasks_system(storage: &Storage, command_queue: &CommandQueue) {
+    let start_instant = Instant::now();
+    loop {
+        // The queue isn't borrowed while a task runs so the task can queue
+        // more tasks
+        let Some(task) = storage
+            .resource_mut::<LoadQueue>()
+            .and_then(|mut load_queue| load_queue.pop())
+        else {
+            return;
+        };
+        task(storage, command_queue);
+
+        let Some(mut load_queue) = storage.resource_mut::<LoadQueue>() else {
+            return;
+        };
+        load_queue.task_done();
+        if start_instant.elapsed() >= load_queue.frame_budget {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_ecs::{
+        system::{self, Into},
+        Ecs,
+    };
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Loaded {
+        count: u32,
+    }
+
+    #[test]
+    fn run_load_tasks_system_respects_frame_budget() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(Loaded { count: 0 });
+        let mut load_queue = LoadQueue::new().with_frame_budget(Duration::ZERO);
+        for _ in 0..2 {
+            load_queue.push(|storage, _| {
+                storage.resource_mut::<Loaded>().unwrap().count += 1;
+            });
+        }
+        ecs.insert_resource(load_queue);
+        let mut schedule = system::Schedule::new();
+        schedule.add_stage(&());
+        schedule.add_system(&(), run_load_tasks_system);
+
+        schedule.run_systems(&mut ecs);
+        assert_eq!(ecs.resource::<Loaded>().unwrap().count, 1);
+        assert_eq!(ecs.resource::<LoadQueue>().unwrap().progress(), (1, 2));
+
+        schedule.run_systems(&mut ecs);
+        assert_eq!(ecs.resource::<Loaded>().unwrap().count, 2);
+        assert!(ecs.resource::<LoadQueue>().unwrap().is_done());
+    }
+
+    #[test]
+    fn load_tasks_can_queue_tasks() {
+        let mut ecs = Ecs::new();
+        ecs.insert_resource(Loaded { count: 0 });
+        let mut load_queue = LoadQueue::new().with_frame_budget(Duration::MAX);
+        load_queue.push(|storage, _| {
+            storage
+                .resource_mut::<LoadQueue>()
+                .unwrap()
+                .push(|storage, _| {
+                    storage.resource_mut::<Loaded>().unwrap().count += 1;
+                });
+        });
+        ecs.insert_resource(load_queue);
+
+        ecs.run_single_run_system(&run_load_tasks_system.into_system());
+        assert_eq!(ecs.resource::<Loaded>().unwrap().count, 1);
+        assert!(ecs.resource::<LoadQueue>().unwrap().is_done());
+    }
+}
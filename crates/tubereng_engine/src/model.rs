@@ -0,0 +1,162 @@
+//! Bridges the models loaded by the asset store to the renderer.
+//!
+//! ```ignore
+//! let model_handle = asset_store.load::<Model>("models/ship.obj")?;
+//! let model = asset_store.get(model_handle).unwrap();
+//! let meshes = load_model_meshes(&mut gfx, model);
+//! let materials = load_model_materials(&mut gfx, model);
+//! for (mesh, material) in meshes.into_iter().zip(materials) {
+//!     command_queue.insert((Transform::default(), Mesh { mesh, material: Some(material) }));
+//! }
+//! ```
+//!
+//! Large models can be loaded over several frames with [`queue_model_loading`].
+use std::{
+    borrow::Cow,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use log::warn;
+use tubereng_asset::AssetStore;
+use tubereng_ecs::commands::CommandQueue;
+use tubereng_model::{MeshData, Model};
+use tubereng_renderer::{material, mesh, texture, GraphicsState};
+
+use crate::loading::LoadQueue;
+
+/// Uploads the meshes of a model to the GPU and returns their ids, to be used
+/// in [`mesh::Mesh`] components. The normals missing from the model are
+/// computed, see [`Model::compute_normals`].
+pub fn load_model_meshes(gfx: &mut GraphicsState, model: &Model) -> Vec<mesh::Id> {
+    model
+        .meshes()
+        .iter()
+        .map(|mesh_data| load_mesh(gfx, mesh_data))
+        .collect()
+}
+
+/// Creates a solid-color material for each mesh of a model from the base
+/// color factor of its material, meshes sharing a color share a material.
+/// The returned ids are in the order of the meshes of the model.
+pub fn load_model_materials(gfx: &mut GraphicsState, model: &Model) -> Vec<material::Id> {
+    let mut loaded_materials: Vec<([u8; 4], material::Id)> = vec![];
+    model
+        .meshes()
+        .iter()
+        .map(|mesh_data| {
+            let color = color_to_rgba8(mesh_data.base_color());
+            if let Some((_, material_id)) = loaded_materials.iter().find(|(c, _)| *c == color) {
+                return *material_id;
+            }
+
+            let material_id = load_solid_color_material(gfx, color);
+            loaded_materials.push((color, material_id));
+            material_id
+        })
+        .collect()
+}
+
+fn load_solid_color_material(gfx: &mut GraphicsState, color: [u8; 4]) -> material::Id {
+    let texture_id = gfx.load_texture(&texture::Descriptor {
+        data: &color,
+        width: 1,
+        height: 1,
+    });
+    gfx.load_material(&material::Descriptor {
+        base_color: texture_id,
+        region: texture::Rect::new(0.0, 0.0, 1.0, 1.0),
+    })
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn color_to_rgba8(color: [f32; 4]) -> [u8; 4] {
+    color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+/// Uploads a mesh to the GPU, computing its normals first if the model
+/// doesn't provide them
+fn load_mesh(gfx: &mut GraphicsState, mesh_data: &MeshData) -> mesh::Id {
+    let mesh_data = if mesh_data.normals().is_empty() {
+        let mut mesh_data = mesh_data.clone();
+        mesh_data.compute_normals(false);
+        Cow::Owned(mesh_data)
+    } else {
+        Cow::Borrowed(mesh_data)
+    };
+    let vertices = mesh_data
+        .positions()
+        .iter()
+        .zip(mesh_data.texture_coordinates())
+        .zip(mesh_data.normals())
+        .map(|((position, texture_coordinates), normal)| {
+            mesh::Vertex::new(*position, *texture_coordinates).with_normal(*normal)
+        })
+        .collect::<Vec<_>>();
+    gfx.load_mesh(&mesh::Descriptor {
+        vertices: &vertices,
+        indices: mesh_data.indices(),
+    })
+}
+
+/// Queues the loading of a model in the [`LoadQueue`]: the model is read by
+/// a first task, then each of its meshes is uploaded to the GPU by its own
+/// task. `on_loaded` is called with the ids of the meshes once they are all
+/// uploaded. Models that fail to load are skipped with a warning.
+///
+/// # Panics
+///
+/// The tasks will panic if the ``AssetStore``, the ``LoadQueue`` or the
+/// ``GraphicsState`` are missing from the engine resources
+pub fn queue_model_loading<F>(load_queue: &mut LoadQueue, model_path: &str, on_loaded: F)
+where
+    F: 'static + FnOnce(&CommandQueue, Vec<mesh::Id>) + Send + Sync,
+{
+    let model_path = model_path.to_string();
+    load_queue.push(move |storage, _| {
+        let model = storage
+            .resource::<AssetStore>()
+            .expect("AssetStore should be present in the engine's resources")
+            .load_without_storing::<Model>(&model_path);
+        let model = match model {
+            Ok(model) => Arc::new(model),
+            Err(e) => {
+                warn!("Couldn't load the model {model_path}: {e:?}");
+                return;
+            }
+        };
+
+        let mut load_queue = storage
+            .resource_mut::<LoadQueue>()
+            .expect("LoadQueue should be present in the engine's resources");
+        let mesh_ids = Arc::new(Mutex::new(vec![]));
+        for mesh_index in 0..model.meshes().len() {
+            let model = model.clone();
+            let mesh_ids = mesh_ids.clone();
+            load_queue.push(move |storage, _| {
+                let mut gfx = storage
+                    .resource_mut::<GraphicsState>()
+                    .expect("GraphicsState should be present in the engine's resources");
+                mesh_ids
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(load_mesh(&mut gfx, &model.meshes()[mesh_index]));
+            });
+        }
+        load_queue.push(move |_, command_queue| {
+            on_loaded(
+                command_queue,
+                std::mem::take(&mut *mesh_ids.lock().unwrap_or_else(PoisonError::into_inner)),
+            );
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_to_rgba8_clamps_and_rounds() {
+        assert_eq!(color_to_rgba8([1.0, 0.5, -1.0, 2.0]), [255, 128, 0, 255]);
+    }
+}
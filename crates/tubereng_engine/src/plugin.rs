@@ -0,0 +1,122 @@
+//! Plugins bundle the resources and systems of a feature so it can be added
+//! to an application in one call.
+//!
+//! Render passes are added from an init system, as the graphics pipeline
+//! only exists once the window has been created:
+//!
+//! ```ignore
+//! struct TilemapPlugin;
+//! impl Plugin for TilemapPlugin {
+//!     fn build(&self, builder: &mut EngineBuilder) {
+//!         builder
+//!             .insert_resource(TilemapCache::new())
+//!             .register_init_system(add_tilemap_pass_system)
+//!             .register_system(&Update, animate_tiles_system);
+//!     }
+//! }
+//!
+//! let engine = Engine::builder().with_plugin(&TilemapPlugin).build(vfs);
+//! ```
+
+use crate::EngineBuilder;
+
+pub trait Plugin {
+    /// Registers the resources and systems of the plugin
+    fn build(&self, builder: &mut EngineBuilder);
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_asset::vfs::VirtualFileSystem;
+    use tubereng_ecs::system::ResMut;
+
+    use crate::{system_stage, Engine};
+
+    use super::*;
+
+    struct MockFS;
+    impl VirtualFileSystem for MockFS {
+        fn read_bytes(&self, _path: &str) -> tubereng_asset::Result<Vec<u8>> {
+            Ok(vec![])
+        }
+    }
+
+    #[derive(Debug)]
+    struct Counter(u32);
+
+    fn increment_counter_system(mut counter: ResMut<Counter>) {
+        counter.0 += 1;
+    }
+
+    struct CounterPlugin {
+        initial_value: u32,
+    }
+
+    impl Plugin for CounterPlugin {
+        fn build(&self, builder: &mut EngineBuilder) {
+            builder
+                .insert_resource(Counter(self.initial_value))
+                .register_init_system(increment_counter_system)
+                .register_shutdown_system(increment_counter_system)
+                .register_system(&system_stage::Update, increment_counter_system);
+        }
+    }
+
+    #[test]
+    fn with_plugin_registers_resources_and_systems() {
+        let mut engine = Engine::builder()
+            .with_plugin(&CounterPlugin { initial_value: 5 })
+            .build(MockFS);
+
+        assert_eq!(engine.ecs.resource::<Counter>().unwrap().0, 5);
+        assert_eq!(engine.init_systems.len(), 1);
+        for init_system in &mut engine.init_systems {
+            init_system.run(&mut engine.ecs);
+        }
+        assert_eq!(engine.ecs.resource::<Counter>().unwrap().0, 6);
+    }
+
+    fn reset_counter_system(mut counter: ResMut<Counter>) {
+        counter.0 = 10;
+    }
+
+    #[test]
+    fn with_init_system_keeps_plugin_init_systems() {
+        let mut engine = Engine::builder()
+            .with_plugin(&CounterPlugin { initial_value: 5 })
+            .with_init_system(reset_counter_system)
+            .build(MockFS);
+
+        assert_eq!(engine.init_systems.len(), 2);
+        for init_system in &mut engine.init_systems {
+            init_system.run(&mut engine.ecs);
+        }
+        assert_eq!(engine.ecs.resource::<Counter>().unwrap().0, 11);
+    }
+
+    #[test]
+    fn shutdown_runs_shutdown_systems_once() {
+        let mut engine = Engine::builder()
+            .with_plugin(&CounterPlugin { initial_value: 0 })
+            .build(MockFS);
+
+        engine.shutdown();
+        engine.shutdown();
+        assert_eq!(engine.ecs.resource::<Counter>().unwrap().0, 1);
+    }
+
+    #[test]
+    fn frame_control_skips_disabled_stages() {
+        let mut engine = Engine::builder()
+            .with_plugin(&CounterPlugin { initial_value: 0 })
+            .build(MockFS);
+        engine.set_rendering_enabled(false);
+
+        engine.update(0.0);
+        assert_eq!(engine.ecs.resource::<Counter>().unwrap().0, 2);
+
+        engine.set_simulation_enabled(false);
+        engine.update(0.0);
+        assert_eq!(engine.ecs.resource::<Counter>().unwrap().0, 2);
+    }
+}
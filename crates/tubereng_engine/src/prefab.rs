@@ -0,0 +1,269 @@
+//! Prefabs are trees of entities described in RON files that can be
+//! instantiated in a single call.
+//!
+//! Components are referenced by name in the prefab files, so every component
+//! type used in a prefab must be registered at startup, either with
+//! [`EngineBuilder::with_prefab_component`](crate::EngineBuilder::with_prefab_component)
+//! or through the [`ComponentRegistry`] resource:
+//!
+//! ```ignore
+//! Engine::builder()
+//!     .with_prefab_component::<Enemy>("Enemy")
+//!     .with_prefab_component::<Collider>("Collider")
+//! ```
+//!
+//! A prefab file looks like this:
+//!
+//! ```ron
+//! (
+//!     components: {
+//!         "Enemy": (),
+//!         "Health": (10),
+//!     },
+//!     children: [
+//!         (components: { "Collider": (width: 16.0, height: 16.0) }),
+//!     ],
+//! )
+//! ```
+//!
+//! Children are attached to their parent with the
+//! [`ChildOf`](tubereng_ecs::relationship::ChildOf) relationship.
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{de::DeserializeOwned, Deserialize};
+use tubereng_asset::{Asset, AssetError, AssetHandle, AssetLoader, AssetStore};
+use tubereng_ecs::{
+    bundle::{BundleError, EntityBundle, LocalId},
+    commands::CommandQueue,
+    EntityDefinition, EntityId,
+};
+
+#[derive(Debug)]
+pub enum PrefabError {
+    PrefabNotFound,
+    UnregisteredComponent(String),
+    ComponentDeserializationFailed(String, ron::Error),
+    BundleSpawningFailed(BundleError),
+}
+
+type DeserializeFn = fn(ron::Value) -> Result<Box<dyn EntityDefinition>, ron::Error>;
+
+/// Maps the component names used in prefab files to their deserializer
+#[derive(Default)]
+pub struct ComponentRegistry {
+    deserializers: HashMap<String, DeserializeFn>,
+}
+
+impl ComponentRegistry {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a component type under the given name
+    pub fn register<C>(&mut self, name: &str)
+    where
+        C: 'static + DeserializeOwned + std::fmt::Debug + Send + Sync,
+    {
+        self.deserializers
+            .insert(name.to_string(), deserialize_fn_of::<C>);
+    }
+
+    #[must_use]
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.deserializers.contains_key(name)
+    }
+
+    fn deserialize(
+        &self,
+        name: &str,
+        value: ron::Value,
+    ) -> Result<Box<dyn EntityDefinition>, PrefabError> {
+        let deserialize_fn = self
+            .deserializers
+            .get(name)
+            .ok_or_else(|| PrefabError::UnregisteredComponent(name.to_string()))?;
+        deserialize_fn(value)
+            .map_err(|e| PrefabError::ComponentDeserializationFailed(name.to_string(), e))
+    }
+}
+
+fn deserialize_fn_of<C>(value: ron::Value) -> Result<Box<dyn EntityDefinition>, ron::Error>
+where
+    C: 'static + DeserializeOwned + std::fmt::Debug + Send + Sync,
+{
+    Ok(Box::new((value.into_rust::<C>()?,)))
+}
+
+#[derive(Debug, Deserialize)]
+struct PrefabEntity {
+    #[serde(default)]
+    components: BTreeMap<String, ron::Value>,
+    #[serde(default)]
+    children: Vec<PrefabEntity>,
+}
+
+#[derive(Debug)]
+pub struct Prefab {
+    root: PrefabEntity,
+}
+
+impl Prefab {
+    /// Builds a bundle containing the entities of the prefab
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if a component of the prefab is not registered or
+    /// cannot be deserialized
+    pub fn to_bundle(&self, registry: &ComponentRegistry) -> Result<EntityBundle, PrefabError> {
+        let mut bundle = EntityBundle::new();
+        let root = Self::add_entity_to_bundle(&self.root, &mut bundle, registry)?;
+        bundle.set_root(root);
+        Ok(bundle)
+    }
+
+    fn add_entity_to_bundle(
+        entity: &PrefabEntity,
+        bundle: &mut EntityBundle,
+        registry: &ComponentRegistry,
+    ) -> Result<LocalId, PrefabError> {
+        let local_id = bundle.add_entity(());
+        for (name, value) in &entity.components {
+            bundle.add_definition(local_id, registry.deserialize(name, value.clone())?);
+        }
+
+        for child in &entity.children {
+            let child_id = Self::add_entity_to_bundle(child, bundle, registry)?;
+            bundle.add_child(child_id, local_id);
+        }
+
+        Ok(local_id)
+    }
+}
+
+impl Asset for Prefab {
+    type Loader = PrefabLoader;
+}
+
+pub struct PrefabLoader;
+impl AssetLoader<Prefab> for PrefabLoader {
+    fn load(file_content: &[u8]) -> tubereng_asset::Result<Prefab> {
+        let root = ron::de::from_bytes(file_content)
+            .map_err(|e| AssetError::PrefabParsingFailed(e.to_string()))?;
+        Ok(Prefab { root })
+    }
+}
+
+/// Queues the instantiation of a prefab and returns the id its root entity
+/// will have
+///
+/// # Errors
+///
+/// Will return [`Err`] if the prefab is not in the asset store, or if one of
+/// its components is not registered or cannot be deserialized
+pub fn spawn_prefab(
+    command_queue: &CommandQueue,
+    asset_store: &AssetStore,
+    registry: &ComponentRegistry,
+    handle: AssetHandle<Prefab>,
+) -> Result<EntityId, PrefabError> {
+    let prefab = asset_store.get(handle).ok_or(PrefabError::PrefabNotFound)?;
+    command_queue
+        .spawn_bundle(prefab.to_bundle(registry)?)
+        .map_err(PrefabError::BundleSpawningFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_asset::vfs::VirtualFileSystem;
+    use tubereng_ecs::{
+        relationship::{ChildOf, RelationshipKind},
+        system::Into,
+        Ecs,
+    };
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Enemy;
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Health(i32);
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Collider {
+        width: f32,
+        height: f32,
+    }
+
+    const ENEMY_PREFAB: &str = r#"(
+        components: {
+            "Enemy": (),
+            "Health": (10),
+        },
+        children: [
+            (components: { "Health": (2) }),
+            (components: { "Collider": (width: 16.0, height: 8.0) }),
+        ],
+    )"#;
+
+    struct MockFS;
+    impl VirtualFileSystem for MockFS {
+        fn read_bytes(&self, _path: &str) -> tubereng_asset::Result<Vec<u8>> {
+            Ok(ENEMY_PREFAB.as_bytes().to_vec())
+        }
+
+        fn is_embedded(&self) -> bool {
+            true
+        }
+    }
+
+    fn registry() -> ComponentRegistry {
+        let mut registry = ComponentRegistry::new();
+        registry.register::<Enemy>("Enemy");
+        registry.register::<Health>("Health");
+        registry.register::<Collider>("Collider");
+        registry
+    }
+
+    #[test]
+    fn spawn_prefab_spawns_entity_tree() {
+        let mut ecs = Ecs::new();
+        ecs.define_relationship::<ChildOf>(RelationshipKind::OneToMany);
+        let mut asset_store = AssetStore::new(MockFS);
+        let handle = asset_store.load::<Prefab>("enemy.ron").unwrap();
+        ecs.insert_resource(asset_store);
+        ecs.insert_resource(registry());
+
+        ecs.run_single_run_system(
+            &(move |queue: &CommandQueue,
+                    asset_store: tubereng_ecs::system::Res<AssetStore>,
+                    registry: tubereng_ecs::system::Res<ComponentRegistry>| {
+                let root = spawn_prefab(queue, &asset_store, &registry, handle).unwrap();
+                assert_eq!(root, 0);
+            })
+            .into_system(),
+        );
+
+        assert_eq!(ecs.entity_count(), 3);
+        assert_eq!(ecs.component::<Enemy>(0).as_deref(), Some(&Enemy));
+        assert_eq!(ecs.component::<Health>(0).as_deref(), Some(&Health(10)));
+        let children = ecs.relationship::<ChildOf>().unwrap().sources(0).unwrap();
+        assert_eq!(children.len(), 2);
+        assert_eq!(ecs.component::<Health>(1).as_deref(), Some(&Health(2)));
+        assert_eq!(
+            ecs.component::<Collider>(2).as_deref(),
+            Some(&Collider {
+                width: 16.0,
+                height: 8.0
+            })
+        );
+    }
+
+    #[test]
+    fn prefab_with_unregistered_component() {
+        let prefab = PrefabLoader::load(br#"(components: { "Unknown": () })"#).unwrap();
+        assert!(matches!(
+            prefab.to_bundle(&registry()),
+            Err(PrefabError::UnregisteredComponent(name)) if name == "Unknown"
+        ));
+    }
+}
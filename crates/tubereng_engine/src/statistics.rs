@@ -0,0 +1,132 @@
+//! Frame timing statistics, updated by the engine every frame.
+//!
+//! ```ignore
+//! fn display_fps_system(statistics: Res<EngineStatistics>, mut gfx: ResMut<GraphicsState>) {
+//!     let text = format!("{:.0} FPS", statistics.average_fps());
+//!     gfx.draw_ui_text(0.0, 0.0, &text, Color::WHITE);
+//! }
+//! ```
+
+/// The number of frames the average FPS is computed over
+pub const FRAME_WINDOW_SIZE: usize = 60;
+
+/// Resource holding statistics about the last frames run by the engine
+#[derive(Debug)]
+pub struct EngineStatistics {
+    last_frame_cpu_time: f32,
+    last_frame_duration: f32,
+    target_fps: Option<u32>,
+    frame_durations: [f32; FRAME_WINDOW_SIZE],
+    next_frame_index: usize,
+    recorded_frame_count: usize,
+    entity_count: usize,
+}
+
+impl EngineStatistics {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            last_frame_cpu_time: 0.0,
+            last_frame_duration: 0.0,
+            target_fps: None,
+            frame_durations: [0.0; FRAME_WINDOW_SIZE],
+            next_frame_index: 0,
+            recorded_frame_count: 0,
+            entity_count: 0,
+        }
+    }
+
+    /// Records a frame given the time elapsed since the previous frame and
+    /// the time spent by the engine updating it, in seconds
+    pub fn record_frame(&mut self, frame_duration: f32, cpu_time: f32, entity_count: usize) {
+        self.last_frame_cpu_time = cpu_time;
+        self.last_frame_duration = frame_duration;
+        self.entity_count = entity_count;
+        self.frame_durations[self.next_frame_index] = frame_duration;
+        self.next_frame_index = (self.next_frame_index + 1) % FRAME_WINDOW_SIZE;
+        self.recorded_frame_count = (self.recorded_frame_count + 1).min(FRAME_WINDOW_SIZE);
+    }
+
+    /// The time spent by the engine updating the last frame, in seconds
+    #[must_use]
+    pub fn last_frame_cpu_time(&self) -> f32 {
+        self.last_frame_cpu_time
+    }
+
+    /// The time elapsed between the start of the last frame and the start of
+    /// the previous one, in seconds. With a frame rate cap, a frame duration
+    /// above the target while the CPU time stays below it means the
+    /// application is GPU-bound.
+    #[must_use]
+    pub fn last_frame_duration(&self) -> f32 {
+        self.last_frame_duration
+    }
+
+    /// The frame rate cap of the engine, if any
+    #[must_use]
+    pub fn target_fps(&self) -> Option<u32> {
+        self.target_fps
+    }
+
+    pub(crate) fn set_target_fps(&mut self, target_fps: Option<u32>) {
+        self.target_fps = target_fps;
+    }
+
+    /// The average number of frames per second over the last
+    /// [`FRAME_WINDOW_SIZE`] frames
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn average_fps(&self) -> f32 {
+        let total_duration: f32 = self.frame_durations[..self.recorded_frame_count]
+            .iter()
+            .sum();
+        if total_duration <= 0.0 {
+            return 0.0;
+        }
+
+        self.recorded_frame_count as f32 / total_duration
+    }
+
+    #[must_use]
+    pub fn entity_count(&self) -> usize {
+        self.entity_count
+    }
+}
+
+impl Default for EngineStatistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn average_fps_without_frames() {
+        let statistics = EngineStatistics::new();
+        assert!(statistics.average_fps().abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn average_fps_over_recorded_frames() {
+        let mut statistics = EngineStatistics::new();
+        statistics.record_frame(0.5, 0.1, 3);
+        statistics.record_frame(0.25, 0.2, 4);
+        assert!((statistics.average_fps() - 2.0 / 0.75).abs() < 1e-5);
+        assert_eq!(statistics.entity_count(), 4);
+        assert!((statistics.last_frame_duration() - 0.25).abs() < f32::EPSILON);
+        assert!((statistics.last_frame_cpu_time() - 0.2).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn average_fps_only_keeps_the_last_frames() {
+        let mut statistics = EngineStatistics::new();
+        statistics.record_frame(1.0, 0.0, 0);
+        for _ in 0..FRAME_WINDOW_SIZE {
+            statistics.record_frame(0.01, 0.0, 0);
+        }
+        assert!((statistics.average_fps() - 100.0).abs() < 1e-2);
+    }
+}
@@ -0,0 +1,64 @@
+//! Secondary windows, opened next to the main window of the application.
+//!
+//! Systems request windows through the [`WindowRequests`] resource, the
+//! runner creates them at the end of the frame:
+//!
+//! ```ignore
+//! fn open_tools_window_system(mut window_requests: ResMut<WindowRequests>) {
+//!     window_requests.request(WindowDescriptor::new("Tools", 320, 480));
+//! }
+//! ```
+//!
+//! The render graph draws to the main window, secondary windows are cleared
+//! to their clear color. The input events of secondary windows are ignored.
+
+use tubereng_renderer::Color;
+
+/// The properties of a secondary window
+#[derive(Debug, Clone)]
+pub struct WindowDescriptor {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub clear_color: Color,
+}
+
+impl WindowDescriptor {
+    /// Creates the descriptor of a window cleared to black
+    #[must_use]
+    pub fn new(title: &str, width: u32, height: u32) -> Self {
+        Self {
+            title: title.to_string(),
+            width,
+            height,
+            clear_color: Color::BLACK,
+        }
+    }
+
+    #[must_use]
+    pub fn with_clear_color(mut self, clear_color: Color) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+}
+
+/// Resource holding the secondary windows to open at the end of the frame
+#[derive(Debug, Default)]
+pub struct WindowRequests {
+    requests: Vec<WindowDescriptor>,
+}
+
+impl WindowRequests {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&mut self, window_descriptor: WindowDescriptor) {
+        self.requests.push(window_descriptor);
+    }
+
+    pub(crate) fn take(&mut self) -> Vec<WindowDescriptor> {
+        std::mem::take(&mut self.requests)
+    }
+}
@@ -0,0 +1,90 @@
+use tubereng_renderer::{Color, GraphicsState};
+
+use crate::{
+    text_height, text_width, Component, ComponentId, Context, Rect, Renderable, Response, PADDING,
+};
+
+/// A button displaying a label
+///
+/// ```ignore
+/// if Button::new("Play").show(ctx).clicked {
+///     start_game();
+/// }
+/// ```
+pub struct Button {
+    label: String,
+    position: Option<(f32, f32)>,
+    id: ComponentId,
+    bounds: Rect,
+}
+
+impl Button {
+    #[must_use]
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            position: None,
+            id: ComponentId::new(label),
+            bounds: Rect::new(
+                0.0,
+                0.0,
+                text_width(label) + 2.0 * PADDING,
+                text_height(label) + 2.0 * PADDING,
+            ),
+        }
+    }
+
+    /// Sets the position of the button instead of laying it out in its
+    /// parent
+    #[must_use]
+    pub fn with_position(mut self, x: f32, y: f32) -> Self {
+        self.position = Some((x, y));
+        self
+    }
+
+    #[must_use]
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Adds the button to the context and returns the interactions that
+    /// happened with it
+    pub fn show(mut self, ctx: &mut Context) -> Response {
+        self.id = ctx.component_id(&self.label);
+        let (x, y) = self
+            .position
+            .or_else(|| ctx.allocate(self.bounds.height))
+            .unwrap_or_default();
+        self.bounds.x = x;
+        self.bounds.y = y;
+        ctx.add_component(self)
+    }
+}
+
+impl Component for Button {
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn bounds(&self) -> Rect {
+        self.bounds
+    }
+}
+
+impl Renderable for Button {
+    fn render(&self, gfx: &mut GraphicsState) {
+        gfx.draw_ui_quad(
+            self.bounds.x,
+            self.bounds.y,
+            self.bounds.width,
+            self.bounds.height,
+            Color::new(0.3, 0.3, 0.3),
+        );
+        gfx.draw_ui_text(
+            self.bounds.x + PADDING,
+            self.bounds.y + PADDING,
+            &self.label,
+            Color::WHITE,
+        );
+    }
+}
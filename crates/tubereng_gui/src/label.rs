@@ -0,0 +1,71 @@
+use tubereng_renderer::{Color, GraphicsState};
+
+use crate::{text_height, text_width, Component, ComponentId, Context, Rect, Renderable, Response};
+
+/// A non-interactive text
+pub struct Label {
+    text: String,
+    color: Color,
+    position: Option<(f32, f32)>,
+    id: ComponentId,
+    bounds: Rect,
+}
+
+impl Label {
+    #[must_use]
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            color: Color::WHITE,
+            position: None,
+            id: ComponentId::new(text),
+            bounds: Rect::new(0.0, 0.0, text_width(text), text_height(text)),
+        }
+    }
+
+    #[must_use]
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the position of the label instead of laying it out in its parent
+    #[must_use]
+    pub fn with_position(mut self, x: f32, y: f32) -> Self {
+        self.position = Some((x, y));
+        self
+    }
+
+    #[must_use]
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Adds the label to the context
+    pub fn show(mut self, ctx: &mut Context) -> Response {
+        self.id = ctx.component_id(&self.text);
+        let (x, y) = self
+            .position
+            .or_else(|| ctx.allocate(self.bounds.height))
+            .unwrap_or_default();
+        self.bounds.x = x;
+        self.bounds.y = y;
+        ctx.add_component(self)
+    }
+}
+
+impl Component for Label {
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn bounds(&self) -> Rect {
+        self.bounds
+    }
+}
+
+impl Renderable for Label {
+    fn render(&self, gfx: &mut GraphicsState) {
+        gfx.draw_ui_text(self.bounds.x, self.bounds.y, &self.text, self.color);
+    }
+}
@@ -1 +1,466 @@
+#![warn(clippy::pedantic)]
 
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use tubereng_ecs::system::{Res, ResMut};
+use tubereng_input::{mouse, Input};
+use tubereng_renderer::GraphicsState;
+
+pub mod button;
+pub mod label;
+pub mod window;
+
+pub use button::Button;
+pub use label::Label;
+pub use window::Window;
+
+pub use tubereng_renderer::ui_pass::{GLYPH_HEIGHT, GLYPH_WIDTH};
+/// Space between a component and its content in pixels
+pub const PADDING: f32 = 4.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ComponentId(u64);
+
+impl ComponentId {
+    #[must_use]
+    pub fn new<H: Hash>(source: H) -> Self {
+        let mut hasher = DefaultHasher::new();
+        source.hash(&mut hasher);
+        Self(hasher.finish())
+    }
+
+    /// Derives the id of a child component from the id of its parent
+    #[must_use]
+    pub fn with<H: Hash>(self, source: H) -> Self {
+        Self::new((self.0, source))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    #[must_use]
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[must_use]
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+pub trait Renderable {
+    fn render(&self, gfx: &mut GraphicsState);
+}
+
+pub trait Component: Renderable + Send + Sync {
+    fn id(&self) -> ComponentId;
+    fn bounds(&self) -> Rect;
+
+    /// Whether the component can be moved around by dragging it with the
+    /// mouse
+    fn is_draggable(&self) -> bool {
+        false
+    }
+}
+
+/// The interactions that happened with a component since the previous frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Response {
+    pub hovered: bool,
+    pub clicked: bool,
+}
+
+struct Layout {
+    parent: ComponentId,
+    x: f32,
+    y: f32,
+}
+
+struct ComponentEntry {
+    component: Box<dyn Component>,
+    /// The top-level draggable component this component belongs to
+    layer: Option<ComponentId>,
+}
+
+/// The state of the GUI.
+///
+/// Components are added every frame by calling their `show` method, inputs
+/// received between two frames are resolved against the components of the
+/// previous frame and reported in the [`Response`] of the components of the
+/// next frame.
+///
+/// Draggable components such as windows are stacked: only the topmost
+/// component under the cursor is hovered, and pressing a window brings it to
+/// the front and gives it the focus.
+#[derive(Default)]
+pub struct Context {
+    cursor_position: (f32, f32),
+    components: Vec<ComponentEntry>,
+    /// The layers from the back to the front
+    layers: Vec<ComponentId>,
+    hovered_component: Option<ComponentId>,
+    pressed_component: Option<ComponentId>,
+    grabbed_component: Option<ComponentId>,
+    focused_component: Option<ComponentId>,
+    pending_clicks: HashSet<ComponentId>,
+    clicks: HashSet<ComponentId>,
+    component_positions: HashMap<ComponentId, (f32, f32)>,
+    layout: Option<Layout>,
+}
+
+impl Context {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Clears the components of the previous frame and makes the clicks
+    /// received since then available to the components of this frame
+    pub fn begin_frame(&mut self) {
+        self.components.clear();
+        self.layout = None;
+        self.clicks = std::mem::take(&mut self.pending_clicks);
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn on_input(&mut self, input: &Input) {
+        match input {
+            Input::CursorMoved((x, y)) => self.on_cursor_moved(*x as f32, *y as f32),
+            Input::MouseButtonDown(mouse::Button::Left) => self.on_mouse_button_down(),
+            Input::MouseButtonUp(mouse::Button::Left) => self.on_mouse_button_up(),
+            _ => {}
+        }
+    }
+
+    pub fn on_cursor_moved(&mut self, x: f32, y: f32) {
+        let (previous_x, previous_y) = self.cursor_position;
+        self.cursor_position = (x, y);
+
+        if let Some(position) = self
+            .grabbed_component
+            .and_then(|grabbed_component| self.component_positions.get_mut(&grabbed_component))
+        {
+            position.0 += x - previous_x;
+            position.1 += y - previous_y;
+        }
+
+        self.hovered_component = self
+            .topmost_component_at(x, y)
+            .map(|entry| entry.component.id());
+    }
+
+    pub fn on_mouse_button_down(&mut self) {
+        let (x, y) = self.cursor_position;
+        let Some(entry) = self.topmost_component_at(x, y) else {
+            self.focused_component = None;
+            return;
+        };
+
+        let id = entry.component.id();
+        let layer = entry.layer;
+        let is_draggable = entry.component.is_draggable();
+        self.pressed_component = Some(id);
+        if is_draggable {
+            self.grabbed_component = Some(id);
+        }
+
+        self.focused_component = Some(layer.unwrap_or(id));
+        if let Some(layer) = layer {
+            self.layers.retain(|l| *l != layer);
+            self.layers.push(layer);
+        }
+    }
+
+    pub fn on_mouse_button_up(&mut self) {
+        if let Some(pressed_component) = self.pressed_component.take() {
+            if self.hovered_component == Some(pressed_component) {
+                self.pending_clicks.insert(pressed_component);
+            }
+        }
+        self.grabbed_component = None;
+    }
+
+    /// Adds a component for the current frame and returns the interactions
+    /// that happened with it since the previous frame
+    pub fn add_component<C>(&mut self, component: C) -> Response
+    where
+        C: 'static + Component,
+    {
+        let id = component.id();
+        let layer = match &self.layout {
+            Some(layout) => Some(layout.parent),
+            None if component.is_draggable() => Some(id),
+            None => None,
+        };
+        if let Some(layer) = layer {
+            if !self.layers.contains(&layer) {
+                self.layers.push(layer);
+            }
+        }
+
+        self.components.push(ComponentEntry {
+            component: Box::new(component),
+            layer,
+        });
+        Response {
+            hovered: self.hovered_component == Some(id),
+            clicked: self.clicks.contains(&id),
+        }
+    }
+
+    /// Returns the components of the current frame from the back to the front
+    pub fn components(&self) -> impl Iterator<Item = &dyn Component> {
+        let mut entries = self.components.iter().collect::<Vec<_>>();
+        entries.sort_by_key(|entry| self.z_index(entry.layer));
+        entries.into_iter().map(|entry| entry.component.as_ref())
+    }
+
+    /// Returns the id of the component that has the focus, this is the window
+    /// containing the component that has been pressed last
+    #[must_use]
+    pub fn focused_component(&self) -> Option<ComponentId> {
+        self.focused_component
+    }
+
+    fn z_index(&self, layer: Option<ComponentId>) -> usize {
+        layer
+            .and_then(|layer| self.layers.iter().position(|l| *l == layer))
+            .map_or(0, |index| index + 1)
+    }
+
+    fn topmost_component_at(&self, x: f32, y: f32) -> Option<&ComponentEntry> {
+        self.components
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.component.bounds().contains(x, y))
+            .max_by_key(|(index, entry)| (self.z_index(entry.layer), *index))
+            .map(|(_, entry)| entry)
+    }
+
+    #[must_use]
+    pub fn cursor_position(&self) -> (f32, f32) {
+        self.cursor_position
+    }
+
+    /// Computes the id of a component from its label, taking into account
+    /// the component it is laid out in
+    pub(crate) fn component_id(&self, label: &str) -> ComponentId {
+        match &self.layout {
+            Some(layout) => layout.parent.with(label),
+            None => ComponentId::new(label),
+        }
+    }
+
+    /// Returns the position of a component that can be moved, initializing
+    /// it with `initial_position` the first time
+    pub(crate) fn component_position(
+        &mut self,
+        id: ComponentId,
+        initial_position: (f32, f32),
+    ) -> (f32, f32) {
+        *self
+            .component_positions
+            .entry(id)
+            .or_insert(initial_position)
+    }
+
+    pub(crate) fn begin_layout(&mut self, parent: ComponentId, x: f32, y: f32) {
+        self.layout = Some(Layout { parent, x, y });
+    }
+
+    pub(crate) fn end_layout(&mut self) {
+        self.layout = None;
+    }
+
+    /// Allocates space for a component of the given height in the current
+    /// layout and returns its position
+    pub(crate) fn allocate(&mut self, height: f32) -> Option<(f32, f32)> {
+        let layout = self.layout.as_mut()?;
+        let position = (layout.x, layout.y);
+        layout.y += height + PADDING;
+        Some(position)
+    }
+}
+
+/// Returns the width in pixels of a text rendered with the GUI font
+#[must_use]
+pub fn text_width(text: &str) -> f32 {
+    #[allow(clippy::cast_precision_loss)]
+    let longest_line_length = text
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0) as f32;
+    longest_line_length * GLYPH_WIDTH
+}
+
+/// Returns the height in pixels of a text rendered with the GUI font
+#[must_use]
+pub fn text_height(text: &str) -> f32 {
+    #[allow(clippy::cast_precision_loss)]
+    let line_count = text.lines().count().max(1) as f32;
+    line_count * GLYPH_HEIGHT
+}
+
+pub fn render_system(ctx: Res<Context>, mut gfx: ResMut<GraphicsState>) {
+    for component in ctx.components() {
+        component.render(&mut gfx);
+    }
+    std::mem::drop(ctx);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_frame(ctx: &mut Context) -> (Response, Response) {
+        ctx.begin_frame();
+        let mut button_response = Response::default();
+        let window_response = Window::new("Menu")
+            .with_position(10.0, 10.0)
+            .show(ctx, |ctx| {
+                button_response = Button::new("Play").show(ctx);
+            });
+        (window_response, button_response)
+    }
+
+    #[test]
+    fn button_clicked() {
+        let mut ctx = Context::new();
+        run_frame(&mut ctx);
+        let button_bounds = ctx.components().nth(1).map(Component::bounds).unwrap();
+        ctx.on_cursor_moved(button_bounds.x + 1.0, button_bounds.y + 1.0);
+        ctx.on_mouse_button_down();
+        let (_, button_response) = run_frame(&mut ctx);
+        assert!(button_response.hovered);
+        assert!(!button_response.clicked);
+
+        ctx.on_mouse_button_up();
+        let (_, button_response) = run_frame(&mut ctx);
+        assert!(button_response.clicked);
+
+        let (_, button_response) = run_frame(&mut ctx);
+        assert!(!button_response.clicked);
+    }
+
+    #[test]
+    fn button_not_clicked_when_released_outside() {
+        let mut ctx = Context::new();
+        run_frame(&mut ctx);
+        let button_bounds = ctx.components().nth(1).map(Component::bounds).unwrap();
+        ctx.on_cursor_moved(button_bounds.x + 1.0, button_bounds.y + 1.0);
+        ctx.on_mouse_button_down();
+        ctx.on_cursor_moved(0.0, 0.0);
+        ctx.on_mouse_button_up();
+        let (_, button_response) = run_frame(&mut ctx);
+        assert!(!button_response.clicked);
+    }
+
+    #[test]
+    fn window_dragged() {
+        let mut ctx = Context::new();
+        run_frame(&mut ctx);
+        ctx.on_cursor_moved(12.0, 12.0);
+        ctx.on_mouse_button_down();
+        ctx.on_cursor_moved(32.0, 42.0);
+        ctx.on_mouse_button_up();
+        run_frame(&mut ctx);
+
+        let window_bounds = ctx.components().next().map(Component::bounds).unwrap();
+        assert_eq!((window_bounds.x, window_bounds.y), (30.0, 40.0));
+    }
+
+    fn run_overlapping_windows_frame(ctx: &mut Context) -> (Rect, Rect) {
+        ctx.begin_frame();
+        Window::new("Back")
+            .with_position(10.0, 10.0)
+            .show(ctx, |_| {});
+        Window::new("Front")
+            .with_position(50.0, 50.0)
+            .show(ctx, |_| {});
+        let bounds_of = |title: &str| {
+            ctx.components()
+                .find(|component| component.id() == ComponentId::new(title))
+                .map(Component::bounds)
+                .unwrap()
+        };
+        (bounds_of("Back"), bounds_of("Front"))
+    }
+
+    #[test]
+    fn only_topmost_overlapping_window_dragged() {
+        let mut ctx = Context::new();
+        run_overlapping_windows_frame(&mut ctx);
+        ctx.on_cursor_moved(100.0, 100.0);
+        ctx.on_mouse_button_down();
+        ctx.on_cursor_moved(110.0, 120.0);
+        ctx.on_mouse_button_up();
+
+        let (back_bounds, front_bounds) = run_overlapping_windows_frame(&mut ctx);
+        assert_eq!((back_bounds.x, back_bounds.y), (10.0, 10.0));
+        assert_eq!((front_bounds.x, front_bounds.y), (60.0, 70.0));
+        assert_eq!(ctx.focused_component(), Some(ComponentId::new("Front")));
+    }
+
+    #[test]
+    fn pressed_window_brought_to_front() {
+        let mut ctx = Context::new();
+        run_overlapping_windows_frame(&mut ctx);
+        ctx.on_cursor_moved(15.0, 15.0);
+        ctx.on_mouse_button_down();
+        ctx.on_mouse_button_up();
+        assert_eq!(ctx.focused_component(), Some(ComponentId::new("Back")));
+
+        run_overlapping_windows_frame(&mut ctx);
+        let topmost = ctx.components().last().map(Component::id);
+        assert_eq!(topmost, Some(ComponentId::new("Back")));
+
+        ctx.on_cursor_moved(100.0, 100.0);
+        ctx.on_mouse_button_down();
+        ctx.on_cursor_moved(90.0, 90.0);
+        ctx.on_mouse_button_up();
+        let (back_bounds, front_bounds) = run_overlapping_windows_frame(&mut ctx);
+        assert_eq!((back_bounds.x, back_bounds.y), (0.0, 0.0));
+        assert_eq!((front_bounds.x, front_bounds.y), (50.0, 50.0));
+    }
+
+    #[test]
+    fn label_laid_out_below_button() {
+        let mut ctx = Context::new();
+        ctx.begin_frame();
+        Window::new("Menu")
+            .with_position(10.0, 10.0)
+            .show(&mut ctx, |ctx| {
+                Button::new("Play").show(ctx);
+                Label::new("Press play\nto start").show(ctx);
+            });
+
+        let button_bounds = ctx.components().nth(1).map(Component::bounds).unwrap();
+        let label_bounds = ctx.components().nth(2).map(Component::bounds).unwrap();
+        assert_eq!(
+            label_bounds,
+            Rect::new(
+                button_bounds.x,
+                button_bounds.y + button_bounds.height + PADDING,
+                10.0 * GLYPH_WIDTH,
+                2.0 * GLYPH_HEIGHT
+            )
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use tubereng_renderer::{Color, GraphicsState};
+
+use crate::{Component, ComponentId, Context, Rect, Renderable, Response, GLYPH_HEIGHT, PADDING};
+
+/// Height of the title bar of a window in pixels
+pub const TITLE_BAR_HEIGHT: f32 = GLYPH_HEIGHT + 2.0 * PADDING;
+
+/// A window that can be dragged around and contains other components
+pub struct Window {
+    id: ComponentId,
+    title: String,
+    bounds: Rect,
+}
+
+impl Window {
+    #[must_use]
+    pub fn new(title: &str) -> Self {
+        Self {
+            id: ComponentId::new(title),
+            title: title.to_string(),
+            bounds: Rect::new(0.0, 0.0, 200.0, 150.0),
+        }
+    }
+
+    /// Sets the initial position of the window, the window keeps its current
+    /// position once it has been shown
+    #[must_use]
+    pub fn with_position(mut self, x: f32, y: f32) -> Self {
+        self.bounds.x = x;
+        self.bounds.y = y;
+        self
+    }
+
+    #[must_use]
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.bounds.width = width;
+        self.bounds.height = height;
+        self
+    }
+
+    #[must_use]
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    /// Adds the window to the context, `add_contents` adds the components
+    /// laid out inside the window
+    pub fn show<F>(mut self, ctx: &mut Context, add_contents: F) -> Response
+    where
+        F: FnOnce(&mut Context),
+    {
+        let id = self.id;
+        let (x, y) = ctx.component_position(id, (self.bounds.x, self.bounds.y));
+        self.bounds.x = x;
+        self.bounds.y = y;
+
+        let response = ctx.add_component(self);
+        ctx.begin_layout(id, x + PADDING, y + TITLE_BAR_HEIGHT + PADDING);
+        add_contents(ctx);
+        ctx.end_layout();
+        response
+    }
+}
+
+impl Component for Window {
+    fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    fn bounds(&self) -> Rect {
+        self.bounds
+    }
+
+    fn is_draggable(&self) -> bool {
+        true
+    }
+}
+
+impl Renderable for Window {
+    fn render(&self, gfx: &mut GraphicsState) {
+        gfx.draw_ui_quad(
+            self.bounds.x,
+            self.bounds.y,
+            self.bounds.width,
+            self.bounds.height,
+            Color::new(0.1, 0.1, 0.1),
+        );
+        gfx.draw_ui_quad(
+            self.bounds.x,
+            self.bounds.y,
+            self.bounds.width,
+            TITLE_BAR_HEIGHT,
+            Color::new(0.2, 0.2, 0.4),
+        );
+        gfx.draw_ui_text(
+            self.bounds.x + PADDING,
+            self.bounds.y + PADDING,
+            &self.title,
+            Color::WHITE,
+        );
+    }
+}
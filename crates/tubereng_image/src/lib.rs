@@ -53,12 +53,12 @@ pub struct ImageLoader;
 impl AssetLoader<Image> for ImageLoader {
     fn load(file_content: &[u8]) -> tubereng_asset::Result<Image> {
         let cursor = Cursor::new(file_content);
-        let image_reader = image::io::Reader::new(cursor);
+        let image_reader = image::ImageReader::new(cursor);
         let image = image_reader
             .with_guessed_format()
-            .map_err(|_| AssetError::ImageDecodingFailed)?
+            .map_err(|e| AssetError::ImageDecodingFailed(e.to_string()))?
             .decode()
-            .map_err(|_| AssetError::ImageDecodingFailed)?;
+            .map_err(|e| AssetError::ImageDecodingFailed(e.to_string()))?;
 
         let width = image.width();
         let height = image.height();
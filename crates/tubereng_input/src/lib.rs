@@ -6,13 +6,46 @@ pub enum Input {
     MouseButtonUp(mouse::Button),
     KeyDown(keyboard::Key),
     KeyUp(keyboard::Key),
+    /// A raw motion of the mouse device, independent of the cursor position
+    /// so it keeps being reported when the cursor is clamped at the edges of
+    /// the window
     MouseMotion((f64, f64)),
     CursorMoved((f64, f64)),
+    /// A scroll of the mouse wheel, in lines
+    MouseWheel((f64, f64)),
+    /// A character typed by the user, control characters such as backspace
+    /// or enter are only reported as key events
+    Text(char),
+}
+
+/// Resource configuring how raw inputs are interpreted
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputConfig {
+    /// The maximum time between two presses of a mouse button for them to
+    /// form a double-click, in seconds
+    pub double_click_interval: f64,
+    /// The maximum distance between the cursor positions of two presses of a
+    /// mouse button for them to form a double-click, in pixels
+    pub double_click_distance: f64,
+    /// The factor applied to the mouse motion by [`InputState::look_delta`]
+    pub mouse_sensitivity: f64,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            double_click_interval: 0.4,
+            double_click_distance: 4.0,
+            mouse_sensitivity: 1.0,
+        }
+    }
 }
 
 pub struct InputState {
     pub keyboard: keyboard::State,
     pub mouse: mouse::State,
+    text_input: String,
+    config: InputConfig,
 }
 
 impl InputState {
@@ -21,22 +54,58 @@ impl InputState {
         Self {
             keyboard: keyboard::State::new(),
             mouse: mouse::State::new(),
+            text_input: String::new(),
+            config: InputConfig::default(),
         }
     }
 
+    pub fn set_config(&mut self, config: InputConfig) {
+        self.config = config;
+    }
+
+    /// Advances the clock used to time inputs, in seconds
+    pub fn advance_time(&mut self, delta_time: f32) {
+        self.mouse.advance_time(f64::from(delta_time));
+    }
+
+    /// Returns the mouse motion of the current frame scaled by the
+    /// configured mouse sensitivity, to rotate a camera for instance. It is
+    /// `(0, 0)` if the mouse didn't move during the frame.
+    #[must_use]
+    pub fn look_delta(&self) -> (f64, f64) {
+        let (dx, dy) = *self.mouse.motion();
+        (
+            dx * self.config.mouse_sensitivity,
+            dy * self.config.mouse_sensitivity,
+        )
+    }
+
+    /// Returns the text typed by the user during the current frame
+    #[must_use]
+    pub fn text_input(&self) -> &str {
+        &self.text_input
+    }
+
     pub fn clear_last_frame_inputs(&mut self) {
         self.mouse.clear_last_frame_inputs();
         self.keyboard.clear_last_frame_inputs();
+        self.text_input.clear();
     }
 
     pub fn on_input(&mut self, input: &Input) {
         match input {
-            Input::MouseButtonDown(button) => self.mouse.on_button_down(*button),
+            Input::MouseButtonDown(button) => self.mouse.on_button_down(*button, &self.config),
             Input::MouseButtonUp(button) => self.mouse.on_button_up(*button),
             Input::KeyDown(key) => self.keyboard.on_key_down(*key),
             Input::KeyUp(key) => self.keyboard.on_key_up(*key),
             Input::MouseMotion(motion) => self.mouse.on_motion(*motion),
             Input::CursorMoved(position) => self.mouse.on_move(*position),
+            Input::MouseWheel(delta) => self.mouse.on_wheel(*delta),
+            Input::Text(character) => {
+                if !character.is_control() {
+                    self.text_input.push(*character);
+                }
+            }
         }
     }
 }
@@ -50,16 +119,29 @@ impl Default for InputState {
 pub mod mouse {
     use log::trace;
 
+    use crate::InputConfig;
+
     #[derive(Default, Debug, Clone, Copy)]
     pub(crate) struct ButtonState {
         pub current: bool,
         pub previous: bool,
     }
 
+    #[derive(Debug, Clone, Copy)]
+    struct Press {
+        time: f64,
+        position: (f64, f64),
+    }
+
     pub struct State {
+        #[allow(clippy::struct_field_names)]
         pub(super) button_state: [ButtonState; BUTTON_COUNT],
         last_motion: (f64, f64),
         position: (f64, f64),
+        wheel_delta: (f64, f64),
+        time: f64,
+        last_presses: [Option<Press>; BUTTON_COUNT],
+        double_clicks: [bool; BUTTON_COUNT],
     }
 
     impl State {
@@ -69,9 +151,15 @@ pub mod mouse {
                 button_state: [ButtonState::default(); BUTTON_COUNT],
                 last_motion: (0.0, 0.0),
                 position: (0.0, 0.0),
+                wheel_delta: (0.0, 0.0),
+                time: 0.0,
+                last_presses: [None; BUTTON_COUNT],
+                double_clicks: [false; BUTTON_COUNT],
             }
         }
 
+        /// Returns the raw motion of the mouse during the current frame, see
+        /// [`Input::MouseMotion`](crate::Input::MouseMotion)
         #[must_use]
         pub fn motion(&self) -> &(f64, f64) {
             &self.last_motion
@@ -83,13 +171,26 @@ pub mod mouse {
         }
 
         pub(crate) fn on_motion(&mut self, motion: (f64, f64)) {
-            self.last_motion = motion;
+            self.last_motion.0 += motion.0;
+            self.last_motion.1 += motion.1;
         }
 
         pub(crate) fn on_move(&mut self, position: (f64, f64)) {
             self.position = position;
         }
 
+        /// Returns the horizontal and vertical scrolling of the mouse wheel
+        /// during the current frame, in lines
+        #[must_use]
+        pub fn wheel_delta(&self) -> &(f64, f64) {
+            &self.wheel_delta
+        }
+
+        pub(crate) fn on_wheel(&mut self, delta: (f64, f64)) {
+            self.wheel_delta.0 += delta.0;
+            self.wheel_delta.1 += delta.1;
+        }
+
         #[must_use]
         pub fn is_button_down(&self, button: Button) -> bool {
             self.button_state[button as usize].current
@@ -105,18 +206,52 @@ pub mod mouse {
             !self.button_state[button as usize].current
         }
 
+        /// Returns true if the button has been pressed twice in a short
+        /// interval at nearby positions during the current frame, as
+        /// configured by the [`InputConfig`]
+        #[must_use]
+        pub fn is_double_click(&self, button: Button) -> bool {
+            self.double_clicks[button as usize]
+        }
+
+        pub(crate) fn advance_time(&mut self, delta_time: f64) {
+            self.time += delta_time;
+        }
+
         pub(crate) fn on_button_up(&mut self, button: Button) {
             trace!("Button up: {button:?}");
             self.button_state[button as usize].current = false;
         }
 
-        pub(crate) fn on_button_down(&mut self, button: Button) {
+        pub(crate) fn on_button_down(&mut self, button: Button, config: &InputConfig) {
             trace!("Button down: {button:?}");
             self.button_state[button as usize].current = true;
+
+            let press = Press {
+                time: self.time,
+                position: self.position,
+            };
+            let is_double_click = self.last_presses[button as usize].is_some_and(|last_press| {
+                let (dx, dy) = (
+                    press.position.0 - last_press.position.0,
+                    press.position.1 - last_press.position.1,
+                );
+                press.time - last_press.time <= config.double_click_interval
+                    && dx.hypot(dy) <= config.double_click_distance
+            });
+            if is_double_click {
+                self.double_clicks[button as usize] = true;
+                // A third press starts a new double-click
+                self.last_presses[button as usize] = None;
+            } else {
+                self.last_presses[button as usize] = Some(press);
+            }
         }
 
         pub(crate) fn clear_last_frame_inputs(&mut self) {
             self.last_motion = (0.0, 0.0);
+            self.wheel_delta = (0.0, 0.0);
+            self.double_clicks = [false; BUTTON_COUNT];
             for button_state in &mut self.button_state {
                 button_state.previous = button_state.current;
             }
@@ -201,7 +336,7 @@ pub mod keyboard {
     // TODO:
     // Use https://doc.rust-lang.org/std/mem/fn.variant_count.html when it stabilizes
     // In the meantime a proc_macro could be made to generate this constant.
-    const KEY_COUNT: usize = 39;
+    const KEY_COUNT: usize = 51;
     #[derive(Debug, Copy, Clone)]
     pub enum Key {
         Escape = 0,
@@ -242,6 +377,18 @@ pub mod keyboard {
         X,
         Y,
         Z,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
         Unknown,
     }
 
@@ -258,6 +405,22 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn input_state_look_delta() {
+        let mut input = InputState::new();
+        input.set_config(InputConfig {
+            mouse_sensitivity: 0.5,
+            ..Default::default()
+        });
+        input.on_input(&Input::MouseMotion((4.0, -2.0)));
+        input.on_input(&Input::MouseMotion((2.0, 6.0)));
+        assert_eq!(input.look_delta(), (3.0, 2.0));
+
+        input.clear_last_frame_inputs();
+        assert_eq!(input.look_delta(), (0.0, 0.0));
+    }
+
     #[test]
     fn input_state_initial_key_state_is_false() {
         let input = InputState::new();
@@ -286,4 +449,66 @@ mod tests {
         input.on_input(&Input::KeyDown(Key::A));
         assert!(input.keyboard.is_key_down(Key::A));
     }
+
+    #[test]
+    fn input_state_accumulates_text_input() {
+        let mut input = InputState::new();
+        input.on_input(&Input::Text('h'));
+        input.on_input(&Input::Text('\u{8}'));
+        input.on_input(&Input::Text('é'));
+        assert_eq!(input.text_input(), "hé");
+
+        input.clear_last_frame_inputs();
+        assert_eq!(input.text_input(), "");
+    }
+
+    fn click(input: &mut InputState, position: (f64, f64)) {
+        input.on_input(&Input::CursorMoved(position));
+        input.on_input(&Input::MouseButtonDown(mouse::Button::Left));
+        input.on_input(&Input::MouseButtonUp(mouse::Button::Left));
+    }
+
+    #[test]
+    fn input_state_detects_double_clicks() {
+        let mut input = InputState::new();
+        click(&mut input, (10.0, 10.0));
+        assert!(!input.mouse.is_double_click(mouse::Button::Left));
+        input.clear_last_frame_inputs();
+        input.advance_time(0.1);
+
+        click(&mut input, (11.0, 10.0));
+        assert!(input.mouse.is_double_click(mouse::Button::Left));
+        assert!(!input.mouse.is_double_click(mouse::Button::Right));
+        input.clear_last_frame_inputs();
+        assert!(!input.mouse.is_double_click(mouse::Button::Left));
+    }
+
+    #[test]
+    fn input_state_ignores_slow_or_distant_clicks() {
+        let mut input = InputState::new();
+        input.set_config(InputConfig {
+            double_click_interval: 0.2,
+            double_click_distance: 2.0,
+            ..Default::default()
+        });
+        click(&mut input, (10.0, 10.0));
+        input.advance_time(0.3);
+        click(&mut input, (10.0, 10.0));
+        assert!(!input.mouse.is_double_click(mouse::Button::Left));
+
+        input.advance_time(0.1);
+        click(&mut input, (20.0, 10.0));
+        assert!(!input.mouse.is_double_click(mouse::Button::Left));
+    }
+
+    #[test]
+    fn input_state_accumulates_wheel_delta() {
+        let mut input = InputState::new();
+        input.on_input(&Input::MouseWheel((0.0, 1.0)));
+        input.on_input(&Input::MouseWheel((0.0, 2.0)));
+        assert!((input.mouse.wheel_delta().1 - 3.0).abs() < f64::EPSILON);
+
+        input.clear_last_frame_inputs();
+        assert!(input.mouse.wheel_delta().1.abs() < f64::EPSILON);
+    }
 }
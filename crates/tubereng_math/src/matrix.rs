@@ -6,7 +6,8 @@ use crate::vector::{Vector3, Vector4};
 
 pub type Matrix4f = Matrix4<f32>;
 
-#[derive(Clone, Copy)]
+/// A 4x4 matrix stored in row-major order, `self[row][column]` indexes it
+#[derive(Clone, Copy, PartialEq)]
 pub struct Matrix4<T = f32> {
     values: [T; 16],
 }
@@ -32,10 +33,41 @@ impl<T> Matrix4<T> {
     const COLS: usize = 4;
     const ROWS: usize = 4;
 
+    /// Creates a matrix from its values in row-major order
     pub const fn with_values(values: [T; 16]) -> Self {
         Self { values }
     }
 
+    pub fn from_rows(rows: [[T; 4]; 4]) -> Self
+    where
+        T: Copy,
+    {
+        Self {
+            values: std::array::from_fn(|index| rows[index / Self::COLS][index % Self::COLS]),
+        }
+    }
+
+    #[must_use]
+    pub fn from_columns(columns: [[T; 4]; 4]) -> Self
+    where
+        T: Copy,
+    {
+        Self::from_rows(columns).transpose()
+    }
+
+    #[must_use]
+    pub fn transpose(&self) -> Self
+    where
+        T: Copy,
+    {
+        Self {
+            values: std::array::from_fn(|index| {
+                let (row, column) = (index / Self::COLS, index % Self::COLS);
+                self.values[column * Self::COLS + row]
+            }),
+        }
+    }
+
     //noinspection RsBorrowChecker
     #[rustfmt::skip]
     pub fn new_orthographic<U>(
@@ -143,6 +175,20 @@ impl<T> Matrix4<T>
 where
     T: Copy + NumericOps + Zero + One + IsZero,
 {
+    pub fn determinant(&self) -> T {
+        let a2323 = self[2][2] * self[3][3] - self[2][3] * self[3][2];
+        let a1323 = self[2][1] * self[3][3] - self[2][3] * self[3][1];
+        let a1223 = self[2][1] * self[3][2] - self[2][2] * self[3][1];
+        let a0323 = self[2][0] * self[3][3] - self[2][3] * self[3][0];
+        let a0223 = self[2][0] * self[3][2] - self[2][2] * self[3][0];
+        let a0123 = self[2][0] * self[3][1] - self[2][1] * self[3][0];
+
+        self[0][0] * (self[1][1] * a2323 - self[1][2] * a1323 + self[1][3] * a1223)
+            - self[0][1] * (self[1][0] * a2323 - self[1][2] * a0323 + self[1][3] * a0223)
+            + self[0][2] * (self[1][0] * a1323 - self[1][1] * a0323 + self[1][3] * a0123)
+            - self[0][3] * (self[1][0] * a1223 - self[1][1] * a0223 + self[1][2] * a0123)
+    }
+
     #[rustfmt::skip]
     pub fn try_inverse(&self) -> Option<Matrix4<T>> {
         let a2323 = self[2][2] * self[3][3] - self[2][3] * self[3][2];
@@ -323,8 +369,21 @@ impl<T> Identity for Matrix4<T>
 mod tests {
     use assert_float_eq::*;
 
+    use crate::quaternion::Quaternion;
+    use crate::vector::Vector3f;
+
     use super::*;
 
+    #[rustfmt::skip]
+    fn sample_matrix() -> Matrix4<i32> {
+        Matrix4::with_values([
+            1, 2, 3, 4,
+            5, 6, 7, 8,
+            9, 39, 11, 12,
+            13, 14, 15, 16
+        ])
+    }
+
     #[test]
     fn identity() {
         let m = Matrix4::<i32>::identity();
@@ -457,4 +516,54 @@ mod tests {
         assert_float_absolute_eq!(inverse[3][2], -1.0, 0.1);
         assert_float_absolute_eq!(inverse[3][3], -0.5, 0.1);
     }
+
+    #[test]
+    fn transpose() {
+        let m = sample_matrix();
+
+        let transposed = m.transpose();
+
+        assert_eq!(transposed[0][1], 5);
+        assert_eq!(transposed[1][0], 2);
+        assert_eq!(transposed[1][2], 39);
+        assert!(transposed.transpose() == m);
+    }
+
+    #[test]
+    fn from_rows_and_columns() {
+        let rows = [
+            [1, 2, 3, 4],
+            [5, 6, 7, 8],
+            [9, 39, 11, 12],
+            [13, 14, 15, 16],
+        ];
+
+        assert!(Matrix4::from_rows(rows) == sample_matrix());
+        assert!(Matrix4::from_columns(rows) == sample_matrix().transpose());
+    }
+
+    #[test]
+    fn determinant() {
+        assert_eq!(Matrix4::<i32>::identity().determinant(), 1);
+        assert_eq!(sample_matrix().determinant(), 0);
+        assert_eq!(
+            Matrix4::<i32>::new_scale(&Vector3::new(2, 3, 4)).determinant(),
+            24
+        );
+    }
+
+    #[test]
+    fn rotation_transpose_is_inverse() {
+        let axis = Vector3f::new(1.0, 2.0, 3.0).normalized();
+        let rotation = Quaternion::from_axis_angle(&axis, 0.74).rotation_matrix();
+
+        let result = rotation * rotation.transpose();
+
+        let identity = Matrix4f::identity();
+        for i in 0..4 {
+            for j in 0..4 {
+                assert_float_absolute_eq!(result[i][j], identity[i][j], 0.0001);
+            }
+        }
+    }
 }
@@ -150,6 +150,8 @@ pub trait Float: Display + Copy + Zero + One + Two + OneQuarter + Pi + NumericOp
     fn squared(self) -> Self;
     fn sqrt(self) -> Self;
     fn to_radians(self) -> Self;
+    fn asin(self) -> Self;
+    fn atan2(self, other: Self) -> Self;
 }
 
 impl Float for f32 {
@@ -180,6 +182,14 @@ impl Float for f32 {
     fn to_radians(self) -> Self {
         self.to_radians()
     }
+
+    fn asin(self) -> Self {
+        self.asin()
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        self.atan2(other)
+    }
 }
 
 impl Float for f64 {
@@ -210,4 +220,12 @@ impl Float for f64 {
     fn to_radians(self) -> Self {
         self.to_radians()
     }
+
+    fn asin(self) -> Self {
+        self.asin()
+    }
+
+    fn atan2(self, other: Self) -> Self {
+        self.atan2(other)
+    }
 }
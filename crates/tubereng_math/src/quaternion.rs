@@ -25,6 +25,30 @@ where
         }
     }
 
+    /// Creates the quaternion of the identity rotation
+    #[must_use]
+    pub fn identity() -> Self {
+        Self::new(T::one(), Vector3::new(T::zero(), T::zero(), T::zero()))
+    }
+
+    /// Creates a quaternion from its components in the `[x, y, z, w]` order
+    /// used by glTF
+    pub fn from_array([x, y, z, w]: [T; 4]) -> Self {
+        Self::new(w, Vector3::new(x, y, z))
+    }
+
+    /// Returns the components of the quaternion in the `[x, y, z, w]` order
+    pub fn to_array(&self) -> [T; 4] {
+        [
+            self.vector_part.x,
+            self.vector_part.y,
+            self.vector_part.z,
+            self.scalar_part,
+        ]
+    }
+
+    /// Creates the rotation of `angle` radians around `axis`, which is
+    /// expected to be normalized
     pub fn from_axis_angle(axis: &Vector3<T>, angle: T) -> Self {
         let half_angle = angle.half();
         let half_angle_sin = half_angle.sin();
@@ -36,6 +60,9 @@ where
         Self::new(w, Vector3::new(x, y, z))
     }
 
+    /// Creates a rotation from Euler angles in radians, given as
+    /// `(roll, pitch, yaw)`: the rotations around the X, Y and Z axes
+    /// applied in the X, Y, Z order
     pub fn from_euler(angles: &Vector3<T>) -> Self {
         let roll = angles.x;
         let pitch = angles.y;
@@ -58,6 +85,49 @@ where
         Quaternion::new(w, Vector3::new(x, y, z))
     }
 
+    /// Returns the Euler angles of the rotation as `(roll, pitch, yaw)`, the
+    /// inverse of [`Quaternion::from_euler`].
+    ///
+    /// The pitch is in the `[-pi/2, pi/2]` range.
+    pub fn to_euler(&self) -> Vector3<T> {
+        let (w, x, y, z) = (
+            self.scalar_part,
+            self.vector_part.x,
+            self.vector_part.y,
+            self.vector_part.z,
+        );
+        let roll = (T::two() * (w * x + y * z)).atan2(T::one() - T::two() * (x * x + y * y));
+        let sin_pitch = T::two() * (w * y - z * x);
+        let sin_pitch = if sin_pitch > T::one() {
+            T::one()
+        } else if sin_pitch < -T::one() {
+            -T::one()
+        } else {
+            sin_pitch
+        };
+        let pitch = sin_pitch.asin();
+        let yaw = (T::two() * (w * z + x * y)).atan2(T::one() - T::two() * (y * y + z * z));
+
+        Vector3::new(roll, pitch, yaw)
+    }
+
+    /// Creates the rotation orienting -Z towards `forward`, with Y as close as
+    /// possible to `up`
+    #[rustfmt::skip]
+    pub fn look_rotation(forward: &Vector3<T>, up: &Vector3<T>) -> Self {
+        let z_axis = -forward.normalized();
+        let x_axis = up.cross(&z_axis).normalized();
+        let y_axis = z_axis.cross(&x_axis);
+
+        Matrix4::with_values([
+            x_axis.x, y_axis.x, z_axis.x, T::zero(),
+            x_axis.y, y_axis.y, z_axis.y, T::zero(),
+            x_axis.z, y_axis.z, z_axis.z, T::zero(),
+            T::zero(), T::zero(), T::zero(), T::one(),
+        ])
+        .into()
+    }
+
     pub fn apply_to_vector(&self, v: &Vector3<T>) -> Vector3<T> {
         let u = self.vector_part;
         let s = self.scalar_part;
@@ -318,4 +388,67 @@ mod tests {
         assert_eq!(rotated_vector.y as i32, 0);
         assert_eq!(rotated_vector.z as i32, 0);
     }
+
+    fn assert_vector_approx_eq(a: &Vector3f, b: &Vector3f) {
+        assert_float_absolute_eq!(a.x, b.x, 0.0001);
+        assert_float_absolute_eq!(a.y, b.y, 0.0001);
+        assert_float_absolute_eq!(a.z, b.z, 0.0001);
+    }
+
+    #[test]
+    fn from_array() {
+        let quaternion = Quaternion::from_array([0.1, 0.2, 0.3, 0.9]);
+
+        assert_float_absolute_eq!(quaternion.scalar_part, 0.9, 0.0001);
+        assert_float_absolute_eq!(quaternion.vector_part.x, 0.1, 0.0001);
+        for (component, expected) in quaternion.to_array().into_iter().zip([0.1, 0.2, 0.3, 0.9]) {
+            assert_float_absolute_eq!(component, expected, 0.0001);
+        }
+    }
+
+    #[test]
+    fn axis_angle_round_trip() {
+        let axis = Vector3f::new(0.0, 0.0, 1.0);
+        let quarter_turn = Quaternion::from_axis_angle(&axis, PI / 2.0);
+
+        let rotated_vector = quarter_turn.apply_to_vector(&Vector3f::new(1.0, 0.0, 0.0));
+        assert_vector_approx_eq(&rotated_vector, &Vector3f::new(0.0, 1.0, 0.0));
+
+        let back = Quaternion::from_axis_angle(&axis, -PI / 2.0) * quarter_turn;
+        assert_vector_approx_eq(
+            &back.apply_to_vector(&Vector3f::new(1.0, 0.0, 0.0)),
+            &Vector3f::new(1.0, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn euler_round_trip() {
+        let angles = Vector3f::new(0.4, 1.3, -2.0);
+
+        let round_trip = Quaternion::from_euler(&angles).to_euler();
+
+        assert_vector_approx_eq(&round_trip, &angles);
+    }
+
+    #[test]
+    fn euler_single_axis() {
+        let angles = Quaternion::from_axis_angle(&Vector3f::new(0.0, 1.0, 0.0), 0.5).to_euler();
+
+        assert_vector_approx_eq(&angles, &Vector3f::new(0.0, 0.5, 0.0));
+    }
+
+    #[test]
+    fn look_rotation() {
+        let rotation =
+            Quaternion::look_rotation(&Vector3f::new(1.0, 0.0, 0.0), &Vector3f::new(0.0, 1.0, 0.0));
+
+        assert_vector_approx_eq(
+            &rotation.apply_to_vector(&Vector3f::new(0.0, 0.0, -1.0)),
+            &Vector3f::new(1.0, 0.0, 0.0),
+        );
+        assert_vector_approx_eq(
+            &rotation.apply_to_vector(&Vector3f::new(0.0, 1.0, 0.0)),
+            &Vector3f::new(0.0, 1.0, 0.0),
+        );
+    }
 }
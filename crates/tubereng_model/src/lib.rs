@@ -0,0 +1,648 @@
+#![warn(clippy::pedantic)]
+
+use std::io::{BufReader, Cursor};
+
+use gltf::animation::util::ReadOutputs;
+use tubereng_asset::{Asset, AssetError, AssetLoader};
+
+/// The geometry of a mesh of a model, ready to be uploaded to the GPU
+#[derive(Debug, Clone)]
+pub struct MeshData {
+    positions: Vec<[f32; 3]>,
+    texture_coordinates: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+    base_color: [f32; 4],
+    smooth_shading: bool,
+}
+
+impl MeshData {
+    #[must_use]
+    pub fn positions(&self) -> &[[f32; 3]] {
+        &self.positions
+    }
+
+    /// The texture coordinates of the vertices, with the origin at the
+    /// top-left corner of the texture
+    #[must_use]
+    pub fn texture_coordinates(&self) -> &[[f32; 2]] {
+        &self.texture_coordinates
+    }
+
+    /// The normals of the vertices, empty if the file doesn't provide them
+    /// until they are computed with [`MeshData::compute_normals`]
+    #[must_use]
+    pub fn normals(&self) -> &[[f32; 3]] {
+        &self.normals
+    }
+
+    /// Whether the computed normals are averaged between the faces sharing a
+    /// vertex, see [`MeshData::compute_normals`]
+    #[must_use]
+    pub fn smooth_shading(&self) -> bool {
+        self.smooth_shading
+    }
+
+    /// Computes the normals of the vertices from the faces of the mesh. Does
+    /// nothing if the mesh already has normals, unless `force` is set.
+    ///
+    /// With [smooth shading](MeshData::smooth_shading), the normal of a vertex
+    /// is the average of the normals of the faces sharing it, weighted by
+    /// their area. Otherwise the shading is flat: the vertices are duplicated
+    /// so each face has its own, with the normal of the face.
+    pub fn compute_normals(&mut self, force: bool) {
+        if !self.normals.is_empty() && !force {
+            return;
+        }
+
+        if self.smooth_shading {
+            self.normals = compute_smooth_normals(&self.positions, &self.indices);
+        } else {
+            self.flatten();
+        }
+    }
+
+    /// Gives each face its own vertices, with the normal of the face
+    fn flatten(&mut self) {
+        let triangles = self
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]].map(|i| i as usize))
+            .filter(|triangle| triangle.iter().all(|&i| i < self.positions.len()))
+            .collect::<Vec<_>>();
+        let vertex_count = triangles.len() * 3;
+        let mut positions = Vec::with_capacity(vertex_count);
+        let mut texture_coordinates = Vec::with_capacity(vertex_count);
+        let mut normals = Vec::with_capacity(vertex_count);
+        for triangle in triangles {
+            let triangle_positions = triangle.map(|i| self.positions[i]);
+            let face_normal = normalized(face_normal(triangle_positions));
+            for vertex in triangle {
+                positions.push(self.positions[vertex]);
+                texture_coordinates.push(
+                    self.texture_coordinates
+                        .get(vertex)
+                        .copied()
+                        .unwrap_or_default(),
+                );
+                normals.push(face_normal);
+            }
+        }
+
+        self.indices = (0..u32::try_from(positions.len()).unwrap_or(u32::MAX)).collect();
+        self.positions = positions;
+        self.texture_coordinates = texture_coordinates;
+        self.normals = normals;
+    }
+
+    #[must_use]
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    /// The RGBA base color factor of the material of the mesh, white if the
+    /// mesh has no material
+    #[must_use]
+    pub fn base_color(&self) -> [f32; 4] {
+        self.base_color
+    }
+}
+
+/// How the values of an animation channel are interpolated between two
+/// keyframes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    Step,
+    /// Each keyframe has an in-tangent, a value and an out-tangent, stored in
+    /// this order in the channel values
+    CubicSpline,
+}
+
+/// The keyframe values of an animation channel
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChannelValues {
+    Translations(Vec<[f32; 3]>),
+    /// Unit quaternions given as `[x, y, z, w]`
+    Rotations(Vec<[f32; 4]>),
+    Scales(Vec<[f32; 3]>),
+}
+
+/// The keyframes animating a property of a node
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    /// The index of the animated node in the glTF file
+    pub node: usize,
+    pub interpolation: Interpolation,
+    /// The times of the keyframes, in seconds
+    pub keyframe_times: Vec<f32>,
+    pub values: ChannelValues,
+}
+
+/// A node animation of a glTF model
+#[derive(Debug, Clone, PartialEq)]
+pub struct Animation {
+    pub name: Option<String>,
+    pub channels: Vec<Channel>,
+}
+
+/// A 3d model made of one or several meshes.
+///
+/// Models can be loaded from Wavefront OBJ files and from glTF files whose
+/// buffers are embedded (binary `.glb` files or data URIs). Only the base
+/// color factor of the glTF materials is kept, node transforms are ignored.
+/// The translation, rotation and scale animations of the nodes of glTF files
+/// are loaded.
+///
+/// Normals missing from the file are not computed on load, see
+/// [`Model::compute_normals`]. Meshes without normals are shaded flat, as
+/// required by glTF, except the OBJ meshes drawn with smoothing enabled.
+/// As the OBJ parser doesn't report smoothing groups, an OBJ object is
+/// smooth unless its first face is preceded by `s off` or `s 0`, and all its
+/// faces are shaded alike.
+#[derive(Debug)]
+pub struct Model {
+    meshes: Vec<MeshData>,
+    animations: Vec<Animation>,
+}
+
+impl Model {
+    #[must_use]
+    pub fn meshes(&self) -> &[MeshData] {
+        &self.meshes
+    }
+
+    #[must_use]
+    pub fn animations(&self) -> &[Animation] {
+        &self.animations
+    }
+
+    /// Computes the normals of the meshes lacking them, or of every mesh if
+    /// `force` is set, see [`MeshData::compute_normals`]
+    pub fn compute_normals(&mut self, force: bool) {
+        for mesh in &mut self.meshes {
+            mesh.compute_normals(force);
+        }
+    }
+}
+
+impl Asset for Model {
+    type Loader = ModelLoader;
+}
+
+pub struct ModelLoader;
+impl AssetLoader<Model> for ModelLoader {
+    fn load(file_content: &[u8]) -> tubereng_asset::Result<Model> {
+        if is_gltf(file_content) {
+            load_gltf(file_content)
+        } else {
+            load_obj(file_content)
+        }
+    }
+}
+
+fn is_gltf(file_content: &[u8]) -> bool {
+    file_content.starts_with(b"glTF")
+        || file_content
+            .iter()
+            .find(|byte| !byte.is_ascii_whitespace())
+            .is_some_and(|byte| *byte == b'{')
+}
+
+fn load_obj(file_content: &[u8]) -> tubereng_asset::Result<Model> {
+    let mut reader = BufReader::new(Cursor::new(file_content));
+    let (models, _) = tobj::load_obj_buf(
+        &mut reader,
+        &tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        },
+        |_| Err(tobj::LoadError::OpenFileFailed),
+    )
+    .map_err(|e| AssetError::ModelDecodingFailed(e.to_string()))?;
+
+    let smooth_shadings = obj_smooth_shadings(file_content);
+    let meshes = models
+        .into_iter()
+        .enumerate()
+        .map(|(model_index, model)| {
+            let mesh = model.mesh;
+            let positions = mesh
+                .positions
+                .chunks_exact(3)
+                .map(|p| [p[0], p[1], p[2]])
+                .collect::<Vec<_>>();
+            // OBJ texture coordinates have their origin at the bottom-left
+            // corner of the texture
+            let texture_coordinates = if mesh.texcoords.is_empty() {
+                vec![[0.0, 0.0]; positions.len()]
+            } else {
+                mesh.texcoords
+                    .chunks_exact(2)
+                    .map(|t| [t[0], 1.0 - t[1]])
+                    .collect()
+            };
+            let normals = mesh
+                .normals
+                .chunks_exact(3)
+                .map(|n| [n[0], n[1], n[2]])
+                .collect();
+
+            MeshData {
+                positions,
+                texture_coordinates,
+                normals,
+                indices: mesh.indices,
+                base_color: [1.0; 4],
+                smooth_shading: smooth_shadings.get(model_index).copied().unwrap_or(true),
+            }
+        })
+        .collect();
+
+    Ok(Model {
+        meshes,
+        animations: vec![],
+    })
+}
+
+/// Returns whether each object of an OBJ file is smooth shaded, in the order
+/// the objects are loaded: an object is smooth unless the smoothing is off
+/// when its first face is read. Objects are split by `o` and `g` statements
+/// like the OBJ parser does.
+fn obj_smooth_shadings(file_content: &[u8]) -> Vec<bool> {
+    let mut smooth_shadings = vec![];
+    let mut smooth = true;
+    let mut object_has_faces = false;
+    for line in String::from_utf8_lossy(file_content).lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("o" | "g") => object_has_faces = false,
+            Some("s") => smooth = !matches!(words.next(), Some("off" | "0") | None),
+            Some("f") if !object_has_faces => {
+                smooth_shadings.push(smooth);
+                object_has_faces = true;
+            }
+            _ => {}
+        }
+    }
+    smooth_shadings
+}
+
+fn load_gltf(file_content: &[u8]) -> tubereng_asset::Result<Model> {
+    let (document, buffers, _) = gltf::import_slice(file_content)
+        .map_err(|e| AssetError::ModelDecodingFailed(e.to_string()))?;
+
+    let mut meshes = vec![];
+    for primitive in document.meshes().flat_map(|mesh| mesh.primitives()) {
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let Some(positions) = reader.read_positions() else {
+            continue;
+        };
+
+        let positions = positions.collect::<Vec<_>>();
+        let texture_coordinates = reader.read_tex_coords(0).map_or_else(
+            || vec![[0.0, 0.0]; positions.len()],
+            |texture_coordinates| texture_coordinates.into_f32().collect(),
+        );
+        let indices: Vec<u32> = reader.read_indices().map_or_else(
+            || (0..u32::try_from(positions.len()).unwrap_or(u32::MAX)).collect(),
+            |indices| indices.into_u32().collect(),
+        );
+        let normals = reader
+            .read_normals()
+            .map_or_else(Vec::new, Iterator::collect);
+
+        meshes.push(MeshData {
+            positions,
+            texture_coordinates,
+            normals,
+            indices,
+            base_color: primitive
+                .material()
+                .pbr_metallic_roughness()
+                .base_color_factor(),
+            // glTF requires flat normals when they are missing
+            smooth_shading: false,
+        });
+    }
+
+    let animations = document
+        .animations()
+        .map(|animation| Animation {
+            name: animation.name().map(ToString::to_string),
+            channels: animation
+                .channels()
+                .filter_map(|channel| load_gltf_channel(&channel, &buffers))
+                .collect(),
+        })
+        .collect();
+
+    Ok(Model { meshes, animations })
+}
+
+/// Computes the normal of each vertex by averaging the normals of the faces
+/// sharing it, weighted by their area. Vertices without any face get a zero
+/// normal.
+fn compute_smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![[0.0; 3]; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+        let (Some(pa), Some(pb), Some(pc)) = (positions.get(a), positions.get(b), positions.get(c))
+        else {
+            continue;
+        };
+        let face_normal = face_normal([*pa, *pb, *pc]);
+        for vertex in [a, b, c] {
+            for axis in 0..3 {
+                normals[vertex][axis] += face_normal[axis];
+            }
+        }
+    }
+
+    normals.into_iter().map(normalized).collect()
+}
+
+/// The normal of a triangle, whose length is twice the area of the triangle
+fn face_normal([a, b, c]: [[f32; 3]; 3]) -> [f32; 3] {
+    let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    [
+        ab[1] * ac[2] - ab[2] * ac[1],
+        ab[2] * ac[0] - ab[0] * ac[2],
+        ab[0] * ac[1] - ab[1] * ac[0],
+    ]
+}
+
+/// Scales a vector to a unit length, zero vectors are kept as is
+fn normalized(vector: [f32; 3]) -> [f32; 3] {
+    let length = (vector[0] * vector[0] + vector[1] * vector[1] + vector[2] * vector[2]).sqrt();
+    if length > 0.0 {
+        vector.map(|n| n / length)
+    } else {
+        vector
+    }
+}
+
+/// Loads an animation channel, `None` if it animates the morph target
+/// weights or has no keyframes
+fn load_gltf_channel(
+    channel: &gltf::animation::Channel,
+    buffers: &[gltf::buffer::Data],
+) -> Option<Channel> {
+    let reader = channel.reader(|buffer| Some(&buffers[buffer.index()]));
+    let keyframe_times = reader.read_inputs()?.collect();
+    let values = match reader.read_outputs()? {
+        ReadOutputs::Translations(translations) => {
+            ChannelValues::Translations(translations.collect())
+        }
+        ReadOutputs::Rotations(rotations) => {
+            ChannelValues::Rotations(rotations.into_f32().collect())
+        }
+        ReadOutputs::Scales(scales) => ChannelValues::Scales(scales.collect()),
+        ReadOutputs::MorphTargetWeights(_) => return None,
+    };
+    let interpolation = match channel.sampler().interpolation() {
+        gltf::animation::Interpolation::Linear => Interpolation::Linear,
+        gltf::animation::Interpolation::Step => Interpolation::Step,
+        gltf::animation::Interpolation::CubicSpline => Interpolation::CubicSpline,
+    };
+
+    Some(Channel {
+        node: channel.target().node().index(),
+        interpolation,
+        keyframe_times,
+        values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn load_obj_model() {
+        let obj = b"
+o Quad
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 1.0 1.0 0.0
+v 0.0 1.0 0.0
+vt 0.0 0.0
+vt 1.0 0.0
+vt 1.0 1.0
+vt 0.0 1.0
+f 1/1 2/2 3/3 4/4
+";
+        let model = ModelLoader::load(obj).unwrap();
+        assert_eq!(model.meshes().len(), 1);
+        let mesh = &model.meshes()[0];
+        assert_eq!(mesh.positions().len(), 4);
+        assert_eq!(mesh.texture_coordinates().len(), 4);
+        assert_eq!(mesh.indices().len(), 6);
+        assert_eq!(&mesh.texture_coordinates()[..1], &[[0.0, 1.0]]);
+        assert!(mesh.normals().is_empty());
+        assert!(mesh.smooth_shading());
+
+        let mut model = model;
+        model.compute_normals(false);
+        assert_eq!(model.meshes()[0].normals(), &[[0.0, 0.0, 1.0]; 4]);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn compute_normals_keeps_existing_normals_unless_forced() {
+        let obj = b"
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 0.0 1.0 0.0
+vn 1.0 0.0 0.0
+f 1//1 2//1 3//1
+";
+        let mut model = ModelLoader::load(obj).unwrap();
+        model.compute_normals(false);
+        assert_eq!(model.meshes()[0].normals(), &[[1.0, 0.0, 0.0]; 3]);
+
+        model.compute_normals(true);
+        assert_eq!(model.meshes()[0].normals(), &[[0.0, 0.0, 1.0]; 3]);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn compute_normals_of_flat_shaded_obj_objects() {
+        // Two faces folded along a shared edge, smoothing is off for the
+        // first object only
+        let obj = b"
+v 0.0 0.0 0.0
+v 1.0 0.0 0.0
+v 0.0 1.0 0.0
+v 0.0 0.0 -1.0
+o Flat
+s off
+f 1 2 3
+f 4 1 2
+o Smooth
+s 1
+f 1 2 3
+f 4 1 2
+";
+        let mut model = ModelLoader::load(obj).unwrap();
+        model.compute_normals(false);
+        let [flat, smooth] = model.meshes() else {
+            panic!("the model should have two meshes");
+        };
+        assert!(!flat.smooth_shading());
+        assert_eq!(flat.positions().len(), 6);
+        assert_eq!(flat.indices(), &[0, 1, 2, 3, 4, 5]);
+        assert_eq!(
+            flat.normals(),
+            &[
+                [0.0, 0.0, 1.0],
+                [0.0, 0.0, 1.0],
+                [0.0, 0.0, 1.0],
+                [0.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0]
+            ]
+        );
+
+        assert!(smooth.smooth_shading());
+        assert_eq!(smooth.positions().len(), 4);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn compute_smooth_normals_averages_shared_vertices() {
+        // Two faces folded along the edge between the first two vertices
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 0.0, -1.0],
+        ];
+        let normals = compute_smooth_normals(&positions, &[0, 1, 2, 3, 0, 1]);
+        let diagonal = 1.0 / 2.0f32.sqrt();
+        assert_eq!(normals[0], [0.0, diagonal, diagonal]);
+        assert_eq!(normals[2], [0.0, 0.0, 1.0]);
+        assert_eq!(normals[3], [0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn load_gltf_model() {
+        // A single triangle whose buffer is embedded as a data URI
+        let gltf = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{
+                "byteLength": 36,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+            }],
+            "bufferViews": [{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }],
+            "accessors": [{
+                "bufferView": 0,
+                "componentType": 5126,
+                "count": 3,
+                "type": "VEC3",
+                "min": [0.0, 0.0, 0.0],
+                "max": [1.0, 1.0, 0.0]
+            }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 } }] }]
+        }"#;
+        let model = ModelLoader::load(gltf).unwrap();
+        assert_eq!(model.meshes().len(), 1);
+        let mesh = &model.meshes()[0];
+        assert_eq!(
+            mesh.positions(),
+            &[[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        assert_eq!(mesh.indices(), &[0, 1, 2]);
+        assert_eq!(mesh.base_color(), [1.0; 4]);
+        assert!(!mesh.smooth_shading());
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn load_gltf_model_base_color() {
+        let gltf = br#"{
+            "asset": { "version": "2.0" },
+            "buffers": [{
+                "byteLength": 36,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+            }],
+            "bufferViews": [{ "buffer": 0, "byteOffset": 0, "byteLength": 36 }],
+            "accessors": [{
+                "bufferView": 0,
+                "componentType": 5126,
+                "count": 3,
+                "type": "VEC3",
+                "min": [0.0, 0.0, 0.0],
+                "max": [1.0, 1.0, 0.0]
+            }],
+            "materials": [{
+                "pbrMetallicRoughness": { "baseColorFactor": [1.0, 0.5, 0.25, 1.0] }
+            }],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }]
+        }"#;
+        let model = ModelLoader::load(gltf).unwrap();
+        assert_eq!(model.meshes()[0].base_color(), [1.0, 0.5, 0.25, 1.0]);
+    }
+
+    #[test]
+    fn load_gltf_animation() {
+        // A node rotating a quarter turn around the y axis in one second
+        let gltf = br#"{
+            "asset": { "version": "2.0" },
+            "nodes": [{ "name": "spinner" }],
+            "buffers": [{
+                "byteLength": 40,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAgD8AAAAAAAAAAAAAAAAAAIA/AAAAAPMENT8AAAAA8wQ1Pw=="
+            }],
+            "bufferViews": [
+                { "buffer": 0, "byteOffset": 0, "byteLength": 8 },
+                { "buffer": 0, "byteOffset": 8, "byteLength": 32 }
+            ],
+            "accessors": [
+                {
+                    "bufferView": 0,
+                    "componentType": 5126,
+                    "count": 2,
+                    "type": "SCALAR",
+                    "min": [0.0],
+                    "max": [1.0]
+                },
+                { "bufferView": 1, "componentType": 5126, "count": 2, "type": "VEC4" }
+            ],
+            "animations": [{
+                "name": "spin",
+                "samplers": [{ "input": 0, "output": 1, "interpolation": "LINEAR" }],
+                "channels": [{ "sampler": 0, "target": { "node": 0, "path": "rotation" } }]
+            }]
+        }"#;
+        let model = ModelLoader::load(gltf).unwrap();
+        assert!(model.meshes().is_empty());
+        assert_eq!(
+            model.animations(),
+            &[Animation {
+                name: Some("spin".to_string()),
+                channels: vec![Channel {
+                    node: 0,
+                    interpolation: Interpolation::Linear,
+                    keyframe_times: vec![0.0, 1.0],
+                    values: ChannelValues::Rotations(vec![
+                        [0.0, 0.0, 0.0, 1.0],
+                        [0.0, 0.707_106_77, 0.0, 0.707_106_77]
+                    ]),
+                }],
+            }]
+        );
+    }
+
+    #[test]
+    fn load_invalid_gltf_model() {
+        assert!(matches!(
+            ModelLoader::load(b"{ \"asset\": 3 }"),
+            Err(AssetError::ModelDecodingFailed(_))
+        ));
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use crate::texture;
+
+/// The number of frames a texture bind group is kept without being used
+pub(crate) const DEFAULT_EVICTION_THRESHOLD: u32 = 300;
+
+/// Per-texture bind groups of a pass. Bind groups that haven't been used for
+/// more than the eviction threshold are dropped, so textures used once don't
+/// stay resident forever.
+pub(crate) struct TextureBindGroupCache<B = wgpu::BindGroup> {
+    bind_groups: HashMap<texture::Id, CachedBindGroup<B>>,
+    current_frame: u64,
+    eviction_threshold: u32,
+}
+
+struct CachedBindGroup<B> {
+    bind_group: B,
+    last_used_frame: u64,
+}
+
+impl<B> TextureBindGroupCache<B> {
+    pub(crate) fn new() -> Self {
+        Self {
+            bind_groups: HashMap::new(),
+            current_frame: 0,
+            eviction_threshold: DEFAULT_EVICTION_THRESHOLD,
+        }
+    }
+
+    pub(crate) fn set_eviction_threshold(&mut self, eviction_threshold: u32) {
+        self.eviction_threshold = eviction_threshold;
+    }
+
+    /// Marks the bind group of a texture as used during the current frame,
+    /// returns false if there is no bind group for the texture
+    pub(crate) fn mark_used(&mut self, texture: texture::Id) -> bool {
+        let Some(cached_bind_group) = self.bind_groups.get_mut(&texture) else {
+            return false;
+        };
+
+        cached_bind_group.last_used_frame = self.current_frame;
+        true
+    }
+
+    pub(crate) fn insert(&mut self, texture: texture::Id, bind_group: B) {
+        self.bind_groups.insert(
+            texture,
+            CachedBindGroup {
+                bind_group,
+                last_used_frame: self.current_frame,
+            },
+        );
+    }
+
+    pub(crate) fn remove(&mut self, texture: texture::Id) {
+        self.bind_groups.remove(&texture);
+    }
+
+    pub(crate) fn get(&self, texture: texture::Id) -> &B {
+        &self.bind_groups[&texture].bind_group
+    }
+
+    /// Drops the bind groups unused for too long and starts a new frame
+    pub(crate) fn evict_unused(&mut self) {
+        let current_frame = self.current_frame;
+        let eviction_threshold = u64::from(self.eviction_threshold);
+        self.bind_groups.retain(|_, cached_bind_group| {
+            current_frame - cached_bind_group.last_used_frame <= eviction_threshold
+        });
+        self.current_frame += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evict_bind_groups_unused_for_more_than_the_threshold() {
+        let used = texture::Id::new(0, 0);
+        let unused = texture::Id::new(1, 0);
+        let mut cache = TextureBindGroupCache::<()>::new();
+        cache.set_eviction_threshold(2);
+        cache.insert(used, ());
+        cache.insert(unused, ());
+        for _ in 0..3 {
+            cache.evict_unused();
+            assert!(cache.mark_used(used));
+        }
+        assert!(cache.bind_groups.contains_key(&unused));
+
+        cache.evict_unused();
+        assert!(cache.mark_used(used));
+        assert!(!cache.bind_groups.contains_key(&unused));
+    }
+}
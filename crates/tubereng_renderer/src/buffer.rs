@@ -0,0 +1,38 @@
+use std::ops::Deref;
+
+/// Identifier of a GPU buffer created with the [`GraphicsState`](crate::GraphicsState)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Id(usize);
+impl Deref for Id {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+pub struct Cache {
+    buffers: Vec<wgpu::Buffer>,
+}
+
+impl Cache {
+    #[must_use]
+    pub fn new() -> Self {
+        Self { buffers: vec![] }
+    }
+
+    pub(crate) fn insert(&mut self, buffer: wgpu::Buffer) -> Id {
+        self.buffers.push(buffer);
+        Id(self.buffers.len() - 1)
+    }
+
+    pub(crate) fn get(&self, id: Id) -> &wgpu::Buffer {
+        &self.buffers[*id]
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
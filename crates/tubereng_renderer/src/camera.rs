@@ -1,25 +1,212 @@
-use tubereng_math::matrix::Matrix4f;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use log::warn;
+use tubereng_core::{DeltaTime, Transform};
+use tubereng_ecs::{query::SingleError, relationship::ChildOf, ComponentRef, EntityId, Storage};
+use tubereng_math::{
+    matrix::Matrix4f,
+    vector::{Vector2f, Vector3f},
+};
+
+use crate::WindowSize;
+
+/// Marker of the camera used by the 2d pass, exactly one 2d camera is
+/// expected to be active
 #[derive(Debug)]
 pub struct Active;
 
+/// Marker of the camera used by the 3d pass, at most one 3d camera is
+/// expected to be active
+#[derive(Debug)]
+pub struct Active3d;
+
 #[derive(Debug)]
 pub struct D2 {
-    projection: Matrix4f,
+    viewport_width: f32,
+    viewport_height: f32,
+    fit_height: Option<f32>,
+    pixel_snapping: bool,
 }
 
 impl D2 {
+    /// Creates a camera with a fixed viewport, stretched over the whole
+    /// window
     #[must_use]
     pub fn new(viewport_width: f32, viewport_height: f32) -> Self {
         Self {
-            projection: Matrix4f::new_orthographic(
-                0.0,
-                viewport_width,
-                viewport_height,
-                0.0,
-                -1000.0,
-                1000.0,
-            ),
+            viewport_width,
+            viewport_height,
+            fit_height: None,
+            pixel_snapping: false,
+        }
+    }
+
+    /// Creates a camera showing `world_height` units vertically, its viewport
+    /// width follows the aspect ratio of the window so sprites never stretch
+    #[must_use]
+    pub fn new_fit_height(world_height: f32) -> Self {
+        Self {
+            viewport_width: world_height,
+            viewport_height: world_height,
+            fit_height: Some(world_height),
+            pixel_snapping: false,
+        }
+    }
+
+    /// Rounds the translation of the view projection to whole pixels so
+    /// pixel-art sprites land on integer screen coordinates and don't jitter
+    /// when the camera moves by fractions of a pixel.
+    ///
+    /// Only the projected camera position is snapped, the world transforms
+    /// are untouched, so the error doesn't accumulate. The trade-off is that
+    /// slow camera motion moves the scene pixel by pixel instead of smoothly.
+    #[must_use]
+    pub fn with_pixel_snapping(mut self) -> Self {
+        self.pixel_snapping = true;
+        self
+    }
+
+    #[must_use]
+    pub fn pixel_snapping(&self) -> bool {
+        self.pixel_snapping
+    }
+
+    fn orthographic(viewport_width: f32, viewport_height: f32) -> Matrix4f {
+        Matrix4f::new_orthographic(0.0, viewport_width, viewport_height, 0.0, -1000.0, 1000.0)
+    }
+
+    /// Returns the size of the region of the world seen by the camera for a
+    /// window of the given size, the region starts at the camera position
+    pub(crate) fn viewport_size(&self, window_size: WindowSize) -> (f32, f32) {
+        match self.fit_height {
+            Some(world_height) if window_size.height > 0 => {
+                #[allow(clippy::cast_precision_loss)]
+                let aspect_ratio = window_size.width as f32 / window_size.height as f32;
+                (world_height * aspect_ratio, world_height)
+            }
+            _ => (self.viewport_width, self.viewport_height),
+        }
+    }
+
+    /// Returns the projection of the camera for a window of the given size
+    pub(crate) fn projection(&self, window_size: WindowSize) -> Matrix4f {
+        let (viewport_width, viewport_height) = self.viewport_size(window_size);
+        Self::orthographic(viewport_width, viewport_height)
+    }
+}
+
+/// Returns the active 2d camera, `None` if there is none. If several cameras
+/// are active, the one with the lowest entity id is returned and a warning is
+/// logged the first time.
+#[must_use]
+pub fn active(storage: &Storage) -> Option<(EntityId, ComponentRef<D2>)> {
+    static MULTIPLE_CAMERAS_WARNED: AtomicBool = AtomicBool::new(false);
+    active_camera::<D2, Active>(storage, "2d", &MULTIPLE_CAMERAS_WARNED)
+}
+
+/// Returns the active 3d camera, see [`active`]
+#[must_use]
+pub fn active_3d(storage: &Storage) -> Option<(EntityId, ComponentRef<D3>)> {
+    static MULTIPLE_CAMERAS_WARNED: AtomicBool = AtomicBool::new(false);
+    active_camera::<D3, Active3d>(storage, "3d", &MULTIPLE_CAMERAS_WARNED)
+}
+
+fn active_camera<C: 'static, Marker: 'static>(
+    storage: &Storage,
+    kind: &str,
+    multiple_cameras_warned: &AtomicBool,
+) -> Option<(EntityId, ComponentRef<C>)> {
+    let mut camera_query = storage.query::<(&C, &Marker)>();
+    let (camera_id, (camera, _)) = match camera_query.single_with_id() {
+        Ok(camera) => camera,
+        Err(SingleError::MultipleMatches) => {
+            if !multiple_cameras_warned.swap(true, Ordering::Relaxed) {
+                warn!("Several active {kind} cameras are present in the scene, only the first one is used");
+            }
+            camera_query.iter_with_ids().next()?
+        }
+        Err(SingleError::NoMatch) => return None,
+    };
+    Some((camera_id, camera))
+}
+
+/// Rounds the translation of a view projection matrix to whole pixels of a
+/// window of the given size. The snapping happens in clip space so it
+/// accounts for the zoom of the camera.
+pub(crate) fn snap_to_pixels(mut view_proj: Matrix4f, window_size: WindowSize) -> Matrix4f {
+    #[allow(clippy::cast_precision_loss)]
+    let half_size = [
+        window_size.width as f32 / 2.0,
+        window_size.height as f32 / 2.0,
+    ];
+    for (row, half_size) in half_size.into_iter().enumerate() {
+        if half_size > 0.0 {
+            view_proj[row][3] = (view_proj[row][3] * half_size).round() / half_size;
+        }
+    }
+
+    view_proj
+}
+
+/// Returns the corners of the bounding box of a rectangle of the xy plane
+/// starting at the origin, once transformed
+pub(crate) fn transformed_rect_bounds(
+    rect_size: (f32, f32),
+    transform: &Matrix4f,
+) -> (Vector2f, Vector2f) {
+    let (width, height) = rect_size;
+    let corners = [(0.0, 0.0), (width, 0.0), (0.0, height), (width, height)]
+        .map(|(x, y)| transform.transform_vec3(&Vector3f::new(x, y, 0.0)));
+
+    let mut min = Vector2f::new(f32::INFINITY, f32::INFINITY);
+    let mut max = Vector2f::new(f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for corner in corners {
+        min.x = min.x.min(corner.x);
+        min.y = min.y.min(corner.y);
+        max.x = max.x.max(corner.x);
+        max.y = max.y.max(corner.y);
+    }
+
+    (min, max)
+}
+
+/// Maps the clip space depth of right-handed projections from `[-1, 1]` to
+/// the `[0, 1]` range used by wgpu
+#[rustfmt::skip]
+const OPENGL_TO_WGPU_MATRIX: Matrix4f = Matrix4f::with_values([
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.5, 0.5,
+    0.0, 0.0, 0.0, 1.0,
+]);
+
+#[derive(Debug)]
+pub struct D3 {
+    projection: Matrix4f,
+}
+
+impl D3 {
+    /// Creates a perspective camera looking towards -Z
+    #[must_use]
+    pub fn new_perspective(fov_y_degrees: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        Self {
+            projection: OPENGL_TO_WGPU_MATRIX
+                * Matrix4f::new_perspective(fov_y_degrees, aspect_ratio, near, far),
+        }
+    }
+
+    /// Creates an orthographic camera looking towards -Z
+    #[must_use]
+    pub fn new_orthographic(
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+        near: f32,
+        far: f32,
+    ) -> Self {
+        Self {
+            projection: Matrix4f::new_orthographic(left, right, bottom, top, -near, -far),
         }
     }
 
@@ -27,3 +214,155 @@ impl D2 {
         &self.projection
     }
 }
+
+/// Component making a camera trail the world position of an entity
+#[derive(Debug, Clone)]
+pub struct Follow {
+    pub target: EntityId,
+    /// Offset added to the position of the target
+    pub offset: Vector2f,
+    /// Time in seconds the camera takes to catch up most of the distance to
+    /// its destination, the camera snaps to it if zero
+    pub smoothing: f32,
+}
+
+/// Moves the cameras having a [`Follow`] component towards their target.
+///
+/// The global position of the target is computed from its transform and the
+/// transforms of its ancestors, so a target moved or spawned during the frame
+/// is followed immediately. Cameras whose target has been despawned are left
+/// in place.
+pub fn follow_system(storage: &Storage) {
+    let Some(delta_time) = storage
+        .resource::<DeltaTime>()
+        .map(|delta_time| delta_time.0)
+    else {
+        return;
+    };
+    let followers = storage
+        .query::<&Follow>()
+        .iter_with_ids()
+        .map(|(camera_id, follow)| (camera_id, follow.clone()))
+        .collect::<Vec<_>>();
+
+    for (camera_id, follow) in followers {
+        let Some(target_matrix) = global_transform_matrix(storage, follow.target) else {
+            continue;
+        };
+        let Some(mut camera_transform) = storage.component_mut::<Transform>(camera_id) else {
+            continue;
+        };
+
+        let destination_x = target_matrix[0][3] + follow.offset.x;
+        let destination_y = target_matrix[1][3] + follow.offset.y;
+        let t = if follow.smoothing > 0.0 {
+            1.0 - (-delta_time / follow.smoothing).exp()
+        } else {
+            1.0
+        };
+
+        let translation = &mut camera_transform.translation;
+        translation.x += (destination_x - translation.x) * t;
+        translation.y += (destination_y - translation.y) * t;
+    }
+}
+
+/// Combines the transform of an entity with the transforms of its
+/// ancestors, returns `None` if the entity has no transform
+fn global_transform_matrix(storage: &Storage, entity_id: EntityId) -> Option<Matrix4f> {
+    let mut matrix = storage.component::<Transform>(entity_id)?.as_matrix4();
+    if let Some(child_of_relationship) = storage.relationship::<ChildOf>() {
+        for parent in child_of_relationship.successors(entity_id) {
+            if let Some(parent_transform) = storage.component::<Transform>(parent) {
+                matrix = parent_transform.as_matrix4() * matrix;
+            }
+        }
+    }
+
+    Some(matrix)
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_ecs::{relationship::RelationshipKind, system::Into, Ecs};
+
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn active_returns_the_first_active_camera() {
+        let mut ecs = Ecs::new();
+        ecs.run_single_run_system(
+            &(|storage: &Storage| assert!(active(storage).is_none())).into_system(),
+        );
+
+        ecs.insert((D2::new(800.0, 600.0),));
+        let first_camera = ecs.insert((D2::new(400.0, 300.0), Active));
+        ecs.insert((D2::new(200.0, 150.0), Active));
+        ecs.run_single_run_system(
+            &(move |storage: &Storage| {
+                let (camera_id, camera) = active(storage).unwrap();
+                assert_eq!(camera_id, first_camera);
+                assert_eq!(camera.viewport_width, 400.0);
+                assert!(active_3d(storage).is_none());
+            })
+            .into_system(),
+        );
+    }
+
+    #[test]
+    fn follow_system_follows_a_target_without_cached_transform() {
+        let mut ecs = Ecs::new();
+        ecs.define_relationship::<ChildOf>(RelationshipKind::OneToMany);
+        ecs.insert_resource(DeltaTime(0.016));
+        let parent = ecs.insert((Transform {
+            translation: Vector3f::new(100.0, 0.0, 0.0),
+            ..Default::default()
+        },));
+        let target = ecs.insert((Transform {
+            translation: Vector3f::new(10.0, 5.0, 0.0),
+            ..Default::default()
+        },));
+        ecs.insert_relationship::<ChildOf>(target, parent);
+        let camera = ecs.insert((
+            Transform::default(),
+            Follow {
+                target,
+                offset: Vector2f::new(0.0, 1.0),
+                smoothing: 0.0,
+            },
+        ));
+
+        ecs.run_single_run_system(&follow_system.into_system());
+
+        let camera_transform = ecs.component::<Transform>(camera).unwrap();
+        assert!((camera_transform.translation.x - 110.0).abs() < 1e-4);
+        assert!((camera_transform.translation.y - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn snap_to_pixels_rounds_the_camera_translation() {
+        let window_size = WindowSize {
+            width: 800,
+            height: 600,
+        };
+        let camera = D2::new(400.0, 300.0).with_pixel_snapping();
+        let camera_transform = Matrix4f::new_translation(&Vector3f::new(10.3, 5.6, 0.0));
+        let view_proj = snap_to_pixels(
+            camera.projection(window_size) * camera_transform.try_inverse().unwrap(),
+            window_size,
+        );
+
+        // The camera shows 2 pixels per world unit
+        let screen_position = |world_position: Vector3f| {
+            let clip_position = view_proj.transform_vec3(&world_position);
+            (
+                (clip_position.x + 1.0) * 400.0,
+                (1.0 - clip_position.y) * 300.0,
+            )
+        };
+        let (x, y) = screen_position(Vector3f::new(20.0, 10.0, 0.0));
+        assert!((x - 19.0).abs() < 1e-3, "{x}");
+        assert!((y - 9.0).abs() < 1e-3, "{y}");
+    }
+}
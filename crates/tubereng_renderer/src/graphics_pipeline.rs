@@ -1,9 +1,23 @@
+use log::warn;
 use tubereng_ecs::Storage;
 
-use crate::GraphicsState;
+use crate::{texture, GraphicsState, RenderStats};
 
+/// The render graph, executing its render and compute passes once per frame.
+///
+/// Passes run in insertion order, except that a render pass sampling a
+/// render target (see [`RenderPass::inputs`]) always runs after the passes
+/// writing to that target (see [`RenderPass::output`]). A compute pass
+/// producing a buffer consumed by a render pass must then be added before
+/// the render pass.
 pub struct GraphicsPipeline {
-    passes: Vec<Box<dyn RenderPass>>,
+    passes: Vec<Pass>,
+    execution_order: Vec<usize>,
+}
+
+enum Pass {
+    Render(Box<dyn RenderPass>),
+    Compute(Box<dyn ComputePass>),
 }
 
 impl GraphicsPipeline {
@@ -12,19 +26,61 @@ impl GraphicsPipeline {
         Builder::default()
     }
 
+    fn new(passes: Vec<Pass>) -> Self {
+        let mut pipeline = Self {
+            passes,
+            execution_order: vec![],
+        };
+        pipeline.update_execution_order();
+        pipeline
+    }
+
     pub fn add_pass<P>(&mut self, pass: P)
     where
         P: 'static + RenderPass,
     {
-        self.passes.push(Box::new(pass));
+        self.passes.push(Pass::Render(Box::new(pass)));
+        self.update_execution_order();
+    }
+
+    pub fn add_compute_pass<P>(&mut self, pass: P)
+    where
+        P: 'static + ComputePass,
+    {
+        self.passes.push(Pass::Compute(Box::new(pass)));
+        self.update_execution_order();
+    }
+
+    fn update_execution_order(&mut self) {
+        let dependencies = self
+            .passes
+            .iter()
+            .map(|pass| match pass {
+                Pass::Render(pass) => (
+                    pass.inputs().to_vec(),
+                    pass.output()
+                        .into_iter()
+                        .chain(pass.depth_output())
+                        .collect(),
+                ),
+                Pass::Compute(_) => (vec![], vec![]),
+            })
+            .collect::<Vec<_>>();
+        self.execution_order = execution_order(&dependencies);
     }
 
     pub fn prepare(&mut self, storage: &Storage) {
-        for pass in &mut self.passes {
-            pass.prepare(storage);
+        for &index in &self.execution_order {
+            match &mut self.passes[index] {
+                Pass::Render(pass) => pass.prepare(storage),
+                Pass::Compute(pass) => pass.prepare(storage),
+            }
         }
     }
 
+    /// Executes the passes, passes without output render to
+    /// `surface_texture_view`. A pass whose output or inputs aren't loaded
+    /// is skipped.
     pub fn execute(
         &self,
         graphics: &mut GraphicsState,
@@ -32,15 +88,88 @@ impl GraphicsPipeline {
         surface_texture_view: &wgpu::TextureView,
         storage: &Storage,
     ) {
-        for pass in &self.passes {
-            pass.execute(graphics, encoder, surface_texture_view, storage);
+        for &index in &self.execution_order {
+            let pass = match &self.passes[index] {
+                Pass::Render(pass) => pass,
+                Pass::Compute(pass) => {
+                    let mut compute_pass =
+                        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                            label: Some("compute_pass"),
+                            timestamp_writes: None,
+                        });
+                    pass.dispatch(&mut compute_pass, storage);
+                    continue;
+                }
+            };
+            let target_view = match pass.output() {
+                Some(output) if !graphics.has_texture(output) => continue,
+                Some(output) => Some(
+                    graphics
+                        .texture_cache
+                        .get(output)
+                        .create_view(&wgpu::TextureViewDescriptor::default()),
+                ),
+                None => None,
+            };
+            if !pass
+                .inputs()
+                .iter()
+                .all(|&input| graphics.has_texture(input))
+            {
+                continue;
+            }
+
+            pass.execute(
+                graphics,
+                encoder,
+                target_view.as_ref().unwrap_or(surface_texture_view),
+                storage,
+            );
+            if let Some(mut render_stats) = storage.resource_mut::<RenderStats>() {
+                render_stats.render_passes += 1;
+            }
         }
     }
 }
 
+/// Orders the passes described by their inputs and outputs so that the
+/// passes writing to a render target run before the passes sampling it,
+/// keeping the insertion order otherwise.
+///
+/// Falls back to the insertion order for the passes involved in a cycle.
+fn execution_order(passes: &[(Vec<texture::Id>, Vec<texture::Id>)]) -> Vec<usize> {
+    let depends_on = |consumer: usize, producer: usize| {
+        consumer != producer
+            && passes[producer]
+                .1
+                .iter()
+                .any(|output| passes[consumer].0.contains(output))
+    };
+
+    let mut order = Vec::with_capacity(passes.len());
+    let mut scheduled = vec![false; passes.len()];
+    while order.len() < passes.len() {
+        let next = (0..passes.len()).find(|&candidate| {
+            !scheduled[candidate]
+                && (0..passes.len())
+                    .all(|producer| scheduled[producer] || !depends_on(candidate, producer))
+        });
+        let Some(next) = next else {
+            warn!("The render passes have cyclic dependencies, running the remaining ones in insertion order");
+            order.extend((0..passes.len()).filter(|&index| !scheduled[index]));
+            break;
+        };
+
+        scheduled[next] = true;
+        order.push(next);
+    }
+
+    order
+}
+
 #[derive(Default)]
 pub struct Builder {
-    passes: Vec<Box<dyn RenderPass>>,
+    passes: Vec<Pass>,
 }
 
 impl Builder {
@@ -48,18 +177,26 @@ impl Builder {
     where
         P: 'static + RenderPass,
     {
-        self.passes.push(Box::new(pass));
+        self.passes.push(Pass::Render(Box::new(pass)));
+        self
+    }
+
+    pub fn add_compute_pass<P>(&mut self, pass: P) -> &mut Self
+    where
+        P: 'static + ComputePass,
+    {
+        self.passes.push(Pass::Compute(Box::new(pass)));
         self
     }
 
     pub fn build(&mut self) -> GraphicsPipeline {
         let mut passes = vec![];
         passes.append(&mut self.passes);
-        GraphicsPipeline { passes }
+        GraphicsPipeline::new(passes)
     }
 }
 
-pub trait RenderPass {
+pub trait RenderPass: Send + Sync {
     fn prepare(&mut self, storage: &Storage);
     fn execute(
         &self,
@@ -68,4 +205,71 @@ pub trait RenderPass {
         surface_texture_view: &wgpu::TextureView,
         storage: &Storage,
     );
+
+    /// The render targets sampled by the pass, the pass runs after the
+    /// passes writing to them. Their bind groups are obtained with
+    /// [`GraphicsState::sampled_texture_bind_group`].
+    fn inputs(&self) -> &[texture::Id] {
+        &[]
+    }
+
+    /// The render target the pass writes to, the pass renders to the
+    /// surface if `None`
+    fn output(&self) -> Option<texture::Id> {
+        None
+    }
+
+    /// The depth target the pass writes to, if any, so passes sampling it
+    /// run after this pass
+    fn depth_output(&self) -> Option<texture::Id> {
+        None
+    }
+}
+
+/// A pass running compute shaders, for instance to simulate particles in a
+/// storage buffer read by a later render pass.
+///
+/// The pipeline and bind groups of the pass can be created with
+/// [`GraphicsState::create_compute_pipeline`] and
+/// [`GraphicsState::create_buffers_bind_group`].
+pub trait ComputePass: Send + Sync {
+    fn prepare(&mut self, storage: &Storage);
+
+    /// Records the work of the pass in a compute pass begun by the graph,
+    /// usually by setting a pipeline and its bind groups before calling
+    /// [`wgpu::ComputePass::dispatch_workgroups`]
+    fn dispatch<'a>(&'a self, compute_pass: &mut wgpu::ComputePass<'a>, storage: &Storage);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn execution_order_keeps_insertion_order() {
+        assert_eq!(
+            execution_order(&[(vec![], vec![]), (vec![], vec![])]),
+            [0, 1]
+        );
+    }
+
+    #[test]
+    fn execution_order_runs_producers_first() {
+        let scene_color = texture::Id::new(0, 0);
+        let passes = [
+            (vec![scene_color], vec![]),
+            (vec![], vec![scene_color]),
+            (vec![], vec![]),
+            (vec![], vec![scene_color]),
+        ];
+        assert_eq!(execution_order(&passes), [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn execution_order_with_cycle() {
+        let a = texture::Id::new(0, 0);
+        let b = texture::Id::new(1, 0);
+        let passes = [(vec![b], vec![a]), (vec![a], vec![b]), (vec![], vec![])];
+        assert_eq!(execution_order(&passes), [2, 0, 1]);
+    }
 }
@@ -3,68 +3,222 @@
 use std::{borrow::BorrowMut, collections::HashMap, sync::Arc};
 
 use graphics_pipeline::{GraphicsPipeline, RenderPass};
-use raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawWindowHandle};
+use log::{error, info, warn};
+use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
+use tubereng_core::ExitRequest;
 use tubereng_ecs::{
     system::{Res, ResMut},
     Ecs, Storage,
 };
-use wgpu::SurfaceTargetUnsafe;
+use wgpu::{util::DeviceExt, SurfaceTargetUnsafe};
 
+mod bind_group_cache;
+pub mod buffer;
 pub mod camera;
 pub mod graphics_pipeline;
+pub mod light_2d;
 pub mod material;
-mod mesh;
+pub mod mesh;
 pub mod pass_2d;
+pub mod pass_3d;
+pub mod post_process;
 pub mod sprite;
 pub mod texture;
+pub mod tilemap;
+pub mod ui_pass;
 
+pub use sprite::pick_sprite;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct WindowSize {
     pub width: u32,
     pub height: u32,
 }
 
+/// Identifies a secondary window rendered by the [`GraphicsState`], the
+/// window the renderer was initialized with being the main window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WindowId(pub u64);
+
+/// The surface of a secondary window. The render graph draws to the main
+/// window, secondary windows are cleared to their clear color each frame.
+struct SecondaryWindow<'w> {
+    surface: wgpu::Surface<'w>,
+    surface_configuration: wgpu::SurfaceConfiguration,
+    clear_color: Color,
+}
+
+/// An error preventing the renderer from being initialized
+#[derive(Debug)]
+pub enum RendererInitError {
+    SurfaceCreationFailed(wgpu::CreateSurfaceError),
+    WindowHandleFetchingFailed(raw_window_handle::HandleError),
+    NoAdapter,
+    /// The adapter lacks the contained required features
+    UnsupportedFeatures(wgpu::Features),
+    /// The adapter doesn't support the contained required limits
+    UnsupportedLimits(Vec<&'static str>),
+    DeviceRequestFailed(wgpu::RequestDeviceError),
+}
+
+/// The GPU features and limits required by the application, checked against
+/// the capabilities of the adapter at initialization, and the settings of the
+/// default passes
+#[derive(Debug, Clone)]
+pub struct RendererConfig {
+    pub features: wgpu::Features,
+    pub limits: wgpu::Limits,
+    /// The number of frames the 2d and UI passes keep the bind group of an
+    /// unused texture
+    pub bind_group_eviction_threshold: u32,
+}
+
+impl RendererConfig {
+    #[must_use]
+    pub fn with_features(mut self, features: wgpu::Features) -> Self {
+        self.features = features;
+        self
+    }
+
+    #[must_use]
+    pub fn with_limits(mut self, limits: wgpu::Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_bind_groups(mut self, max_bind_groups: u32) -> Self {
+        self.limits.max_bind_groups = max_bind_groups;
+        self
+    }
+
+    #[must_use]
+    pub fn with_bind_group_eviction_threshold(mut self, frames: u32) -> Self {
+        self.bind_group_eviction_threshold = frames;
+        self
+    }
+
+    /// Checks that the adapter supports the required features and limits
+    fn validate(
+        &self,
+        adapter_features: wgpu::Features,
+        adapter_limits: &wgpu::Limits,
+    ) -> Result<(), RendererInitError> {
+        let missing_features = self.features - adapter_features;
+        if !missing_features.is_empty() {
+            return Err(RendererInitError::UnsupportedFeatures(missing_features));
+        }
+
+        let mut unsupported_limits = vec![];
+        self.limits
+            .check_limits_with_fail_fn(adapter_limits, false, |name, _, _| {
+                unsupported_limits.push(name);
+            });
+        if !unsupported_limits.is_empty() {
+            return Err(RendererInitError::UnsupportedLimits(unsupported_limits));
+        }
+
+        Ok(())
+    }
+}
+
+/// No feature is required, the limits are the default ones, or the WebGL2
+/// ones on wasm32, and unused bind groups are kept for 300 frames
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            features: wgpu::Features::empty(),
+            limits: if cfg!(target_arch = "wasm32") {
+                wgpu::Limits::downlevel_webgl2_defaults()
+            } else {
+                wgpu::Limits::default()
+            },
+            bind_group_eviction_threshold: bind_group_cache::DEFAULT_EVICTION_THRESHOLD,
+        }
+    }
+}
+
+/// Information about the GPU adapter selected at initialization
+#[derive(Debug, Clone)]
+pub struct AdapterInfo {
+    pub name: String,
+    pub backend: wgpu::Backend,
+    pub device_type: wgpu::DeviceType,
+    pub driver: String,
+    pub driver_info: String,
+    /// Whether the surface couldn't be created with the primary backends and
+    /// the GL backend has been used instead
+    pub is_gl_fallback: bool,
+}
+
+impl AdapterInfo {
+    fn new(adapter_info: wgpu::AdapterInfo, is_gl_fallback: bool) -> Self {
+        Self {
+            name: adapter_info.name,
+            backend: adapter_info.backend,
+            device_type: adapter_info.device_type,
+            driver: adapter_info.driver,
+            driver_info: adapter_info.driver_info,
+            is_gl_fallback,
+        }
+    }
+}
+
 pub struct WgpuState<'w> {
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
     surface: wgpu::Surface<'w>,
+    secondary_windows: HashMap<WindowId, SecondaryWindow<'w>>,
     device: wgpu::Device,
     queue: wgpu::Queue,
     surface_configuration: wgpu::SurfaceConfiguration,
+    adapter_info: AdapterInfo,
     window_size: WindowSize,
-    _window: RawWindowHandle,
 }
 
 pub struct GraphicsState<'w> {
     pub(crate) wgpu_state: WgpuState<'w>,
     pub(crate) texture_cache: texture::Cache,
     material_bind_group_layout: wgpu::BindGroupLayout,
+    depth_texture_bind_group_layout: wgpu::BindGroupLayout,
     placeholder_material_id: Option<material::Id>,
     pub(crate) material_cache: material::Cache,
+    /// Textures unloaded since the last rendered frame, whose bind groups
+    /// must be dropped by the passes
+    unloaded_textures: Vec<texture::Id>,
+    sampled_texture_bind_groups: HashMap<texture::Id, wgpu::BindGroup>,
+    pub(crate) mesh_cache: mesh::Cache,
+    buffer_cache: buffer::Cache,
+    font_texture_id: Option<texture::Id>,
+    ui_draw_commands: Vec<ui_pass::DrawCommand>,
 }
 
 impl<'w> GraphicsState<'w> {
     /// Creates a new `WGPUState`
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Will panic if:
+    /// Will return [`Err`] if:
     ///  - The surface cannot be created
     ///  - No adapter is found
+    ///  - The adapter doesn't support the features or limits of the
+    ///    configuration
     ///  - The device cannot be set up
     ///  - The handle of the window cannot be obtained
-    pub async fn new<W>(window: W) -> Self
+    pub async fn new<W>(
+        window: W,
+        window_size: WindowSize,
+        config: &RendererConfig,
+    ) -> Result<Self, RendererInitError>
     where
         W: HasWindowHandle + HasDisplayHandle + std::marker::Send + std::marker::Sync,
     {
-        const WINDOW_SIZE: WindowSize = WindowSize {
-            width: 800,
-            height: 600,
-        };
-
         let mut instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::PRIMARY,
             ..Default::default()
         });
 
-        let surface = Self::create_surface(&mut instance, &window);
+        let (surface, is_gl_fallback) = Self::create_surface(&mut instance, &window)?;
 
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
@@ -73,23 +227,22 @@ impl<'w> GraphicsState<'w> {
                 force_fallback_adapter: false,
             })
             .await
-            .expect("No adapter found");
+            .ok_or(RendererInitError::NoAdapter)?;
+        let adapter_info = AdapterInfo::new(adapter.get_info(), is_gl_fallback);
+        info!("Selected adapter: {adapter_info:?}");
+        config.validate(adapter.features(), &adapter.limits())?;
 
         let (device, queue) = adapter
             .request_device(
                 &wgpu::DeviceDescriptor {
-                    required_features: wgpu::Features::empty(),
-                    required_limits: if cfg!(target_arch = "wasm32") {
-                        wgpu::Limits::downlevel_webgl2_defaults()
-                    } else {
-                        wgpu::Limits::default()
-                    },
+                    required_features: config.features,
+                    required_limits: config.limits.clone(),
                     label: None,
                 },
                 None,
             )
             .await
-            .expect("Couldn't setup device");
+            .map_err(RendererInitError::DeviceRequestFailed)?;
         let surface_capabilities = surface.get_capabilities(&adapter);
         let surface_format = surface_capabilities
             .formats
@@ -98,7 +251,6 @@ impl<'w> GraphicsState<'w> {
             .find(wgpu::TextureFormat::is_srgb)
             .unwrap_or(surface_capabilities.formats[0]);
 
-        let window_size = WINDOW_SIZE;
         let surface_configuration = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
@@ -110,53 +262,224 @@ impl<'w> GraphicsState<'w> {
             desired_maximum_frame_latency: 2,
         };
         surface.configure(&device, &surface_configuration);
+        info!("Surface format: {surface_format:?}");
 
-        let material_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                label: Some("material_bind_group_layout"),
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            multisampled: false,
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-            });
+        let material_bind_group_layout = Self::create_texture_bind_group_layout(
+            &device,
+            "material_bind_group_layout",
+            wgpu::TextureSampleType::Float { filterable: true },
+            wgpu::SamplerBindingType::Filtering,
+        );
+        let depth_texture_bind_group_layout = Self::create_texture_bind_group_layout(
+            &device,
+            "depth_texture_bind_group_layout",
+            wgpu::TextureSampleType::Depth,
+            wgpu::SamplerBindingType::NonFiltering,
+        );
 
-        GraphicsState {
+        Ok(GraphicsState {
             wgpu_state: WgpuState {
+                instance,
+                adapter,
                 surface,
+                secondary_windows: HashMap::new(),
                 device,
                 queue,
                 surface_configuration,
+                adapter_info,
                 window_size,
-                _window: window
-                    .window_handle()
-                    .expect("Couldn't obtain window handle")
-                    .into(),
             },
             texture_cache: texture::Cache::new(),
             material_cache: material::Cache::new(),
+            unloaded_textures: vec![],
+            sampled_texture_bind_groups: HashMap::new(),
+            mesh_cache: mesh::Cache::new(),
+            buffer_cache: buffer::Cache::new(),
             placeholder_material_id: None,
             material_bind_group_layout,
-        }
+            depth_texture_bind_group_layout,
+            font_texture_id: None,
+            ui_draw_commands: vec![],
+        })
+    }
+
+    /// Creates the layout of a bind group with a texture at binding 0 and
+    /// its sampler at binding 1, visible from the fragment stage
+    fn create_texture_bind_group_layout(
+        device: &wgpu::Device,
+        label: &str,
+        sample_type: wgpu::TextureSampleType,
+        sampler_binding_type: wgpu::SamplerBindingType,
+    ) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(label),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(sampler_binding_type),
+                    count: None,
+                },
+            ],
+        })
     }
 
     pub fn window_size(&self) -> &WindowSize {
         &self.wgpu_state.window_size
     }
 
+    /// Reconfigures the surface for a new window size, zero sizes are ignored
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let wgpu_state = &mut self.wgpu_state;
+        wgpu_state.window_size = WindowSize { width, height };
+        wgpu_state.surface_configuration.width = width;
+        wgpu_state.surface_configuration.height = height;
+        wgpu_state
+            .surface
+            .configure(&wgpu_state.device, &wgpu_state.surface_configuration);
+    }
+
+    /// Creates the surface of a secondary window, cleared to `clear_color`
+    /// every frame. The window must outlive its surface, see
+    /// [`GraphicsState::remove_window`].
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Err`] if the handle of the window cannot be obtained or
+    /// if the surface cannot be created
+    pub fn add_window<W>(
+        &mut self,
+        window_id: WindowId,
+        window: &W,
+        window_size: WindowSize,
+        clear_color: Color,
+    ) -> Result<(), RendererInitError>
+    where
+        W: HasWindowHandle + HasDisplayHandle,
+    {
+        let wgpu_state = &mut self.wgpu_state;
+        // SAFETY: The caller keeps the window alive until the surface is
+        // removed
+        let surface = unsafe {
+            let surface_target = SurfaceTargetUnsafe::from_window(window)
+                .map_err(RendererInitError::WindowHandleFetchingFailed)?;
+            wgpu_state
+                .instance
+                .create_surface_unsafe(surface_target)
+                .map_err(RendererInitError::SurfaceCreationFailed)?
+        };
+        let surface_capabilities = surface.get_capabilities(&wgpu_state.adapter);
+        let format = if surface_capabilities
+            .formats
+            .contains(&wgpu_state.surface_configuration.format)
+        {
+            wgpu_state.surface_configuration.format
+        } else {
+            surface_capabilities.formats[0]
+        };
+        let surface_configuration = wgpu::SurfaceConfiguration {
+            format,
+            width: window_size.width.max(1),
+            height: window_size.height.max(1),
+            present_mode: surface_capabilities.present_modes[0],
+            alpha_mode: surface_capabilities.alpha_modes[0],
+            ..wgpu_state.surface_configuration.clone()
+        };
+        surface.configure(&wgpu_state.device, &surface_configuration);
+        wgpu_state.secondary_windows.insert(
+            window_id,
+            SecondaryWindow {
+                surface,
+                surface_configuration,
+                clear_color,
+            },
+        );
+        Ok(())
+    }
+
+    /// Reconfigures the surface of a secondary window for a new window size,
+    /// zero sizes are ignored
+    pub fn resize_window(&mut self, window_id: WindowId, width: u32, height: u32) {
+        let wgpu_state = &mut self.wgpu_state;
+        let Some(secondary_window) = wgpu_state.secondary_windows.get_mut(&window_id) else {
+            return;
+        };
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        secondary_window.surface_configuration.width = width;
+        secondary_window.surface_configuration.height = height;
+        secondary_window
+            .surface
+            .configure(&wgpu_state.device, &secondary_window.surface_configuration);
+    }
+
+    /// Drops the surface of a secondary window, to be called before the
+    /// window is destroyed
+    pub fn remove_window(&mut self, window_id: WindowId) {
+        self.wgpu_state.secondary_windows.remove(&window_id);
+    }
+
+    /// Clears the secondary windows and presents them
+    fn present_secondary_windows(&mut self) {
+        let wgpu_state = &mut self.wgpu_state;
+        for secondary_window in wgpu_state.secondary_windows.values() {
+            let surface_texture = match secondary_window.surface.get_current_texture() {
+                Ok(surface_texture) => surface_texture,
+                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                    secondary_window
+                        .surface
+                        .configure(&wgpu_state.device, &secondary_window.surface_configuration);
+                    continue;
+                }
+                Err(e) => {
+                    warn!("Couldn't acquire the surface texture of a secondary window: {e}");
+                    continue;
+                }
+            };
+            let view = surface_texture
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            let mut encoder =
+                wgpu_state
+                    .device
+                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                        label: Some("secondary_window_encoder"),
+                    });
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("secondary_window_clear_pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear((&secondary_window.clear_color).into()),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            wgpu_state.queue.submit(std::iter::once(encoder.finish()));
+            surface_texture.present();
+        }
+    }
+
     pub fn device(&self) -> &wgpu::Device {
         &self.wgpu_state.device
     }
@@ -169,32 +492,48 @@ impl<'w> GraphicsState<'w> {
         self.wgpu_state.surface_configuration.format
     }
 
-    fn create_surface<W>(instance: &mut wgpu::Instance, window: &W) -> wgpu::Surface<'w>
+    #[must_use]
+    pub fn adapter_info(&self) -> &AdapterInfo {
+        &self.wgpu_state.adapter_info
+    }
+
+    #[must_use]
+    pub fn limits(&self) -> wgpu::Limits {
+        self.wgpu_state.device.limits()
+    }
+
+    /// Creates the surface, falling back on the GL backend if the surface
+    /// cannot be created with the primary backends. Returns whether the GL
+    /// fallback has been used.
+    fn create_surface<W>(
+        instance: &mut wgpu::Instance,
+        window: &W,
+    ) -> Result<(wgpu::Surface<'w>, bool), RendererInitError>
     where
         W: HasWindowHandle + HasDisplayHandle + std::marker::Send + std::marker::Sync,
     {
         let mut surface = unsafe {
-            instance.create_surface_unsafe(
-                SurfaceTargetUnsafe::from_window(window)
-                    .expect("Couldn't create SurfaceTargetUnsafe"),
-            )
+            let surface_target = SurfaceTargetUnsafe::from_window(window)
+                .map_err(RendererInitError::WindowHandleFetchingFailed)?;
+            instance.create_surface_unsafe(surface_target)
         };
 
-        if surface.is_err() {
+        let is_gl_fallback = surface.is_err();
+        if is_gl_fallback {
             *instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
                 backends: wgpu::Backends::GL,
                 ..Default::default()
             });
 
             surface = unsafe {
-                instance.create_surface_unsafe(
-                    SurfaceTargetUnsafe::from_window(window)
-                        .expect("Couldn't create SurfaceTargetUnsafe"),
-                )
+                let surface_target = SurfaceTargetUnsafe::from_window(window)
+                    .map_err(RendererInitError::WindowHandleFetchingFailed)?;
+                instance.create_surface_unsafe(surface_target)
             };
         }
 
-        surface.unwrap()
+        let surface = surface.map_err(RendererInitError::SurfaceCreationFailed)?;
+        Ok((surface, is_gl_fallback))
     }
 
     pub fn load_texture(&mut self, descriptor: &texture::Descriptor) -> texture::Id {
@@ -243,6 +582,375 @@ impl<'w> GraphicsState<'w> {
         self.texture_cache.insert(texture_info, texture)
     }
 
+    /// Creates a texture in the surface format that passes can render to
+    /// (see [`RenderPass::output`]) and sample, usually with the dimensions of
+    /// the window. The render target isn't resized with the window.
+    pub fn create_render_target(&mut self, width: u32, height: u32) -> texture::Id {
+        self.create_render_target_with_format(width, height, self.surface_texture_format())
+    }
+
+    /// Creates a render target in [`post_process::HDR_TEXTURE_FORMAT`], keeping colors
+    /// above 1.0 for a tonemap pass (see [`post_process::TonemapPass`]).
+    /// The render target isn't resized with the window.
+    pub fn create_hdr_render_target(&mut self, width: u32, height: u32) -> texture::Id {
+        self.create_render_target_with_format(width, height, post_process::HDR_TEXTURE_FORMAT)
+    }
+
+    fn create_render_target_with_format(
+        &mut self,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+    ) -> texture::Id {
+        let texture = self
+            .wgpu_state
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("render_target"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            });
+
+        self.texture_cache
+            .insert(texture::Info { width, height }, texture)
+    }
+
+    /// The format of the texture a pass renders to: the format of its output
+    /// render target, or the surface format if the pass has no output
+    #[must_use]
+    pub fn target_texture_format(&self, output: Option<texture::Id>) -> wgpu::TextureFormat {
+        output
+            .and_then(|output| self.texture_cache.try_get(output))
+            .map_or_else(|| self.surface_texture_format(), wgpu::Texture::format)
+    }
+
+    /// Creates a depth texture that a 3d pass can write to (see
+    /// [`pass_3d::Pass::with_depth_prepass`]) and later passes can sample,
+    /// usually with the dimensions of the window. A 3d pass writing to the
+    /// depth target resizes it with the window.
+    pub fn create_depth_target(&mut self, width: u32, height: u32) -> texture::Id {
+        let texture = self.create_depth_target_texture(width, height);
+        self.texture_cache
+            .insert(texture::Info { width, height }, texture)
+    }
+
+    /// Recreates a depth target with new dimensions, keeping its id. Returns
+    /// false if the depth target isn't loaded.
+    pub fn resize_depth_target(&mut self, id: texture::Id, width: u32, height: u32) -> bool {
+        if !self.texture_cache.contains(id) {
+            return false;
+        }
+
+        let texture = self.create_depth_target_texture(width, height);
+        self.sampled_texture_bind_groups.remove(&id);
+        self.texture_cache
+            .replace(id, texture::Info { width, height }, texture)
+    }
+
+    fn create_depth_target_texture(&self, width: u32, height: u32) -> wgpu::Texture {
+        self.wgpu_state
+            .device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("depth_target"),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: pass_3d::DEPTH_TEXTURE_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+    }
+
+    /// The layout of the bind groups of the textures sampled by the passes:
+    /// the texture at binding 0 and a filtering sampler at binding 1
+    #[must_use]
+    pub fn sampled_texture_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.material_bind_group_layout
+    }
+
+    /// The layout of the bind groups of the depth targets sampled by the
+    /// passes: the depth texture at binding 0 and a non-filtering sampler at
+    /// binding 1
+    #[must_use]
+    pub fn depth_texture_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.depth_texture_bind_group_layout
+    }
+
+    /// The bind group sampling a texture with linear filtering, laid out as
+    /// described by [`Self::sampled_texture_bind_group_layout`], or by
+    /// [`Self::depth_texture_bind_group_layout`] for depth targets. The bind
+    /// group is created on first use and kept until the texture is unloaded
+    /// or resized. Returns `None` if the texture isn't loaded.
+    pub fn sampled_texture_bind_group(&mut self, id: texture::Id) -> Option<&wgpu::BindGroup> {
+        if !self.sampled_texture_bind_groups.contains_key(&id) {
+            let bind_group = self.create_sampled_texture_bind_group(id)?;
+            self.sampled_texture_bind_groups.insert(id, bind_group);
+        }
+
+        self.sampled_texture_bind_groups.get(&id)
+    }
+
+    fn create_sampled_texture_bind_group(&self, id: texture::Id) -> Option<wgpu::BindGroup> {
+        let texture = self.texture_cache.try_get(id)?;
+        let is_depth = texture.format().has_depth_aspect();
+        let filter_mode = if is_depth {
+            wgpu::FilterMode::Nearest
+        } else {
+            wgpu::FilterMode::Linear
+        };
+        let device = &self.wgpu_state.device;
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: None,
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: filter_mode,
+            min_filter: filter_mode,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sampled_texture_bind_group"),
+            layout: if is_depth {
+                &self.depth_texture_bind_group_layout
+            } else {
+                &self.material_bind_group_layout
+            },
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        }))
+    }
+
+    /// Creates a compute pipeline from a WGSL shader
+    #[must_use]
+    pub fn create_compute_pipeline(
+        &self,
+        label: &str,
+        wgsl_source: &str,
+        entry_point: &str,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+    ) -> wgpu::ComputePipeline {
+        let device = &self.wgpu_state.device;
+        let shader_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(label),
+            source: wgpu::ShaderSource::Wgsl(wgsl_source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point,
+        })
+    }
+
+    /// Creates the layout of a bind group of storage buffers visible from
+    /// the given stages, with one binding per entry of `read_only` in order
+    #[must_use]
+    pub fn create_storage_buffers_bind_group_layout(
+        &self,
+        visibility: wgpu::ShaderStages,
+        read_only: &[bool],
+    ) -> wgpu::BindGroupLayout {
+        let entries = (0u32..)
+            .zip(read_only)
+            .map(|(binding, &read_only)| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .collect::<Vec<_>>();
+
+        self.wgpu_state
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("storage_buffers_bind_group_layout"),
+                entries: &entries,
+            })
+    }
+
+    /// Creates a uniform buffer initialized with `data`
+    pub fn create_uniform_buffer<T: bytemuck::Pod>(&mut self, data: &T) -> buffer::Id {
+        let buffer = self
+            .wgpu_state
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("uniform_buffer"),
+                contents: bytemuck::bytes_of(data),
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            });
+        self.buffer_cache.insert(buffer)
+    }
+
+    /// Creates a storage buffer initialized with `data`, the buffer can also
+    /// be used as a vertex buffer by the render passes
+    pub fn create_storage_buffer<T: bytemuck::Pod>(&mut self, data: &[T]) -> buffer::Id {
+        let buffer = self
+            .wgpu_state
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("storage_buffer"),
+                contents: bytemuck::cast_slice(data),
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::VERTEX
+                    | wgpu::BufferUsages::COPY_DST,
+            });
+        self.buffer_cache.insert(buffer)
+    }
+
+    /// Writes `data` at the start of a buffer, the write is performed before
+    /// the next submitted frame
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the buffer doesn't exist or is smaller than `data`
+    pub fn update_buffer<T: bytemuck::Pod>(&self, id: buffer::Id, data: &[T]) {
+        self.wgpu_state.queue.write_buffer(
+            self.buffer_cache.get(id),
+            0,
+            bytemuck::cast_slice(data),
+        );
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if the buffer doesn't exist
+    #[must_use]
+    pub fn buffer(&self, id: buffer::Id) -> &wgpu::Buffer {
+        self.buffer_cache.get(id)
+    }
+
+    /// Creates the layout of a bind group of uniform buffers visible from
+    /// the given stages, with `count` bindings
+    #[must_use]
+    pub fn create_uniform_buffers_bind_group_layout(
+        &self,
+        visibility: wgpu::ShaderStages,
+        count: u32,
+    ) -> wgpu::BindGroupLayout {
+        let entries = (0..count)
+            .map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .collect::<Vec<_>>();
+
+        self.wgpu_state
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("uniform_buffers_bind_group_layout"),
+                entries: &entries,
+            })
+    }
+
+    /// Binds buffers in order, with a layout created by
+    /// [`Self::create_uniform_buffers_bind_group_layout`] or
+    /// [`Self::create_storage_buffers_bind_group_layout`]
+    ///
+    /// # Panics
+    ///
+    /// Will panic if one of the buffers doesn't exist
+    #[must_use]
+    pub fn create_buffers_bind_group(
+        &self,
+        layout: &wgpu::BindGroupLayout,
+        buffers: &[buffer::Id],
+    ) -> wgpu::BindGroup {
+        let entries = (0u32..)
+            .zip(buffers)
+            .map(|(binding, &buffer)| wgpu::BindGroupEntry {
+                binding,
+                resource: self.buffer_cache.get(buffer).as_entire_binding(),
+            })
+            .collect::<Vec<_>>();
+
+        self.wgpu_state
+            .device
+            .create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("buffers_bind_group"),
+                layout,
+                entries: &entries,
+            })
+    }
+
+    /// Returns the dimensions of a loaded texture, or `None` if no texture
+    /// is loaded for this id
+    #[must_use]
+    pub fn texture_info(&self, id: texture::Id) -> Option<&texture::Info> {
+        self.texture_cache.try_info(id)
+    }
+
+    #[must_use]
+    pub fn has_texture(&self, id: texture::Id) -> bool {
+        self.texture_cache.contains(id)
+    }
+
+    /// Unloads a texture from the GPU, returns false if the texture wasn't
+    /// loaded.
+    ///
+    /// Materials created from the texture keep it alive until they are
+    /// unloaded themselves.
+    pub fn unload_texture(&mut self, id: texture::Id) -> bool {
+        let unloaded = self.texture_cache.unload(id);
+        if unloaded {
+            self.sampled_texture_bind_groups.remove(&id);
+            self.unloaded_textures.push(id);
+        }
+        unloaded
+    }
+
+    pub(crate) fn unloaded_textures(&self) -> &[texture::Id] {
+        &self.unloaded_textures
+    }
+
+    /// Unloads a material from the GPU, returns false if the material wasn't
+    /// loaded
+    pub fn unload_material(&mut self, id: material::Id) -> bool {
+        self.material_cache.unload(id)
+    }
+
     pub fn load_material(&mut self, descriptor: &material::Descriptor) -> material::Id {
         let device = &self.wgpu_state.device;
         let base_color_texture = self.texture_cache.get(descriptor.base_color);
@@ -277,6 +985,80 @@ impl<'w> GraphicsState<'w> {
         self.material_cache
             .insert(material::Material { bind_group })
     }
+
+    /// Uploads a mesh to the GPU
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the mesh has more than `u32::MAX` indices
+    pub fn load_mesh(&mut self, descriptor: &mesh::Descriptor) -> mesh::Id {
+        let device = &self.wgpu_state.device;
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("mesh_vertex_buffer"),
+            contents: bytemuck::cast_slice(descriptor.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("mesh_index_buffer"),
+            contents: bytemuck::cast_slice(descriptor.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        self.mesh_cache.insert(mesh::GpuMesh {
+            vertex_buffer,
+            index_buffer,
+            index_count: u32::try_from(descriptor.indices.len())
+                .expect("The mesh should have less than u32::MAX indices"),
+        })
+    }
+
+    /// Queues the drawing of a plain quad in the UI, `x` and `y` are the
+    /// screen coordinates of the top-left corner of the quad
+    pub fn draw_ui_quad(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.ui_draw_commands.push(ui_pass::DrawCommand::DrawUiQuad(
+            ui_pass::DrawUiQuadCommand {
+                x,
+                y,
+                width,
+                height,
+                color,
+            },
+        ));
+    }
+
+    /// Queues the drawing of a nine-slice in the UI, such as a resizable
+    /// panel background. Nine-slices whose texture isn't loaded are skipped.
+    pub fn draw_ui_nine_slice(&mut self, command: ui_pass::DrawUiNineSliceCommand) {
+        self.ui_draw_commands
+            .push(ui_pass::DrawCommand::DrawUiNineSlice(command));
+    }
+
+    /// Queues the drawing of a text in the UI at the native size of the
+    /// font, `x` and `y` are the screen coordinates of the top-left corner of
+    /// the text
+    pub fn draw_ui_text(&mut self, x: f32, y: f32, text: &str, color: Color) {
+        self.draw_ui_text_with_size(x, y, text, ui_pass::GLYPH_HEIGHT, color);
+    }
+
+    /// Queues the drawing of a text in the UI whose glyphs are `size` pixels
+    /// high, see [`GraphicsState::draw_ui_text`]
+    pub fn draw_ui_text_with_size(&mut self, x: f32, y: f32, text: &str, size: f32, color: Color) {
+        self.draw_ui_text_command(ui_pass::DrawUiTextCommand {
+            x,
+            y,
+            text: text.to_string(),
+            size,
+            max_width: None,
+            color,
+        });
+    }
+
+    /// Queues the drawing of a text in the UI, e.g. a multi-line dialogue
+    /// wrapped to the width of a panel
+    pub fn draw_ui_text_command(&mut self, command: ui_pass::DrawUiTextCommand) {
+        self.ui_draw_commands
+            .push(ui_pass::DrawCommand::DrawUiText(command));
+    }
 }
 
 #[derive(Default)]
@@ -300,20 +1082,57 @@ impl PipelineCache {
     }
 }
 
+/// Rendering statistics of the current frame, reset by
+/// [`begin_frame_system`] and filled while the passes are executed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RenderStats {
+    /// The number of render passes executed, including the clear pass
+    pub render_passes: u32,
+    pub draw_calls: u32,
+    /// The number of batches drawn by the 2d and UI passes
+    pub batches: u32,
+    pub vertices: u32,
+    /// The number of times a texture or material bind group was bound
+    pub bind_group_switches: u32,
+    /// The number of bytes of vertex data written to the GPU by the 2d pass
+    pub vertex_bytes_uploaded: u64,
+}
+
+impl RenderStats {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    pub(crate) fn record_draw(&mut self, vertex_count: u32) {
+        self.draw_calls += 1;
+        self.vertices += vertex_count;
+    }
+}
+
 pub struct FrameRenderingContext {
     pub surface_texture: Option<wgpu::SurfaceTexture>,
     pub surface_texture_view: Option<wgpu::TextureView>,
     pub encoder: Option<wgpu::CommandEncoder>,
 }
 
+/// Initializes the renderer and inserts its resources
+///
+/// # Errors
+///
+/// Will return [`Err`] if the graphics state cannot be created, see
+/// [`GraphicsState::new`]
 pub async fn renderer_init<W>(
     ecs: &mut Ecs,
     window: Arc<W>,
+    window_size: WindowSize,
+    config: &RendererConfig,
     placeholder_texture: &texture::Descriptor<'_>,
-) where
+    font_texture: &texture::Descriptor<'_>,
+) -> Result<(), RendererInitError>
+where
     W: HasWindowHandle + HasDisplayHandle + std::marker::Send + std::marker::Sync,
 {
-    let mut gfx = GraphicsState::new(window).await;
+    let mut gfx = GraphicsState::new(window, window_size, config).await?;
     let placeholder_texture_id = gfx.load_texture(placeholder_texture);
     let placeholder_material_id = gfx.load_material(&material::Descriptor {
         base_color: placeholder_texture_id,
@@ -325,34 +1144,68 @@ pub async fn renderer_init<W>(
         },
     });
     gfx.placeholder_material_id = Some(placeholder_material_id);
+    gfx.font_texture_id = Some(gfx.load_texture(font_texture));
 
-    if ecs.resource::<GraphicsPipeline>().is_none() {
+    if !ecs.contains_resource::<GraphicsPipeline>() {
         let pipeline = GraphicsPipeline::builder()
-            .add_pass(ClearPass)
-            .add_pass(pass_2d::Pass::new(gfx.device()))
+            .add_pass(ClearPass::new())
+            .add_pass(pass_3d::Pass::new(gfx.device()))
+            .add_pass(
+                pass_2d::Pass::new(gfx.device())
+                    .with_bind_group_eviction_threshold(config.bind_group_eviction_threshold),
+            )
+            .add_pass(
+                ui_pass::Pass::new(gfx.device())
+                    .with_bind_group_eviction_threshold(config.bind_group_eviction_threshold),
+            )
             .build();
         ecs.insert_resource(pipeline);
     }
     ecs.insert_resource(gfx);
     ecs.insert_resource(PipelineCache::default());
+    ecs.insert_resource(RenderStats::default());
+    ecs.add_event::<sprite::AnimationFinished>();
     ecs.insert_resource(FrameRenderingContext {
         surface_texture: None,
         surface_texture_view: None,
         encoder: None,
     });
+
+    Ok(())
 }
 
-/// Start a new frame
-///
-/// # Panics
+/// Acquires the surface texture and begins a new frame.
 ///
-/// Will panic if the swapchain texture cannot be fetched
+/// If the surface is lost or outdated, it is reconfigured and the frame is
+/// skipped. If the GPU runs out of memory, the engine is asked to exit.
 pub fn begin_frame_system(
     mut graphics: ResMut<GraphicsState>,
     mut frame_ctx: ResMut<FrameRenderingContext>,
+    mut render_stats: ResMut<RenderStats>,
+    mut exit_request: ResMut<ExitRequest>,
 ) {
+    render_stats.reset();
     let graphics = graphics.borrow_mut();
-    let surface_texture = graphics.wgpu_state.surface.get_current_texture().unwrap();
+    let surface_texture = match graphics.wgpu_state.surface.get_current_texture() {
+        Ok(surface_texture) => surface_texture,
+        Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+            warn!("Surface lost or outdated, reconfiguring it");
+            graphics.wgpu_state.surface.configure(
+                &graphics.wgpu_state.device,
+                &graphics.wgpu_state.surface_configuration,
+            );
+            return;
+        }
+        Err(wgpu::SurfaceError::Timeout) => {
+            warn!("Timed out while acquiring the surface texture, skipping frame");
+            return;
+        }
+        Err(wgpu::SurfaceError::OutOfMemory) => {
+            error!("Out of memory while acquiring the surface texture");
+            exit_request.request();
+            return;
+        }
+    };
     let surface_texture_view = surface_texture
         .texture
         .create_view(&wgpu::TextureViewDescriptor::default());
@@ -373,32 +1226,55 @@ pub fn prepare_passes_system(mut graph: ResMut<GraphicsPipeline>, storage: &Stor
     graph.prepare(storage);
 }
 
-/// Renders a frame
-///
-/// # Panics
-///
-/// Panics if the surface texture cannot be obtained
+/// Renders a frame, does nothing if the frame has been skipped by
+/// [`begin_frame_system`]
 pub fn finish_frame_system(
     mut graphics: ResMut<GraphicsState>,
     mut frame_ctx: ResMut<FrameRenderingContext>,
     graph: Res<GraphicsPipeline>,
     storage: &Storage,
 ) {
-    let mut encoder = frame_ctx.encoder.take().unwrap();
-    let surface_texture_view = frame_ctx.surface_texture_view.take().unwrap();
+    let (Some(mut encoder), Some(surface_texture_view), Some(surface_texture)) = (
+        frame_ctx.encoder.take(),
+        frame_ctx.surface_texture_view.take(),
+        frame_ctx.surface_texture.take(),
+    ) else {
+        graphics.ui_draw_commands.clear();
+        graphics.unloaded_textures.clear();
+        return;
+    };
     graph.execute(&mut graphics, &mut encoder, &surface_texture_view, storage);
     graphics
         .wgpu_state
         .queue
         .submit(std::iter::once(encoder.finish()));
 
-    let surface_texture = frame_ctx.surface_texture.take().unwrap();
     surface_texture.present();
+    graphics.present_secondary_windows();
+    graphics.unloaded_textures.clear();
     std::mem::drop(graphics);
     std::mem::drop(graph);
 }
 
-pub struct ClearPass;
+/// Clears the surface, or a render target if an output is set
+#[derive(Default)]
+pub struct ClearPass {
+    output: Option<texture::Id>,
+}
+
+impl ClearPass {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_output(mut self, output: texture::Id) -> Self {
+        self.output = Some(output);
+        self
+    }
+}
+
 impl RenderPass for ClearPass {
     fn prepare(&mut self, _storage: &Storage) {}
     fn execute(
@@ -423,12 +1299,18 @@ impl RenderPass for ClearPass {
             occlusion_query_set: None,
         });
     }
+
+    fn output(&self) -> Option<texture::Id> {
+        self.output
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color {
     r: f32,
     g: f32,
     b: f32,
+    a: f32,
 }
 
 impl Color {
@@ -436,16 +1318,54 @@ impl Color {
         r: 0.0,
         g: 0.0,
         b: 0.0,
+        a: 1.0,
     };
     pub const WHITE: Color = Color {
         r: 1.0,
         g: 1.0,
         b: 1.0,
+        a: 1.0,
+    };
+    pub const TRANSPARENT: Color = Color {
+        r: 0.0,
+        g: 0.0,
+        b: 0.0,
+        a: 0.0,
     };
 
+    /// Creates an opaque color
     #[must_use]
     pub fn new(r: f32, g: f32, b: f32) -> Color {
-        Color { r, g, b }
+        Self::rgba(r, g, b, 1.0)
+    }
+
+    #[must_use]
+    pub fn rgba(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color { r, g, b, a }
+    }
+
+    /// Creates an opaque color from its `0xRRGGBB` hexadecimal notation
+    #[must_use]
+    pub fn from_hex(hex: u32) -> Color {
+        let [_, r, g, b] = hex.to_be_bytes();
+        Self::from_u8(r, g, b, u8::MAX)
+    }
+
+    #[must_use]
+    pub fn from_u8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        let to_f32 = |channel: u8| f32::from(channel) / f32::from(u8::MAX);
+        Self::rgba(to_f32(r), to_f32(g), to_f32(b), to_f32(a))
+    }
+
+    #[must_use]
+    pub fn a(&self) -> f32 {
+        self.a
+    }
+
+    /// Returns the same color with the given alpha
+    #[must_use]
+    pub fn with_alpha(self, a: f32) -> Color {
+        Color { a, ..self }
     }
 }
 
@@ -454,3 +1374,54 @@ impl From<&Color> for [f32; 3] {
         [value.r, value.g, value.b]
     }
 }
+
+impl From<&Color> for [f32; 4] {
+    fn from(value: &Color) -> Self {
+        [value.r, value.g, value.b, value.a]
+    }
+}
+
+impl From<&Color> for wgpu::Color {
+    fn from(value: &Color) -> Self {
+        wgpu::Color {
+            r: f64::from(value.r),
+            g: f64::from(value.g),
+            b: f64::from(value.b),
+            a: f64::from(value.a),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renderer_config_validation() {
+        let limits = wgpu::Limits::default();
+        let config = RendererConfig::default().with_features(wgpu::Features::POLYGON_MODE_LINE);
+        assert!(matches!(
+            config.validate(wgpu::Features::empty(), &limits),
+            Err(RendererInitError::UnsupportedFeatures(
+                wgpu::Features::POLYGON_MODE_LINE
+            ))
+        ));
+        assert!(config
+            .validate(wgpu::Features::POLYGON_MODE_LINE, &limits)
+            .is_ok());
+
+        let config = RendererConfig::default().with_max_bind_groups(limits.max_bind_groups + 4);
+        assert!(matches!(
+            config.validate(wgpu::Features::empty(), &limits),
+            Err(RendererInitError::UnsupportedLimits(unsupported_limits))
+                if unsupported_limits == ["max_bind_groups"]
+        ));
+    }
+
+    #[test]
+    fn color_from_hex() {
+        assert_eq!(Color::from_hex(0xff_3300), Color::from_u8(255, 51, 0, 255));
+        assert_eq!(Color::from_hex(0xff_ffff), Color::WHITE);
+        assert_eq!(Color::from_hex(0x00_0000), Color::BLACK);
+    }
+}
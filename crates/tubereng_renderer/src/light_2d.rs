@@ -0,0 +1,99 @@
+use tubereng_math::vector::Vector2f;
+
+use crate::Color;
+
+/// The maximum number of 2d lights affecting a frame, the lights beyond it
+/// are ignored
+pub const MAX_LIGHTS_2D: usize = 16;
+
+/// Component lighting the sprites and tiles around the origin of its
+/// transform, drawn with the active 2d camera
+#[derive(Debug, Clone, Copy)]
+pub struct Light2d {
+    pub color: Color,
+    /// The distance at which the light stops having an effect, in world
+    /// units
+    pub radius: f32,
+    pub intensity: f32,
+}
+
+/// Resource holding the light received by the 2d scene regardless of the
+/// lights, white by default so the scene is fully lit without any light
+#[derive(Debug, Clone, Copy)]
+pub struct Ambient2d {
+    pub color: Color,
+}
+
+impl Default for Ambient2d {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Light2dUniform {
+    position: [f32; 2],
+    radius: f32,
+    intensity: f32,
+    color: [f32; 4],
+}
+
+/// Returns the lights overlapping the region of the world seen by the camera,
+/// at most [`MAX_LIGHTS_2D`]
+pub(crate) fn visible_lights(
+    lights: impl Iterator<Item = (Vector2f, Light2d)>,
+    visible_bounds: (Vector2f, Vector2f),
+) -> Vec<Light2dUniform> {
+    let (visible_min, visible_max) = visible_bounds;
+    lights
+        .filter(|(position, light)| {
+            position.x + light.radius >= visible_min.x
+                && position.x - light.radius <= visible_max.x
+                && position.y + light.radius >= visible_min.y
+                && position.y - light.radius <= visible_max.y
+        })
+        .take(MAX_LIGHTS_2D)
+        .map(|(position, light)| Light2dUniform {
+            position: [position.x, position.y],
+            radius: light.radius,
+            intensity: light.intensity,
+            color: (&light.color).into(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn visible_lights_culls_and_caps_lights() {
+        let light = Light2d {
+            color: Color::WHITE,
+            radius: 10.0,
+            intensity: 1.0,
+        };
+        let visible_bounds = (Vector2f::new(0.0, 0.0), Vector2f::new(100.0, 100.0));
+
+        let lights = visible_lights(
+            [
+                (Vector2f::new(-5.0, 50.0), light),
+                (Vector2f::new(-20.0, 50.0), light),
+            ]
+            .into_iter(),
+            visible_bounds,
+        );
+        assert_eq!(lights.len(), 1);
+        assert_eq!(lights[0].position, [-5.0, 50.0]);
+
+        let lights = visible_lights(
+            std::iter::repeat_n((Vector2f::new(50.0, 50.0), light), 20),
+            visible_bounds,
+        );
+        assert_eq!(lights.len(), MAX_LIGHTS_2D);
+    }
+}
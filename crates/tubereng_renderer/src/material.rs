@@ -2,13 +2,19 @@ use std::ops::Deref;
 
 use crate::texture;
 
+/// Identifier of a material loaded in the [`Cache`], a stale id of an
+/// unloaded material never resolves to a material loaded later
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Id(usize);
+pub struct Id {
+    index: usize,
+    generation: u32,
+}
+
 impl Deref for Id {
     type Target = usize;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.index
     }
 }
 
@@ -28,24 +34,68 @@ pub struct Descriptor {
     pub region: texture::Rect,
 }
 
+struct Slot {
+    generation: u32,
+    material: Option<Material>,
+}
+
 pub struct Cache {
-    material: Vec<Material>,
+    slots: Vec<Slot>,
+    free_slots: Vec<usize>,
 }
 
 impl Cache {
     #[must_use]
     pub fn new() -> Self {
-        Self { material: vec![] }
+        Self {
+            slots: vec![],
+            free_slots: vec![],
+        }
     }
 
     pub fn insert(&mut self, material: Material) -> Id {
-        self.material.push(material);
-        Id(self.material.len() - 1)
+        if let Some(index) = self.free_slots.pop() {
+            let slot = &mut self.slots[index];
+            slot.material = Some(material);
+            return Id {
+                index,
+                generation: slot.generation,
+            };
+        }
+
+        self.slots.push(Slot {
+            generation: 0,
+            material: Some(material),
+        });
+        Id {
+            index: self.slots.len() - 1,
+            generation: 0,
+        }
+    }
+
+    /// Drops a material and frees its slot, returns false if the material
+    /// wasn't loaded
+    pub fn unload(&mut self, id: Id) -> bool {
+        let Some(slot) = self
+            .slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation && slot.material.is_some())
+        else {
+            return false;
+        };
+
+        slot.material = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(id.index);
+        true
     }
 
     #[must_use]
     pub fn get(&self, id: Id) -> Option<&Material> {
-        self.material.get(*id)
+        self.slots
+            .get(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.material.as_ref())
     }
 }
 
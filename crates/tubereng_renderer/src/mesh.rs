@@ -1,5 +1,7 @@
 use std::ops::Deref;
 
+use crate::material;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Id(usize);
 impl Deref for Id {
@@ -15,12 +17,32 @@ impl Deref for Id {
 pub struct Vertex {
     pub(crate) position: [f32; 3],
     pub(crate) texture_coordinates: [f32; 2],
+    /// The normal used to light the vertex in the 3d pass, vertices with a
+    /// zero normal are drawn unlit
+    pub(crate) normal: [f32; 3],
 }
 
 impl Vertex {
-    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
-        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2, 2 => Float32x3];
+
+    /// Creates a vertex with a zero normal, see [`Vertex::with_normal`]
+    #[must_use]
+    pub fn new(position: [f32; 3], texture_coordinates: [f32; 2]) -> Self {
+        Self {
+            position,
+            texture_coordinates,
+            normal: [0.0; 3],
+        }
+    }
+
+    #[must_use]
+    pub fn with_normal(mut self, normal: [f32; 3]) -> Self {
+        self.normal = normal;
+        self
+    }
 
+    #[must_use]
     pub fn layout<'a>() -> wgpu::VertexBufferLayout<'a> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
@@ -29,3 +51,50 @@ impl Vertex {
         }
     }
 }
+
+/// Component drawing a mesh loaded in the [`GraphicsState`](crate::GraphicsState)
+/// with the active 3d camera
+#[derive(Debug)]
+pub struct Mesh {
+    pub mesh: Id,
+    /// The material of the mesh, the placeholder material is used if none is
+    /// set
+    pub material: Option<material::Id>,
+}
+
+pub struct Descriptor<'a> {
+    pub vertices: &'a [Vertex],
+    pub indices: &'a [u32],
+}
+
+pub(crate) struct GpuMesh {
+    pub(crate) vertex_buffer: wgpu::Buffer,
+    pub(crate) index_buffer: wgpu::Buffer,
+    pub(crate) index_count: u32,
+}
+
+pub struct Cache {
+    meshes: Vec<GpuMesh>,
+}
+
+impl Cache {
+    #[must_use]
+    pub fn new() -> Self {
+        Self { meshes: vec![] }
+    }
+
+    pub(crate) fn insert(&mut self, mesh: GpuMesh) -> Id {
+        self.meshes.push(mesh);
+        Id(self.meshes.len() - 1)
+    }
+
+    pub(crate) fn get(&self, id: Id) -> &GpuMesh {
+        &self.meshes[*id]
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
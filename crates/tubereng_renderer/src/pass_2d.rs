@@ -1,52 +1,415 @@
-use std::collections::HashMap;
-
-use tubereng_core::TransformCache;
-use tubereng_ecs::Storage;
-use tubereng_math::{matrix::Matrix4f, vector::Vector3f};
+use std::ops::Range;
+
+use bytemuck::Zeroable;
+use tubereng_core::{Time, Transform, TransformCache};
+use tubereng_ecs::{relationship::ChildOf, EntityId, Storage};
+use tubereng_math::{
+    matrix::Matrix4f,
+    vector::{Vector2f, Vector3f},
+};
 use wgpu::include_wgsl;
 
 use crate::{
+    bind_group_cache::TextureBindGroupCache,
     camera,
     graphics_pipeline::RenderPass,
+    light_2d::{self, Ambient2d, Light2d, Light2dUniform, MAX_LIGHTS_2D},
     mesh::Vertex,
-    sprite::{AnimatedSprite, Sprite},
-    texture, GraphicsState, PipelineCache,
+    post_process::HDR_TEXTURE_FORMAT,
+    sprite::{self, AnimatedSprite, BlendMode, Sprite},
+    texture,
+    tilemap::{self, Tilemap},
+    GraphicsState, PipelineCache, RenderStats,
 };
 
+/// What a quad is drawn for, identifying the quads of a batch from one frame
+/// to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum QuadSource {
+    Sprite(EntityId),
+    AnimatedSprite(EntityId),
+    Tile {
+        tilemap: EntityId,
+        column: usize,
+        row: usize,
+    },
+}
+
 struct Quad2d {
     pub(crate) transform: Matrix4f,
     texture_id: texture::Id,
     texture_rect: texture::Rect,
-}
-struct PendingBatch {
-    pub(crate) vertices: Vec<Vertex>,
-    pub(crate) texture_id: texture::Id,
+    /// Whether the texture region is rotated by 90° clockwise, see
+    /// [`Sprite::texture_rect_rotated`]
+    texture_rect_rotated: bool,
+    blend_mode: BlendMode,
+    source: QuadSource,
+    /// Whether the components the quad is built from changed this frame
+    changed: bool,
 }
 
-impl PendingBatch {
-    pub fn new(texture_id: texture::Id) -> Self {
-        Self {
-            vertices: vec![],
-            texture_id,
+impl Quad2d {
+    fn z(&self) -> f32 {
+        self.transform[2][3]
+    }
+
+    /// The size of the quad in local space
+    fn size(&self) -> (f32, f32) {
+        if self.texture_rect_rotated {
+            (self.texture_rect.height, self.texture_rect.width)
+        } else {
+            (self.texture_rect.width, self.texture_rect.height)
+        }
+    }
+
+    /// The texture coordinates of the top-left, bottom-left, bottom-right and
+    /// top-right corners of the quad
+    fn corner_texture_coordinates(&self, texture_w: f32, texture_h: f32) -> [[f32; 2]; 4] {
+        let rect = &self.texture_rect;
+        let left = rect.x / texture_w;
+        let right = (rect.x + rect.width) / texture_w;
+        let top = rect.y / texture_h;
+        let bottom = (rect.y + rect.height) / texture_h;
+        if self.texture_rect_rotated {
+            // The top edge of the sprite is the right edge of the region
+            [[right, top], [left, top], [left, bottom], [right, bottom]]
+        } else {
+            [[left, top], [left, bottom], [right, bottom], [right, top]]
         }
     }
+
+    /// Returns the vertices of the two triangles of the quad in world space
+    fn vertices(&self, texture_w: f32, texture_h: f32) -> [Vertex; 6] {
+        let (quad_w, quad_h) = self.size();
+        let [top_left_uv, bottom_left_uv, bottom_right_uv, top_right_uv] =
+            self.corner_texture_coordinates(texture_w, texture_h);
+        let corner = |x, y| {
+            self.transform
+                .transform_vec3(&Vector3f::new(x, y, 0.0))
+                .into()
+        };
+        let top_left = corner(0.0, 0.0);
+        let bottom_left = corner(0.0, quad_h);
+        let bottom_right = corner(quad_w, quad_h);
+        let top_right = corner(quad_w, 0.0);
+        [
+            Vertex::new(top_left, top_left_uv),
+            Vertex::new(bottom_left, bottom_left_uv),
+            Vertex::new(bottom_right, bottom_right_uv),
+            Vertex::new(bottom_right, bottom_right_uv),
+            Vertex::new(top_right, top_right_uv),
+            Vertex::new(top_left, top_left_uv),
+        ]
+    }
+}
+
+/// Returns true if the transform of an entity or of one of its parents
+/// changed this frame
+fn transform_changed(storage: &Storage, entity_id: EntityId) -> bool {
+    storage.dirty_state::<Transform>(entity_id)
+        || storage
+            .relationship::<ChildOf>()
+            .is_some_and(|child_of_relationship| {
+                child_of_relationship
+                    .successors(entity_id)
+                    .into_iter()
+                    .any(|parent| storage.dirty_state::<Transform>(parent))
+            })
+}
+
+/// Margin around the region seen by the camera in which quads are still
+/// drawn, in world units, so quads don't pop in at the edges of the screen
+const CULLING_MARGIN: f32 = 32.0;
+
+/// Drops the quads whose bounds don't overlap the region of the world seen
+/// by the camera, expanded by [`CULLING_MARGIN`]
+fn cull_quads(quads: &mut Vec<Quad2d>, visible_bounds: (Vector2f, Vector2f)) {
+    let (visible_min, visible_max) = visible_bounds;
+    quads.retain(|quad| {
+        let (min, max) = camera::transformed_rect_bounds(quad.size(), &quad.transform);
+        min.x <= visible_max.x + CULLING_MARGIN
+            && max.x >= visible_min.x - CULLING_MARGIN
+            && min.y <= visible_max.y + CULLING_MARGIN
+            && max.y >= visible_min.y - CULLING_MARGIN
+    });
+}
+
+/// Sorts the quads back to front by their world z coordinate, then by blend
+/// mode and texture within a same z so each texture of a layer is drawn in a
+/// single batch per blend mode.
+///
+/// The sort is stable so quads sharing z, blend mode and texture keep their
+/// order.
+fn sort_quads_for_batching(quads: &mut [Quad2d]) {
+    quads.sort_by(|a, b| {
+        a.z()
+            .total_cmp(&b.z())
+            .then_with(|| a.blend_mode.cmp(&b.blend_mode))
+            .then_with(|| a.texture_id.cmp(&b.texture_id))
+    });
 }
 
 struct BatchMetadata {
     start_vertex_index: u32,
     end_vertex_index: u32,
     texture_id: texture::Id,
+    blend_mode: BlendMode,
+    /// The quads of the batch, in drawing order
+    sources: Vec<QuadSource>,
+}
+
+impl BatchMetadata {
+    fn vertex_range(&self) -> Range<usize> {
+        self.start_vertex_index as usize..self.end_vertex_index as usize
+    }
+}
+
+/// The batches of the pass and their vertices, kept from one frame to the
+/// next so only the batches whose quads changed are rebuilt and uploaded
+#[derive(Default)]
+struct Batches {
+    metadata: Vec<BatchMetadata>,
+    vertices: Vec<Vertex>,
+    /// The frame the batches were built for. The dirty flags of the
+    /// components only cover a single frame, so the batches are rebuilt
+    /// after a frame without rendering.
+    frame: Option<u64>,
+}
+
+impl Batches {
+    fn clear(&mut self) {
+        self.metadata.clear();
+        self.vertices.clear();
+        self.frame = None;
+    }
+
+    /// Groups the sorted quads in batches and returns the vertex ranges to
+    /// upload, adding their size to the [`RenderStats`].
+    ///
+    /// A batch drawing the same quads as a batch of the previous frame keeps
+    /// its vertices if none of its quads changed, and isn't uploaded again
+    /// if it keeps its place in the vertex buffer as well.
+    fn rebuild<F>(
+        &mut self,
+        storage: &Storage,
+        quads: &[Quad2d],
+        texture_size: F,
+    ) -> Vec<Range<usize>>
+    where
+        F: Fn(texture::Id) -> (u32, u32),
+    {
+        let frame = storage.resource::<Time>().map(|time| time.frame_count());
+        let previous_frame = std::mem::replace(&mut self.frame, frame);
+        let previous_vertices = std::mem::take(&mut self.vertices);
+        let mut previous_metadata = std::mem::take(&mut self.metadata);
+        let consecutive_frames = matches!(
+            (previous_frame, frame),
+            (Some(previous_frame), Some(frame)) if previous_frame + 1 == frame
+        );
+        if !consecutive_frames {
+            // The quads changed during the frames without rendering are
+            // unknown
+            previous_metadata.clear();
+        }
+
+        let mut upload_ranges = vec![];
+        for batch_quads in
+            quads.chunk_by(|a, b| a.texture_id == b.texture_id && a.blend_mode == b.blend_mode)
+        {
+            let texture_id = batch_quads[0].texture_id;
+            let blend_mode = batch_quads[0].blend_mode;
+            let start_vertex_index = self.vertices.len();
+            let changed = batch_quads.iter().any(|quad| quad.changed);
+            let unchanged_batch = previous_metadata.iter().find(|previous_batch| {
+                !changed
+                    && previous_batch.texture_id == texture_id
+                    && previous_batch.blend_mode == blend_mode
+                    && previous_batch
+                        .sources
+                        .iter()
+                        .eq(batch_quads.iter().map(|quad| &quad.source))
+            });
+            if let Some(previous_batch) = unchanged_batch {
+                self.vertices
+                    .extend_from_slice(&previous_vertices[previous_batch.vertex_range()]);
+                if previous_batch.vertex_range().start != start_vertex_index {
+                    upload_ranges.push(start_vertex_index..self.vertices.len());
+                }
+            } else {
+                #[allow(clippy::cast_precision_loss)]
+                let (texture_w, texture_h) = {
+                    let (width, height) = texture_size(texture_id);
+                    (width as f32, height as f32)
+                };
+                for quad in batch_quads {
+                    self.vertices
+                        .extend_from_slice(&quad.vertices(texture_w, texture_h));
+                }
+                upload_ranges.push(start_vertex_index..self.vertices.len());
+            }
+            self.metadata.push(BatchMetadata {
+                start_vertex_index: u32::try_from(start_vertex_index).unwrap(),
+                end_vertex_index: u32::try_from(self.vertices.len()).unwrap(),
+                texture_id,
+                blend_mode,
+                sources: batch_quads.iter().map(|quad| quad.source).collect(),
+            });
+        }
+
+        if let Some(mut render_stats) = storage.resource_mut::<RenderStats>() {
+            render_stats.vertex_bytes_uploaded += upload_ranges
+                .iter()
+                .map(|range| (range.len() * std::mem::size_of::<Vertex>()) as u64)
+                .sum::<u64>();
+        }
+        upload_ranges
+    }
+}
+
+/// Queues the quads of the sprites and animated sprites whose texture is
+/// loaded, `texture_size` returning the size of a loaded texture
+fn queue_sprite_quads<F>(
+    quads: &mut Vec<Quad2d>,
+    storage: &Storage,
+    transform_cache: &TransformCache,
+    texture_size: &F,
+) where
+    F: Fn(texture::Id) -> Option<(u32, u32)>,
+{
+    for (id, sprite) in storage.query::<&Sprite>().iter_with_ids() {
+        let Some((texture_width, texture_height)) = texture_size(sprite.texture) else {
+            continue;
+        };
+        #[allow(clippy::cast_precision_loss)]
+        let texture_rect = sprite.texture_rect.clone().unwrap_or(texture::Rect {
+            x: 0.0,
+            y: 0.0,
+            width: texture_width as f32,
+            height: texture_height as f32,
+        });
+        let mut quad = Quad2d {
+            transform: transform_cache.get(id),
+            texture_id: sprite.texture,
+            texture_rect,
+            texture_rect_rotated: sprite.texture_rect_rotated,
+            blend_mode: sprite.blend_mode,
+            source: QuadSource::Sprite(id),
+            changed: storage.dirty_state::<Sprite>(id) || transform_changed(storage, id),
+        };
+        let (width, height) = quad.size();
+        quad.transform *= sprite::pivot_transform(sprite.pivot, width, height);
+        quads.push(quad);
+    }
+
+    for (id, animated_sprite) in storage.query::<&AnimatedSprite>().iter_with_ids() {
+        if texture_size(animated_sprite.texture_atlas).is_none() {
+            continue;
+        }
+        let animation = &animated_sprite.animation;
+        let rect =
+            animation.animations[animation.current_animation][animation.current_frame].clone();
+        quads.push(Quad2d {
+            transform: transform_cache.get(id)
+                * sprite::pivot_transform(animated_sprite.pivot, rect.width, rect.height),
+            texture_id: animated_sprite.texture_atlas,
+            texture_rect: rect,
+            texture_rect_rotated: false,
+            blend_mode: animated_sprite.blend_mode,
+            source: QuadSource::AnimatedSprite(id),
+            changed: storage.dirty_state::<AnimatedSprite>(id) || transform_changed(storage, id),
+        });
+    }
+}
+
+/// Queues the tiles of the tilemaps seen by the camera, whose viewport
+/// starts at its transform
+fn queue_visible_tile_quads<F>(
+    quads: &mut Vec<Quad2d>,
+    storage: &Storage,
+    transform_cache: &TransformCache,
+    texture_size: &F,
+    camera_viewport_size: (f32, f32),
+    camera_transform: &Matrix4f,
+) where
+    F: Fn(texture::Id) -> Option<(u32, u32)>,
+{
+    for (id, tilemap) in storage.query::<&Tilemap>().iter_with_ids() {
+        let Some((atlas_width, _)) = texture_size(tilemap.atlas) else {
+            continue;
+        };
+        let tilemap_transform = transform_cache.get(id);
+        let Some((min, max)) = tilemap::bounds_in_local_space(
+            camera_viewport_size,
+            camera_transform,
+            &tilemap_transform,
+        ) else {
+            continue;
+        };
+
+        let changed = storage.dirty_state::<Tilemap>(id) || transform_changed(storage, id);
+        let (columns, rows) = tilemap.tiles_in_rect(min, max);
+        for row in rows {
+            for column in columns.clone() {
+                let Some(tile) = tilemap.tile(column, row) else {
+                    continue;
+                };
+
+                #[allow(clippy::cast_precision_loss)]
+                let tile_translation = Vector3f::new(
+                    column as f32 * tilemap.tile_width,
+                    row as f32 * tilemap.tile_height,
+                    0.0,
+                );
+                #[allow(clippy::cast_precision_loss)]
+                quads.push(Quad2d {
+                    transform: tilemap_transform * Matrix4f::new_translation(&tile_translation),
+                    texture_id: tilemap.atlas,
+                    texture_rect: tilemap.atlas_rect(tile, atlas_width as f32),
+                    texture_rect_rotated: false,
+                    blend_mode: BlendMode::Alpha,
+                    source: QuadSource::Tile {
+                        tilemap: id,
+                        column,
+                        row,
+                    },
+                    changed,
+                });
+            }
+        }
+    }
+}
+
+/// Returns the name of the pipeline cached for a blend mode
+fn pipeline_name(
+    blend_mode: BlendMode,
+    target_texture_format: wgpu::TextureFormat,
+) -> &'static str {
+    let hdr = target_texture_format == HDR_TEXTURE_FORMAT;
+    match (blend_mode, hdr) {
+        (BlendMode::Opaque, false) => "pass_2d_pipeline_opaque",
+        (BlendMode::Alpha, false) => "pass_2d_pipeline",
+        (BlendMode::Additive, false) => "pass_2d_pipeline_additive",
+        (BlendMode::Multiply, false) => "pass_2d_pipeline_multiply",
+        (BlendMode::Opaque, true) => "pass_2d_pipeline_opaque_hdr",
+        (BlendMode::Alpha, true) => "pass_2d_pipeline_hdr",
+        (BlendMode::Additive, true) => "pass_2d_pipeline_additive_hdr",
+        (BlendMode::Multiply, true) => "pass_2d_pipeline_multiply_hdr",
+    }
 }
 
 #[repr(C)]
 #[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
 pub struct PassUniform {
     view_proj: [[f32; 4]; 4],
+    ambient: [f32; 4],
+    light_count: u32,
+    _padding: [u32; 3],
+    lights: [Light2dUniform; MAX_LIGHTS_2D],
 }
 
 pub struct Pass {
-    pending_batches: Vec<PendingBatch>,
-    batches_metadata: Vec<BatchMetadata>,
+    pending_quads: Vec<Quad2d>,
+    batches: Batches,
     #[allow(clippy::struct_field_names)]
     pass_uniform_buffer: wgpu::Buffer,
     #[allow(clippy::struct_field_names)]
@@ -54,8 +417,9 @@ pub struct Pass {
     #[allow(clippy::struct_field_names)]
     pass_uniform_bind_group: wgpu::BindGroup,
     texture_bind_group_layout: wgpu::BindGroupLayout,
-    texture_bind_groups: HashMap<texture::Id, wgpu::BindGroup>,
+    texture_bind_groups: TextureBindGroupCache,
     vertex_buffer: wgpu::Buffer,
+    output: Option<texture::Id>,
 }
 
 impl Pass {
@@ -104,7 +468,7 @@ impl Pass {
                 label: Some("pass_uniform_bind_group_layout"),
                 entries: &[wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Buffer {
                         ty: wgpu::BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -124,97 +488,77 @@ impl Pass {
         });
 
         Self {
-            pending_batches: vec![],
-            batches_metadata: vec![],
+            pending_quads: vec![],
+            batches: Batches::default(),
             texture_bind_group_layout,
-            texture_bind_groups: HashMap::new(),
+            texture_bind_groups: TextureBindGroupCache::new(),
             vertex_buffer,
+            output: None,
             pass_uniform_buffer,
             pass_uniform_bind_group,
             pass_uniform_bind_group_layout,
         }
     }
 
-    #[allow(clippy::cast_precision_loss)]
-    fn queue_quad_2d(&mut self, quad: &Quad2d, texture_info: &texture::Info) {
-        let local_to_world_matrix = quad.transform;
-
-        let texture_w = texture_info.width as f32;
-        let texture_h = texture_info.height as f32;
-        let quad_texture_u = quad.texture_rect.x;
-        let quad_texture_v = quad.texture_rect.y;
-        let quad_texture_w = quad.texture_rect.width;
-        let quad_texture_h = quad.texture_rect.height;
-
-        let top_left = local_to_world_matrix
-            .transform_vec3(&Vector3f::new(0.0, 0.0, 0.0))
-            .into();
-        let bottom_left = local_to_world_matrix
-            .transform_vec3(&Vector3f::new(0.0, quad_texture_h, 0.0))
-            .into();
-        let bottom_right = local_to_world_matrix
-            .transform_vec3(&Vector3f::new(quad_texture_w, quad_texture_h, 0.0))
-            .into();
-        let top_right = local_to_world_matrix
-            .transform_vec3(&Vector3f::new(quad_texture_w, 0.0, 0.0))
-            .into();
-        let texture_id = quad.texture_id;
-
-        let batch = match self.pending_batches.last_mut() {
-            Some(batch) if batch.texture_id == texture_id => batch,
-            _ => {
-                self.pending_batches.push(PendingBatch::new(texture_id));
-                // SAFETY: We just added a batch to the pending batch list
-                unsafe { self.pending_batches.last_mut().unwrap_unchecked() }
-            }
-        };
+    /// Sets the number of frames the bind group of a texture is kept after
+    /// the last frame it was used in, 300 frames by default
+    #[must_use]
+    pub fn with_bind_group_eviction_threshold(mut self, frames: u32) -> Self {
+        self.texture_bind_groups.set_eviction_threshold(frames);
+        self
+    }
 
-        #[allow(clippy::cast_precision_loss)]
-        batch.vertices.extend_from_slice(&[
-            Vertex {
-                position: top_left,
-                texture_coordinates: [quad_texture_u / texture_w, quad_texture_v / texture_h],
-            },
-            Vertex {
-                position: bottom_left,
-                texture_coordinates: [
-                    quad_texture_u / texture_w,
-                    (quad_texture_v + quad_texture_h) / texture_h,
-                ],
-            },
-            Vertex {
-                position: bottom_right,
-                texture_coordinates: [
-                    (quad_texture_u + quad_texture_w) / texture_w,
-                    (quad_texture_v + quad_texture_h) / texture_h,
-                ],
-            },
-            Vertex {
-                position: bottom_right,
-                texture_coordinates: [
-                    (quad_texture_u + quad_texture_w) / texture_w,
-                    (quad_texture_v + quad_texture_h) / texture_h,
-                ],
-            },
-            Vertex {
-                position: top_right,
-                texture_coordinates: [
-                    (quad_texture_u + quad_texture_w) / texture_w,
-                    quad_texture_v / texture_h,
-                ],
-            },
-            Vertex {
-                position: top_left,
-                texture_coordinates: [quad_texture_u / texture_w, quad_texture_v / texture_h],
-            },
-        ]);
+    /// Renders the sprites to a render target instead of the surface
+    #[must_use]
+    pub fn with_output(mut self, output: texture::Id) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    /// Writes the camera matrix and the lights seen by the camera to the pass
+    /// uniform buffer
+    fn write_pass_uniform(
+        &self,
+        storage: &Storage,
+        gfx: &GraphicsState,
+        transform_cache: &TransformCache,
+        view_proj: Matrix4f,
+        visible_bounds: (Vector2f, Vector2f),
+    ) {
+        let ambient = storage
+            .resource::<Ambient2d>()
+            .map(|ambient| *ambient)
+            .unwrap_or_default();
+        let mut light_query = storage.query::<&Light2d>();
+        let visible_lights = light_2d::visible_lights(
+            light_query.iter_with_ids().map(|(id, light)| {
+                let transform = transform_cache.get(id);
+                (Vector2f::new(transform[0][3], transform[1][3]), *light)
+            }),
+            visible_bounds,
+        );
+        let mut lights = [Light2dUniform::zeroed(); MAX_LIGHTS_2D];
+        lights[..visible_lights.len()].copy_from_slice(&visible_lights);
+
+        gfx.queue().write_buffer(
+            &self.pass_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[PassUniform {
+                view_proj: view_proj.into(),
+                ambient: (&ambient.color).into(),
+                light_count: u32::try_from(visible_lights.len()).unwrap(),
+                _padding: [0; 3],
+                lights,
+            }]),
+        );
     }
 
     #[must_use]
     pub fn create_pass_2d_pipeline(
         device: &wgpu::Device,
         bind_group_layouts: &[&wgpu::BindGroupLayout],
-        surface_texture_format: wgpu::TextureFormat,
+        target_texture_format: wgpu::TextureFormat,
+        blend_mode: BlendMode,
     ) -> wgpu::RenderPipeline {
         let shader_module = device.create_shader_module(include_wgsl!("./pass_2d.wgsl"));
 
@@ -250,17 +594,10 @@ impl Pass {
             },
             fragment: Some(wgpu::FragmentState {
                 module: &shader_module,
-                entry_point: "fs_main",
+                entry_point: blend_mode.fragment_entry_point(),
                 targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_texture_format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent {
-                            src_factor: wgpu::BlendFactor::SrcAlpha,
-                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
-                            operation: wgpu::BlendOperation::Add,
-                        },
-                        alpha: wgpu::BlendComponent::default(),
-                    }),
+                    format: target_texture_format,
+                    blend: blend_mode.blend_state(),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
             }),
@@ -271,11 +608,10 @@ impl Pass {
     fn create_texture_bind_group_for_texture_if_required(
         &mut self,
         texture: texture::Id,
-        gfx: &std::cell::Ref<'_, GraphicsState<'_>>,
+        gfx: &GraphicsState<'_>,
     ) {
-        if let std::collections::hash_map::Entry::Vacant(e) =
-            self.texture_bind_groups.entry(texture)
-        {
+        if !self.texture_bind_groups.mark_used(texture) {
+            let texture_id = texture;
             let texture = gfx.texture_cache.get(texture);
             let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
             let texture_sampler = gfx.device().create_sampler(&wgpu::SamplerDescriptor {
@@ -304,7 +640,8 @@ impl Pass {
                 ],
             });
 
-            e.insert(texture_bind_group);
+            self.texture_bind_groups
+                .insert(texture_id, texture_bind_group);
         }
     }
 }
@@ -315,81 +652,70 @@ impl RenderPass for Pass {
             .resource::<GraphicsState>()
             .expect("Graphics state should be present");
 
-        let (camera_id, (camera, _)) = storage
-            .query::<(&camera::D2, &camera::Active)>()
-            .iter_with_ids()
-            .next()
-            .expect("An active 2d camera should be present in the scene");
+        let Some((camera_id, camera)) = camera::active(storage) else {
+            // Nothing is drawn without a camera
+            self.batches.clear();
+            return;
+        };
 
         let transform_cache = storage
             .resource::<TransformCache>()
             .expect("TransformCache resource should be present");
         let camera_transform = transform_cache.get(camera_id);
         let inverse_transform = camera_transform.try_inverse().unwrap();
-        gfx.queue().write_buffer(
-            &self.pass_uniform_buffer,
-            0,
-            bytemuck::cast_slice(&[PassUniform {
-                view_proj: (*camera.projection() * inverse_transform).into(),
-            }]),
-        );
+        let mut view_proj = camera.projection(*gfx.window_size()) * inverse_transform;
+        if camera.pixel_snapping() {
+            view_proj = camera::snap_to_pixels(view_proj, *gfx.window_size());
+        }
+        let camera_viewport_size = camera.viewport_size(*gfx.window_size());
+        let visible_bounds =
+            camera::transformed_rect_bounds(camera_viewport_size, &camera_transform);
+        self.write_pass_uniform(storage, &gfx, &transform_cache, view_proj, visible_bounds);
 
-        for (id, sprite) in storage.query::<&Sprite>().iter_with_ids() {
-            self.create_texture_bind_group_for_texture_if_required(sprite.texture, &gfx);
-            let texture_info = gfx.texture_cache.info(sprite.texture);
-            #[allow(clippy::cast_precision_loss)]
-            self.queue_quad_2d(
-                &Quad2d {
-                    transform: transform_cache.get(id),
-                    texture_id: sprite.texture,
-                    texture_rect: sprite.texture_rect.clone().unwrap_or(texture::Rect {
-                        x: 0.0,
-                        y: 0.0,
-                        width: texture_info.width as f32,
-                        height: texture_info.height as f32,
-                    }),
-                },
-                texture_info,
-            );
+        for &texture in gfx.unloaded_textures() {
+            self.texture_bind_groups.remove(texture);
         }
 
-        for (id, animated_sprite) in storage.query::<&AnimatedSprite>().iter_with_ids() {
-            self.create_texture_bind_group_for_texture_if_required(
-                animated_sprite.texture_atlas,
-                &gfx,
-            );
-            let texture_info = gfx.texture_cache.info(animated_sprite.texture_atlas);
-            let animation = &animated_sprite.animation;
-            let rect =
-                animation.animations[animation.current_animation][animation.current_frame].clone();
-            #[allow(clippy::cast_precision_loss)]
-            self.queue_quad_2d(
-                &Quad2d {
-                    transform: transform_cache.get(id),
-                    texture_id: animated_sprite.texture_atlas,
-                    texture_rect: rect,
-                },
-                texture_info,
-            );
+        let texture_size = |texture_id| {
+            gfx.texture_cache
+                .try_info(texture_id)
+                .map(|texture_info| (texture_info.width, texture_info.height))
+        };
+        let mut quads = std::mem::take(&mut self.pending_quads);
+        queue_sprite_quads(&mut quads, storage, &transform_cache, &texture_size);
+        queue_visible_tile_quads(
+            &mut quads,
+            storage,
+            &transform_cache,
+            &texture_size,
+            camera_viewport_size,
+            &camera_transform,
+        );
+        cull_quads(&mut quads, visible_bounds);
+        sort_quads_for_batching(&mut quads);
+        let upload_ranges = self.batches.rebuild(storage, &quads, |texture_id| {
+            texture_size(texture_id).expect("The texture of a queued quad should be loaded")
+        });
+        quads.clear();
+        self.pending_quads = quads;
+
+        let batch_textures = self
+            .batches
+            .metadata
+            .iter()
+            .map(|batch| batch.texture_id)
+            .collect::<Vec<_>>();
+        for texture in batch_textures {
+            self.create_texture_bind_group_for_texture_if_required(texture, &gfx);
         }
+        self.texture_bind_groups.evict_unused();
 
-        let mut vertex_count = 0u32;
-        self.batches_metadata.clear();
-        for batch in self.pending_batches.drain(..) {
-            let start_vertex_index = vertex_count;
-            gfx.wgpu_state.queue.write_buffer(
+        for range in upload_ranges {
+            gfx.queue().write_buffer(
                 &self.vertex_buffer,
-                (vertex_count as usize * std::mem::size_of::<Vertex>()) as wgpu::BufferAddress,
-                bytemuck::cast_slice(&batch.vertices),
+                (range.start * std::mem::size_of::<Vertex>()) as wgpu::BufferAddress,
+                bytemuck::cast_slice(&self.batches.vertices[range]),
             );
-            vertex_count += u32::try_from(batch.vertices.len()).unwrap();
-
-            let end_vertex_index = vertex_count;
-            self.batches_metadata.push(BatchMetadata {
-                start_vertex_index,
-                end_vertex_index,
-                texture_id: batch.texture_id,
-            });
         }
     }
 
@@ -400,19 +726,24 @@ impl RenderPass for Pass {
         surface_texture_view: &wgpu::TextureView,
         storage: &Storage,
     ) {
+        let target_texture_format = gfx.target_texture_format(self.output);
         let mut pipeline_cache = storage.resource_mut::<PipelineCache>().unwrap();
-        if !pipeline_cache.has("pass_2d_pipeline") {
-            pipeline_cache.insert(
-                "pass_2d_pipeline",
-                Self::create_pass_2d_pipeline(
-                    gfx.device(),
-                    &[
-                        &self.pass_uniform_bind_group_layout,
-                        &self.texture_bind_group_layout,
-                    ],
-                    gfx.surface_texture_format(),
-                ),
-            );
+        for batch in &self.batches.metadata {
+            let name = pipeline_name(batch.blend_mode, target_texture_format);
+            if !pipeline_cache.has(name) {
+                pipeline_cache.insert(
+                    name,
+                    Self::create_pass_2d_pipeline(
+                        gfx.device(),
+                        &[
+                            &self.pass_uniform_bind_group_layout,
+                            &self.texture_bind_group_layout,
+                        ],
+                        target_texture_format,
+                        batch.blend_mode,
+                    ),
+                );
+            }
         }
         let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("pass_2d"),
@@ -429,13 +760,245 @@ impl RenderPass for Pass {
             occlusion_query_set: None,
         });
 
-        rpass.set_pipeline(pipeline_cache.get("pass_2d_pipeline").unwrap());
         rpass.set_bind_group(0, &self.pass_uniform_bind_group, &[]);
-        for batch in &self.batches_metadata {
+        let mut render_stats = storage.resource_mut::<RenderStats>();
+        let mut current_blend_mode = None;
+        let mut bound_texture = None;
+        for batch in &self.batches.metadata {
+            if current_blend_mode != Some(batch.blend_mode) {
+                rpass.set_pipeline(
+                    pipeline_cache
+                        .get(pipeline_name(batch.blend_mode, target_texture_format))
+                        .unwrap(),
+                );
+                current_blend_mode = Some(batch.blend_mode);
+            }
             rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            let texture_bind_group = &self.texture_bind_groups[&batch.texture_id];
-            rpass.set_bind_group(1, texture_bind_group, &[]);
+            if bound_texture != Some(batch.texture_id) {
+                let texture_bind_group = self.texture_bind_groups.get(batch.texture_id);
+                rpass.set_bind_group(1, texture_bind_group, &[]);
+                bound_texture = Some(batch.texture_id);
+                if let Some(render_stats) = render_stats.as_mut() {
+                    render_stats.bind_group_switches += 1;
+                }
+            }
             rpass.draw(batch.start_vertex_index..batch.end_vertex_index, 0..1);
+            if let Some(render_stats) = render_stats.as_mut() {
+                render_stats.batches += 1;
+                render_stats.record_draw(batch.end_vertex_index - batch.start_vertex_index);
+            }
         }
     }
+
+    fn output(&self) -> Option<texture::Id> {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(texture_id: usize, z: f32) -> Quad2d {
+        Quad2d {
+            transform: Matrix4f::new_translation(&Vector3f::new(0.0, 0.0, z)),
+            texture_id: texture::Id::new(texture_id, 0),
+            texture_rect: texture::Rect {
+                x: 0.0,
+                y: 0.0,
+                width: 1.0,
+                height: 1.0,
+            },
+            texture_rect_rotated: false,
+            blend_mode: BlendMode::Alpha,
+            source: QuadSource::Sprite(0),
+            changed: true,
+        }
+    }
+
+    /// Builds the batches of the sprites of a frame and returns the number of
+    /// vertex bytes uploaded, as reported by the render stats
+    fn render_frame(storage: &mut Storage, batches: &mut Batches) -> u64 {
+        storage.resource_mut::<Time>().unwrap().advance(0.016);
+        storage.resource_mut::<RenderStats>().unwrap().reset();
+        let mut transform_cache = TransformCache::new();
+        for (id, transform) in storage.query::<&Transform>().iter_with_ids() {
+            transform_cache.set(id, transform.as_matrix4());
+        }
+        let mut quads = vec![];
+        queue_sprite_quads(&mut quads, storage, &transform_cache, &|_| Some((16, 16)));
+        sort_quads_for_batching(&mut quads);
+        batches.rebuild(storage, &quads, |_| (16, 16));
+        storage.clear_dirty_flags();
+        storage
+            .resource::<RenderStats>()
+            .unwrap()
+            .vertex_bytes_uploaded
+    }
+
+    fn sprite_scene() -> Storage {
+        let mut storage = Storage::new();
+        storage.insert_resource(Time::new());
+        storage.insert_resource(RenderStats::default());
+        for i in 0..4 {
+            #[allow(clippy::cast_precision_loss)]
+            storage.insert((
+                Sprite::new(texture::Id::new(i % 2, 0)),
+                Transform {
+                    translation: Vector3f::new(i as f32 * 16.0, 0.0, 0.0),
+                    ..Default::default()
+                },
+            ));
+        }
+        storage
+    }
+
+    const QUAD_BYTES: u64 = 6 * std::mem::size_of::<Vertex>() as u64;
+
+    #[test]
+    fn static_scene_uploads_no_vertices_on_the_second_frame() {
+        let mut storage = sprite_scene();
+        let mut batches = Batches::default();
+        assert_eq!(render_frame(&mut storage, &mut batches), 4 * QUAD_BYTES);
+        assert_eq!(render_frame(&mut storage, &mut batches), 0);
+    }
+
+    #[test]
+    fn only_the_batches_of_changed_sprites_are_rebuilt() {
+        let mut storage = sprite_scene();
+        let mut batches = Batches::default();
+        render_frame(&mut storage, &mut batches);
+        let first_batch_vertices = batches.vertices[batches.metadata[0].vertex_range()].to_vec();
+
+        storage
+            .component_mut::<Transform>(1)
+            .unwrap()
+            .translate(Vector3f::new(0.0, 8.0, 0.0));
+        // Entity 1 is drawn in the second batch, with the texture 1
+        assert_eq!(render_frame(&mut storage, &mut batches), 2 * QUAD_BYTES);
+        assert_eq!(
+            bytemuck::cast_slice::<_, u8>(&batches.vertices[batches.metadata[0].vertex_range()]),
+            bytemuck::cast_slice::<_, u8>(&first_batch_vertices)
+        );
+    }
+
+    #[test]
+    fn batches_are_rebuilt_after_a_frame_without_rendering() {
+        let mut storage = sprite_scene();
+        let mut batches = Batches::default();
+        render_frame(&mut storage, &mut batches);
+        storage.resource_mut::<Time>().unwrap().advance(0.016);
+        assert_eq!(render_frame(&mut storage, &mut batches), 4 * QUAD_BYTES);
+    }
+
+    fn batch_count(quads: &[Quad2d]) -> usize {
+        let mut texture_ids = quads.iter().map(|quad| quad.texture_id).collect::<Vec<_>>();
+        texture_ids.dedup();
+        texture_ids.len()
+    }
+
+    #[test]
+    fn pipeline_names_differ_per_blend_mode_and_target_format() {
+        let blend_modes = [
+            BlendMode::Opaque,
+            BlendMode::Alpha,
+            BlendMode::Additive,
+            BlendMode::Multiply,
+        ];
+        let mut names = [wgpu::TextureFormat::Bgra8UnormSrgb, HDR_TEXTURE_FORMAT]
+            .into_iter()
+            .flat_map(|format| {
+                blend_modes
+                    .iter()
+                    .map(move |&blend_mode| pipeline_name(blend_mode, format))
+            })
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        assert_eq!(names.len(), 8);
+    }
+
+    #[test]
+    fn cull_quads_outside_of_the_viewport() {
+        // A 100x100 grid of 16x16 quads, far larger than the 800x600 viewport
+        #[allow(clippy::cast_precision_loss)]
+        let mut quads = (0..100 * 100)
+            .map(|i| {
+                let mut quad = quad(0, 0.0);
+                quad.transform = Matrix4f::new_translation(&Vector3f::new(
+                    (i % 100) as f32 * 16.0,
+                    (i / 100) as f32 * 16.0,
+                    0.0,
+                ));
+                quad.texture_rect.width = 16.0;
+                quad.texture_rect.height = 16.0;
+                quad
+            })
+            .collect::<Vec<_>>();
+
+        cull_quads(
+            &mut quads,
+            (Vector2f::new(0.0, 0.0), Vector2f::new(800.0, 600.0)),
+        );
+        // 53 columns and 40 rows are within the viewport and its margin
+        assert_eq!(quads.len(), 53 * 40);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn rotated_texture_rect_rotates_corner_texture_coordinates() {
+        let mut quad = quad(0, 0.0);
+        quad.texture_rect = texture::Rect::new(16.0, 0.0, 8.0, 32.0);
+        let [top_left, bottom_left, bottom_right, top_right] =
+            quad.corner_texture_coordinates(64.0, 64.0);
+        assert_eq!(quad.size(), (8.0, 32.0));
+
+        quad.texture_rect_rotated = true;
+        assert_eq!(quad.size(), (32.0, 8.0));
+        assert_eq!(
+            quad.corner_texture_coordinates(64.0, 64.0),
+            [top_right, top_left, bottom_left, bottom_right]
+        );
+    }
+
+    #[test]
+    fn sort_quads_for_batching_groups_textures() {
+        let mut quads = (0..8).map(|i| quad(i % 2, 0.0)).collect::<Vec<_>>();
+        assert_eq!(batch_count(&quads), 8);
+        sort_quads_for_batching(&mut quads);
+        assert_eq!(batch_count(&quads), 2);
+    }
+
+    #[test]
+    fn sort_quads_for_batching_keeps_layers_ordered() {
+        let mut quads = vec![quad(0, 1.0), quad(1, 0.0), quad(0, 0.0), quad(1, 1.0)];
+        sort_quads_for_batching(&mut quads);
+        let order = quads
+            .iter()
+            .map(|quad| (*quad.texture_id, quad.z()))
+            .collect::<Vec<_>>();
+        assert_eq!(order, vec![(0, 0.0), (1, 0.0), (0, 1.0), (1, 1.0)]);
+    }
+
+    #[test]
+    fn sort_quads_for_batching_orders_blend_modes_within_a_layer() {
+        let mut quads = vec![quad(0, 1.0), quad(0, 0.0), quad(0, 0.0), quad(0, 0.0)];
+        quads[0].blend_mode = BlendMode::Opaque;
+        quads[1].blend_mode = BlendMode::Additive;
+        quads[3].blend_mode = BlendMode::Opaque;
+        sort_quads_for_batching(&mut quads);
+        let order = quads
+            .iter()
+            .map(|quad| (quad.z(), quad.blend_mode))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            vec![
+                (0.0, BlendMode::Opaque),
+                (0.0, BlendMode::Alpha),
+                (0.0, BlendMode::Additive),
+                (1.0, BlendMode::Opaque)
+            ]
+        );
+    }
 }
@@ -7,11 +7,24 @@ struct VertexInput {
 
 struct VertexOutput {
     @builtin(position) position: vec4<f32>,
-    @location(0) texture_coordinates: vec2<f32>
+    @location(0) texture_coordinates: vec2<f32>,
+    @location(1) world_position: vec2<f32>,
+}
+
+const MAX_LIGHTS: u32 = 16u;
+
+struct Light {
+    position: vec2<f32>,
+    radius: f32,
+    intensity: f32,
+    color: vec4<f32>,
 }
 
 struct PassUniform {
     view_proj: mat4x4<f32>,
+    ambient: vec4<f32>,
+    light_count: u32,
+    lights: array<Light, MAX_LIGHTS>,
 }
 
 @group(0) @binding(0)
@@ -27,11 +40,30 @@ fn vs_main(in: VertexInput) -> VertexOutput {
     var out: VertexOutput;
     out.position = u_pass.view_proj * vec4<f32>(in.position, 1.0);
     out.texture_coordinates = in.texture_coordinates;
+    out.world_position = in.position.xy;
     return out;
 }
 
+fn shade(in: VertexOutput) -> vec4<f32> {
+    let sample = textureSample(t_base_color, s_base_color, in.texture_coordinates);
+    var light = u_pass.ambient.rgb;
+    for (var i = 0u; i < min(u_pass.light_count, MAX_LIGHTS); i++) {
+        let l = u_pass.lights[i];
+        let attenuation = clamp(1.0 - distance(in.world_position, l.position) / l.radius, 0.0, 1.0);
+        light += l.color.rgb * l.intensity * attenuation * attenuation;
+    }
+    return vec4<f32>(sample.rgb * light, sample.a);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let sample = textureSample(t_base_color, s_base_color, in.texture_coordinates);
-    return sample;
+    return shade(in);
+}
+
+// Blends toward white by the alpha, so the multiply blend leaves the
+// background untouched where the sprite is transparent
+@fragment
+fn fs_multiply(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = shade(in);
+    return vec4<f32>(mix(vec3<f32>(1.0), color.rgb, color.a), color.a);
 }
@@ -0,0 +1,542 @@
+use tubereng_core::TransformCache;
+use tubereng_ecs::Storage;
+use wgpu::include_wgsl;
+
+use crate::{
+    camera,
+    graphics_pipeline::RenderPass,
+    material,
+    mesh::{self, Mesh, Vertex},
+    texture, GraphicsState, PipelineCache, RenderStats, WindowSize,
+};
+
+pub(crate) const DEPTH_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+pub struct PassUniform {
+    view_proj: [[f32; 4]; 4],
+}
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct Instance {
+    model: [[f32; 4]; 4],
+}
+
+impl Instance {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        3 => Float32x4,
+        4 => Float32x4,
+        5 => Float32x4,
+        6 => Float32x4
+    ];
+
+    fn layout<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Instance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+struct DrawCall {
+    mesh: mesh::Id,
+    material: material::Id,
+    instance: u32,
+}
+
+struct DepthTexture {
+    view: wgpu::TextureView,
+    width: u32,
+    height: u32,
+}
+
+/// Draws the [`Mesh`] components as seen by the active 3d camera
+///
+/// With a depth pre-pass, the depth of the meshes is first rendered alone to
+/// a depth target that later passes can sample, then the colors are rendered
+/// with that depth.
+pub struct Pass {
+    draw_calls: Vec<DrawCall>,
+    instance_buffer: wgpu::Buffer,
+    #[allow(clippy::struct_field_names)]
+    pass_uniform_buffer: wgpu::Buffer,
+    #[allow(clippy::struct_field_names)]
+    pass_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    #[allow(clippy::struct_field_names)]
+    pass_uniform_bind_group: wgpu::BindGroup,
+    depth_texture: Option<DepthTexture>,
+    depth_prepass_target: Option<texture::Id>,
+}
+
+impl Pass {
+    const MAX_INSTANCES: usize = 1024;
+
+    #[must_use]
+    pub fn new(device: &wgpu::Device) -> Self {
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("pass_3d_instance_buffer"),
+            size: (Self::MAX_INSTANCES * std::mem::size_of::<Instance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pass_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("pass_3d_uniform"),
+            size: std::mem::size_of::<PassUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pass_uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("pass_3d_uniform_bind_group_layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let pass_uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("pass_3d_uniform_bind_group"),
+            layout: &pass_uniform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: pass_uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            draw_calls: vec![],
+            instance_buffer,
+            pass_uniform_buffer,
+            pass_uniform_bind_group_layout,
+            pass_uniform_bind_group,
+            depth_texture: None,
+            depth_prepass_target: None,
+        }
+    }
+
+    /// Enables the depth pre-pass, rendering the depth to a target created
+    /// with [`GraphicsState::create_depth_target`]. Disabled by default.
+    #[must_use]
+    pub fn with_depth_prepass(mut self, depth_target: texture::Id) -> Self {
+        self.depth_prepass_target = Some(depth_target);
+        self
+    }
+
+    fn create_depth_texture_if_required(&mut self, gfx: &mut GraphicsState<'_>) {
+        let window_size = *gfx.window_size();
+        if let Some(depth_target) = self.depth_prepass_target {
+            let depth_target_size = gfx
+                .texture_cache
+                .try_info(depth_target)
+                .map(|info| (info.width(), info.height()));
+            if depth_target_requires_resize(depth_target_size, window_size) {
+                gfx.resize_depth_target(depth_target, window_size.width, window_size.height);
+            }
+            return;
+        }
+
+        if let Some(depth_texture) = &self.depth_texture {
+            if !depth_target_requires_resize(
+                Some((depth_texture.width, depth_texture.height)),
+                window_size,
+            ) {
+                return;
+            }
+        }
+
+        let texture = gfx.device().create_texture(&wgpu::TextureDescriptor {
+            label: Some("pass_3d_depth_texture"),
+            size: wgpu::Extent3d {
+                width: window_size.width,
+                height: window_size.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_TEXTURE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        self.depth_texture = Some(DepthTexture {
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            width: window_size.width,
+            height: window_size.height,
+        });
+    }
+
+    /// Creates the pipeline of the color pass if required and returns its
+    /// identifier in the pipeline cache
+    fn ensure_color_pipeline(
+        &self,
+        gfx: &GraphicsState,
+        pipeline_cache: &mut PipelineCache,
+        after_depth_prepass: bool,
+    ) -> &'static str {
+        let layouts = [
+            &self.pass_uniform_bind_group_layout,
+            &gfx.material_bind_group_layout,
+        ];
+        if after_depth_prepass {
+            if !pipeline_cache.has("pass_3d_after_depth_prepass_pipeline") {
+                pipeline_cache.insert(
+                    "pass_3d_after_depth_prepass_pipeline",
+                    Self::create_pipeline(
+                        gfx.device(),
+                        &layouts,
+                        Some(gfx.surface_texture_format()),
+                        wgpu::CompareFunction::LessEqual,
+                    ),
+                );
+            }
+            "pass_3d_after_depth_prepass_pipeline"
+        } else {
+            if !pipeline_cache.has("pass_3d_pipeline") {
+                pipeline_cache.insert(
+                    "pass_3d_pipeline",
+                    Self::create_pass_3d_pipeline(
+                        gfx.device(),
+                        &layouts,
+                        gfx.surface_texture_format(),
+                    ),
+                );
+            }
+            "pass_3d_pipeline"
+        }
+    }
+
+    /// Renders the depth of the meshes alone to the depth target
+    fn execute_depth_prepass(
+        &self,
+        gfx: &GraphicsState,
+        encoder: &mut wgpu::CommandEncoder,
+        depth_view: &wgpu::TextureView,
+        pipeline_cache: &mut PipelineCache,
+        mut render_stats: Option<&mut RenderStats>,
+    ) {
+        if !pipeline_cache.has("pass_3d_depth_prepass_pipeline") {
+            pipeline_cache.insert(
+                "pass_3d_depth_prepass_pipeline",
+                Self::create_pipeline(
+                    gfx.device(),
+                    &[&self.pass_uniform_bind_group_layout],
+                    None,
+                    wgpu::CompareFunction::Less,
+                ),
+            );
+        }
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("pass_3d_depth_prepass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        rpass.set_pipeline(
+            pipeline_cache
+                .get("pass_3d_depth_prepass_pipeline")
+                .unwrap(),
+        );
+        rpass.set_bind_group(0, &self.pass_uniform_bind_group, &[]);
+        rpass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        for draw_call in &self.draw_calls {
+            let mesh = gfx.mesh_cache.get(draw_call.mesh);
+            rpass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            rpass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            rpass.draw_indexed(
+                0..mesh.index_count,
+                0,
+                draw_call.instance..draw_call.instance + 1,
+            );
+            if let Some(render_stats) = render_stats.as_mut() {
+                render_stats.record_draw(mesh.index_count);
+            }
+        }
+        if let Some(render_stats) = render_stats {
+            render_stats.render_passes += 1;
+        }
+    }
+
+    #[must_use]
+    pub fn create_pass_3d_pipeline(
+        device: &wgpu::Device,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        surface_texture_format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        Self::create_pipeline(
+            device,
+            bind_group_layouts,
+            Some(surface_texture_format),
+            wgpu::CompareFunction::Less,
+        )
+    }
+
+    /// Creates a pipeline rendering the colors of the meshes, or only their
+    /// depth if no color format is given
+    fn create_pipeline(
+        device: &wgpu::Device,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        color_format: Option<wgpu::TextureFormat>,
+        depth_compare: wgpu::CompareFunction,
+    ) -> wgpu::RenderPipeline {
+        let shader_module = device.create_shader_module(include_wgsl!("./pass_3d.wgsl"));
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("pass_3d_pipeline"),
+                bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+
+        let color_targets = color_format.map(|color_format| {
+            [Some(wgpu::ColorTargetState {
+                format: color_format,
+                blend: Some(wgpu::BlendState::REPLACE),
+                write_mask: wgpu::ColorWrites::ALL,
+            })]
+        });
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[Vertex::layout(), Instance::layout()],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_TEXTURE_FORMAT,
+                depth_write_enabled: true,
+                depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: color_targets
+                .as_ref()
+                .map(|color_targets| wgpu::FragmentState {
+                    module: &shader_module,
+                    entry_point: "fs_main",
+                    targets: color_targets,
+                }),
+            multiview: None,
+        })
+    }
+}
+
+/// Returns true if a depth texture of the given size, `None` if missing,
+/// doesn't match the size of the window
+fn depth_target_requires_resize(
+    depth_target_size: Option<(u32, u32)>,
+    window_size: WindowSize,
+) -> bool {
+    depth_target_size != Some((window_size.width, window_size.height))
+}
+
+impl RenderPass for Pass {
+    fn prepare(&mut self, storage: &Storage) {
+        self.draw_calls.clear();
+        let Some((camera_id, camera)) = camera::active_3d(storage) else {
+            return;
+        };
+
+        let mut gfx = storage
+            .resource_mut::<GraphicsState>()
+            .expect("Graphics state should be present");
+        self.create_depth_texture_if_required(&mut gfx);
+
+        let transform_cache = storage
+            .resource::<TransformCache>()
+            .expect("TransformCache resource should be present");
+        let camera_transform = transform_cache.get(camera_id);
+        let inverse_transform = camera_transform.try_inverse().unwrap();
+        gfx.queue().write_buffer(
+            &self.pass_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[PassUniform {
+                view_proj: (*camera.projection() * inverse_transform).into(),
+            }]),
+        );
+
+        let mut instances = vec![];
+        for (id, mesh) in storage.query::<&Mesh>().iter_with_ids() {
+            if instances.len() == Self::MAX_INSTANCES {
+                break;
+            }
+
+            self.draw_calls.push(DrawCall {
+                mesh: mesh.mesh,
+                material: mesh
+                    .material
+                    .filter(|&material| gfx.material_cache.get(material).is_some())
+                    .or(gfx.placeholder_material_id)
+                    .expect("The placeholder material should have been loaded"),
+                instance: u32::try_from(instances.len()).unwrap(),
+            });
+            instances.push(Instance {
+                model: transform_cache.get(id).into(),
+            });
+        }
+
+        gfx.queue()
+            .write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+    }
+
+    fn execute(
+        &self,
+        gfx: &mut GraphicsState,
+        encoder: &mut wgpu::CommandEncoder,
+        surface_texture_view: &wgpu::TextureView,
+        storage: &Storage,
+    ) {
+        if self.draw_calls.is_empty() {
+            return;
+        }
+        let prepass_depth_view = match self.depth_prepass_target {
+            Some(depth_target) => {
+                let Some(depth_texture) = gfx.texture_cache.try_get(depth_target) else {
+                    return;
+                };
+                Some(depth_texture.create_view(&wgpu::TextureViewDescriptor::default()))
+            }
+            None => None,
+        };
+        let Some(depth_view) = prepass_depth_view.as_ref().or(self
+            .depth_texture
+            .as_ref()
+            .map(|depth_texture| &depth_texture.view))
+        else {
+            return;
+        };
+
+        let mut pipeline_cache = storage.resource_mut::<PipelineCache>().unwrap();
+        let mut render_stats = storage.resource_mut::<RenderStats>();
+        if prepass_depth_view.is_some() {
+            self.execute_depth_prepass(
+                gfx,
+                encoder,
+                depth_view,
+                &mut pipeline_cache,
+                render_stats.as_deref_mut(),
+            );
+        }
+        let color_pipeline_identifier =
+            self.ensure_color_pipeline(gfx, &mut pipeline_cache, prepass_depth_view.is_some());
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("pass_3d"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface_texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: if prepass_depth_view.is_some() {
+                        wgpu::LoadOp::Load
+                    } else {
+                        wgpu::LoadOp::Clear(1.0)
+                    },
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        rpass.set_pipeline(pipeline_cache.get(color_pipeline_identifier).unwrap());
+        rpass.set_bind_group(0, &self.pass_uniform_bind_group, &[]);
+        rpass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        let mut bound_material = None;
+        for draw_call in &self.draw_calls {
+            let mesh = gfx.mesh_cache.get(draw_call.mesh);
+            if bound_material != Some(draw_call.material) {
+                let material = gfx
+                    .material_cache
+                    .get(draw_call.material)
+                    .expect("The material of the mesh should be loaded");
+                rpass.set_bind_group(1, material.bind_group(), &[]);
+                bound_material = Some(draw_call.material);
+                if let Some(render_stats) = render_stats.as_mut() {
+                    render_stats.bind_group_switches += 1;
+                }
+            }
+            rpass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+            rpass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+            rpass.draw_indexed(
+                0..mesh.index_count,
+                0,
+                draw_call.instance..draw_call.instance + 1,
+            );
+            if let Some(render_stats) = render_stats.as_mut() {
+                render_stats.record_draw(mesh.index_count);
+            }
+        }
+    }
+
+    fn depth_output(&self) -> Option<texture::Id> {
+        self.depth_prepass_target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn depth_prepass_target_follows_window_resize() {
+        let window_size = WindowSize {
+            width: 800,
+            height: 600,
+        };
+        assert!(!depth_target_requires_resize(Some((800, 600)), window_size));
+
+        let resized_window_size = WindowSize {
+            width: 1024,
+            height: 768,
+        };
+        assert!(depth_target_requires_resize(
+            Some((800, 600)),
+            resized_window_size
+        ));
+        assert!(depth_target_requires_resize(None, resized_window_size));
+    }
+}
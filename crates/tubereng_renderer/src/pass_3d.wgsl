@@ -0,0 +1,64 @@
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) texture_coordinates: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+}
+
+struct InstanceInput {
+    @location(3) model_0: vec4<f32>,
+    @location(4) model_1: vec4<f32>,
+    @location(5) model_2: vec4<f32>,
+    @location(6) model_3: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinates: vec2<f32>,
+    @location(1) normal: vec3<f32>,
+}
+
+// Direction towards the light lighting the meshes, and the light received by
+// the faces turned away from it
+const LIGHT_DIRECTION: vec3<f32> = vec3<f32>(0.3, 0.8, 0.5);
+const AMBIENT_LIGHT: f32 = 0.3;
+
+struct PassUniform {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> u_pass: PassUniform;
+
+@group(1) @binding(0)
+var t_base_color: texture_2d<f32>;
+@group(1) @binding(1)
+var s_base_color: sampler;
+
+@vertex
+fn vs_main(in: VertexInput, instance: InstanceInput) -> VertexOutput {
+    let model = mat4x4<f32>(
+        instance.model_0,
+        instance.model_1,
+        instance.model_2,
+        instance.model_3,
+    );
+    var out: VertexOutput;
+    out.position = u_pass.view_proj * model * vec4<f32>(in.position, 1.0);
+    out.texture_coordinates = in.texture_coordinates;
+    // Correct as long as the models are scaled uniformly
+    out.normal = (model * vec4<f32>(in.normal, 0.0)).xyz;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_base_color, s_base_color, in.texture_coordinates);
+    // Meshes without normals are drawn unlit
+    if (dot(in.normal, in.normal) == 0.0) {
+        return color;
+    }
+    let diffuse = max(dot(normalize(in.normal), normalize(LIGHT_DIRECTION)), 0.0);
+    let light = AMBIENT_LIGHT + (1.0 - AMBIENT_LIGHT) * diffuse;
+    return vec4<f32>(color.rgb * light, color.a);
+}
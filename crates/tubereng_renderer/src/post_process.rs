@@ -0,0 +1,157 @@
+//! Post-processing passes, sampling the render target of a previous pass.
+//!
+//! ```ignore
+//! let scene_color = gfx.create_hdr_render_target(width, height);
+//! let pipeline = GraphicsPipeline::builder()
+//!     .add_pass(ClearPass::new().with_output(scene_color))
+//!     .add_pass(pass_2d::Pass::new(gfx.device()).with_output(scene_color))
+//!     .add_pass(TonemapPass::new(scene_color))
+//!     .build();
+//! ```
+
+use tubereng_ecs::Storage;
+use wgpu::include_wgsl;
+
+use crate::{graphics_pipeline::RenderPass, texture, GraphicsState, PipelineCache};
+
+/// The format of the render targets created by
+/// [`GraphicsState::create_hdr_render_target`]
+pub const HDR_TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Applies a Reinhard tonemap to a render target with a full-screen
+/// triangle, writing to the surface or to another render target
+pub struct TonemapPass {
+    inputs: [texture::Id; 1],
+    output: Option<texture::Id>,
+}
+
+impl TonemapPass {
+    #[must_use]
+    pub fn new(input: texture::Id) -> Self {
+        Self {
+            inputs: [input],
+            output: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_output(mut self, output: texture::Id) -> Self {
+        self.output = Some(output);
+        self
+    }
+
+    #[must_use]
+    fn create_tonemap_pipeline(
+        device: &wgpu::Device,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        target_texture_format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let shader_module = device.create_shader_module(include_wgsl!("./post_process.wgsl"));
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("tonemap_pass_pipeline"),
+                bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("tonemap_pass_pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: target_texture_format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+    }
+}
+
+impl RenderPass for TonemapPass {
+    fn prepare(&mut self, _storage: &Storage) {}
+
+    fn inputs(&self) -> &[texture::Id] {
+        &self.inputs
+    }
+
+    fn output(&self) -> Option<texture::Id> {
+        self.output
+    }
+
+    fn execute(
+        &self,
+        gfx: &mut GraphicsState,
+        encoder: &mut wgpu::CommandEncoder,
+        target_view: &wgpu::TextureView,
+        storage: &Storage,
+    ) {
+        let target_texture_format = gfx.target_texture_format(self.output);
+        let pipeline_name = pipeline_name(target_texture_format);
+        let mut pipeline_cache = storage.resource_mut::<PipelineCache>().unwrap();
+        if !pipeline_cache.has(pipeline_name) {
+            pipeline_cache.insert(
+                pipeline_name,
+                Self::create_tonemap_pipeline(
+                    gfx.device(),
+                    &[gfx.sampled_texture_bind_group_layout()],
+                    target_texture_format,
+                ),
+            );
+        }
+
+        let Some(input_bind_group) = gfx.sampled_texture_bind_group(self.inputs[0]) else {
+            return;
+        };
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("tonemap_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        rpass.set_pipeline(pipeline_cache.get(pipeline_name).unwrap());
+        rpass.set_bind_group(0, input_bind_group, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+fn pipeline_name(target_texture_format: wgpu::TextureFormat) -> &'static str {
+    if target_texture_format == HDR_TEXTURE_FORMAT {
+        "tonemap_pass_pipeline_hdr"
+    } else {
+        "tonemap_pass_pipeline"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hdr_target_uses_its_own_pipeline() {
+        assert_ne!(
+            pipeline_name(HDR_TEXTURE_FORMAT),
+            pipeline_name(wgpu::TextureFormat::Bgra8UnormSrgb)
+        );
+    }
+}
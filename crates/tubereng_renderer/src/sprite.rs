@@ -1,12 +1,128 @@
-use tubereng_core::DeltaTime;
-use tubereng_ecs::system::{Res, Q};
+use tubereng_core::{DeltaTime, TransformCache};
+use tubereng_ecs::{
+    event::EventWriter,
+    system::{Res, Q},
+    EntityId, Storage,
+};
+use tubereng_math::{
+    matrix::Matrix4f,
+    vector::{Vector2f, Vector3f},
+};
 
-use crate::texture;
+use crate::{texture, GraphicsState};
+
+/// How a sprite is combined with what is drawn behind it.
+///
+/// Sprites are drawn back to front by their world z coordinate whatever their
+/// blend mode. Within a same z, opaque sprites are drawn first, then the
+/// alpha blended, additive and multiplied ones, in this order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum BlendMode {
+    /// The sprite replaces the pixels behind it, including its transparent
+    /// ones
+    Opaque,
+    #[default]
+    Alpha,
+    /// The sprite brightens the pixels behind it, e.g. for glow effects
+    Additive,
+    /// The sprite darkens the pixels behind it, e.g. for shadows
+    Multiply,
+}
+
+impl BlendMode {
+    pub(crate) fn blend_state(self) -> Option<wgpu::BlendState> {
+        let color = match self {
+            BlendMode::Opaque => return None,
+            BlendMode::Alpha => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            BlendMode::Additive => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            // The fragment shader blends the sprite toward white by its
+            // alpha so transparent texels leave the background untouched
+            BlendMode::Multiply => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Dst,
+                dst_factor: wgpu::BlendFactor::Zero,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+
+        Some(wgpu::BlendState {
+            color,
+            alpha: wgpu::BlendComponent::default(),
+        })
+    }
+
+    /// The entry point of the fragment shader of the 2d pass drawing sprites
+    /// with this blend mode
+    pub(crate) fn fragment_entry_point(self) -> &'static str {
+        match self {
+            BlendMode::Multiply => "fs_multiply",
+            _ => "fs_main",
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct Sprite {
     pub texture: texture::Id,
     pub texture_rect: Option<texture::Rect>,
+    /// Whether the region of the texture holds the sprite rotated by 90°
+    /// clockwise, as packed by some texture packers. The sprite is drawn
+    /// upright, with the width and height of the region swapped.
+    pub texture_rect_rotated: bool,
+    /// The point of the sprite placed at the origin of its transform,
+    /// normalized so `(0, 0)` is the top-left corner and `(1, 1)` the
+    /// bottom-right corner. Rotation and scaling happen about this point.
+    pub pivot: Vector2f,
+    pub blend_mode: BlendMode,
+}
+
+impl Sprite {
+    /// Creates a sprite drawing the whole texture, alpha blended and pivoting
+    /// about its top-left corner
+    #[must_use]
+    pub fn new(texture: texture::Id) -> Self {
+        Self {
+            texture,
+            texture_rect: None,
+            texture_rect_rotated: false,
+            pivot: Vector2f::default(),
+            blend_mode: BlendMode::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_texture_rect(mut self, texture_rect: texture::Rect) -> Self {
+        self.texture_rect = Some(texture_rect);
+        self
+    }
+
+    /// Draws a region of the texture holding the sprite rotated by 90°
+    /// clockwise, see [`Sprite::texture_rect_rotated`]
+    #[must_use]
+    pub fn with_rotated_texture_rect(mut self, texture_rect: texture::Rect) -> Self {
+        self.texture_rect = Some(texture_rect);
+        self.texture_rect_rotated = true;
+        self
+    }
+
+    #[must_use]
+    pub fn with_pivot(mut self, pivot: Vector2f) -> Self {
+        self.pivot = pivot;
+        self
+    }
+
+    #[must_use]
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
 }
 
 #[derive(Debug)]
@@ -16,6 +132,11 @@ pub struct AnimationState {
     pub current_frame: usize,
     pub secs_per_frame: f32,
     pub ticks: f32,
+    /// Whether the animation restarts after its last frame, true by default
+    pub looping: bool,
+    /// Set once a non-looping animation played its last frame, the
+    /// animation is stopped until it is reset
+    pub finished: bool,
 }
 
 impl Default for AnimationState {
@@ -26,32 +147,311 @@ impl Default for AnimationState {
             current_frame: 0,
             secs_per_frame: 1.0,
             ticks: 0.0,
+            looping: true,
+            finished: false,
         }
     }
 }
 
+/// Event sent when an animated sprite played the last frame of its
+/// animation. Looping animations send it each time they restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationFinished {
+    pub entity: EntityId,
+    pub animation_index: usize,
+}
+
 #[derive(Debug)]
 #[allow(clippy::module_name_repetitions)]
 pub struct AnimatedSprite {
     pub texture_atlas: texture::Id,
     pub animation: AnimationState,
+    /// The point of the sprite placed at the origin of its transform, see
+    /// [`Sprite::pivot`]
+    pub pivot: Vector2f,
+    pub blend_mode: BlendMode,
+}
+
+impl AnimatedSprite {
+    /// Creates an animated sprite, alpha blended and pivoting about its
+    /// top-left corner
+    #[must_use]
+    pub fn new(texture_atlas: texture::Id, animation: AnimationState) -> Self {
+        Self {
+            texture_atlas,
+            animation,
+            pivot: Vector2f::default(),
+            blend_mode: BlendMode::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_pivot(mut self, pivot: Vector2f) -> Self {
+        self.pivot = pivot;
+        self
+    }
+
+    #[must_use]
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+}
+
+/// Returns the local transform moving the pivot of a quad of the given size
+/// to the origin
+pub(crate) fn pivot_transform(pivot: Vector2f, width: f32, height: f32) -> Matrix4f {
+    Matrix4f::new_translation(&Vector3f::new(-pivot.x * width, -pivot.y * height, 0.0))
 }
 
 pub fn animate_sprite_system(
     delta_time: Res<DeltaTime>,
+    mut animation_finished_events: EventWriter<AnimationFinished>,
     mut query_animated_sprite: Q<&mut AnimatedSprite>,
 ) {
     let now = delta_time.0;
-    for mut sprite in query_animated_sprite.iter() {
-        sprite.animation.ticks += now;
-        if sprite.animation.ticks > sprite.animation.secs_per_frame {
-            let animation_frame_count =
-                sprite.animation.animations[sprite.animation.current_animation].len();
-            sprite.animation.ticks -= sprite.animation.secs_per_frame;
-            sprite.animation.current_frame =
-                (sprite.animation.current_frame + 1) % animation_frame_count;
+    for (id, mut sprite) in query_animated_sprite.iter_with_ids() {
+        let animation = &mut sprite.animation;
+        if animation.finished {
+            continue;
+        }
+
+        animation.ticks += now;
+        if animation.ticks > animation.secs_per_frame {
+            let animation_frame_count = animation.animations[animation.current_animation].len();
+            animation.ticks -= animation.secs_per_frame;
+            if animation.current_frame + 1 < animation_frame_count {
+                animation.current_frame += 1;
+                continue;
+            }
+
+            animation_finished_events.send(AnimationFinished {
+                entity: id,
+                animation_index: animation.current_animation,
+            });
+            if animation.looping {
+                animation.current_frame = 0;
+            } else {
+                animation.finished = true;
+            }
         }
     }
 
     std::mem::drop(delta_time);
 }
+
+/// Returns the topmost sprite or animated sprite whose quad contains a point
+/// in world space, such as the cursor position converted with the active 2d
+/// camera.
+///
+/// The sprites with the highest z are on top, ties are resolved in favor of
+/// the last sprite. The point is tested in the local space of each sprite so
+/// rotated and scaled sprites are handled.
+#[must_use]
+pub fn pick_sprite(storage: &Storage, world_position: Vector2f) -> Option<EntityId> {
+    let gfx = storage.resource::<GraphicsState>()?;
+    let transform_cache = storage.resource::<TransformCache>()?;
+    let mut sprite_query = storage.query::<&Sprite>();
+    let mut animated_sprite_query = storage.query::<&AnimatedSprite>();
+
+    #[allow(clippy::cast_precision_loss)]
+    let sprites = sprite_query.iter_with_ids().filter_map(|(id, sprite)| {
+        let size = if let Some(rect) = &sprite.texture_rect {
+            if sprite.texture_rect_rotated {
+                (rect.height, rect.width)
+            } else {
+                (rect.width, rect.height)
+            }
+        } else {
+            let texture_info = gfx.texture_info(sprite.texture)?;
+            (texture_info.width as f32, texture_info.height as f32)
+        };
+        Some((
+            id,
+            transform_cache.get(id) * pivot_transform(sprite.pivot, size.0, size.1),
+            size,
+        ))
+    });
+    let animated_sprites = animated_sprite_query
+        .iter_with_ids()
+        .map(|(id, animated_sprite)| {
+            let animation = &animated_sprite.animation;
+            let rect = &animation.animations[animation.current_animation][animation.current_frame];
+            (
+                id,
+                transform_cache.get(id)
+                    * pivot_transform(animated_sprite.pivot, rect.width, rect.height),
+                (rect.width, rect.height),
+            )
+        });
+
+    topmost_quad_at(sprites.chain(animated_sprites), world_position)
+}
+
+/// Returns the quad containing the point with the highest z, quads are
+/// described by their id, local to world transform and size
+fn topmost_quad_at(
+    quads: impl IntoIterator<Item = (EntityId, Matrix4f, (f32, f32))>,
+    point: Vector2f,
+) -> Option<EntityId> {
+    let mut topmost: Option<(EntityId, f32)> = None;
+    for (id, transform, (width, height)) in quads {
+        let Some(world_to_local) = transform.try_inverse() else {
+            continue;
+        };
+
+        let z = transform[2][3];
+        let local_point = world_to_local.transform_vec3(&Vector3f::new(point.x, point.y, z));
+        let contains_point =
+            (0.0..=width).contains(&local_point.x) && (0.0..=height).contains(&local_point.y);
+        if contains_point && topmost.is_none_or(|(_, topmost_z)| z >= topmost_z) {
+            topmost = Some((id, z));
+        }
+    }
+
+    topmost.map(|(id, _)| id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use tubereng_ecs::{event::Events, system::Into, Ecs};
+    use tubereng_math::quaternion::Quaternion;
+
+    use super::*;
+
+    fn quad(id: EntityId, x: f32, y: f32, z: f32) -> (EntityId, Matrix4f, (f32, f32)) {
+        (
+            id,
+            Matrix4f::new_translation(&Vector3f::new(x, y, z)),
+            (10.0, 10.0),
+        )
+    }
+
+    #[test]
+    fn topmost_quad_at_respects_z_order() {
+        let quads = [quad(0, 0.0, 0.0, 1.0), quad(1, 5.0, 5.0, 0.0)];
+        assert_eq!(topmost_quad_at(quads, Vector2f::new(7.0, 7.0)), Some(0));
+        assert_eq!(topmost_quad_at(quads, Vector2f::new(12.0, 12.0)), Some(1));
+        assert_eq!(topmost_quad_at(quads, Vector2f::new(-1.0, 2.0)), None);
+    }
+
+    #[test]
+    fn topmost_quad_at_rotated_quad() {
+        let rotation = Quaternion::from_axis_angle(&Vector3f::new(0.0, 0.0, 1.0), FRAC_PI_2);
+        let quads = [(0, rotation.rotation_matrix(), (10.0, 2.0))];
+        assert_eq!(topmost_quad_at(quads, Vector2f::new(-1.0, 5.0)), Some(0));
+        assert_eq!(topmost_quad_at(quads, Vector2f::new(5.0, 1.0)), None);
+    }
+
+    /// Returns the value of one of the factors used by the blend modes
+    fn blend_factor(factor: wgpu::BlendFactor, source: [f32; 4], destination: f32) -> f32 {
+        match factor {
+            wgpu::BlendFactor::Zero => 0.0,
+            wgpu::BlendFactor::One => 1.0,
+            wgpu::BlendFactor::SrcAlpha => source[3],
+            wgpu::BlendFactor::OneMinusSrcAlpha => 1.0 - source[3],
+            wgpu::BlendFactor::Dst => destination,
+            factor => panic!("unexpected blend factor {factor:?}"),
+        }
+    }
+
+    /// Blends the output of the fragment shader with a destination color as
+    /// the GPU does
+    fn blend(blend_mode: BlendMode, texel: [f32; 4], destination: f32) -> f32 {
+        // Mirrors `fs_multiply` in pass_2d.wgsl
+        let source = if blend_mode.fragment_entry_point() == "fs_multiply" {
+            let color = 1.0 + (texel[0] - 1.0) * texel[3];
+            [color, color, color, texel[3]]
+        } else {
+            texel
+        };
+        let component = blend_mode.blend_state().unwrap().color;
+        source[0] * blend_factor(component.src_factor, source, destination)
+            + destination * blend_factor(component.dst_factor, source, destination)
+    }
+
+    #[test]
+    fn multiply_blend_darkens_the_background_by_the_sprite_alpha() {
+        let background = 0.8;
+        let texel_color = 0.5;
+        let blended = |alpha| {
+            blend(
+                BlendMode::Multiply,
+                [texel_color, 0.0, 0.0, alpha],
+                background,
+            )
+        };
+        assert!((blended(0.0) - background).abs() < f32::EPSILON);
+        assert!((blended(0.5) - background * 0.75).abs() < f32::EPSILON);
+        assert!((blended(1.0) - background * texel_color).abs() < f32::EPSILON);
+    }
+
+    #[test]
+    fn sprite_builder() {
+        let rect = texture::Rect::new(0.0, 0.0, 16.0, 32.0);
+        let sprite = Sprite::new(texture::Id::new(0, 0))
+            .with_rotated_texture_rect(rect.clone())
+            .with_pivot(Vector2f::new(0.5, 0.5))
+            .with_blend_mode(BlendMode::Additive);
+        assert_eq!(sprite.texture_rect, Some(rect));
+        assert!(sprite.texture_rect_rotated);
+        assert_eq!(sprite.pivot, Vector2f::new(0.5, 0.5));
+        assert_eq!(sprite.blend_mode, BlendMode::Additive);
+    }
+
+    #[test]
+    fn animate_sprite_system_sends_animation_finished() {
+        let mut ecs = Ecs::new();
+        ecs.add_event::<AnimationFinished>();
+        ecs.insert_resource(DeltaTime(0.6));
+        let frames = vec![texture::Rect::new(0.0, 0.0, 16.0, 16.0); 2];
+        let texture_atlas = texture::Id::new(0, 0);
+        let looping = ecs.insert((AnimatedSprite::new(
+            texture_atlas,
+            AnimationState {
+                animations: vec![frames.clone()],
+                secs_per_frame: 0.5,
+                ..Default::default()
+            },
+        ),));
+        let one_shot = ecs.insert((AnimatedSprite::new(
+            texture_atlas,
+            AnimationState {
+                animations: vec![frames],
+                secs_per_frame: 0.5,
+                looping: false,
+                ..Default::default()
+            },
+        ),));
+
+        let system = animate_sprite_system.into_system();
+        let mut finished_entities = vec![];
+        for _ in 0..4 {
+            ecs.run_single_run_system(&system);
+            ecs.update_events();
+            let events = ecs.resource::<Events<AnimationFinished>>().unwrap();
+            finished_entities.extend(events.iter().map(|event| event.entity));
+        }
+
+        assert_eq!(finished_entities, vec![looping, one_shot, looping]);
+        let animated_sprite = ecs.component::<AnimatedSprite>(one_shot).unwrap();
+        assert!(animated_sprite.animation.finished);
+        assert_eq!(animated_sprite.animation.current_frame, 1);
+    }
+
+    #[test]
+    fn pivot_transform_centers_the_quad() {
+        let rotation = Quaternion::from_axis_angle(&Vector3f::new(0.0, 0.0, 1.0), FRAC_PI_2);
+        let transform = Matrix4f::new_translation(&Vector3f::new(100.0, 100.0, 0.0))
+            * rotation.rotation_matrix()
+            * pivot_transform(Vector2f::new(0.5, 0.5), 10.0, 4.0);
+
+        let center = transform.transform_vec3(&Vector3f::new(5.0, 2.0, 0.0));
+        assert!((center.x - 100.0).abs() < 1e-4 && (center.y - 100.0).abs() < 1e-4);
+        let quads = [(0, transform, (10.0, 4.0))];
+        assert_eq!(topmost_quad_at(quads, Vector2f::new(101.0, 104.0)), Some(0));
+        assert_eq!(topmost_quad_at(quads, Vector2f::new(104.0, 101.0)), None);
+    }
+}
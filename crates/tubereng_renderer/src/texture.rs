@@ -1,43 +1,130 @@
 use std::ops::Deref;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub struct Id(usize);
+/// Identifier of a texture loaded in the [`Cache`].
+///
+/// Ids carry the generation of their slot so the id of an unloaded texture
+/// doesn't resolve to a texture loaded later in the same slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id {
+    index: usize,
+    generation: u32,
+}
+
+impl Id {
+    pub(crate) fn new(index: usize, generation: u32) -> Self {
+        Self { index, generation }
+    }
+}
+
 impl Deref for Id {
     type Target = usize;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.index
     }
 }
 
+struct Slot {
+    generation: u32,
+    texture: Option<(Info, wgpu::Texture)>,
+}
+
 pub struct Cache {
-    infos: Vec<Info>,
-    textures: Vec<wgpu::Texture>,
+    slots: Vec<Slot>,
+    free_slots: Vec<usize>,
 }
 
 impl Cache {
     #[must_use]
     pub fn new() -> Self {
         Self {
-            infos: vec![],
-            textures: vec![],
+            slots: vec![],
+            free_slots: vec![],
         }
     }
 
     pub fn insert(&mut self, texture_info: Info, texture: wgpu::Texture) -> Id {
-        self.infos.push(texture_info);
-        self.textures.push(texture);
-        Id(self.textures.len() - 1)
+        if let Some(index) = self.free_slots.pop() {
+            let slot = &mut self.slots[index];
+            slot.texture = Some((texture_info, texture));
+            return Id::new(index, slot.generation);
+        }
+
+        self.slots.push(Slot {
+            generation: 0,
+            texture: Some((texture_info, texture)),
+        });
+        Id::new(self.slots.len() - 1, 0)
+    }
+
+    /// Replaces a loaded texture, keeping its id. Returns false if the
+    /// texture wasn't loaded.
+    pub fn replace(&mut self, id: Id, texture_info: Info, texture: wgpu::Texture) -> bool {
+        let Some(slot) = self
+            .slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation && slot.texture.is_some())
+        else {
+            return false;
+        };
+
+        slot.texture = Some((texture_info, texture));
+        true
+    }
+
+    /// Drops a texture and frees its slot, returns false if the texture
+    /// wasn't loaded
+    pub fn unload(&mut self, id: Id) -> bool {
+        let Some(slot) = self
+            .slots
+            .get_mut(id.index)
+            .filter(|slot| slot.generation == id.generation && slot.texture.is_some())
+        else {
+            return false;
+        };
+
+        slot.texture = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(id.index);
+        true
     }
 
+    fn slot(&self, id: Id) -> Option<&(Info, wgpu::Texture)> {
+        self.slots
+            .get(id.index)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.texture.as_ref())
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if the texture isn't loaded
     #[must_use]
     pub fn info(&self, id: Id) -> &Info {
-        &self.infos[*id]
+        self.try_info(id).expect("The texture should be loaded")
+    }
+
+    #[must_use]
+    pub fn try_info(&self, id: Id) -> Option<&Info> {
+        self.slot(id).map(|(info, _)| info)
+    }
+
+    #[must_use]
+    pub fn contains(&self, id: Id) -> bool {
+        self.slot(id).is_some()
     }
 
+    /// # Panics
+    ///
+    /// Will panic if the texture isn't loaded
     #[must_use]
     pub fn get(&self, id: Id) -> &wgpu::Texture {
-        &self.textures[*id]
+        self.try_get(id).expect("The texture should be loaded")
+    }
+
+    #[must_use]
+    pub fn try_get(&self, id: Id) -> Option<&wgpu::Texture> {
+        self.slot(id).map(|(_, texture)| texture)
     }
 }
 
@@ -69,7 +156,7 @@ pub struct Descriptor<'a> {
     pub height: u32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Rect {
     pub x: f32,
     pub y: f32,
@@ -88,3 +175,117 @@ impl Rect {
         }
     }
 }
+
+/// A texture divided in a grid of cells of the same size. Cells are indexed
+/// row by row from the top-left corner of the texture.
+#[derive(Debug, Clone)]
+pub struct TextureAtlas {
+    pub texture_id: Id,
+    cell_width: f32,
+    cell_height: f32,
+    columns: usize,
+    rows: usize,
+    margin: f32,
+    padding: f32,
+}
+
+impl TextureAtlas {
+    /// Creates an atlas of `columns` by `rows` cells without spacing
+    #[must_use]
+    pub fn grid(
+        texture_id: Id,
+        cell_width: f32,
+        cell_height: f32,
+        columns: usize,
+        rows: usize,
+    ) -> Self {
+        Self {
+            texture_id,
+            cell_width,
+            cell_height,
+            columns,
+            rows,
+            margin: 0.0,
+            padding: 0.0,
+        }
+    }
+
+    /// Sets the space between the borders of the texture and the cells
+    #[must_use]
+    pub fn with_margin(mut self, margin: f32) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    /// Sets the space between two neighbouring cells
+    #[must_use]
+    pub fn with_padding(mut self, padding: f32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    #[must_use]
+    pub fn cell_count(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    /// Returns the region of a cell, `None` if the index is out of the atlas
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn cell(&self, index: usize) -> Option<Rect> {
+        if index >= self.cell_count() {
+            return None;
+        }
+
+        let column = (index % self.columns) as f32;
+        let row = (index / self.columns) as f32;
+        Some(Rect::new(
+            self.margin + column * (self.cell_width + self.padding),
+            self.margin + row * (self.cell_height + self.padding),
+            self.cell_width,
+            self.cell_height,
+        ))
+    }
+
+    /// Returns the regions of several cells, e.g. the frames of an animation
+    ///
+    /// # Panics
+    ///
+    /// Will panic if an index is out of the atlas
+    #[must_use]
+    pub fn cells(&self, indices: impl IntoIterator<Item = usize>) -> Vec<Rect> {
+        indices
+            .into_iter()
+            .map(|index| self.cell(index).expect("The cell should be in the atlas"))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn texture_atlas_cell() {
+        let atlas = TextureAtlas::grid(Id::new(0, 0), 16.0, 8.0, 4, 2);
+        assert_eq!(atlas.cell_count(), 8);
+        assert_eq!(atlas.cell(0), Some(Rect::new(0.0, 0.0, 16.0, 8.0)));
+        assert_eq!(atlas.cell(5), Some(Rect::new(16.0, 8.0, 16.0, 8.0)));
+        assert_eq!(atlas.cell(8), None);
+    }
+
+    #[test]
+    fn texture_atlas_cell_with_spacing() {
+        let atlas = TextureAtlas::grid(Id::new(0, 0), 16.0, 16.0, 4, 4)
+            .with_margin(1.0)
+            .with_padding(2.0);
+        assert_eq!(atlas.cell(0), Some(Rect::new(1.0, 1.0, 16.0, 16.0)));
+        assert_eq!(
+            atlas.cells([1, 4]),
+            vec![
+                Rect::new(19.0, 1.0, 16.0, 16.0),
+                Rect::new(1.0, 19.0, 16.0, 16.0)
+            ]
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::ops::Range;
+
+use tubereng_math::{matrix::Matrix4f, vector::Vector2f};
+
+use crate::{camera, texture};
+
+/// Component drawing a grid of tiles from a texture atlas with the active 2d
+/// camera.
+///
+/// Tiles are laid out from the origin of the entity, row by row, and only the
+/// tiles visible by the camera are drawn. The tiles of the atlas are indexed
+/// row by row from its top-left corner.
+#[derive(Debug)]
+pub struct Tilemap {
+    pub atlas: texture::Id,
+    pub tile_width: f32,
+    pub tile_height: f32,
+    columns: usize,
+    rows: usize,
+    tiles: Vec<Option<usize>>,
+}
+
+impl Tilemap {
+    /// Creates an empty tilemap of `columns` by `rows` tiles
+    #[must_use]
+    pub fn new(
+        atlas: texture::Id,
+        tile_width: f32,
+        tile_height: f32,
+        columns: usize,
+        rows: usize,
+    ) -> Self {
+        Self {
+            atlas,
+            tile_width,
+            tile_height,
+            columns,
+            rows,
+            tiles: vec![None; columns * rows],
+        }
+    }
+
+    #[must_use]
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    #[must_use]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the atlas index of a tile, `None` if the tile is empty or out
+    /// of the tilemap
+    #[must_use]
+    pub fn tile(&self, column: usize, row: usize) -> Option<usize> {
+        if column >= self.columns || row >= self.rows {
+            return None;
+        }
+
+        self.tiles[row * self.columns + column]
+    }
+
+    /// Sets the atlas index of a tile, `None` empties the tile
+    ///
+    /// # Panics
+    ///
+    /// Will panic if the tile is out of the tilemap
+    pub fn set_tile(&mut self, column: usize, row: usize, tile: Option<usize>) {
+        assert!(
+            column < self.columns && row < self.rows,
+            "The tile should be in the tilemap"
+        );
+        self.tiles[row * self.columns + column] = tile;
+    }
+
+    /// Returns the columns and rows of the tiles overlapping a rectangle
+    /// given by its corners in the local space of the tilemap
+    pub(crate) fn tiles_in_rect(
+        &self,
+        min: Vector2f,
+        max: Vector2f,
+    ) -> (Range<usize>, Range<usize>) {
+        let tile_range = |min: f32, max: f32, tile_size: f32, count: usize| {
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_sign_loss,
+                clippy::cast_precision_loss
+            )]
+            let clamp = |value: f32| value.clamp(0.0, count as f32) as usize;
+            clamp((min / tile_size).floor())..clamp((max / tile_size).ceil())
+        };
+
+        (
+            tile_range(min.x, max.x, self.tile_width, self.columns),
+            tile_range(min.y, max.y, self.tile_height, self.rows),
+        )
+    }
+
+    /// Returns the region of a tile in an atlas of the given width
+    pub(crate) fn atlas_rect(&self, tile: usize, atlas_width: f32) -> texture::Rect {
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_sign_loss,
+            clippy::cast_precision_loss
+        )]
+        let (column, row) = {
+            let atlas_columns = ((atlas_width / self.tile_width) as usize).max(1);
+            ((tile % atlas_columns) as f32, (tile / atlas_columns) as f32)
+        };
+
+        texture::Rect::new(
+            column * self.tile_width,
+            row * self.tile_height,
+            self.tile_width,
+            self.tile_height,
+        )
+    }
+}
+
+/// Returns the bounds of a rectangle of the xy plane transformed to the local
+/// space of a tilemap, `rect_to_world` maps the rectangle from its own space
+pub(crate) fn bounds_in_local_space(
+    rect_size: (f32, f32),
+    rect_to_world: &Matrix4f,
+    local_to_world: &Matrix4f,
+) -> Option<(Vector2f, Vector2f)> {
+    let rect_to_local = local_to_world.try_inverse()? * *rect_to_world;
+    Some(camera::transformed_rect_bounds(rect_size, &rect_to_local))
+}
+
+#[cfg(test)]
+mod tests {
+    use tubereng_math::vector::Vector3f;
+
+    use super::*;
+
+    #[test]
+    fn tiles_in_rect() {
+        let tilemap = Tilemap::new(texture::Id::new(0, 0), 16.0, 8.0, 10, 10);
+        assert_eq!(
+            tilemap.tiles_in_rect(Vector2f::new(20.0, -5.0), Vector2f::new(40.0, 9.0)),
+            (1..3, 0..2)
+        );
+        assert_eq!(
+            tilemap.tiles_in_rect(Vector2f::new(-100.0, 500.0), Vector2f::new(0.0, 600.0)),
+            (0..0, 10..10)
+        );
+    }
+
+    #[test]
+    fn camera_bounds_in_local_space() {
+        let camera_to_world = Matrix4f::new_translation(&Vector3f::new(-20.0, 0.0, 0.0));
+        let local_to_world = Matrix4f::new_translation(&Vector3f::new(80.0, 50.0, 0.0));
+        let (min, max) =
+            bounds_in_local_space((800.0, 600.0), &camera_to_world, &local_to_world).unwrap();
+        assert!((min.x + 100.0).abs() < 1e-3 && (min.y + 50.0).abs() < 1e-3);
+        assert!((max.x - 700.0).abs() < 1e-3 && (max.y - 550.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn tile_access() {
+        let mut tilemap = Tilemap::new(texture::Id::new(0, 0), 16.0, 16.0, 4, 2);
+        tilemap.set_tile(3, 1, Some(5));
+        assert_eq!(tilemap.tile(3, 1), Some(5));
+        assert_eq!(tilemap.tile(0, 0), None);
+        assert_eq!(tilemap.tile(4, 0), None);
+
+        let rect = tilemap.atlas_rect(5, 64.0);
+        assert_eq!((rect.x, rect.y), (16.0, 16.0));
+    }
+}
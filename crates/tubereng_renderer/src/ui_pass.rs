@@ -0,0 +1,977 @@
+use tubereng_ecs::Storage;
+use tubereng_math::matrix::Matrix4f;
+use wgpu::include_wgsl;
+
+use crate::{
+    bind_group_cache::TextureBindGroupCache, graphics_pipeline::RenderPass, texture, Color,
+    GraphicsState, PipelineCache, RenderStats, WindowSize,
+};
+
+/// Width of a glyph of the UI font in pixels, at its native size
+pub const GLYPH_WIDTH: f32 = 8.0;
+/// Height of a glyph of the UI font in pixels, at its native size
+pub const GLYPH_HEIGHT: f32 = 16.0;
+/// Distance between the tops of two lines of text in pixels, at the native
+/// size of the font
+pub const LINE_HEIGHT: f32 = GLYPH_HEIGHT;
+/// Number of glyphs per row in the font atlas
+const FONT_ATLAS_COLUMNS: u32 = 16;
+/// First character of the font atlas, the atlas contains the printable ASCII
+/// characters
+const FONT_ATLAS_FIRST_CHARACTER: char = ' ';
+const FONT_ATLAS_LAST_CHARACTER: char = '~';
+/// Character of the font atlas whose glyph is completely filled, used to draw
+/// plain quads
+const FONT_ATLAS_FILLED_CHARACTER: char = '\u{7f}';
+
+/// Resource mapping the UI coordinates to the window, recomputed every frame
+/// so the UI follows the window size. Without it, UI coordinates are window
+/// pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UiScaleMode {
+    /// The virtual resolution is stretched over the whole window
+    Stretch { width: f32, height: f32 },
+    /// The UI is `height` units tall, its width follows the aspect ratio of
+    /// the window
+    FitHeight(f32),
+    /// The UI is `width` units wide, its height follows the aspect ratio of
+    /// the window
+    FitWidth(f32),
+    /// The virtual resolution is scaled by the largest whole factor fitting
+    /// in the window and centered, the UI is clipped to it
+    Integer { width: f32, height: f32 },
+}
+
+impl UiScaleMode {
+    /// Returns the placement of the UI in a window of the given size
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn viewport(&self, window_size: WindowSize) -> UiViewport {
+        let window_width = window_size.width.max(1) as f32;
+        let window_height = window_size.height.max(1) as f32;
+        match *self {
+            UiScaleMode::Stretch { width, height } => UiViewport {
+                scale: (window_width / width, window_height / height),
+                offset: (0.0, 0.0),
+                clip: None,
+            },
+            UiScaleMode::FitHeight(height) => UiViewport::uniform(window_height / height),
+            UiScaleMode::FitWidth(width) => UiViewport::uniform(window_width / width),
+            UiScaleMode::Integer { width, height } => {
+                let scale = (window_width / width)
+                    .min(window_height / height)
+                    .floor()
+                    .max(1.0);
+                let offset = (
+                    ((window_width - width * scale) / 2.0).floor(),
+                    ((window_height - height * scale) / 2.0).floor(),
+                );
+                UiViewport {
+                    scale: (scale, scale),
+                    offset,
+                    clip: Some((width * scale, height * scale)),
+                }
+            }
+        }
+    }
+}
+
+/// The placement of the UI in the window: a UI point `(x, y)` lands on the
+/// window pixel `(x * scale.0 + offset.0, y * scale.1 + offset.1)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UiViewport {
+    pub scale: (f32, f32),
+    pub offset: (f32, f32),
+    /// The size in window pixels of the region the UI is clipped to,
+    /// starting at the offset
+    pub clip: Option<(f32, f32)>,
+}
+
+impl UiViewport {
+    /// The placement of a UI in window pixels
+    pub const IDENTITY: UiViewport = UiViewport {
+        scale: (1.0, 1.0),
+        offset: (0.0, 0.0),
+        clip: None,
+    };
+
+    fn uniform(scale: f32) -> Self {
+        Self {
+            scale: (scale, scale),
+            offset: (0.0, 0.0),
+            clip: None,
+        }
+    }
+
+    /// Converts a position in window pixels, such as the cursor position, to
+    /// UI coordinates
+    #[must_use]
+    pub fn window_to_ui(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            (x - self.offset.0) / self.scale.0,
+            (y - self.offset.1) / self.scale.1,
+        )
+    }
+
+    /// Returns the projection of the UI coordinates for a window of the given
+    /// size
+    #[allow(clippy::cast_precision_loss)]
+    fn projection(&self, window_size: WindowSize) -> Matrix4f {
+        let (left, top) = self.window_to_ui(0.0, 0.0);
+        let (right, bottom) =
+            self.window_to_ui(window_size.width as f32, window_size.height as f32);
+        Matrix4f::new_orthographic(left, right, bottom, top, -1.0, 1.0)
+    }
+
+    /// Returns the clipping region as a scissor rect `[x, y, width, height]`
+    /// within a window of the given size
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn scissor_rect(&self, window_size: WindowSize) -> Option<[u32; 4]> {
+        let (width, height) = self.clip?;
+        let x = (self.offset.0.max(0.0) as u32).min(window_size.width);
+        let y = (self.offset.1.max(0.0) as u32).min(window_size.height);
+        Some([
+            x,
+            y,
+            (width as u32).min(window_size.width - x),
+            (height as u32).min(window_size.height - y),
+        ])
+    }
+}
+
+pub enum DrawCommand {
+    DrawUiQuad(DrawUiQuadCommand),
+    DrawUiText(DrawUiTextCommand),
+    DrawUiNineSlice(DrawUiNineSliceCommand),
+}
+
+pub struct DrawUiQuadCommand {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub color: Color,
+}
+
+pub struct DrawUiTextCommand {
+    pub x: f32,
+    pub y: f32,
+    pub text: String,
+    /// The height of the glyphs in pixels, [`GLYPH_HEIGHT`] draws the font
+    /// at its native size
+    pub size: f32,
+    /// The width in pixels the text is wrapped to, see [`wrap_text`]
+    pub max_width: Option<f32>,
+    pub color: Color,
+}
+
+/// Wraps a text to a maximum width in pixels by replacing spaces with line
+/// breaks, for glyphs of the given size. Words longer than a line are broken
+/// at the last character fitting in the line.
+#[must_use]
+pub fn wrap_text(text: &str, max_width: f32, size: f32) -> String {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let max_line_length = ((max_width / (GLYPH_WIDTH * size / GLYPH_HEIGHT)) as usize).max(1);
+    let mut wrapped_text = String::with_capacity(text.len());
+    for (line_index, line) in text.split('\n').enumerate() {
+        if line_index > 0 {
+            wrapped_text.push('\n');
+        }
+
+        let mut line_length = 0;
+        for word in line.split(' ') {
+            let word_length = word.chars().count();
+            if line_length > 0 && line_length + 1 + word_length <= max_line_length {
+                wrapped_text.push(' ');
+                line_length += 1;
+            } else if line_length > 0 {
+                wrapped_text.push('\n');
+                line_length = 0;
+            }
+
+            for character in word.chars() {
+                if line_length == max_line_length {
+                    wrapped_text.push('\n');
+                    line_length = 0;
+                }
+                wrapped_text.push(character);
+                line_length += 1;
+            }
+        }
+    }
+
+    wrapped_text
+}
+
+/// Returns the characters of a text with the rect of their glyph, given as
+/// `[left, top, right, bottom]`. Glyphs are scaled to the size of the text.
+fn layout_text(command: &DrawUiTextCommand) -> Vec<(char, [f32; 4])> {
+    let glyph_width = GLYPH_WIDTH * command.size / GLYPH_HEIGHT;
+    let line_height = LINE_HEIGHT * command.size / GLYPH_HEIGHT;
+    let text = match command.max_width {
+        Some(max_width) => wrap_text(&command.text, max_width, command.size),
+        None => command.text.clone(),
+    };
+    let (left, size) = (command.x, command.size);
+    let mut x = command.x;
+    let mut y = command.y;
+    text.chars()
+        .filter_map(move |character| {
+            if character == '\n' {
+                x = left;
+                y += line_height;
+                return None;
+            }
+
+            let rect = [x, y, x + glyph_width, y + size];
+            x += glyph_width;
+            Some((character, rect))
+        })
+        .collect()
+}
+
+/// Widths of the borders of a nine-slice, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Insets {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Insets {
+    #[must_use]
+    pub fn uniform(inset: f32) -> Self {
+        Self {
+            left: inset,
+            top: inset,
+            right: inset,
+            bottom: inset,
+        }
+    }
+}
+
+/// Draws a region of a texture stretched over a rect, keeping the corners at
+/// their size and stretching the edges along a single axis
+pub struct DrawUiNineSliceCommand {
+    pub texture: texture::Id,
+    pub texture_rect: texture::Rect,
+    /// The borders of the nine-slice, both in the texture region and on
+    /// screen
+    pub insets: Insets,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub color: Color,
+}
+
+#[repr(C)]
+#[derive(bytemuck::Zeroable, bytemuck::Pod, Debug, Copy, Clone)]
+struct Vertex {
+    position: [f32; 2],
+    texture_coordinates: [f32; 2],
+    color: [f32; 4],
+}
+
+impl Vertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    fn layout<'a>() -> wgpu::VertexBufferLayout<'a> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(bytemuck::Pod, bytemuck::Zeroable, Clone, Copy)]
+struct PassUniform {
+    view_proj: [[f32; 4]; 4],
+}
+
+/// Vertices of the pass drawn with the same texture, up to the start of the
+/// next batch
+struct Batch {
+    texture_id: texture::Id,
+    start_vertex_index: u32,
+}
+
+/// Renders the UI draw commands queued on the [`GraphicsState`] on top of
+/// the scene
+pub struct Pass {
+    vertices: Vec<Vertex>,
+    vertex_count: u32,
+    batches: Vec<Batch>,
+    vertex_buffer: wgpu::Buffer,
+    #[allow(clippy::struct_field_names)]
+    pass_uniform_buffer: wgpu::Buffer,
+    #[allow(clippy::struct_field_names)]
+    pass_uniform_bind_group_layout: wgpu::BindGroupLayout,
+    #[allow(clippy::struct_field_names)]
+    pass_uniform_bind_group: wgpu::BindGroup,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_groups: TextureBindGroupCache,
+    scissor_rect: Option<[u32; 4]>,
+}
+
+impl Pass {
+    const MAX_VERTICES: usize = 60_000;
+
+    #[must_use]
+    pub fn new(device: &wgpu::Device) -> Self {
+        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ui_pass_vertex_buffer"),
+            size: (Self::MAX_VERTICES * std::mem::size_of::<Vertex>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("ui_pass_texture_bind_group_layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let pass_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ui_pass_uniform"),
+            size: std::mem::size_of::<PassUniform>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pass_uniform_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("ui_pass_uniform_bind_group_layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+
+        let pass_uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("ui_pass_uniform_bind_group"),
+            layout: &pass_uniform_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: pass_uniform_buffer.as_entire_binding(),
+            }],
+        });
+
+        Self {
+            vertices: vec![],
+            vertex_count: 0,
+            batches: vec![],
+            vertex_buffer,
+            pass_uniform_buffer,
+            pass_uniform_bind_group_layout,
+            pass_uniform_bind_group,
+            texture_bind_group_layout,
+            texture_bind_groups: TextureBindGroupCache::new(),
+            scissor_rect: None,
+        }
+    }
+
+    fn queue_quad_command(
+        &mut self,
+        command: &DrawUiQuadCommand,
+        font_texture_info: &texture::Info,
+    ) {
+        // The texture coordinates are taken inside the filled glyph so that
+        // sampling never bleeds on its neighbours
+        let [u, v, u_end, v_end] =
+            glyph_texture_rect(FONT_ATLAS_FILLED_CHARACTER, font_texture_info);
+        let (u_center, v_center) = (f32::midpoint(u, u_end), f32::midpoint(v, v_end));
+        self.queue_quad(
+            [
+                command.x,
+                command.y,
+                command.x + command.width,
+                command.y + command.height,
+            ],
+            [u_center, v_center, u_center, v_center],
+            (&command.color).into(),
+        );
+    }
+
+    fn queue_text(&mut self, command: &DrawUiTextCommand, font_texture_info: &texture::Info) {
+        let color = (&command.color).into();
+        for (character, rect) in layout_text(command) {
+            let texture_rect =
+                if (FONT_ATLAS_FIRST_CHARACTER..=FONT_ATLAS_LAST_CHARACTER).contains(&character) {
+                    glyph_texture_rect(character, font_texture_info)
+                } else {
+                    glyph_texture_rect('?', font_texture_info)
+                };
+
+            self.queue_quad(rect, texture_rect, color);
+        }
+    }
+
+    fn queue_nine_slice(&mut self, command: &DrawUiNineSliceCommand, texture_info: &texture::Info) {
+        #[allow(clippy::cast_precision_loss)]
+        let texture_size = (texture_info.width as f32, texture_info.height as f32);
+        let color = (&command.color).into();
+        for (rect, texture_rect) in nine_slice_quads(command, texture_size) {
+            self.queue_quad(rect, texture_rect, color);
+        }
+    }
+
+    /// Starts a new batch if the following quads use another texture than
+    /// the previous ones
+    fn use_texture(&mut self, texture_id: texture::Id) {
+        if self
+            .batches
+            .last()
+            .is_none_or(|batch| batch.texture_id != texture_id)
+        {
+            self.batches.push(Batch {
+                texture_id,
+                start_vertex_index: u32::try_from(self.vertices.len()).unwrap(),
+            });
+        }
+    }
+
+    /// Queues the vertices of a quad, `rect` and `texture_rect` are given as
+    /// `[left, top, right, bottom]`
+    fn queue_quad(&mut self, rect: [f32; 4], texture_rect: [f32; 4], color: [f32; 4]) {
+        let [left, top, right, bottom] = rect;
+        let [u, v, u_end, v_end] = texture_rect;
+        let top_left = Vertex {
+            position: [left, top],
+            texture_coordinates: [u, v],
+            color,
+        };
+        let bottom_left = Vertex {
+            position: [left, bottom],
+            texture_coordinates: [u, v_end],
+            color,
+        };
+        let bottom_right = Vertex {
+            position: [right, bottom],
+            texture_coordinates: [u_end, v_end],
+            color,
+        };
+        let top_right = Vertex {
+            position: [right, top],
+            texture_coordinates: [u_end, v],
+            color,
+        };
+        self.vertices.extend_from_slice(&[
+            top_left,
+            bottom_left,
+            bottom_right,
+            bottom_right,
+            top_right,
+            top_left,
+        ]);
+    }
+
+    #[must_use]
+    fn create_ui_pass_pipeline(
+        device: &wgpu::Device,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        surface_texture_format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let shader_module = device.create_shader_module(include_wgsl!("./ui_pass.wgsl"));
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("ui_pass_pipeline"),
+                bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[Vertex::layout()],
+            },
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                unclipped_depth: false,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_texture_format,
+                    blend: Some(wgpu::BlendState {
+                        color: wgpu::BlendComponent {
+                            src_factor: wgpu::BlendFactor::SrcAlpha,
+                            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                            operation: wgpu::BlendOperation::Add,
+                        },
+                        alpha: wgpu::BlendComponent::default(),
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+    }
+
+    /// Sets the number of frames the bind group of a texture is kept after
+    /// the last frame it was used in, 300 frames by default
+    #[must_use]
+    pub fn with_bind_group_eviction_threshold(mut self, frames: u32) -> Self {
+        self.texture_bind_groups.set_eviction_threshold(frames);
+        self
+    }
+
+    fn create_texture_bind_group_for_texture_if_required(
+        &mut self,
+        texture: texture::Id,
+        filter: wgpu::FilterMode,
+        gfx: &GraphicsState<'_>,
+    ) {
+        if !self.texture_bind_groups.mark_used(texture) {
+            let texture_id = texture;
+            let texture = gfx.texture_cache.get(texture);
+            let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            let texture_sampler = gfx.device().create_sampler(&wgpu::SamplerDescriptor {
+                label: None,
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: filter,
+                min_filter: filter,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            });
+
+            let texture_bind_group = gfx.device().create_bind_group(&wgpu::BindGroupDescriptor {
+                label: None,
+                layout: &self.texture_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&texture_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&texture_sampler),
+                    },
+                ],
+            });
+
+            self.texture_bind_groups
+                .insert(texture_id, texture_bind_group);
+        }
+    }
+}
+
+/// Returns the nine quads of a nine-slice as `(rect, texture_rect)` pairs,
+/// both given as `[left, top, right, bottom]`, row by row from the top-left
+/// corner.
+///
+/// The borders are shrunk proportionally if the rect is too small to fit
+/// them.
+fn nine_slice_quads(
+    command: &DrawUiNineSliceCommand,
+    texture_size: (f32, f32),
+) -> [([f32; 4], [f32; 4]); 9] {
+    let insets = &command.insets;
+    let horizontal_scale = (command.width / (insets.left + insets.right)).min(1.0);
+    let vertical_scale = (command.height / (insets.top + insets.bottom)).min(1.0);
+    let xs = [
+        command.x,
+        command.x + insets.left * horizontal_scale,
+        command.x + command.width - insets.right * horizontal_scale,
+        command.x + command.width,
+    ];
+    let ys = [
+        command.y,
+        command.y + insets.top * vertical_scale,
+        command.y + command.height - insets.bottom * vertical_scale,
+        command.y + command.height,
+    ];
+
+    let region = &command.texture_rect;
+    let (texture_w, texture_h) = texture_size;
+    let us = [
+        region.x,
+        region.x + insets.left,
+        region.x + region.width - insets.right,
+        region.x + region.width,
+    ]
+    .map(|u| u / texture_w);
+    let vs = [
+        region.y,
+        region.y + insets.top,
+        region.y + region.height - insets.bottom,
+        region.y + region.height,
+    ]
+    .map(|v| v / texture_h);
+
+    std::array::from_fn(|i| {
+        let (column, row) = (i % 3, i / 3);
+        (
+            [xs[column], ys[row], xs[column + 1], ys[row + 1]],
+            [us[column], vs[row], us[column + 1], vs[row + 1]],
+        )
+    })
+}
+
+/// Returns the texture coordinates of the glyph of a character in the font
+/// atlas as `[left, top, right, bottom]`, inset by half a texel so the linear
+/// filtering never samples the neighbouring glyphs of the unpadded atlas
+#[allow(clippy::cast_precision_loss)]
+fn glyph_texture_rect(character: char, font_texture_info: &texture::Info) -> [f32; 4] {
+    let texture_w = font_texture_info.width as f32;
+    let texture_h = font_texture_info.height as f32;
+    let glyph_index = character as u32 - FONT_ATLAS_FIRST_CHARACTER as u32;
+    let x = (glyph_index % FONT_ATLAS_COLUMNS) as f32 * GLYPH_WIDTH;
+    let y = (glyph_index / FONT_ATLAS_COLUMNS) as f32 * GLYPH_HEIGHT;
+    [
+        (x + 0.5) / texture_w,
+        (y + 0.5) / texture_h,
+        (x + GLYPH_WIDTH - 0.5) / texture_w,
+        (y + GLYPH_HEIGHT - 0.5) / texture_h,
+    ]
+}
+
+impl RenderPass for Pass {
+    fn prepare(&mut self, storage: &Storage) {
+        let mut gfx = storage
+            .resource_mut::<GraphicsState>()
+            .expect("Graphics state should be present");
+        let draw_commands = std::mem::take(&mut gfx.ui_draw_commands);
+        let font_texture_id = gfx
+            .font_texture_id
+            .expect("The font texture should have been loaded");
+        for &texture in gfx.unloaded_textures() {
+            self.texture_bind_groups.remove(texture);
+        }
+        // The font is filtered linearly so scaled text isn't blocky, the
+        // glyph texture coordinates are inset to not bleed on the neighbours
+        self.create_texture_bind_group_for_texture_if_required(
+            font_texture_id,
+            wgpu::FilterMode::Linear,
+            &gfx,
+        );
+
+        let window_size = *gfx.window_size();
+        let viewport = storage
+            .resource::<UiScaleMode>()
+            .map_or(UiViewport::IDENTITY, |scale_mode| {
+                scale_mode.viewport(window_size)
+            });
+        let projection = viewport.projection(window_size);
+        self.scissor_rect = viewport.scissor_rect(window_size);
+        gfx.queue().write_buffer(
+            &self.pass_uniform_buffer,
+            0,
+            bytemuck::cast_slice(&[PassUniform {
+                view_proj: projection.into(),
+            }]),
+        );
+
+        self.vertices.clear();
+        self.batches.clear();
+        let font_texture_info = gfx.texture_cache.info(font_texture_id);
+        for draw_command in &draw_commands {
+            match draw_command {
+                DrawCommand::DrawUiQuad(command) => {
+                    self.use_texture(font_texture_id);
+                    self.queue_quad_command(command, font_texture_info);
+                }
+                DrawCommand::DrawUiText(command) => {
+                    self.use_texture(font_texture_id);
+                    self.queue_text(command, font_texture_info);
+                }
+                DrawCommand::DrawUiNineSlice(command) => {
+                    let Some(texture_info) = gfx.texture_cache.try_info(command.texture) else {
+                        continue;
+                    };
+                    self.create_texture_bind_group_for_texture_if_required(
+                        command.texture,
+                        wgpu::FilterMode::Nearest,
+                        &gfx,
+                    );
+                    self.use_texture(command.texture);
+                    self.queue_nine_slice(command, texture_info);
+                }
+            }
+        }
+        self.texture_bind_groups.evict_unused();
+
+        self.vertices.truncate(Self::MAX_VERTICES);
+        gfx.queue()
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.vertex_count = u32::try_from(self.vertices.len()).unwrap();
+    }
+
+    fn execute(
+        &self,
+        gfx: &mut GraphicsState,
+        encoder: &mut wgpu::CommandEncoder,
+        surface_texture_view: &wgpu::TextureView,
+        storage: &Storage,
+    ) {
+        if self.vertex_count == 0 {
+            return;
+        }
+
+        let mut pipeline_cache = storage.resource_mut::<PipelineCache>().unwrap();
+        if !pipeline_cache.has("ui_pass_pipeline") {
+            pipeline_cache.insert(
+                "ui_pass_pipeline",
+                Self::create_ui_pass_pipeline(
+                    gfx.device(),
+                    &[
+                        &self.pass_uniform_bind_group_layout,
+                        &self.texture_bind_group_layout,
+                    ],
+                    gfx.surface_texture_format(),
+                ),
+            );
+        }
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("ui_pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: surface_texture_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        rpass.set_pipeline(pipeline_cache.get("ui_pass_pipeline").unwrap());
+        rpass.set_bind_group(0, &self.pass_uniform_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        if let Some([x, y, width, height]) = self.scissor_rect {
+            rpass.set_scissor_rect(x, y, width, height);
+        }
+        let mut render_stats = storage.resource_mut::<RenderStats>();
+        let mut bound_texture = None;
+        for (index, batch) in self.batches.iter().enumerate() {
+            let end_vertex_index = self
+                .batches
+                .get(index + 1)
+                .map_or(self.vertex_count, |next_batch| {
+                    next_batch.start_vertex_index
+                })
+                .min(self.vertex_count);
+            if batch.start_vertex_index >= end_vertex_index {
+                continue;
+            }
+
+            if bound_texture != Some(batch.texture_id) {
+                rpass.set_bind_group(1, self.texture_bind_groups.get(batch.texture_id), &[]);
+                bound_texture = Some(batch.texture_id);
+                if let Some(render_stats) = render_stats.as_mut() {
+                    render_stats.bind_group_switches += 1;
+                }
+            }
+            rpass.draw(batch.start_vertex_index..end_vertex_index, 0..1);
+            if let Some(render_stats) = render_stats.as_mut() {
+                render_stats.batches += 1;
+                render_stats.record_draw(end_vertex_index - batch.start_vertex_index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn glyph_texture_rect_is_inset_by_half_a_texel() {
+        let font_texture_info = texture::Info {
+            width: 128,
+            height: 96,
+        };
+        // 'A' is the 34th character of the atlas: column 1 of row 2
+        let [u, v, u_end, v_end] = glyph_texture_rect('A', &font_texture_info);
+        assert_eq!(u, 8.5 / 128.0);
+        assert_eq!(v, 32.5 / 96.0);
+        assert_eq!(u_end, 15.5 / 128.0);
+        assert_eq!(v_end, 47.5 / 96.0);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn ui_scale_mode_viewports() {
+        let window_size = WindowSize {
+            width: 1000,
+            height: 500,
+        };
+
+        let viewport = UiScaleMode::Stretch {
+            width: 500.0,
+            height: 500.0,
+        }
+        .viewport(window_size);
+        assert_eq!(viewport.window_to_ui(1000.0, 500.0), (500.0, 500.0));
+
+        let viewport = UiScaleMode::FitHeight(250.0).viewport(window_size);
+        assert_eq!(viewport.window_to_ui(1000.0, 500.0), (500.0, 250.0));
+
+        let viewport = UiScaleMode::FitWidth(250.0).viewport(window_size);
+        assert_eq!(viewport.window_to_ui(1000.0, 500.0), (250.0, 125.0));
+
+        let viewport = UiScaleMode::Integer {
+            width: 320.0,
+            height: 180.0,
+        }
+        .viewport(window_size);
+        assert_eq!(viewport.scale, (2.0, 2.0));
+        assert_eq!(viewport.offset, (180.0, 70.0));
+        assert_eq!(viewport.window_to_ui(180.0, 70.0), (0.0, 0.0));
+        assert_eq!(
+            viewport.scissor_rect(window_size),
+            Some([180, 70, 640, 360])
+        );
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn nine_slice_quads_layout() {
+        let command = DrawUiNineSliceCommand {
+            texture: texture::Id::new(0, 0),
+            texture_rect: texture::Rect::new(16.0, 0.0, 16.0, 16.0),
+            insets: Insets::uniform(4.0),
+            x: 10.0,
+            y: 20.0,
+            width: 100.0,
+            height: 50.0,
+            color: Color::WHITE,
+        };
+        let quads = nine_slice_quads(&command, (32.0, 16.0));
+
+        assert_eq!(quads.len(), 9);
+        // Corners keep their size
+        assert_eq!(quads[0].0, [10.0, 20.0, 14.0, 24.0]);
+        assert_eq!(quads[8].0, [106.0, 66.0, 110.0, 70.0]);
+        // Edges and center are stretched
+        assert_eq!(quads[1].0, [14.0, 20.0, 106.0, 24.0]);
+        assert_eq!(quads[3].0, [10.0, 24.0, 14.0, 66.0]);
+        assert_eq!(quads[4].0, [14.0, 24.0, 106.0, 66.0]);
+
+        assert_eq!(quads[0].1, [0.5, 0.0, 0.625, 0.25]);
+        assert_eq!(quads[4].1, [0.625, 0.25, 0.875, 0.75]);
+        assert_eq!(quads[8].1, [0.875, 0.75, 1.0, 1.0]);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn nine_slice_quads_shrink_borders() {
+        let command = DrawUiNineSliceCommand {
+            texture: texture::Id::new(0, 0),
+            texture_rect: texture::Rect::new(0.0, 0.0, 16.0, 16.0),
+            insets: Insets::uniform(4.0),
+            x: 0.0,
+            y: 0.0,
+            width: 4.0,
+            height: 16.0,
+            color: Color::WHITE,
+        };
+        let quads = nine_slice_quads(&command, (16.0, 16.0));
+
+        assert_eq!(quads[0].0, [0.0, 0.0, 2.0, 4.0]);
+        assert_eq!(quads[1].0, [2.0, 0.0, 2.0, 4.0]);
+        assert_eq!(quads[2].0, [2.0, 0.0, 4.0, 4.0]);
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn layout_text_scales_glyphs() {
+        let command = DrawUiTextCommand {
+            x: 10.0,
+            y: 20.0,
+            text: "ab\nc".to_string(),
+            size: GLYPH_HEIGHT * 2.0,
+            max_width: None,
+            color: Color::WHITE,
+        };
+        let glyphs = layout_text(&command);
+
+        assert_eq!(
+            glyphs,
+            vec![
+                ('a', [10.0, 20.0, 26.0, 52.0]),
+                ('b', [26.0, 20.0, 42.0, 52.0]),
+                ('c', [10.0, 52.0, 26.0, 84.0]),
+            ]
+        );
+    }
+
+    #[test]
+    fn wrap_text_breaks_lines_between_words() {
+        let max_width = 10.0 * GLYPH_WIDTH;
+        assert_eq!(
+            wrap_text("Hello there, general Kenobi", max_width, GLYPH_HEIGHT),
+            "Hello\nthere,\ngeneral\nKenobi"
+        );
+        assert_eq!(
+            wrap_text("a b c\nd e", max_width, GLYPH_HEIGHT),
+            "a b c\nd e"
+        );
+        // Halving the size doubles the number of glyphs per line
+        assert_eq!(
+            wrap_text("Hello there", max_width, GLYPH_HEIGHT / 2.0),
+            "Hello there"
+        );
+    }
+
+    #[test]
+    fn wrap_text_breaks_long_words() {
+        assert_eq!(
+            wrap_text("abcdefgh ij", 3.0 * GLYPH_WIDTH, GLYPH_HEIGHT),
+            "abc\ndef\ngh\nij"
+        );
+    }
+}
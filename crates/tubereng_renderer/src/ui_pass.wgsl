@@ -0,0 +1,39 @@
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) texture_coordinates: vec2<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texture_coordinates: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+struct PassUniform {
+    view_proj: mat4x4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> u_pass: PassUniform;
+
+@group(1) @binding(0)
+var t_font: texture_2d<f32>;
+@group(1) @binding(1)
+var s_font: sampler;
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = u_pass.view_proj * vec4<f32>(in.position, 0.0, 1.0);
+    out.texture_coordinates = in.texture_coordinates;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let sample = textureSample(t_font, s_font, in.texture_coordinates);
+    return vec4<f32>(in.color.rgb, in.color.a * sample.a);
+}
@@ -1,5 +1,6 @@
 #![warn(clippy::pedantic)]
 
+use std::collections::HashMap;
 use std::sync::Arc;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -7,131 +8,402 @@ use std::time::Instant;
 #[cfg(target_arch = "wasm32")]
 use web_time::Instant;
 
-use tubereng_engine::Engine;
+use log::warn;
+use tubereng_asset::AssetLoader;
+use tubereng_engine::{
+    Cursor, CursorGrabMode, CursorIcon, Engine, FullscreenMode, WindowConfiguration,
+};
+use tubereng_image::ImageLoader;
 use tubereng_input::{keyboard::Key, mouse::Button, Input};
+use tubereng_renderer::{RendererInitError, WindowId};
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize},
     error::{EventLoopError, OsError},
-    event::{DeviceEvent, Event, KeyEvent, MouseButton, WindowEvent},
-    event_loop::EventLoop,
-    keyboard::{KeyCode, PhysicalKey},
-    window::WindowBuilder,
+    event::{
+        DeviceEvent, ElementState, Event, Ime, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent,
+    },
+    event_loop::{EventLoop, EventLoopWindowTarget},
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
+    monitor::MonitorHandle,
+    window::{self, Fullscreen, Icon, Window, WindowBuilder},
 };
 
+/// The number of pixels of a touchpad scroll counted as one mouse wheel line
+const PIXELS_PER_SCROLL_LINE: f64 = 20.0;
+
 #[derive(Debug)]
 pub enum WinitError {
     EventLoopCreationFailed(EventLoopError),
     EventLoopRunningFailed(EventLoopError),
     WindowCreationFailed(OsError),
     WindowHandleFetchingFailed(raw_window_handle::HandleError),
+    RendererInitFailed(RendererInitError),
 }
 
 pub struct WinitTuberRunner;
 impl WinitTuberRunner {
     /// Starts the application using a winit window.
     ///
+    /// Alt+Enter toggles the fullscreen mode of the window. The shutdown
+    /// systems of the engine run when the event loop exits, after the window
+    /// is closed or a system requested the application to exit.
+    ///
+    /// The secondary windows requested by the systems are created at the end
+    /// of the frame, closing one of them only closes that window. The cursor
+    /// changes made through the engine take effect immediately, the ones
+    /// made by the systems at the end of the frame. On the web the cursor
+    /// icons map to the CSS cursors.
+    ///
     /// # Errors
     ///
-    /// Will return [`Err`] if the event loop cannot be created or run, or if
-    /// the window cannot be created.
+    /// Will return [`Err`] if the event loop cannot be created or run, if the
+    /// window cannot be created or if the renderer cannot be initialized.
     ///
     /// # Panics
     ///
     /// For wasm32, might panic if the window canvas cannot be added to the page.
     pub async fn run(mut engine: Engine) -> Result<(), WinitError> {
         let event_loop = EventLoop::new().map_err(WinitError::EventLoopCreationFailed)?;
-        let window = Arc::new(
-            WindowBuilder::new()
-                .with_title(engine.application_title())
-                .with_resizable(false)
-                .with_inner_size(PhysicalSize::new(800, 600))
-                .build(&event_loop)
-                .map_err(WinitError::WindowCreationFailed)?,
-        );
-        #[cfg(target_arch = "wasm32")]
-        {
-            use winit::platform::web::WindowExtWebSys;
-            let _ = window.request_inner_size(PhysicalSize::new(800, 600));
-
-            web_sys::window()
-                .and_then(|win| win.document())
-                .and_then(|doc| {
-                    let dst = doc.get_element_by_id(engine.application_title())?;
-                    let canvas = web_sys::Element::from(window.canvas()?);
-                    dst.append_child(&canvas).ok()?;
-                    Some(())
-                })
-                .expect("Couldn't append canvas to document body.");
-        }
-        engine.init_graphics(window.clone()).await;
-        let mut last_frame_start_instant = Instant::now();
+        let window = create_window(&event_loop, &engine)?;
+        engine
+            .init_graphics(window.clone())
+            .await
+            .map_err(WinitError::RendererInitFailed)?;
+        let cursor_window = window.clone();
+        let cursor_visible = engine.window_configuration().cursor_visible();
+        engine.on_cursor_changed(move |cursor| {
+            apply_cursor(&cursor_window, cursor, cursor_visible);
+        });
+        let mut state = RunnerState {
+            engine,
+            window,
+            secondary_windows: HashMap::new(),
+            cursor: Cursor::default(),
+            last_frame_start_instant: Instant::now(),
+            modifiers: ModifiersState::empty(),
+        };
         event_loop
             .run(move |event, elwt| match event {
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    ..
-                } => {
-                    elwt.exit();
+                Event::WindowEvent { window_id, event } if window_id != state.window.id() => {
+                    state.on_secondary_window_event(window_id, &event);
                 }
+                Event::WindowEvent { event, .. } => state.on_window_event(&event, elwt),
                 Event::DeviceEvent {
                     event: DeviceEvent::MouseMotion { delta },
                     ..
-                } => engine.on_input(Input::MouseMotion(delta)),
-                Event::WindowEvent {
-                    event:
-                        WindowEvent::CursorMoved {
-                            position: PhysicalPosition { x, y },
-                            ..
-                        },
-                    ..
-                } => engine.on_input(Input::CursorMoved((x, y))),
-                Event::WindowEvent {
-                    event: WindowEvent::RedrawRequested,
-                    ..
-                } => {
-                    window.request_redraw();
-                    let frame_start_instant = Instant::now();
-                    let delta_time = (frame_start_instant - last_frame_start_instant).as_secs_f32();
-                    engine.update(delta_time);
-                    last_frame_start_instant = frame_start_instant;
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::MouseInput { state, button, .. },
-                    ..
-                } => match state {
-                    winit::event::ElementState::Pressed => {
-                        engine.on_input(Input::MouseButtonDown(WinitButton(button).into()));
+                } => state.engine.on_input(Input::MouseMotion(delta)),
+                #[cfg(not(target_arch = "wasm32"))]
+                Event::AboutToWait => state.on_about_to_wait(elwt),
+                Event::LoopExiting => state.engine.shutdown(),
+                _ => {}
+            })
+            .map_err(WinitError::EventLoopRunningFailed)?;
+
+        Ok(())
+    }
+}
+
+struct RunnerState {
+    engine: Engine,
+    window: Arc<Window>,
+    secondary_windows: HashMap<window::WindowId, Arc<Window>>,
+    /// The cursor applied to the window
+    cursor: Cursor,
+    last_frame_start_instant: Instant,
+    modifiers: ModifiersState,
+}
+
+impl RunnerState {
+    fn on_window_event(&mut self, event: &WindowEvent, elwt: &EventLoopWindowTarget<()>) {
+        let engine = &mut self.engine;
+        match *event {
+            WindowEvent::CloseRequested => {
+                elwt.exit();
+            }
+            WindowEvent::Resized(PhysicalSize { width, height }) => {
+                engine.on_window_resized(width, height);
+            }
+            WindowEvent::CursorMoved {
+                position: PhysicalPosition { x, y },
+                ..
+            } => engine.on_input(Input::CursorMoved((x, y))),
+            WindowEvent::MouseWheel { delta, .. } => {
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (f64::from(x), f64::from(y)),
+                    MouseScrollDelta::PixelDelta(PhysicalPosition { x, y }) => {
+                        (x / PIXELS_PER_SCROLL_LINE, y / PIXELS_PER_SCROLL_LINE)
                     }
-                    winit::event::ElementState::Released => {
-                        engine.on_input(Input::MouseButtonUp(WinitButton(button).into()));
+                };
+                engine.on_input(Input::MouseWheel(delta));
+            }
+            WindowEvent::RedrawRequested => {
+                // With a frame rate cap, the next redraw is requested once
+                // the target frame duration has elapsed
+                if cfg!(target_arch = "wasm32") || engine.target_frame_duration().is_none() {
+                    self.window.request_redraw();
+                }
+                let frame_start_instant = Instant::now();
+                let delta_time =
+                    (frame_start_instant - self.last_frame_start_instant).as_secs_f32();
+                engine.update(delta_time);
+                self.last_frame_start_instant = frame_start_instant;
+                if let Some(fullscreen_mode) = engine.take_fullscreen_change() {
+                    self.window
+                        .set_fullscreen(winit_fullscreen(fullscreen_mode, &self.window));
+                }
+                let cursor = self.engine.cursor();
+                if cursor != self.cursor {
+                    apply_cursor(
+                        &self.window,
+                        &cursor,
+                        self.engine.window_configuration().cursor_visible(),
+                    );
+                    self.cursor = cursor;
+                }
+                self.open_requested_windows(elwt);
+                if self.engine.should_exit() {
+                    elwt.exit();
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => match state {
+                ElementState::Pressed => {
+                    engine.on_input(Input::MouseButtonDown(WinitButton(button).into()));
+                }
+                ElementState::Released => {
+                    engine.on_input(Input::MouseButtonUp(WinitButton(button).into()));
+                }
+            },
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::Ime(Ime::Commit(ref text)) => {
+                for character in text.chars() {
+                    engine.on_input(Input::Text(character));
+                }
+            }
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        state,
+                        physical_key: PhysicalKey::Code(virtual_keycode),
+                        repeat,
+                        ref text,
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    if virtual_keycode == KeyCode::Enter && self.modifiers.alt_key() && !repeat {
+                        engine.toggle_fullscreen();
                     }
-                },
-                Event::WindowEvent {
-                    event:
-                        WindowEvent::KeyboardInput {
-                            event:
-                                KeyEvent {
-                                    state,
-                                    physical_key: PhysicalKey::Code(virtual_keycode),
-                                    ..
-                                },
-                            ..
-                        },
-                    ..
-                } => match state {
-                    winit::event::ElementState::Pressed => {
-                        engine.on_input(Input::KeyDown(WinitKeyCode(virtual_keycode).into()));
+                    engine.on_input(Input::KeyDown(WinitKeyCode(virtual_keycode).into()));
+                    for character in text.iter().flat_map(|text| text.chars()) {
+                        engine.on_input(Input::Text(character));
                     }
+                }
+                ElementState::Released => {
+                    engine.on_input(Input::KeyUp(WinitKeyCode(virtual_keycode).into()));
+                }
+            },
+            _ => {}
+        }
+    }
 
-                    winit::event::ElementState::Released => {
-                        engine.on_input(Input::KeyUp(WinitKeyCode(virtual_keycode).into()));
-                    }
-                },
-                _ => {}
+    fn on_secondary_window_event(&mut self, window_id: window::WindowId, event: &WindowEvent) {
+        match *event {
+            WindowEvent::CloseRequested => {
+                self.engine.on_window_closed(WindowId(window_id.into()));
+                self.secondary_windows.remove(&window_id);
+            }
+            WindowEvent::Resized(PhysicalSize { width, height }) => {
+                self.engine
+                    .on_secondary_window_resized(WindowId(window_id.into()), width, height);
+            }
+            _ => {}
+        }
+    }
+
+    fn open_requested_windows(&mut self, elwt: &EventLoopWindowTarget<()>) {
+        for window_descriptor in self.engine.take_window_requests() {
+            let window = match WindowBuilder::new()
+                .with_title(&window_descriptor.title)
+                .with_inner_size(PhysicalSize::new(
+                    window_descriptor.width,
+                    window_descriptor.height,
+                ))
+                .build(elwt)
+            {
+                Ok(window) => Arc::new(window),
+                Err(e) => {
+                    warn!(
+                        "Couldn't create the window {}: {e}",
+                        window_descriptor.title
+                    );
+                    continue;
+                }
+            };
+            let window_id = WindowId(window.id().into());
+            if let Err(e) =
+                self.engine
+                    .on_window_opened(window_id, window.as_ref(), &window_descriptor)
+            {
+                warn!(
+                    "Couldn't create the surface of the window {}: {e:?}",
+                    window_descriptor.title
+                );
+                continue;
+            }
+            self.secondary_windows.insert(window.id(), window);
+        }
+    }
+
+    /// Requests the next frame if the target frame duration has elapsed,
+    /// otherwise waits until it does
+    #[cfg(not(target_arch = "wasm32"))]
+    fn on_about_to_wait(&self, elwt: &EventLoopWindowTarget<()>) {
+        let Some(target_frame_duration) = self.engine.target_frame_duration() else {
+            return;
+        };
+
+        let next_frame_instant = self.last_frame_start_instant + target_frame_duration;
+        if Instant::now() >= next_frame_instant {
+            self.window.request_redraw();
+        } else {
+            elwt.set_control_flow(winit::event_loop::ControlFlow::WaitUntil(
+                next_frame_instant,
+            ));
+        }
+    }
+}
+
+/// Creates the window of the application and applies the window
+/// configuration of the engine
+///
+/// # Panics
+///
+/// For wasm32, might panic if the window canvas cannot be added to the page.
+fn create_window(event_loop: &EventLoop<()>, engine: &Engine) -> Result<Arc<Window>, WinitError> {
+    let (width, height) = engine.window_configuration().size();
+    let window = Arc::new(
+        WindowBuilder::new()
+            .with_title(engine.application_title())
+            .with_resizable(false)
+            .with_inner_size(PhysicalSize::new(width, height))
+            .with_window_icon(
+                engine
+                    .window_configuration()
+                    .icon()
+                    .and_then(load_window_icon),
+            )
+            .with_fullscreen(winit_fullscreen_on_monitor(
+                engine.window_configuration().fullscreen(),
+                event_loop.primary_monitor(),
+            ))
+            .build(event_loop)
+            .map_err(WinitError::WindowCreationFailed)?,
+    );
+    apply_cursor_configuration(&window, engine.window_configuration());
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::WindowExtWebSys;
+        let _ = window.request_inner_size(PhysicalSize::new(width, height));
+
+        web_sys::window()
+            .and_then(|win| win.document())
+            .and_then(|doc| {
+                let dst = doc.get_element_by_id(engine.application_title())?;
+                let canvas = web_sys::Element::from(window.canvas()?);
+                dst.append_child(&canvas).ok()?;
+                Some(())
             })
-            .map_err(WinitError::EventLoopRunningFailed)?;
+            .expect("Couldn't append canvas to document body.");
+    }
+    Ok(window)
+}
 
-        Ok(())
+fn winit_fullscreen(fullscreen_mode: FullscreenMode, window: &Window) -> Option<Fullscreen> {
+    winit_fullscreen_on_monitor(fullscreen_mode, window.current_monitor())
+}
+
+/// Converts a fullscreen mode to its winit equivalent. Exclusive fullscreen
+/// falls back to borderless fullscreen if the monitor has no video mode of the
+/// requested resolution.
+fn winit_fullscreen_on_monitor(
+    fullscreen_mode: FullscreenMode,
+    monitor: Option<MonitorHandle>,
+) -> Option<Fullscreen> {
+    match fullscreen_mode {
+        FullscreenMode::Windowed => None,
+        FullscreenMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        FullscreenMode::Exclusive { width, height } => {
+            let video_mode = monitor.as_ref().and_then(|monitor| {
+                monitor
+                    .video_modes()
+                    .filter(|video_mode| video_mode.size() == PhysicalSize::new(width, height))
+                    .max_by_key(winit::monitor::VideoMode::refresh_rate_millihertz)
+            });
+            if let Some(video_mode) = video_mode {
+                Some(Fullscreen::Exclusive(video_mode))
+            } else {
+                warn!("No {width}x{height} video mode available, using borderless fullscreen");
+                Some(Fullscreen::Borderless(monitor))
+            }
+        }
+    }
+}
+
+/// Shows the cursor icon over the window, the cursor of the platform is
+/// hidden while the engine draws a cursor image
+fn apply_cursor(window: &Window, cursor: &Cursor, cursor_visible: bool) {
+    window.set_cursor_visible(cursor_visible && cursor.image.is_none());
+    window.set_cursor_icon(winit_cursor_icon(cursor.icon));
+}
+
+fn winit_cursor_icon(cursor_icon: CursorIcon) -> winit::window::CursorIcon {
+    match cursor_icon {
+        CursorIcon::Default => winit::window::CursorIcon::Default,
+        CursorIcon::Pointer => winit::window::CursorIcon::Pointer,
+        CursorIcon::Text => winit::window::CursorIcon::Text,
+        CursorIcon::Crosshair => winit::window::CursorIcon::Crosshair,
+        CursorIcon::Move => winit::window::CursorIcon::Move,
+        CursorIcon::Grab => winit::window::CursorIcon::Grab,
+        CursorIcon::Grabbing => winit::window::CursorIcon::Grabbing,
+        CursorIcon::NotAllowed => winit::window::CursorIcon::NotAllowed,
+        CursorIcon::Wait => winit::window::CursorIcon::Wait,
+        CursorIcon::ResizeHorizontal => winit::window::CursorIcon::EwResize,
+        CursorIcon::ResizeVertical => winit::window::CursorIcon::NsResize,
+    }
+}
+
+fn load_window_icon(image_bytes: &[u8]) -> Option<Icon> {
+    let image = ImageLoader::load(image_bytes)
+        .map_err(|e| warn!("Couldn't decode the window icon: {e:?}"))
+        .ok()?;
+    Icon::from_rgba(image.data().to_vec(), image.width(), image.height())
+        .map_err(|e| warn!("Couldn't create the window icon: {e}"))
+        .ok()
+}
+
+fn apply_cursor_configuration(window: &Window, window_configuration: &WindowConfiguration) {
+    window.set_cursor_visible(window_configuration.cursor_visible());
+    let grab_mode = match window_configuration.cursor_grab() {
+        CursorGrabMode::None => winit::window::CursorGrabMode::None,
+        CursorGrabMode::Confined => winit::window::CursorGrabMode::Confined,
+        CursorGrabMode::Locked => winit::window::CursorGrabMode::Locked,
+    };
+    // Some platforms only support one of the grab modes, so the other one is
+    // tried as a fallback
+    let result = window
+        .set_cursor_grab(grab_mode)
+        .or_else(|_| match grab_mode {
+            winit::window::CursorGrabMode::Confined => {
+                window.set_cursor_grab(winit::window::CursorGrabMode::Locked)
+            }
+            winit::window::CursorGrabMode::Locked => {
+                window.set_cursor_grab(winit::window::CursorGrabMode::Confined)
+            }
+            winit::window::CursorGrabMode::None => Ok(()),
+        });
+    if let Err(e) = result {
+        warn!("Couldn't grab the cursor: {e}");
     }
 }
 
@@ -154,6 +426,12 @@ impl From<WinitKeyCode> for Key {
         let virtual_key_code = value.0;
         match virtual_key_code {
             KeyCode::Escape => Key::Escape,
+            KeyCode::Enter | KeyCode::NumpadEnter => Key::Return,
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::ShiftLeft => Key::LShift,
+            KeyCode::ShiftRight => Key::RShift,
+            KeyCode::ControlLeft => Key::LControl,
+            KeyCode::ControlRight => Key::RControl,
             KeyCode::Space => Key::Space,
             KeyCode::ArrowUp => Key::ArrowUp,
             KeyCode::ArrowDown => Key::ArrowDown,
@@ -185,6 +463,18 @@ impl From<WinitKeyCode> for Key {
             KeyCode::KeyX => Key::X,
             KeyCode::KeyY => Key::Y,
             KeyCode::KeyZ => Key::Z,
+            KeyCode::F1 => Key::F1,
+            KeyCode::F2 => Key::F2,
+            KeyCode::F3 => Key::F3,
+            KeyCode::F4 => Key::F4,
+            KeyCode::F5 => Key::F5,
+            KeyCode::F6 => Key::F6,
+            KeyCode::F7 => Key::F7,
+            KeyCode::F8 => Key::F8,
+            KeyCode::F9 => Key::F9,
+            KeyCode::F10 => Key::F10,
+            KeyCode::F11 => Key::F11,
+            KeyCode::F12 => Key::F12,
             _ => Key::Unknown,
         }
     }
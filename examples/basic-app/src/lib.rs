@@ -8,8 +8,13 @@ use tubereng::{
         commands::CommandQueue,
         relationship::ChildOf,
         system::{Res, ResMut, Q},
+        EntityDefinition,
+    },
+    engine::{
+        system_stage::Update,
+        window::{WindowDescriptor, WindowRequests},
+        Engine,
     },
-    engine::{system_stage::Update, Engine},
     image::Image,
     input::{keyboard::Key, InputState},
     math::vector::{Vector2f, Vector3f},
@@ -17,8 +22,8 @@ use tubereng::{
     renderer::{
         camera,
         sprite::{AnimatedSprite, AnimationState, Sprite},
-        texture::Rect,
-        GraphicsState,
+        texture::{Rect, TextureAtlas},
+        Color, GraphicsState,
     },
     winit::WinitTuberRunner,
 };
@@ -31,15 +36,22 @@ struct Player {
     acceleration: Vector2f,
     velocity: Vector2f,
 }
-#[derive(Debug)]
-struct Enemy;
+
+/// The components of the player entity
+#[derive(Debug, EntityDefinition)]
+#[entity_definition(crate = tubereng::ecs)]
+struct PlayerEntity {
+    player: Player,
+    grounded: Grounded,
+    transform: Transform,
+}
 
 #[cfg(not(target_arch = "wasm32"))]
 use tubereng::asset::vfs::filesystem::FileSystem;
 #[cfg(target_arch = "wasm32")]
 use {
     include_dir::{include_dir, Dir},
-    tubereng::asset::vfs::web::Web,
+    tubereng::asset::vfs::embedded::Embedded,
 };
 
 #[cfg(target_arch = "wasm32")]
@@ -57,7 +69,7 @@ pub async fn run() {
     }
 
     #[cfg(target_arch = "wasm32")]
-    let vfs = Web::new(&ASSETS);
+    let vfs = Embedded::new(&ASSETS);
     #[cfg(not(target_arch = "wasm32"))]
     let vfs = FileSystem;
 
@@ -66,6 +78,7 @@ pub async fn run() {
         .with_init_system(init)
         .with_system(&Update, move_player_grounded_system)
         .with_system(&Update, move_player_jumping_system)
+        .with_system(&Update, open_tools_window_system)
         .build(vfs);
     WinitTuberRunner::run(engine).await.unwrap();
 }
@@ -82,7 +95,7 @@ fn init(queue: &CommandQueue, asset_store: ResMut<AssetStore>, mut gfx: ResMut<G
     });
 
     let camera = queue.insert((
-        camera::D2::new(800.0, 600.0),
+        camera::D2::new_fit_height(600.0),
         camera::Active,
         Transform {
             translation: Vector3f::new(-400.0, -300.0, 0.0),
@@ -97,39 +110,34 @@ fn init(queue: &CommandQueue, asset_store: ResMut<AssetStore>, mut gfx: ResMut<G
             scale: Vector3f::uniform(12.5),
             ..Default::default()
         },
-        Sprite {
-            texture: texture_id,
-            texture_rect: Some(Rect::new(48.0, 0.0, 64.0, 48.0)),
-        },
+        Sprite::new(texture_id).with_texture_rect(Rect::new(48.0, 0.0, 64.0, 48.0)),
     ));
 
-    let player = queue.insert((
-        Player::default(),
-        Grounded,
-        Transform {
+    let player = queue.insert(PlayerEntity {
+        player: Player::default(),
+        grounded: Grounded,
+        transform: Transform {
             translation: Vector3f::new(0.0, 600.0 - 85.0, 0.0),
             ..Default::default()
         },
-    ));
+    });
 
+    // The first row of the texture holds the ground tile and the frames of
+    // the player
+    let atlas = TextureAtlas::grid(texture_id, 16.0, 16.0, 3, 1);
     let player_sprite = queue.insert((
         Transform {
             scale: Vector3f::new(4.0, 4.0, 4.0),
             ..Default::default()
         },
-        AnimatedSprite {
-            texture_atlas: texture_id,
-            animation: AnimationState {
-                animations: vec![vec![
-                    Rect::new(16.0, 0.0, 16.0, 16.0),
-                    Rect::new(32.0, 0.0, 16.0, 16.0),
-                ]],
-                current_animation: 0,
-                current_frame: 0,
+        AnimatedSprite::new(
+            texture_id,
+            AnimationState {
+                animations: vec![atlas.cells([1, 2])],
                 secs_per_frame: 0.5,
-                ticks: 0.0,
+                ..Default::default()
             },
-        },
+        ),
     ));
 
     queue.insert_relationship::<ChildOf>(player_sprite, player);
@@ -142,14 +150,25 @@ fn init(queue: &CommandQueue, asset_store: ResMut<AssetStore>, mut gfx: ResMut<G
                 scale: Vector3f::new(4.0, 4.0, 4.0),
                 ..Default::default()
             },
-            Sprite {
-                texture: texture_id,
-                texture_rect: Some(Rect::new(0.0, 0.0, 16.0, 16.0)),
-            },
+            Sprite::new(texture_id).with_texture_rect(atlas.cell(0).unwrap()),
         ));
     }
 }
 
+/// Opens a secondary window when F2 is pressed
+fn open_tools_window_system(
+    input_state: Res<InputState>,
+    mut window_requests: ResMut<WindowRequests>,
+) {
+    let keyboard = &input_state.keyboard;
+    if keyboard.is_key_down(Key::F2) && !keyboard.was_key_down(Key::F2) {
+        window_requests.request(
+            WindowDescriptor::new("basic-app tools", 320, 240)
+                .with_clear_color(Color::new(0.2, 0.3, 0.5)),
+        );
+    }
+}
+
 #[derive(Debug)]
 pub struct Jumping;
 #[derive(Debug)]
@@ -193,18 +212,16 @@ fn move_player_grounded_system(
     }
 
     player.velocity.x += player.acceleration.x;
-    if player.velocity.x > MAX_PLAYER_VELOCITY_X {
-        player.velocity.x = MAX_PLAYER_VELOCITY_X;
-    } else if player.velocity.x < -MAX_PLAYER_VELOCITY_X {
-        player.velocity.x = -MAX_PLAYER_VELOCITY_X;
-    }
+    player.velocity.x = player
+        .velocity
+        .x
+        .clamp(-MAX_PLAYER_VELOCITY_X, MAX_PLAYER_VELOCITY_X);
 
     player.velocity.y += player.acceleration.y;
-    if player.velocity.y > MAX_PLAYER_VELOCITY_Y {
-        player.velocity.y = MAX_PLAYER_VELOCITY_Y;
-    } else if player.velocity.y < -MAX_PLAYER_VELOCITY_Y {
-        player.velocity.y = -MAX_PLAYER_VELOCITY_Y;
-    }
+    player.velocity.y = player
+        .velocity
+        .y
+        .clamp(-MAX_PLAYER_VELOCITY_Y, MAX_PLAYER_VELOCITY_Y);
 
     transform.translation.x += player.velocity.x * delta_time;
     transform.translation.y += player.velocity.y * delta_time;
@@ -257,19 +274,17 @@ fn move_player_jumping_system(
     }
 
     player.velocity.x += player.acceleration.x;
-    if player.velocity.x > MAX_PLAYER_VELOCITY_X {
-        player.velocity.x = MAX_PLAYER_VELOCITY_X;
-    } else if player.velocity.x < -MAX_PLAYER_VELOCITY_X {
-        player.velocity.x = -MAX_PLAYER_VELOCITY_X;
-    }
+    player.velocity.x = player
+        .velocity
+        .x
+        .clamp(-MAX_PLAYER_VELOCITY_X, MAX_PLAYER_VELOCITY_X);
 
     player.acceleration.y += GRAVITY;
     player.velocity.y += player.acceleration.y;
-    if player.velocity.y > MAX_PLAYER_VELOCITY_Y {
-        player.velocity.y = MAX_PLAYER_VELOCITY_Y;
-    } else if player.velocity.y < -MAX_PLAYER_VELOCITY_Y {
-        player.velocity.y = -MAX_PLAYER_VELOCITY_Y;
-    }
+    player.velocity.y = player
+        .velocity
+        .y
+        .clamp(-MAX_PLAYER_VELOCITY_Y, MAX_PLAYER_VELOCITY_Y);
 
     transform.translation.x += player.velocity.x * delta_time;
     transform.translation.y += player.velocity.y * delta_time;
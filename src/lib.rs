@@ -6,5 +6,6 @@ pub use tubereng_gui as gui;
 pub use tubereng_image as image;
 pub use tubereng_input as input;
 pub use tubereng_math as math;
+pub use tubereng_model as model;
 pub use tubereng_renderer as renderer;
 pub use tubereng_winit as winit;